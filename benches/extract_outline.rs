@@ -0,0 +1,55 @@
+use std::{num::NonZeroUsize, thread, time::Instant};
+
+use svgen::{
+    algo::{direction_mask, extract_outline},
+    mask::BitMask,
+    structs::TurnPolicy,
+};
+
+/// An 8K mask of rings and specks, with outlines on most rows.
+fn mask(size: [usize; 2]) -> BitMask {
+    BitMask::from_bits(
+        size,
+        (0..size[0] * size[1]).map(|i| {
+            let (x, y) = (i % size[0], i / size[0]);
+            let (cx, cy) = ((x % 97) as i64 - 48, (y % 89) as i64 - 44);
+            let ring = (cx * cx + cy * cy) / 128 % 3 == 0;
+            let speck = (x * 31 + y * 17) % 211 == 0;
+            ring ^ speck
+        }),
+    )
+}
+
+/// Best time of a few runs, in milliseconds.
+fn time<T>(mut f: impl FnMut() -> T) -> f64 {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(f());
+            start.elapsed().as_secs_f64() * 1000.0
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn main() {
+    let size = [7680, 4320];
+    let image = mask(size);
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+
+    assert!(
+        direction_mask(&image, 1) == direction_mask(&image, threads),
+        "threaded direction mask differs"
+    );
+
+    let serial = time(|| direction_mask(&image, 1));
+    let threaded = time(|| direction_mask(&image, threads));
+    println!(
+        "direction mask {}x{}: {serial:.1}ms serial, {threaded:.1}ms on {threads} threads ({:.2}x)",
+        size[0],
+        size[1],
+        serial / threaded
+    );
+
+    let outline = time(|| extract_outline(&image, TurnPolicy::Majority, true));
+    println!("extract_outline: {outline:.1}ms");
+}
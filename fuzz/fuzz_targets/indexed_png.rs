@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use svgen::decoder::{decode_indexed_png, INDEXED_PALETTE_MAX};
+
+fuzz_target!(|bytes: &[u8]| {
+    let Some((image, palette)) = decode_indexed_png(bytes) else {
+        return;
+    };
+    assert!(palette.len() <= INDEXED_PALETTE_MAX);
+    // Indices past the palette are transparent.
+    assert!(image
+        .pixels()
+        .all(|pixel| pixel.0 == [0, 0, 0, 0] || palette.contains(&pixel.0)));
+});
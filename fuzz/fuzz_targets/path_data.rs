@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use svgen::path_optimizer::OptimizedData;
+
+fuzz_target!(|text: &str| {
+    let Ok(data) = text.parse::<OptimizedData>() else {
+        return;
+    };
+    // Parsed paths are written back as valid path data.
+    let optimized = data.optimize();
+    if let Err(error) = optimized.parse::<OptimizedData>() {
+        panic!("{error} in {optimized:?}");
+    }
+    data.to_polygons(0.5);
+});
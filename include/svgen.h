@@ -0,0 +1,121 @@
+#ifndef SVGEN_H
+#define SVGEN_H
+
+/* Generated with cbindgen from src/capi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * See [`ColorMode`].
+ */
+typedef enum SvgenColorMode {
+  SVGEN_COLOR_MODE_BLACK,
+  SVGEN_COLOR_MODE_COLORED,
+  SVGEN_COLOR_MODE_SEPARATION,
+} SvgenColorMode;
+
+/**
+ * See [`Mode`].
+ */
+typedef enum SvgenMode {
+  SVGEN_MODE_SMOOTH,
+  SVGEN_MODE_PIXEL_PERFECT,
+  SVGEN_MODE_POLYGON,
+} SvgenMode;
+
+/**
+ * Outcome of [`svgen_create_svg`].
+ */
+typedef enum SvgenStatus {
+  SVGEN_STATUS_OK,
+  /**
+   * `bytes` or `out_svg` is null.
+   */
+  SVGEN_STATUS_NULL_ARGUMENT,
+  /**
+   * See [`SvgenError::UnsupportedFormat`].
+   */
+  SVGEN_STATUS_UNSUPPORTED_FORMAT,
+  /**
+   * See [`SvgenError::Image`].
+   */
+  SVGEN_STATUS_INVALID_IMAGE,
+  /**
+   * The vectorizer panicked, the message went to the panic hook.
+   */
+  SVGEN_STATUS_PANIC,
+} SvgenStatus;
+
+/**
+ * The [`Options`] settable from C, the others keep their defaults.
+ * Start from [`svgen_options_default`].
+ */
+typedef struct SvgenOptions {
+  enum SvgenColorMode color_mode;
+  enum SvgenMode mode;
+  /**
+   * See [`Options::preprocess`].
+   */
+  bool preprocess;
+  /**
+   * See [`Options::error_threshold`].
+   */
+  double error_threshold;
+  /**
+   * See [`Options::smoothness`].
+   */
+  double smoothness;
+  /**
+   * See [`Options::simplify_threshold`].
+   */
+  double simplify_threshold;
+  /**
+   * See [`CornerOptions::enabled`].
+   */
+  bool corners_enabled;
+  /**
+   * Angle in radians, see [`CornerOptions::threshold`].
+   */
+  double corner_threshold;
+  /**
+   * See [`Options::shared_boundaries`].
+   */
+  bool shared_boundaries;
+} SvgenOptions;
+
+/**
+ * The default [`Options`], as [`SvgenOptions`].
+ */
+struct SvgenOptions svgen_options_default(void);
+
+/**
+ * Traces the encoded image of `len` bytes at `bytes` as [`try_create_svg`] does,
+ * `options` may be null for the defaults.
+ *
+ * On success `*out_svg` is set to the NUL terminated document,
+ * to be released with [`svgen_free_string`], otherwise it is set to null.
+ *
+ * # Safety
+ *
+ * `bytes` must point to `len` readable bytes, `options` must be null or point to
+ * an [`SvgenOptions`] and `out_svg` must be null or writable.
+ */
+enum SvgenStatus svgen_create_svg(const uint8_t *bytes,
+                                  size_t len,
+                                  const struct SvgenOptions *options,
+                                  char **out_svg);
+
+/**
+ * Releases a document returned by [`svgen_create_svg`], null is ignored.
+ *
+ * # Safety
+ *
+ * `svg` must be null or a document from [`svgen_create_svg`] not released yet.
+ */
+void svgen_free_string(char *svg);
+
+#endif  /* SVGEN_H */
@@ -1,4 +1,6 @@
-use crate::{structs::TurnPolicy, vec2::IVec2};
+use std::{num::NonZeroUsize, thread};
+
+use crate::{mask::BitMask, structs::TurnPolicy, vec2::IVec2};
 
 const DIR_L: u8 = 1 << 0;
 const DIR_R: u8 = 1 << 1;
@@ -10,40 +12,98 @@ fn index(x: usize, y: usize, x_span: usize) -> usize {
     x + y * x_span
 }
 
-#[inline(always)]
-fn is_filled_left(image: &[bool], size: &[usize; 2], x: usize, y: usize) -> bool {
-    if x > 0 {
-        image[index(x - 1, y, size[0])]
-    } else {
-        false
-    }
+/// A pseudo-random bit for a position, stable across runs with the same `seed`.
+fn is_random(x: i32, y: i32, seed: u64) -> bool {
+    let mut h = (x as u32).wrapping_mul(0x9e37_79b1) ^ (y as u32).wrapping_mul(0x85eb_ca77);
+    h ^= (seed as u32) ^ ((seed >> 32) as u32).wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h & 1 != 0
 }
 
-#[inline(always)]
-fn is_filled_right(image: &[bool], size: &[usize; 2], x: usize, y: usize) -> bool {
-    if x + 1 < size[0] {
-        image[index(x + 1, y, size[0])]
-    } else {
-        false
-    }
-}
+/// Fewest padded rows a thread of [`direction_mask`] populates,
+/// smaller images are populated on the calling thread.
+const MIN_BAND_ROWS: usize = 256;
 
-#[inline(always)]
-fn is_filled_down(image: &[bool], size: &[usize; 2], x: usize, y: usize) -> bool {
-    if y > 0 {
-        image[index(x, y - 1, size[0])]
+/// Builds the direction mask of [`extract_outline`], of the image padded by one pixel,
+/// flagging the pixel edges between filled and empty pixels, along with the number of edges.
+///
+/// Horizontal bands of rows are populated on up to `threads` threads,
+/// each padded row only depending on the image rows above and below it.
+pub fn direction_mask(image: &BitMask, threads: usize) -> (Vec<u8>, usize) {
+    let padded_size = [image.width() + 1, image.height() + 1];
+    let mut pimage = vec![0u8; padded_size[0] * padded_size[1]];
+
+    let bands = threads.min(padded_size[1] / MIN_BAND_ROWS).max(1);
+    let band_rows = padded_size[1].div_ceil(bands);
+    let band_len = band_rows * padded_size[0];
+    let steps_total = if bands == 1 {
+        direction_band(image, &mut pimage, 0)
     } else {
-        false
-    }
+        thread::scope(|scope| {
+            let handles = pimage
+                .chunks_mut(band_len)
+                .enumerate()
+                .map(|(band, rows)| {
+                    scope.spawn(move || direction_band(image, rows, band * band_rows))
+                })
+                .collect::<Vec<_>>();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        })
+    };
+    (pimage, steps_total)
 }
 
-#[inline(always)]
-fn is_filled_up(image: &[bool], size: &[usize; 2], x: usize, y: usize) -> bool {
-    if y + 1 < size[1] {
-        image[index(x, y + 1, size[0])]
-    } else {
-        false
+/// Populates the padded `rows` starting at padded row `first_row`,
+/// returning the number of edges flagged.
+///
+/// Edges are found a word of pixels at a time, comparing each row with itself shifted
+/// by one pixel and with its neighboring rows.
+fn direction_band(image: &BitMask, rows: &mut [u8], first_row: usize) -> usize {
+    let [width, height] = image.size();
+    let empty = vec![0u64; width.div_ceil(64)];
+    let row = |y: usize| match y < height {
+        true => image.row(y),
+        false => &empty[..],
+    };
+    // Sets `flag` on the cells at pixel `x + offset` for the set bits `x` of `edges`.
+    let mut steps_total = 0;
+    let mut flag_edges =
+        |cells: &mut [u8], word: usize, mut edges: u64, offset: usize, flag: u8| {
+            steps_total += edges.count_ones() as usize;
+            while edges != 0 {
+                let x = word * 64 + edges.trailing_zeros() as usize;
+                cells[x + offset] |= flag;
+                edges &= edges - 1;
+            }
+        };
+
+    for (row_index, cells) in rows.chunks_mut(width + 1).enumerate() {
+        let py = first_row + row_index;
+        // Left and bottom edges of the pixels in the row.
+        if py < height {
+            let (pixels, below) = (row(py), py.checked_sub(1).map_or(&empty[..], row));
+            let mut carry = 0;
+            for (word, (&filled, &below)) in pixels.iter().zip(below).enumerate() {
+                let left = filled << 1 | carry;
+                carry = filled >> 63;
+                flag_edges(cells, word, filled & !left, 0, DIR_U);
+                flag_edges(cells, word, filled & !below, 1, DIR_L);
+            }
+        }
+        // Right and top edges of the pixels in the row before.
+        if py > 0 {
+            let (pixels, above) = (row(py - 1), row(py));
+            for (word, (&filled, &above)) in pixels.iter().zip(above).enumerate() {
+                let next = pixels.get(word + 1).map_or(0, |next| next << 63);
+                let right = filled >> 1 | next;
+                flag_edges(cells, word, filled & !right, 1, DIR_D);
+                flag_edges(cells, word, filled & !above, 0, DIR_R);
+            }
+        }
     }
+    steps_total
 }
 
 /// Moves (x, y) in the given direction.
@@ -58,6 +118,9 @@ fn step_move(dir: u8, x: &mut i32, y: &mut i32) {
 }
 
 /// Returns the first matching direction from the cell without borrowing `pimage` for too long.
+///
+/// `None` when the cell has none of them, which the balanced edges of a direction mask
+/// rule out, as every cell reached along an edge has one leaving it in those directions.
 fn step_first_match(
     pimage: &[u8],
     idx: impl Fn(i32, i32) -> usize,
@@ -66,57 +129,54 @@ fn step_first_match(
     d3: u8,
     x: &mut i32,
     y: &mut i32,
-) -> u8 {
+) -> Option<u8> {
     let cell = pimage[idx(*x, *y)];
-    if (cell & d1) != 0 {
-        step_move(d1, x, y);
-        d1
-    } else if (cell & d2) != 0 {
-        step_move(d2, x, y);
-        d2
-    } else if (cell & d3) != 0 {
-        step_move(d3, x, y);
-        d3
-    } else {
-        unreachable!()
-    }
+    let dir = [d1, d2, d3].into_iter().find(|dir| cell & dir != 0)?;
+    step_move(dir, x, y);
+    Some(dir)
+}
+
+/// Whether the contour starting up the edge at cell `(x, y)` bounds filled pixels
+/// rather than a hole, as Potrace tells its paths apart to turn [`TurnPolicy::Right`]
+/// and [`TurnPolicy::Left`].
+///
+/// The cell is on the lowest row of its contour, the contours crossing that row on its left
+/// have all been traced, except for edges of the contour itself, and enclose it
+/// when they cross it an odd number of times. As many edges cross it in the direction mask,
+/// the traced ones are counted from the edges left.
+fn is_outer_start(pimage: &[u8], x: usize, y: usize, x_span: usize) -> bool {
+    let up = pimage[index(0, y, x_span)..index(x, y, x_span)]
+        .iter()
+        .filter(|cell| *cell & DIR_U != 0)
+        .count();
+    // Down edges of a pixel row are flagged on the cells above it.
+    let down = pimage[index(0, y + 1, x_span)..index(x, y + 1, x_span)]
+        .iter()
+        .filter(|cell| *cell & DIR_D != 0)
+        .count();
+    (up + down) % 2 == 0
 }
 
 /// Extract the outline from an image.
 /// Returns a Vec of (flag, polygon) pairs.
 pub fn extract_outline(
-    image: &[bool],
-    size: &[usize; 2],
+    image: &BitMask,
     turn_policy: TurnPolicy,
     use_simplify: bool,
 ) -> Vec<(bool, Vec<IVec2>)> {
-    let padded_size = [size[0] + 1, size[1] + 1];
-    let mut pimage = vec![0u8; padded_size[0] * padded_size[1]];
+    extract_outline_seeded(image, turn_policy, use_simplify, 0)
+}
 
-    // Populate the padded image with directional flags.
-    let mut steps_total = 0;
-    for y in 0..size[1] {
-        for x in 0..size[0] {
-            if image[index(x, y, size[0])] {
-                if !is_filled_left(image, size, x, y) {
-                    pimage[index(x, y, padded_size[0])] |= DIR_U;
-                    steps_total += 1;
-                }
-                if !is_filled_right(image, size, x, y) {
-                    pimage[index(x + 1, y + 1, padded_size[0])] |= DIR_D;
-                    steps_total += 1;
-                }
-                if !is_filled_down(image, size, x, y) {
-                    pimage[index(x + 1, y, padded_size[0])] |= DIR_L;
-                    steps_total += 1;
-                }
-                if !is_filled_up(image, size, x, y) {
-                    pimage[index(x, y + 1, padded_size[0])] |= DIR_R;
-                    steps_total += 1;
-                }
-            }
-        }
-    }
+/// Like [`extract_outline`], the turns of [`TurnPolicy::Random`] being picked from `seed`.
+pub fn extract_outline_seeded(
+    image: &BitMask,
+    turn_policy: TurnPolicy,
+    use_simplify: bool,
+    seed: u64,
+) -> Vec<(bool, Vec<IVec2>)> {
+    let padded_size = [image.width() + 1, image.height() + 1];
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let (mut pimage, steps_total) = direction_mask(image, threads);
 
     let mut poly_list = Vec::new();
 
@@ -126,10 +186,12 @@ pub fn extract_outline(
         x_init: i32,
         y_init: i32,
         x_span: i32,
-        image_data: (&[bool], IVec2),
+        image_data: (&BitMask, IVec2),
         turn_policy: TurnPolicy,
+        seed: u64,
         use_simplify: bool,
         initial_dir: u8,
+        is_outer: bool,
     ) -> (Vec<IVec2>, usize) {
         let mut poly = Vec::new();
         let (mut x, mut y) = (x_init, y_init);
@@ -139,11 +201,11 @@ pub fn extract_outline(
         let idx = |x: i32, y: i32| -> usize { (x as usize) + (y as usize) * (x_span as usize) };
 
         // Check whether the majority of the neighborhood is filled.
-        let is_majority = |x: i32, y: i32, data: (&[bool], IVec2)| -> bool {
+        let is_majority = |x: i32, y: i32, data: (&BitMask, IVec2)| -> bool {
             let (img, dims) = data;
             let xy_or = |x: i32, y: i32, default: bool| -> bool {
                 if x >= 0 && x < dims.x && y >= 0 && y < dims.y {
-                    img[index(x as usize, y as usize, dims.x as usize)]
+                    img.get(x as usize, y as usize)
                 } else {
                     default
                 }
@@ -217,15 +279,21 @@ pub fn extract_outline(
                 step_move(cell, &mut x, &mut y);
                 cell
             } else {
-                // Ambiguous: choose turn based on policy.
-                let turn_ccw = match turn_policy {
+                // Ambiguous: choose turn based on policy,
+                // turning clockwise keeps the diagonal filled pixels connected.
+                let connect_filled = match turn_policy {
                     TurnPolicy::Black => true,
                     TurnPolicy::White => false,
+                    // Potrace travels holes the other way around.
+                    TurnPolicy::Right => is_outer,
+                    TurnPolicy::Left => !is_outer,
                     TurnPolicy::Majority => is_majority(x, y, image_data),
                     TurnPolicy::Minority => !is_majority(x, y, image_data),
+                    TurnPolicy::Random => is_random(x, y, seed),
                 };
+                let turn_ccw = !connect_filled;
 
-                if !turn_ccw {
+                let next_dir = if !turn_ccw {
                     match prev_dir {
                         DIR_L => {
                             step_first_match(&pimage, &idx, DIR_D, DIR_L, DIR_U, &mut x, &mut y)
@@ -257,6 +325,11 @@ pub fn extract_outline(
                         }
                         _ => unreachable!(),
                     }
+                };
+                match next_dir {
+                    Some(next_dir) => next_dir,
+                    // Close the contour where it is rather than panic.
+                    None => break,
                 }
             };
 
@@ -268,13 +341,18 @@ pub fn extract_outline(
         (poly, handled)
     }
 
-    let image_data = (image, IVec2::new(size[0] as i32, size[1] as i32));
+    let image_data = (
+        image,
+        IVec2::new(image.width() as i32, image.height() as i32),
+    );
     let mut steps_handled = 0;
 
     'outer: for y in 0..padded_size[1] {
         for x in 0..padded_size[0] {
             let cell_index = index(x, y, padded_size[0]);
             if pimage[cell_index] & DIR_U != 0 {
+                let is_outer = matches!(turn_policy, TurnPolicy::Right | TurnPolicy::Left)
+                    && is_outer_start(&pimage, x, y, padded_size[0]);
                 let (poly, handled) = poly_from_direction_mask(
                     &mut pimage,
                     x as i32,
@@ -282,8 +360,10 @@ pub fn extract_outline(
                     padded_size[0] as i32,
                     image_data,
                     turn_policy,
+                    seed,
                     use_simplify,
                     DIR_L,
+                    is_outer,
                 );
                 poly_list.push((true, poly));
                 steps_handled += handled;
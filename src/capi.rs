@@ -0,0 +1,182 @@
+//! C bindings of the vectorizer, for C, C++, Python or Go callers without the WASM route.
+//!
+//! The matching header is `include/svgen.h`, generated with
+//! `cbindgen --config cbindgen.toml --output include/svgen.h`.
+
+use std::{
+    ffi::{c_char, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::{
+    error::SvgenError,
+    structs::{ColorMode, CornerOptions, Mode, Options},
+    try_create_svg,
+};
+
+/// See [`ColorMode`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SvgenColorMode {
+    Black,
+    Colored,
+    Separation,
+}
+
+/// See [`Mode`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SvgenMode {
+    Smooth,
+    PixelPerfect,
+    Polygon,
+}
+
+/// The [`Options`] settable from C, the others keep their defaults.
+/// Start from [`svgen_options_default`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SvgenOptions {
+    pub color_mode: SvgenColorMode,
+    pub mode: SvgenMode,
+    /// See [`Options::preprocess`].
+    pub preprocess: bool,
+    /// See [`Options::error_threshold`].
+    pub error_threshold: f64,
+    /// See [`Options::smoothness`].
+    pub smoothness: f64,
+    /// See [`Options::simplify_threshold`].
+    pub simplify_threshold: f64,
+    /// See [`CornerOptions::enabled`].
+    pub corners_enabled: bool,
+    /// Angle in radians, see [`CornerOptions::threshold`].
+    pub corner_threshold: f64,
+    /// See [`Options::shared_boundaries`].
+    pub shared_boundaries: bool,
+}
+
+impl From<&SvgenOptions> for Options {
+    fn from(options: &SvgenOptions) -> Self {
+        let defaults = Options::default();
+        Options {
+            color_mode: match options.color_mode {
+                SvgenColorMode::Black => ColorMode::Black,
+                SvgenColorMode::Colored => ColorMode::Colored,
+                SvgenColorMode::Separation => ColorMode::Separation,
+            },
+            mode: match options.mode {
+                SvgenMode::Smooth => Mode::Smooth,
+                SvgenMode::PixelPerfect => Mode::PixelPerfect,
+                SvgenMode::Polygon => Mode::Polygon,
+            },
+            preprocess: options.preprocess,
+            error_threshold: options.error_threshold,
+            smoothness: options.smoothness,
+            simplify_threshold: options.simplify_threshold,
+            corners: CornerOptions {
+                enabled: options.corners_enabled,
+                threshold: options.corner_threshold,
+                ..defaults.corners
+            },
+            shared_boundaries: options.shared_boundaries,
+            ..defaults
+        }
+    }
+}
+
+/// Outcome of [`svgen_create_svg`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SvgenStatus {
+    Ok,
+    /// `bytes` or `out_svg` is null.
+    NullArgument,
+    /// See [`SvgenError::UnsupportedFormat`].
+    UnsupportedFormat,
+    /// See [`SvgenError::Image`].
+    InvalidImage,
+    /// The vectorizer panicked, the message went to the panic hook.
+    Panic,
+}
+
+/// The default [`Options`], as [`SvgenOptions`].
+#[no_mangle]
+pub extern "C" fn svgen_options_default() -> SvgenOptions {
+    let options = Options::default();
+    SvgenOptions {
+        color_mode: match options.color_mode {
+            ColorMode::Black => SvgenColorMode::Black,
+            ColorMode::Colored => SvgenColorMode::Colored,
+            ColorMode::Separation => SvgenColorMode::Separation,
+        },
+        mode: match options.mode {
+            Mode::Smooth => SvgenMode::Smooth,
+            Mode::PixelPerfect => SvgenMode::PixelPerfect,
+            Mode::Polygon => SvgenMode::Polygon,
+        },
+        preprocess: options.preprocess,
+        error_threshold: options.error_threshold,
+        smoothness: options.smoothness,
+        simplify_threshold: options.simplify_threshold,
+        corners_enabled: options.corners.enabled,
+        corner_threshold: options.corners.threshold,
+        shared_boundaries: options.shared_boundaries,
+    }
+}
+
+/// Traces the encoded image of `len` bytes at `bytes` as [`try_create_svg`] does,
+/// `options` may be null for the defaults.
+///
+/// On success `*out_svg` is set to the NUL terminated document,
+/// to be released with [`svgen_free_string`], otherwise it is set to null.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes, `options` must be null or point to
+/// an [`SvgenOptions`] and `out_svg` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn svgen_create_svg(
+    bytes: *const u8,
+    len: usize,
+    options: *const SvgenOptions,
+    out_svg: *mut *mut c_char,
+) -> SvgenStatus {
+    if out_svg.is_null() {
+        return SvgenStatus::NullArgument;
+    }
+    *out_svg = ptr::null_mut();
+    if bytes.is_null() {
+        return SvgenStatus::NullArgument;
+    }
+    let image_byte = slice::from_raw_parts(bytes, len);
+    let options = match options.as_ref() {
+        Some(options) => Options::from(options),
+        None => Options::default(),
+    };
+
+    // Unwinding into C is undefined behavior.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| try_create_svg(image_byte, &options)));
+    match result {
+        Ok(Ok(svg)) => {
+            // Documents have no NUL bytes, attribute values are escaped.
+            *out_svg = CString::new(svg).unwrap_or_default().into_raw();
+            SvgenStatus::Ok
+        }
+        Ok(Err(SvgenError::UnsupportedFormat(_))) => SvgenStatus::UnsupportedFormat,
+        Ok(Err(SvgenError::Image(_) | SvgenError::BufferSize { .. })) => SvgenStatus::InvalidImage,
+        Err(_) => SvgenStatus::Panic,
+    }
+}
+
+/// Releases a document returned by [`svgen_create_svg`], null is ignored.
+///
+/// # Safety
+///
+/// `svg` must be null or a document from [`svgen_create_svg`] not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn svgen_free_string(svg: *mut c_char) {
+    if !svg.is_null() {
+        drop(CString::from_raw(svg));
+    }
+}
@@ -8,7 +8,6 @@
 
 const USE_REFIT: bool = true;
 const USE_REFIT_REMOVE: bool = true;
-const CORNER_SCALE: f64 = 2.0; // this is weak, should be made configurable.
 
 macro_rules! unlikely {
     ($body:expr) => {
@@ -16,7 +15,11 @@ macro_rules! unlikely {
     };
 }
 
-use super::curve_fit_single;
+use alloc::{vec, vec::Vec};
+
+use super::{curve_fit_single, vector::Vector};
+#[cfg(not(feature = "std"))]
+use crate::math::F64Ext;
 use crate::vec2::DVec2;
 use crate::{min_heap, vec2::USizeVec2};
 
@@ -27,6 +30,8 @@ pub enum TraceMode {
 }
 
 mod types {
+    use alloc::vec::Vec;
+
     use crate::vec2::{DVec2, USizeVec2};
 
     pub struct Knot {
@@ -55,34 +60,34 @@ mod types {
         pub tan: USizeVec2,
     }
 
-    pub struct PointData<'a> {
+    pub struct PointData<'a, V> {
         /// note, can't use points.len(),
         /// since this may be doubled for cyclic curves
-        pub points: &'a Vec<DVec2>,
+        pub points: &'a Vec<V>,
         pub points_len: usize,
 
         /// This array may be doubled as well.
         pub points_length_cache: &'a Vec<f64>,
 
-        pub tangents: &'a Vec<DVec2>,
+        pub tangents: &'a Vec<V>,
     }
 }
 
 use self::types::{Knot, PointData};
 
-const INVALID: usize = ::std::usize::MAX;
+const INVALID: usize = ::core::usize::MAX;
 
 /// Find the knot furthest from the line between \a knot_l & \a knot_r.
 /// This is to be used as a split point.
-fn knot_find_split_point_on_axis(
-    pd: &PointData,
+fn knot_find_split_point_on_axis<V: Vector>(
+    pd: &PointData<V>,
     knots: &Vec<Knot>,
     k_prev: &Knot,
     k_next: &Knot,
-    plane_no: &DVec2,
+    plane_no: &V,
 ) -> usize {
     let mut split_point: usize = INVALID;
-    let mut split_point_dist_best: f64 = -::std::f64::MAX;
+    let mut split_point_dist_best: f64 = -::core::f64::MAX;
 
     let knots_end = knots.len() - 1;
     let mut k_step = k_prev.index;
@@ -109,10 +114,10 @@ fn knot_find_split_point_on_axis(
     return split_point;
 }
 
-fn knot_remove_error_value(
-    tan_l: &DVec2,
-    tan_r: &DVec2,
-    points_offset: &[DVec2],
+fn knot_remove_error_value<V: Vector>(
+    tan_l: &V,
+    tan_r: &V,
+    points_offset: &[V],
     points_offset_length_cache: &[f64],
 ) -> (f64, usize, DVec2) {
     let ((error_sq, error_index), handle_factor_l, handle_factor_r) =
@@ -132,12 +137,12 @@ fn knot_remove_error_value(
     );
 }
 
-fn knot_calc_curve_error_value_and_index(
-    pd: &PointData,
+fn knot_calc_curve_error_value_and_index<V: Vector>(
+    pd: &PointData<V>,
     knot_l: &Knot,
     knot_r: &Knot,
-    tan_l: &DVec2,
-    tan_r: &DVec2,
+    tan_l: &V,
+    tan_r: &V,
 ) -> (f64, usize, DVec2) {
     let points_offset_len = if knot_l.index < knot_r.index {
         knot_r.index - knot_l.index
@@ -168,12 +173,12 @@ fn knot_calc_curve_error_value_and_index(
     }
 }
 
-fn knot_calc_curve_error_value(
-    pd: &PointData,
+fn knot_calc_curve_error_value<V: Vector>(
+    pd: &PointData<V>,
     knot_l: &Knot,
     knot_r: &Knot,
-    tan_l: &DVec2,
-    tan_r: &DVec2,
+    tan_l: &V,
+    tan_r: &V,
 ) -> (f64, DVec2) {
     let points_offset_len = if knot_l.index < knot_r.index {
         knot_r.index - knot_l.index
@@ -199,8 +204,11 @@ fn knot_calc_curve_error_value(
 }
 
 mod refine_remove {
+    use alloc::vec::Vec;
+
     use super::types::{Knot, PointData};
     use super::{knot_calc_curve_error_value, INVALID};
+    use crate::curve_fit_nd::vector::Vector;
     use crate::min_heap;
     use crate::vec2::DVec2;
 
@@ -214,8 +222,8 @@ mod refine_remove {
         handles: DVec2,
     }
 
-    fn knot_remove_error_recalculate(
-        pd: &PointData,
+    fn knot_remove_error_recalculate<V: Vector>(
+        pd: &PointData<V>,
         heap: &mut min_heap::MinHeap<f64, KnotRemoveState>,
         knots: &Vec<Knot>,
         knots_handle: &mut Vec<min_heap::NodeHandle>,
@@ -255,8 +263,8 @@ mod refine_remove {
         }
     }
 
-    pub fn curve_incremental_simplify(
-        pd: &PointData,
+    pub fn curve_incremental_simplify<V: Vector>(
+        pd: &PointData<V>,
         knots: &mut Vec<Knot>,
         knots_handle: &mut Vec<min_heap::NodeHandle>,
         knots_len_remaining: &mut usize,
@@ -340,12 +348,14 @@ mod refine_remove {
 }
 
 mod refine_refit {
+    use alloc::vec::Vec;
 
     use super::types::{Knot, PointData};
     use super::{
         knot_calc_curve_error_value, knot_calc_curve_error_value_and_index, INVALID,
         USE_REFIT_REMOVE,
     };
+    use crate::curve_fit_nd::vector::Vector;
     use crate::min_heap;
     use crate::vec2::DVec2;
 
@@ -361,8 +371,8 @@ mod refine_refit {
         fit_error_max_sq_pair: DVec2,
     }
 
-    fn knot_refit_error_recalculate(
-        pd: &PointData,
+    fn knot_refit_error_recalculate<V: Vector>(
+        pd: &PointData<V>,
         heap: &mut min_heap::MinHeap<f64, KnotRefitState>,
         knots: &Vec<Knot>,
         knots_handle: &mut Vec<min_heap::NodeHandle>,
@@ -426,8 +436,8 @@ mod refine_refit {
         debug_assert!(cost_sq_src_max <= error_max_sq);
 
         // Specialized function to avoid duplicate code
-        fn knot_calc_curve_error_value_pair_above_error_or_none(
-            pd: &PointData,
+        fn knot_calc_curve_error_value_pair_above_error_or_none<V: Vector>(
+            pd: &PointData<V>,
             k_prev: &Knot,
             k_refit: &Knot,
             k_next: &Knot,
@@ -536,8 +546,8 @@ mod refine_refit {
         }
     }
 
-    pub fn curve_incremental_simplify_refit(
-        pd: &PointData,
+    pub fn curve_incremental_simplify_refit<V: Vector>(
+        pd: &PointData<V>,
         knots: &mut Vec<Knot>,
         knots_handle: &mut Vec<min_heap::NodeHandle>,
         knots_len_remaining: &mut usize,
@@ -650,8 +660,13 @@ mod refine_refit {
 }
 
 mod refine_corner {
+    use alloc::vec::Vec;
+
     use super::types::{Knot, PointData};
     use super::{knot_calc_curve_error_value, knot_find_split_point_on_axis, INVALID};
+    use crate::curve_fit_nd::vector::Vector;
+    #[cfg(not(feature = "std"))]
+    use crate::math::F64Ext;
     use crate::min_heap;
     use crate::vec2::{DVec2, USizeVec2};
 
@@ -669,8 +684,8 @@ mod refine_corner {
     }
 
     /// (Re)calculate the error incurred from turning this into a corner.
-    fn knot_corner_error_recalculate(
-        pd: &PointData,
+    fn knot_corner_error_recalculate<V: Vector>(
+        pd: &PointData<V>,
         heap: &mut min_heap::MinHeap<f64, KnotCornerState>,
         knots_handle: &mut Vec<min_heap::NodeHandle>,
         k_split: &Knot,
@@ -730,8 +745,8 @@ mod refine_corner {
 
     // Attempt to collapse close knots into corners,
     // as long as they fall below the error threshold.
-    pub fn curve_incremental_simplify_corners(
-        pd: &PointData,
+    pub fn curve_incremental_simplify_corners<V: Vector>(
+        pd: &PointData<V>,
         knots: &mut Vec<Knot>,
         knots_handle: &mut Vec<min_heap::NodeHandle>,
         knots_len_remaining: &mut usize,
@@ -859,13 +874,38 @@ mod refine_corner {
     }
 }
 
-pub fn fit_poly_single(
-    points_orig: &Vec<DVec2>,
+/// `corner_angle` of PI or above disables corner detection,
+/// `corner_scale` multiplies `error_threshold` to give the distance a corner
+/// may be from the tangents of its neighbors.
+pub fn fit_poly_single<V: Vector>(
+    points_orig: &Vec<V>,
+    is_cyclic: bool,
+    error_threshold: f64,
+    corner_angle: f64,
+    corner_scale: f64,
+    use_optimize_exhaustive: bool,
+) -> Vec<[V; 3]> {
+    fit_poly_single_with_errors(
+        points_orig,
+        is_cyclic,
+        error_threshold,
+        corner_angle,
+        corner_scale,
+        use_optimize_exhaustive,
+    )
+    .0
+}
+
+/// Like [`fit_poly_single`], along with the largest distance of the points
+/// from each segment of the curve, one per segment starting at the first knot.
+pub fn fit_poly_single_with_errors<V: Vector>(
+    points_orig: &Vec<V>,
     is_cyclic: bool,
     error_threshold: f64,
     corner_angle: f64,
+    corner_scale: f64,
     use_optimize_exhaustive: bool,
-) -> Vec<[DVec2; 3]> {
+) -> (Vec<[V; 3]>, Vec<f64>) {
     // Double size to allow extracting wrapped contiguous slices across start/end boundaries.
     let knots_len = points_orig.len();
     let points_len = points_orig.len();
@@ -883,7 +923,7 @@ pub fn fit_poly_single(
     let mut knots_handle: Vec<min_heap::NodeHandle> =
         vec![min_heap::NodeHandle::INVALID; knots_len];
 
-    let use_corner = corner_angle < ::std::f64::consts::PI;
+    let use_corner = corner_angle < ::core::f64::consts::PI;
 
     for i in 0..knots_len {
         assert!(points_orig[i].is_finite());
@@ -915,17 +955,15 @@ pub fn fit_poly_single(
 
     // All values will be written to, simplest to initialize to dummy values for now.
     let mut points_length_cache: Vec<f64> = vec![-1.0; points_len * if is_cyclic { 2 } else { 1 }];
-    let mut tangents: Vec<DVec2> = vec![DVec2::splat(-1.0); knots_len * 2];
+    let mut tangents: Vec<V> = vec![V::zero(); knots_len * 2];
 
     // Initialize tangents,
     // also set the values for knot handles since some may not collapse.
 
     if knots_len < 2 {
         for (i, k) in (&mut knots).iter_mut().enumerate() {
-            tangents[k.tan.x].x = 0.0;
-            tangents[k.tan.x].y = 0.0;
-            tangents[k.tan.y].x = 0.0;
-            tangents[k.tan.y].y = 0.0;
+            tangents[k.tan.x] = V::zero();
+            tangents[k.tan.y] = V::zero();
             k.handles.x = 0.0;
             k.handles.y = 0.0;
             points_length_cache[i] = 0.0;
@@ -945,16 +983,13 @@ pub fn fit_poly_single(
             let mut t = tan_prev.add(tan_next);
             let _ = t.normalize();
             assert!(t.is_finite());
-            tangents[k.tan.x].x = t.x;
-            tangents[k.tan.x].y = t.y;
-            tangents[k.tan.y].x = t.x;
-            tangents[k.tan.y].y = t.y;
+            tangents[k.tan.x] = t;
+            tangents[k.tan.y] = t;
 
             k.handles.x = len_prev / 3.0;
             k.handles.y = len_next / -3.0;
 
-            tan_prev.x = tan_next.x;
-            tan_prev.y = tan_next.y;
+            tan_prev = tan_next;
 
             len_prev = len_next;
             i_curr = i_next;
@@ -964,10 +999,8 @@ pub fn fit_poly_single(
         let (mut tan_prev, mut len_prev) = points[0].normalized_diff_with_len(points[1]);
         points_length_cache[1] = len_prev;
 
-        tangents[knots[0].tan.x].x = tan_prev.x;
-        tangents[knots[0].tan.x].y = tan_prev.y;
-        tangents[knots[0].tan.y].x = tan_prev.x;
-        tangents[knots[0].tan.y].y = tan_prev.y;
+        tangents[knots[0].tan.x] = tan_prev;
+        tangents[knots[0].tan.y] = tan_prev;
 
         knots[0].handles.x = len_prev / 3.0;
         knots[0].handles.y = len_prev / -3.0;
@@ -982,25 +1015,20 @@ pub fn fit_poly_single(
             let _ = t.normalize();
             assert!(t.is_finite());
 
-            tangents[k.tan.x].x = t.x;
-            tangents[k.tan.x].y = t.y;
-            tangents[k.tan.y].x = t.x;
-            tangents[k.tan.y].y = t.y;
+            tangents[k.tan.x] = t;
+            tangents[k.tan.y] = t;
 
             k.handles.x = len_prev / 3.0;
             k.handles.y = len_next / -3.0;
 
-            tan_prev.x = tan_next.x;
-            tan_prev.y = tan_next.y;
+            tan_prev = tan_next;
 
             len_prev = len_next;
             i_curr = i_next;
         }
         // use prev as next since they're copied above
-        tangents[knots[knots_len - 1].tan.x].x = tan_prev.x;
-        tangents[knots[knots_len - 1].tan.x].y = tan_prev.y;
-        tangents[knots[knots_len - 1].tan.y].x = tan_prev.x;
-        tangents[knots[knots_len - 1].tan.y].y = tan_prev.y;
+        tangents[knots[knots_len - 1].tan.x] = tan_prev;
+        tangents[knots[knots_len - 1].tan.y] = tan_prev;
 
         knots[knots_len - 1].handles.x = len_prev / 3.0;
         knots[knots_len - 1].handles.y = len_prev / -3.0;
@@ -1038,7 +1066,7 @@ pub fn fit_poly_single(
             &mut knots_handle,
             &mut knots_len_remaining,
             DVec2::sq(error_threshold),
-            DVec2::sq(error_threshold * CORNER_SCALE),
+            DVec2::sq(error_threshold * corner_scale),
             corner_angle,
         );
     }
@@ -1058,7 +1086,8 @@ pub fn fit_poly_single(
 
     debug_assert!(knots_len_remaining >= 2);
 
-    let mut cubic_array: Vec<[DVec2; 3]> = Vec::with_capacity(knots_len_remaining);
+    let mut cubic_array: Vec<[V; 3]> = Vec::with_capacity(knots_len_remaining);
+    let mut errors: Vec<f64> = Vec::with_capacity(knots_len_remaining);
 
     {
         let k_first_index: usize = {
@@ -1085,68 +1114,262 @@ pub fn fit_poly_single(
                 *p,
                 p.madd(tangents[k.tan.y], k.handles.y),
             ]);
+            errors.push(k.fit_error_sq_next.sqrt());
 
             k_index = k.next;
         }
     }
 
-    return cubic_array;
+    // The last knot of an open curve has no segment after it.
+    if !is_cyclic {
+        errors.pop();
+    }
+
+    return (cubic_array, errors);
 }
 
-pub fn fit_poly_list(
-    poly_list_src: Vec<(bool, Vec<DVec2>)>,
+/// Fits every polygon with [`fit_poly_single`],
+/// returning the curves in the order of the polygons.
+pub fn fit_poly_list<V: Vector>(
+    poly_list_src: Vec<(bool, Vec<V>)>,
     error_threshold: f64,
     corner_angle: f64,
+    corner_scale: f64,
     use_optimize_exhaustive: bool,
-) -> Vec<(bool, Vec<[DVec2; 3]>)> {
-    let mut curve_list_dst: Vec<(bool, Vec<[DVec2; 3]>)> = Vec::new();
-
-    // Single threaded (we may want to allow users to force this).
-    if poly_list_src.len() <= 1 {
-        for (is_cyclic, poly_src) in poly_list_src {
+) -> Vec<(bool, Vec<[V; 3]>)> {
+    poly_list_src
+        .into_iter()
+        .map(|(is_cyclic, poly_src)| {
             let poly_dst = fit_poly_single(
                 &poly_src,
                 is_cyclic,
                 error_threshold,
                 corner_angle,
+                corner_scale,
                 use_optimize_exhaustive,
             );
-            // println!("{} -> {}", poly_src.len(), poly_dst.len());
-            curve_list_dst.push((is_cyclic, poly_dst));
+            (is_cyclic, poly_dst)
+        })
+        .collect()
+}
+
+/// A cubic Bézier curve.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CubicBezier {
+    pub start: DVec2,
+    pub control1: DVec2,
+    pub control2: DVec2,
+    pub end: DVec2,
+}
+
+/// Corner detection of the curve fitter.
+///
+/// Pixel art usually wants a low threshold (sharp corners everywhere),
+/// photos want corners disabled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CornerOptions {
+    pub enabled: bool,
+    /// Angle in radians between neighboring tangents above which a corner is inserted.
+    pub threshold: f64,
+    /// Distance a corner may be from the tangents of its neighbors,
+    /// relative to the fitting error.
+    pub scale: f64,
+}
+
+impl Default for CornerOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 30.0_f64.to_radians(),
+            scale: 2.0,
         }
-    } else {
-        use std::thread;
+    }
+}
+
+impl CornerOptions {
+    /// The angle passed to the fitter, where PI disables corners.
+    pub fn angle(&self) -> f64 {
+        if self.enabled {
+            self.threshold
+        } else {
+            core::f64::consts::PI
+        }
+    }
+}
 
-        let mut join_handles = Vec::with_capacity(poly_list_src.len());
-        let mut poly_vec_src = Vec::with_capacity(poly_list_src.len());
+/// Parameters of [`fit_points_to_cubics`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FitOptions {
+    /// Maximum distance the curves may deviate from the points.
+    pub error_threshold: f64,
+    /// Angle in radians between neighboring tangents above which a corner is kept,
+    /// `None` fits smooth curves only.
+    pub corner_angle: Option<f64>,
+    /// Distance a corner may be from the tangents of its neighbors,
+    /// relative to `error_threshold`.
+    pub corner_scale: f64,
+    /// Splits the segments between the points into steps no longer than this
+    /// before fitting, for sparse inputs whose curves could otherwise bulge between the points.
+    pub subdivide_limit: Option<f64>,
+    /// Refits neighboring knots together, slower but with fewer curves.
+    pub optimize_exhaustive: bool,
+}
 
-        for poly_src in poly_list_src {
-            poly_vec_src.push(poly_src);
+impl Default for FitOptions {
+    fn default() -> Self {
+        Self {
+            error_threshold: 1.0,
+            corner_angle: Some(30.0_f64.to_radians()),
+            corner_scale: 2.0,
+            subdivide_limit: None,
+            optimize_exhaustive: true,
         }
+    }
+}
 
-        // sort length for more even threading
-        // and so larger at the end so they are popped off and handled first,
-        // smaller ones can be handled when other processors are free.
-        poly_vec_src.sort_by(|a, b| a.1.len().cmp(&b.1.len()));
-
-        while let Some((is_cyclic, poly_src_clone)) = poly_vec_src.pop() {
-            join_handles.push(thread::spawn(move || {
-                let poly_dst = fit_poly_single(
-                    &poly_src_clone,
-                    is_cyclic,
-                    error_threshold,
-                    corner_angle,
-                    use_optimize_exhaustive,
-                );
-                // println!("{} -> {}", poly_src_clone.len(), poly_dst.len());
-                (is_cyclic, poly_dst)
-            }));
+/// Fits a chain of cubic curves through `points`, e.g. the samples of a pen stroke,
+/// each curve starting at the end of the previous one.
+///
+/// Cyclic points are closed by a last curve ending at the start of the first one.
+/// Repeated points are ignored, fewer than two distinct points give no curves.
+///
+/// ```
+/// use svgen::{curve_fit_nd::{fit_points_to_cubics, FitOptions}, vec2::DVec2};
+///
+/// // A quarter circle sampled every 5 degrees.
+/// let points = (0..=18)
+///     .map(|i| (i as f64 * 5.0).to_radians())
+///     .map(|a| DVec2::new(100.0 * a.cos(), 100.0 * a.sin()))
+///     .collect::<Vec<_>>();
+/// let curves = fit_points_to_cubics(&points, false, &FitOptions::default());
+///
+/// assert!(curves.len() < points.len() - 1);
+/// assert_eq!(curves[0].start, points[0]);
+/// assert_eq!(curves.last().unwrap().end, points[18]);
+/// ```
+pub fn fit_points_to_cubics(
+    points: &[DVec2],
+    is_cyclic: bool,
+    opts: &FitOptions,
+) -> Vec<CubicBezier> {
+    fit_points(points, is_cyclic, opts)
+        .into_iter()
+        .map(|[start, control1, control2, end]| CubicBezier {
+            start,
+            control1,
+            control2,
+            end,
+        })
+        .collect()
+}
+
+/// Like [`fit_points_to_cubics`] in any number of dimensions,
+/// each curve being `[start, control1, control2, end]`.
+///
+/// ```
+/// use svgen::curve_fit_nd::{fit_points, FitOptions};
+///
+/// // A helix sampled every 10 degrees.
+/// let points = (0..=72)
+///     .map(|i| (i as f64 * 10.0).to_radians())
+///     .map(|a| [50.0 * a.cos(), 50.0 * a.sin(), 5.0 * a])
+///     .collect::<Vec<_>>();
+/// let curves = fit_points(&points, false, &FitOptions::default());
+///
+/// assert!(curves.len() < points.len() - 1);
+/// assert_eq!(curves[0][0], points[0]);
+/// assert_eq!(curves.last().unwrap()[3], points[72]);
+/// ```
+pub fn fit_points<V: Vector>(points: &[V], is_cyclic: bool, opts: &FitOptions) -> Vec<[V; 4]> {
+    let mut points = points.to_vec();
+    points.dedup();
+    if is_cyclic && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    if points.len() < 2 {
+        return Vec::new();
+    }
+    if let Some(limit) = opts.subdivide_limit {
+        points = points_subdivide_to_limit(is_cyclic, &points, limit);
+    }
+
+    let knots = fit_poly_single(
+        &points,
+        is_cyclic,
+        opts.error_threshold,
+        opts.corner_angle.unwrap_or(core::f64::consts::PI),
+        opts.corner_scale,
+        opts.optimize_exhaustive,
+    );
+
+    let count = if is_cyclic {
+        knots.len()
+    } else {
+        knots.len().saturating_sub(1)
+    };
+    (0..count)
+        .map(|i| {
+            let (k_prev, k_curr) = (&knots[i], &knots[(i + 1) % knots.len()]);
+            [k_prev[1], k_prev[2], k_curr[0], k_curr[1]]
+        })
+        .collect()
+}
+
+/// Splits the segments between the points into steps no longer than `limit`.
+fn points_subdivide_to_limit<V: Vector>(is_cyclic: bool, points: &[V], limit: f64) -> Vec<V> {
+    let mut points_dst = Vec::with_capacity(points.len());
+    if !is_cyclic {
+        points_dst.push(points[0]);
+    }
+
+    let mut v_prev = points[if is_cyclic { points.len() - 1 } else { 0 }];
+    for v_curr in &points[if is_cyclic { 0 } else { 1 }..] {
+        let sub = (v_prev.len_with(*v_curr) / limit).ceil() as usize;
+        for i in 1..sub {
+            points_dst.push(v_prev.interp(*v_curr, i as f64 / sub as f64));
         }
+        points_dst.push(*v_curr);
+        v_prev = *v_curr;
+    }
 
-        for child in join_handles {
-            curve_list_dst.push(child.join().unwrap());
+    points_dst
+}
+
+// Subdivide until segments are smaller then the limit
+pub fn poly_subdivide_to_limit(is_cyclic: bool, poly_src: &Vec<DVec2>, limit: f64) -> Vec<DVec2> {
+    // target size isn't known. but will be at least as big as the source
+    let mut poly_dst: Vec<DVec2> = Vec::with_capacity(poly_src.len());
+
+    let limit_sq = DVec2::sq(limit);
+    let mut v_orig_prev = &poly_src[if is_cyclic { poly_src.len() - 1 } else { 0 }];
+    if !is_cyclic {
+        poly_dst.push(*v_orig_prev);
+    }
+
+    for v_orig_curr in &poly_src[(if is_cyclic { 0 } else { 1 })..] {
+        // subdivided point(s)
+        let len_sq = v_orig_prev.len_squared_with(*v_orig_curr);
+        if len_sq > limit_sq {
+            let len = len_sq.sqrt();
+            let sub = (len / limit).floor();
+            let inc = 1.0 / sub;
+            let mut step = inc;
+            for _ in 0..((sub as usize) - 1) {
+                poly_dst.push(v_orig_prev.interp(*v_orig_curr, step));
+                debug_assert!(step > 0.0 && step < 1.0);
+                step += inc;
+            }
         }
+        // regular point
+        poly_dst.push(*v_orig_curr);
+        v_orig_prev = v_orig_curr;
     }
 
-    curve_list_dst
+    return poly_dst;
+}
+
+pub fn poly_list_subdivide_to_limit(poly_list_src: &mut Vec<(bool, Vec<DVec2>)>, limit: f64) {
+    poly_list_src.iter_mut().for_each(|(is_cyclic, poly_src)| {
+        *poly_src = poly_subdivide_to_limit(*is_cyclic, &poly_src, limit)
+    })
 }
@@ -1,22 +1,22 @@
-use super::vec2::DVec2;
+use alloc::{vec, vec::Vec};
 
-mod types {
-    use crate::vec2::DVec2;
+use super::vector::Vector;
 
+mod types {
     #[derive(Copy, Clone)]
-    pub struct Cubic {
-        pub p0: DVec2,
-        pub p1: DVec2,
-        pub p2: DVec2,
-        pub p3: DVec2,
+    pub struct Cubic<V> {
+        pub p0: V,
+        pub p1: V,
+        pub p2: V,
+        pub p3: V,
     }
 }
 
 mod cubic_solve_fallback {
     use super::types;
-    use crate::vec2::DVec2;
+    use crate::curve_fit_nd::vector::Vector;
 
-    pub fn calc(points: &[DVec2], tan_l: &DVec2, tan_r: &DVec2) -> types::Cubic {
+    pub fn calc<V: Vector>(points: &[V], tan_l: &V, tan_r: &V) -> types::Cubic<V> {
         let p0 = &points[0];
         let p3 = &points[points.len() - 1];
         let alpha = p0.len_with(*p3) / 3.0;
@@ -32,14 +32,14 @@ mod cubic_solve_fallback {
 
 mod cubic_solve_least_square {
     use super::types;
-    use crate::vec2::DVec2;
+    use crate::{curve_fit_nd::vector::Vector, vec2::DVec2};
 
-    pub fn calc(
-        points: &[DVec2],
-        tan_l: &DVec2,
-        tan_r: &DVec2,
+    pub fn calc<V: Vector>(
+        points: &[V],
+        tan_l: &V,
+        tan_r: &V,
         u_prime: &[f64],
-    ) -> Option<types::Cubic> {
+    ) -> Option<types::Cubic<V>> {
         let p0 = &points[0];
         let p3 = &points[points.len() - 1];
 
@@ -48,27 +48,23 @@ mod cubic_solve_least_square {
             let mut c: [DVec2; 2] = [DVec2::ZERO, DVec2::ZERO];
 
             for (pt, u) in points.iter().zip(u_prime) {
-                let a: [DVec2; 2] = [tan_l.mul(bezier::b1(*u)), tan_r.mul(bezier::b2(*u))];
+                let a: [V; 2] = [tan_l.mul(bezier::b1(*u)), tan_r.mul(bezier::b2(*u))];
 
                 let b0_plus_b1 = bezier::b0_plus_b1(*u);
                 let b2_plus_b3 = bezier::b2_plus_b3(*u);
 
                 // inline dot product
-                let tmp = (pt.x - (p0.x * b0_plus_b1)) + (p3.x * b2_plus_b3);
-                x.x += a[0].x * tmp;
-                x.y += a[1].x * tmp;
-
-                c[0].x += a[0].x * a[0].x;
-                c[0].y += a[0].x * a[1].x;
-                c[1].y += a[1].x * a[1].x;
-
-                let tmp = (pt.y - (p0.y * b0_plus_b1)) + (p3.y * b2_plus_b3);
-                x.x += a[0].y * tmp;
-                x.y += a[1].y * tmp;
-
-                c[0].x += a[0].y * a[0].y;
-                c[0].y += a[0].y * a[1].y;
-                c[1].y += a[1].y * a[1].y;
+                for j in 0..V::DIM {
+                    let (a0, a1) = (a[0].component(j), a[1].component(j));
+                    let tmp = (pt.component(j) - (p0.component(j) * b0_plus_b1))
+                        + (p3.component(j) * b2_plus_b3);
+                    x.x += a0 * tmp;
+                    x.y += a1 * tmp;
+
+                    c[0].x += a0 * a0;
+                    c[0].y += a0 * a1;
+                    c[1].y += a1 * a1;
+                }
 
                 c[1].x = c[0].y;
             }
@@ -126,14 +122,16 @@ mod cubic_solve_least_square {
 
 mod cubic_solve_circle {
     use super::types;
-    use crate::vec2::DVec2;
-
-    pub fn calc(
-        points: &[DVec2],
-        tan_l: &DVec2,
-        tan_r: &DVec2,
+    #[cfg(not(feature = "std"))]
+    use crate::math::F64Ext;
+    use crate::{curve_fit_nd::vector::Vector, vec2::DVec2};
+
+    pub fn calc<V: Vector>(
+        points: &[V],
+        tan_l: &V,
+        tan_r: &V,
         points_coords_length: f64,
-    ) -> Option<types::Cubic> {
+    ) -> Option<types::Cubic<V>> {
         let p0 = &points[0];
         let p3 = &points[points.len() - 1];
 
@@ -157,7 +155,7 @@ mod cubic_solve_circle {
     //
     // Return the scale representing how much larger the distance around the circle is.
 
-    fn points_calc_circumference_factor(tan_l: &DVec2, tan_r: &DVec2) -> f64 {
+    fn points_calc_circumference_factor<V: Vector>(tan_l: &V, tan_r: &V) -> f64 {
         let dot = tan_l.dot(*tan_r);
 
         let len_tangent = if dot < 0.0 {
@@ -178,11 +176,11 @@ mod cubic_solve_circle {
 
             // assert(angle >= len_tangent);
             let factor = angle / len_tangent;
-            debug_assert!(factor < (std::f64::consts::PI / 2.0) + (f64::EPSILON * 10.0));
+            debug_assert!(factor < (core::f64::consts::PI / 2.0) + (f64::EPSILON * 10.0));
             return factor;
         } else {
             // tangents are exactly aligned (think two opposite sides of a circle).
-            return std::f64::consts::PI / 2.0;
+            return core::f64::consts::PI / 2.0;
         }
     }
 
@@ -190,7 +188,7 @@ mod cubic_solve_circle {
     // to define a handle, given both points are on a perfect circle.
     //
     // Note: the return value will need to be multiplied by 1.3... for correct results.
-    fn points_calc_circle_tangent_factor(tan_l: &DVec2, tan_r: &DVec2) -> Option<f64> {
+    fn points_calc_circle_tangent_factor<V: Vector>(tan_l: &V, tan_r: &V) -> Option<f64> {
         let tan_dot = tan_l.dot(*tan_r);
         if tan_dot > 1.0 - DVec2::EPS {
             // no angle difference (use fallback, length wont make any difference)
@@ -211,11 +209,11 @@ mod cubic_solve_circle {
 
     // Calculate the scale the handles, which serves as a best-guess
     // used as a fallback when the least-square solution fails.
-    fn points_calc_cubic_scale(
-        v_l: &DVec2,
-        v_r: &DVec2,
-        tan_l: &DVec2,
-        tan_r: &DVec2,
+    fn points_calc_cubic_scale<V: Vector>(
+        v_l: &V,
+        v_r: &V,
+        tan_l: &V,
+        tan_r: &V,
         coords_length: f64,
     ) -> Option<f64> {
         if let Some(len_circle_factor) = points_calc_circle_tangent_factor(tan_l, tan_r) {
@@ -244,16 +242,16 @@ mod cubic_solve_circle {
 
 mod cubic_solve_offset {
     use super::types;
-    use crate::vec2::DVec2;
+    use crate::{curve_fit_nd::vector::Vector, vec2::DVec2};
 
-    pub fn calc(points: &[DVec2], tan_l: &DVec2, tan_r: &DVec2) -> Option<types::Cubic> {
+    pub fn calc<V: Vector>(points: &[V], tan_l: &V, tan_r: &V) -> Option<types::Cubic<V>> {
         let p0 = &points[0];
         let p3 = &points[points.len() - 1];
 
         let dir_unit = p3.normalized_diff(*p0);
 
         // note that normalizing output here is only for better accuracy, not essential.
-        let a: [DVec2; 2] = [
+        let a: [V; 2] = [
             tan_l.project_plane(dir_unit).normalized(),
             tan_r.project_plane(dir_unit).normalized().negated(),
         ];
@@ -299,7 +297,7 @@ mod cubic_solve_offset {
 /// * `u` - Parameter value for `p`.
 ///
 /// Note: return value may be `nan` caller must check for this.
-fn cubic_find_root(cubic: &types::Cubic, p: &DVec2, u: f64) -> f64 {
+fn cubic_find_root<V: Vector>(cubic: &types::Cubic<V>, p: &V, u: f64) -> f64 {
     // Newton-Raphson Method.
     // all vectors
     let q0_u = cubic_calc_point(cubic, u).sub(*p);
@@ -313,9 +311,9 @@ fn cubic_find_root(cubic: &types::Cubic, p: &DVec2, u: f64) -> f64 {
 }
 
 /// Given set of points and their parameterization, try to find a better parameterization.
-fn cubic_reparameterize(
-    cubic: &types::Cubic,
-    points: &[DVec2],
+fn cubic_reparameterize<V: Vector>(
+    cubic: &types::Cubic<V>,
+    points: &[V],
     u_prime_src: &[f64],
 
     u_prime_dst: &mut [f64],
@@ -343,7 +341,10 @@ fn cubic_reparameterize(
     return true;
 }
 
-fn points_calc_coord_length(points: &[DVec2], points_length_cache: &[f64]) -> (Vec<f64>, f64) {
+fn points_calc_coord_length<V: Vector>(
+    points: &[V],
+    points_length_cache: &[f64],
+) -> (Vec<f64>, f64) {
     let mut u: Vec<f64> = Vec::with_capacity(points.len());
     u.push(0.0);
 
@@ -368,51 +369,46 @@ fn points_calc_coord_length(points: &[DVec2], points_length_cache: &[f64]) -> (V
     return (u, w);
 }
 
-fn cubic_calc_point(cubic: &types::Cubic, t: f64) -> DVec2 {
-    let p0 = &cubic.p0;
-    let p1 = &cubic.p1;
-    let p2 = &cubic.p2;
-    let p3 = &cubic.p3;
+fn cubic_calc_point<V: Vector>(cubic: &types::Cubic<V>, t: f64) -> V {
+    let (p0, p1, p2, p3) = (cubic.p0, cubic.p1, cubic.p2, cubic.p3);
     let s = 1.0 - t;
 
-    let p01_x = (p0.x * s) + (p1.x * t);
-    let p12_x = (p1.x * s) + (p2.x * t);
-    let p23_x = (p2.x * s) + (p3.x * t);
-
-    let p01_y = (p0.y * s) + (p1.y * t);
-    let p12_y = (p1.y * s) + (p2.y * t);
-    let p23_y = (p2.y * s) + (p3.y * t);
-
-    DVec2::new(
-        (((p01_x * s) + (p12_x * t)) * s) + (((p12_x * s) + (p23_x * t)) * t),
-        (((p01_y * s) + (p12_y * t)) * s) + (((p12_y * s) + (p23_y * t)) * t),
-    )
+    V::from_fn(|j| {
+        let p01 = (p0.component(j) * s) + (p1.component(j) * t);
+        let p12 = (p1.component(j) * s) + (p2.component(j) * t);
+        let p23 = (p2.component(j) * s) + (p3.component(j) * t);
+        (((p01 * s) + (p12 * t)) * s) + (((p12 * s) + (p23 * t)) * t)
+    })
 }
 
-fn cubic_calc_speed(cubic: &types::Cubic, t: f64) -> DVec2 {
-    let p0 = &cubic.p0;
-    let p1 = &cubic.p1;
-    let p2 = &cubic.p2;
-    let p3 = &cubic.p3;
+fn cubic_calc_speed<V: Vector>(cubic: &types::Cubic<V>, t: f64) -> V {
+    let (p0, p1, p2, p3) = (cubic.p0, cubic.p1, cubic.p2, cubic.p3);
     let s = 1.0 - t;
 
-    DVec2::new(
-        3.0 * ((p1.x - p0.x) * s * s + 2.0 * (p2.x - p0.x) * s * t + (p3.x - p2.x) * t * t),
-        3.0 * ((p1.y - p0.y) * s * s + 2.0 * (p2.y - p0.y) * s * t + (p3.y - p2.y) * t * t),
-    )
+    V::from_fn(|j| {
+        let (p0, p1, p2, p3) = (
+            p0.component(j),
+            p1.component(j),
+            p2.component(j),
+            p3.component(j),
+        );
+        3.0 * ((p1 - p0) * s * s + 2.0 * (p2 - p0) * s * t + (p3 - p2) * t * t)
+    })
 }
 
-fn cubic_calc_acceleration(cubic: &types::Cubic, t: f64) -> DVec2 {
-    let p0 = &cubic.p0;
-    let p1 = &cubic.p1;
-    let p2 = &cubic.p2;
-    let p3 = &cubic.p3;
+fn cubic_calc_acceleration<V: Vector>(cubic: &types::Cubic<V>, t: f64) -> V {
+    let (p0, p1, p2, p3) = (cubic.p0, cubic.p1, cubic.p2, cubic.p3);
     let s = 1.0 - t;
 
-    DVec2::new(
-        6.0 * ((p2.x - 2.0 * p1.x + p0.x) * s + (p3.x - 2.0 * p2.x + p1.x) * t),
-        6.0 * ((p2.y - 2.0 * p1.y + p0.y) * s + (p3.y - 2.0 * p2.y + p1.y) * t),
-    )
+    V::from_fn(|j| {
+        let (p0, p1, p2, p3) = (
+            p0.component(j),
+            p1.component(j),
+            p2.component(j),
+            p3.component(j),
+        );
+        6.0 * ((p2 - 2.0 * p1 + p0) * s + (p3 - 2.0 * p2 + p1) * t)
+    })
 }
 
 #[derive(Clone, Copy)]
@@ -421,7 +417,7 @@ struct FitError {
     pub index: usize,
 }
 
-fn cubic_calc_error(cubic: &types::Cubic, points: &[DVec2], u: &[f64]) -> FitError {
+fn cubic_calc_error<V: Vector>(cubic: &types::Cubic<V>, points: &[V], u: &[f64]) -> FitError {
     let mut error_max_sq = -1.0;
 
     // no need to measure first & last points
@@ -450,9 +446,9 @@ fn cubic_calc_error(cubic: &types::Cubic, points: &[DVec2], u: &[f64]) -> FitErr
 
 /// Like `cubic_calc_error` but return None
 /// in the case we can't improve on `error_max_sq_limit`.
-fn cubic_calc_error_limit(
-    cubic: &types::Cubic,
-    points: &[DVec2],
+fn cubic_calc_error_limit<V: Vector>(
+    cubic: &types::Cubic<V>,
+    points: &[V],
     u: &[f64],
     error_max_sq_limit: f64,
 ) -> Option<FitError> {
@@ -485,12 +481,12 @@ fn cubic_calc_error_limit(
     });
 }
 
-fn fit_cubic_to_points(
-    points: &[DVec2],
+fn fit_cubic_to_points<V: Vector>(
+    points: &[V],
     points_length_cache: &[f64],
-    tan_l: &DVec2,
-    tan_r: &DVec2,
-) -> (types::Cubic, FitError) {
+    tan_l: &V,
+    tan_r: &V,
+) -> (types::Cubic<V>, FitError) {
     let iteration_max = 4;
 
     assert!(points.len() > 2);
@@ -562,7 +558,7 @@ fn fit_cubic_to_points(
                     // break if we're getting worse
                     // break;
                 }
-                ::std::mem::swap(&mut u, &mut u_prime);
+                ::core::mem::swap(&mut u, &mut u_prime);
             } else {
                 break;
             }
@@ -582,12 +578,12 @@ fn fit_cubic_to_points(
 //
 // Return error squared, and both handle locations
 //
-pub fn curve_fit_cubic_to_points_single(
-    points: &[DVec2],
+pub fn curve_fit_cubic_to_points_single<V: Vector>(
+    points: &[V],
     points_length_cache: &[f64],
-    tan_l: &DVec2,
-    tan_r: &DVec2,
-) -> ((f64, usize), DVec2, DVec2) {
+    tan_l: &V,
+    tan_r: &V,
+) -> ((f64, usize), V, V) {
     let (cubic, fit_error) = fit_cubic_to_points(points, points_length_cache, tan_l, tan_r);
 
     return ((fit_error.max_sq, fit_error.index), cubic.p1, cubic.p2);
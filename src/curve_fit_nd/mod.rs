@@ -1,6 +1,13 @@
 mod curve_fit_from_polys;
 mod curve_fit_single;
+mod vector;
 
 pub use crate::vec2;
 
-pub use self::curve_fit_from_polys::{fit_poly_list, fit_poly_single, TraceMode};
+pub use self::vector::Vector;
+
+pub use self::curve_fit_from_polys::{
+    fit_points, fit_points_to_cubics, fit_poly_list, fit_poly_single, fit_poly_single_with_errors,
+    poly_list_subdivide_to_limit, poly_subdivide_to_limit, CornerOptions, CubicBezier, FitOptions,
+    TraceMode,
+};
@@ -0,0 +1,271 @@
+#[cfg(not(feature = "std"))]
+use crate::math::F64Ext;
+use crate::vec2::DVec2;
+
+/// A point or direction the curves are fitted in,
+/// e.g. [`DVec2`] for outlines, `[f64; 3]` for 3D polylines.
+///
+/// Only the components have to be provided,
+/// the arithmetic used by the fitter is derived from them.
+pub trait Vector: Copy + PartialEq + Send + Sync + 'static {
+    /// Number of dimensions.
+    const DIM: usize;
+
+    /// The component of dimension `i`, in `0..DIM`.
+    fn component(self, i: usize) -> f64;
+
+    /// A vector whose component of dimension `i` is `f(i)`.
+    fn from_fn(f: impl FnMut(usize) -> f64) -> Self;
+
+    /// All zeroes.
+    #[inline]
+    fn zero() -> Self {
+        Self::from_fn(|_| 0.0)
+    }
+
+    #[inline]
+    fn is_finite(self) -> bool {
+        (0..Self::DIM).all(|i| self.component(i).is_finite())
+    }
+
+    #[inline]
+    fn negated(self) -> Self {
+        Self::from_fn(|i| -self.component(i))
+    }
+
+    #[inline]
+    fn dot(self, other: Self) -> f64 {
+        (0..Self::DIM).fold(0.0, |dot, i| dot + self.component(i) * other.component(i))
+    }
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self::from_fn(|i| self.component(i) + other.component(i))
+    }
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self::from_fn(|i| self.component(i) - other.component(i))
+    }
+
+    /// Linear interpolation between self and other by factor `t`.
+    #[inline]
+    fn interp(self, other: Self, t: f64) -> Self {
+        let s = 1.0 - t;
+        Self::from_fn(|i| self.component(i) * s + other.component(i) * t)
+    }
+
+    /// Multiply-add: self + (other * f).
+    #[inline]
+    fn madd(self, other: Self, f: f64) -> Self {
+        Self::from_fn(|i| self.component(i) + other.component(i) * f)
+    }
+
+    /// Multiply-subtract: self - (other * f).
+    #[inline]
+    fn msub(self, other: Self, f: f64) -> Self {
+        Self::from_fn(|i| self.component(i) - other.component(i) * f)
+    }
+
+    #[inline]
+    fn mul(self, f: f64) -> Self {
+        Self::from_fn(|i| self.component(i) * f)
+    }
+
+    #[inline]
+    fn len_squared(self) -> f64 {
+        self.dot(self)
+    }
+
+    #[inline]
+    fn len_squared_with(self, other: Self) -> f64 {
+        self.sub(other).len_squared()
+    }
+
+    #[inline]
+    fn len_with(self, other: Self) -> f64 {
+        self.len_squared_with(other).sqrt()
+    }
+
+    #[inline]
+    fn len_negated_with(self, other: Self) -> f64 {
+        self.add(other).len_squared().sqrt()
+    }
+
+    /// Normalizes the vector in-place, returning its original length.
+    /// Zero vectors are left unchanged.
+    #[inline]
+    fn normalize(&mut self) -> f64 {
+        let d = self.len_squared().sqrt();
+        if d != 0.0 {
+            *self = self.mul(1.0 / d);
+        }
+        d
+    }
+
+    #[inline]
+    fn normalized(self) -> Self {
+        let mut v = self;
+        let _ = v.normalize();
+        v
+    }
+
+    /// The normalized difference (self - other).
+    #[inline]
+    fn normalized_diff(self, other: Self) -> Self {
+        self.sub(other).normalized()
+    }
+
+    /// The normalized difference (self - other) along with its original length.
+    #[inline]
+    fn normalized_diff_with_len(self, other: Self) -> (Self, f64) {
+        let mut v = self.sub(other);
+        let d = v.normalize();
+        (v, d)
+    }
+
+    /// Projects self onto the normalized vector `proj`.
+    #[inline]
+    fn project_onto_normalized(self, proj: Self) -> Self {
+        proj.mul(self.dot(proj))
+    }
+
+    /// The component of self orthogonal to the normalized vector `plane`.
+    #[inline]
+    fn project_plane(self, plane: Self) -> Self {
+        self.sub(self.project_onto_normalized(plane))
+    }
+}
+
+impl Vector for DVec2 {
+    const DIM: usize = 2;
+
+    #[inline]
+    fn component(self, i: usize) -> f64 {
+        match i {
+            0 => self.x,
+            _ => self.y,
+        }
+    }
+
+    #[inline]
+    fn from_fn(mut f: impl FnMut(usize) -> f64) -> Self {
+        DVec2::new(f(0), f(1))
+    }
+
+    // The fitter is hot, use the unrolled arithmetic of `DVec2`.
+
+    #[inline]
+    fn zero() -> Self {
+        DVec2::ZERO
+    }
+
+    #[inline]
+    fn is_finite(self) -> bool {
+        DVec2::is_finite(self)
+    }
+
+    #[inline]
+    fn negated(self) -> Self {
+        DVec2::negated(self)
+    }
+
+    #[inline]
+    fn dot(self, other: Self) -> f64 {
+        DVec2::dot(self, other)
+    }
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        DVec2::add(self, other)
+    }
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        DVec2::sub(self, other)
+    }
+
+    #[inline]
+    fn interp(self, other: Self, t: f64) -> Self {
+        DVec2::interp(self, other, t)
+    }
+
+    #[inline]
+    fn madd(self, other: Self, f: f64) -> Self {
+        DVec2::madd(self, other, f)
+    }
+
+    #[inline]
+    fn msub(self, other: Self, f: f64) -> Self {
+        DVec2::msub(self, other, f)
+    }
+
+    #[inline]
+    fn mul(self, f: f64) -> Self {
+        DVec2::mul(self, f)
+    }
+
+    #[inline]
+    fn len_squared(self) -> f64 {
+        DVec2::len_squared(self)
+    }
+
+    #[inline]
+    fn len_squared_with(self, other: Self) -> f64 {
+        DVec2::len_squared_with(self, other)
+    }
+
+    #[inline]
+    fn len_with(self, other: Self) -> f64 {
+        DVec2::len_with(self, other)
+    }
+
+    #[inline]
+    fn len_negated_with(self, other: Self) -> f64 {
+        DVec2::len_negated_with(self, other)
+    }
+
+    #[inline]
+    fn normalize(&mut self) -> f64 {
+        DVec2::normalize(self)
+    }
+
+    #[inline]
+    fn normalized(self) -> Self {
+        DVec2::normalized(self)
+    }
+
+    #[inline]
+    fn normalized_diff(self, other: Self) -> Self {
+        DVec2::normalized_diff(self, other)
+    }
+
+    #[inline]
+    fn normalized_diff_with_len(self, other: Self) -> (Self, f64) {
+        DVec2::normalized_diff_with_len(self, other)
+    }
+
+    #[inline]
+    fn project_onto_normalized(self, proj: Self) -> Self {
+        DVec2::project_onto_normalized(self, proj)
+    }
+
+    #[inline]
+    fn project_plane(self, plane: Self) -> Self {
+        DVec2::project_plane(self, plane)
+    }
+}
+
+impl<const N: usize> Vector for [f64; N] {
+    const DIM: usize = N;
+
+    #[inline]
+    fn component(self, i: usize) -> f64 {
+        self[i]
+    }
+
+    #[inline]
+    fn from_fn(f: impl FnMut(usize) -> f64) -> Self {
+        core::array::from_fn(f)
+    }
+}
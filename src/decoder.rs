@@ -0,0 +1,409 @@
+use std::io::{BufRead, BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+#[cfg(feature = "gif")]
+use image::codecs::gif::GifDecoder;
+#[cfg(feature = "png")]
+use image::{codecs::png::PngDecoder, ImageBuffer, Rgba};
+use image::{
+    error::{DecodingError, ImageFormatHint},
+    DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader, RgbaImage,
+};
+#[cfg(any(feature = "png", feature = "gif"))]
+use image::{AnimationDecoder, Frames, ImageResult};
+
+#[cfg(feature = "icc")]
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+#[cfg(feature = "icc")]
+use crate::telemetry::warn;
+use crate::{
+    error::SvgenError,
+    structs::{InputFormat, Options},
+};
+
+/// Decoded pixels, along with the exact palette of indexed images.
+pub type DecodedImage = (RgbaImage, Option<Vec<[u8; 4]>>);
+
+/// `(frame, delay_ms)` pairs, along with the exact palette of still indexed images.
+pub type FramesWithPalette = (Vec<(RgbaImage, f64)>, Option<Vec<[u8; 4]>>);
+
+/// Largest palette handled by [`decode_indexed_png`],
+/// bigger palettes go through quantization as usual.
+pub const INDEXED_PALETTE_MAX: usize = 16;
+
+/// Largest image handled by [`decode_indexed_png`], in pixels, whose RGBA pixels fit
+/// the 512 MiB allocations `image` allows by default. Bigger ones are left to `image` to reject.
+#[cfg(feature = "png")]
+const INDEXED_PIXELS_MAX: u64 = 512 * 1024 * 1024 / 4;
+
+/// Rejects detected formats whose feature is disabled or that are not in
+/// [`Options::input_formats`].
+pub fn check_format(format: Option<ImageFormat>, options: &Options) -> Result<(), SvgenError> {
+    let Some(format) = format else {
+        return Err(SvgenError::UnsupportedFormat("unknown".to_string()));
+    };
+    match InputFormat::from_image_format(format) {
+        Some(input_format)
+            if input_format.is_enabled() && options.input_formats.contains(&input_format) =>
+        {
+            Ok(())
+        }
+        _ => Err(SvgenError::UnsupportedFormat(
+            format.extensions_str()[0].to_string(),
+        )),
+    }
+}
+
+/// Like [`decode_rgba`], decoding PNG files without checking their chunk checksums
+/// with [`Options::ignore_png_crc`].
+#[cfg_attr(not(feature = "png"), allow(unused_variables))]
+pub fn decode_rgba_with_options<R: BufRead + Seek>(
+    image_reader: ImageReader<R>,
+    options: &Options,
+) -> Result<RgbaImage, SvgenError> {
+    #[cfg(feature = "png")]
+    if options.ignore_png_crc && image_reader.format() == Some(ImageFormat::Png) {
+        return decode_png_ignoring_crc(image_reader.into_inner());
+    }
+    decode_rgba(image_reader)
+}
+
+/// Decodes the first frame of a PNG file through `png`, whose checksum validation
+/// `image` doesn't let callers turn off.
+#[cfg(feature = "png")]
+fn decode_png_ignoring_crc<R: BufRead + Seek>(reader: R) -> Result<RgbaImage, SvgenError> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.ignore_checksums(true);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).map_err(png_error)?;
+    buffer.truncate(frame.buffer_size());
+
+    let (width, height) = (frame.width, frame.height);
+    let image = match frame.color_type {
+        png::ColorType::Grayscale => {
+            ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma8)
+        }
+        png::ColorType::GrayscaleAlpha => {
+            ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLumaA8)
+        }
+        png::ColorType::Rgb => {
+            ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb8)
+        }
+        // Palettes are expanded by the transformations.
+        png::ColorType::Rgba | png::ColorType::Indexed => {
+            ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgba8)
+        }
+    };
+    Ok(image
+        .ok_or_else(|| png_error("frame buffer size mismatch"))?
+        .to_rgba8())
+}
+
+/// Critical chunks of the PNG specification, see [`check_chunks`].
+const PNG_CRITICAL_CHUNKS: [[u8; 4]; 4] = [*b"IHDR", *b"PLTE", *b"IDAT", *b"IEND"];
+
+/// CRC-32 lookup table of PNG chunk checksums, for the reflected polynomial `0xEDB88320`.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+/// Continues the CRC-32 `crc` (starting at `0xFFFFFFFF`, final value inverted) over `bytes`.
+fn update_crc(crc: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(crc, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// A PNG decoding error of `message`.
+fn png_error(message: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> SvgenError {
+    SvgenError::Image(ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Exact(ImageFormat::Png),
+        message,
+    )))
+}
+
+/// Rejects PNG files with a critical chunk outside the specification, which decoders
+/// must not skip, as `image` would, or with a chunk whose checksum doesn't match,
+/// unless [`Options::ignore_png_crc`]. Other formats are let through.
+///
+/// Truncated chunks and the IHDR layout are validated while decoding.
+pub fn check_chunks<R: BufRead + Seek>(
+    image_reader: ImageReader<R>,
+    options: &Options,
+) -> Result<ImageReader<R>, SvgenError> {
+    if image_reader.format() != Some(ImageFormat::Png) {
+        return Ok(image_reader);
+    }
+    let mut reader = image_reader.into_inner();
+    let start = reader.stream_position()?;
+    // Skip the signature.
+    reader.seek(SeekFrom::Current(8))?;
+    let mut header = [0; 8];
+    loop {
+        match reader.read_exact(&mut header) {
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+            result => result?,
+        }
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let kind: [u8; 4] = header[4..].try_into().unwrap();
+        let name = String::from_utf8_lossy(&kind);
+        if kind[0].is_ascii_uppercase() && !PNG_CRITICAL_CHUNKS.contains(&kind) {
+            return Err(png_error(format!("unknown critical chunk {name}")));
+        }
+        if options.ignore_png_crc {
+            // Data and checksum.
+            reader.seek(SeekFrom::Current(length as i64 + 4))?;
+        } else {
+            let mut crc = update_crc(u32::MAX, &kind);
+            let mut data = (&mut reader).take(length as u64);
+            loop {
+                let buffer = data.fill_buf()?;
+                if buffer.is_empty() {
+                    break;
+                }
+                crc = update_crc(crc, buffer);
+                let consumed = buffer.len();
+                data.consume(consumed);
+            }
+            let mut checksum = [0; 4];
+            match reader.read_exact(&mut checksum) {
+                // Truncated, reported while decoding.
+                Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+                result => result?,
+            }
+            if u32::from_be_bytes(checksum) != !crc {
+                return Err(png_error(format!("checksum mismatch in chunk {name}")));
+            }
+        }
+        if kind == *b"IEND" {
+            break;
+        }
+    }
+    reader.seek(SeekFrom::Start(start))?;
+
+    let mut image_reader = ImageReader::new(reader);
+    image_reader.set_format(ImageFormat::Png);
+    Ok(image_reader)
+}
+
+/// Decodes the image of a reader, rotated and flipped upright as set by its EXIF orientation,
+/// e.g. for photos taken with a phone held sideways.
+///
+/// With the `icc` feature, colors are converted to sRGB from the embedded ICC profile.
+pub fn decode_rgba<R: BufRead + Seek>(
+    image_reader: ImageReader<R>,
+) -> Result<RgbaImage, SvgenError> {
+    let mut decoder = image_reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    #[cfg(feature = "icc")]
+    let icc_profile = decoder.icc_profile()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    let image = image.to_rgba8();
+    #[cfg(feature = "icc")]
+    let image = {
+        let mut image = image;
+        if let Some(icc_profile) = icc_profile {
+            convert_to_srgb(&icc_profile, &mut image);
+        }
+        image
+    };
+    Ok(image)
+}
+
+/// Converts RGBA `pixels` from the embedded ICC `profile` to sRGB, keeping their alpha.
+/// Profiles that can't be parsed or that are not RGB, e.g. of CMYK JPEGs,
+/// leave the pixels as they are.
+#[cfg(feature = "icc")]
+pub fn convert_to_srgb(profile: &[u8], pixels: &mut [u8]) {
+    let result = ColorProfile::new_from_slice(profile).and_then(|profile| {
+        if profile.color_space != DataColorSpace::Rgb {
+            return Ok(());
+        }
+        let transform = profile.create_transform_8bit(
+            Layout::Rgba,
+            &ColorProfile::new_srgb(),
+            Layout::Rgba,
+            TransformOptions::default(),
+        )?;
+        let source = pixels.to_vec();
+        transform.transform(&source, pixels)
+    });
+    if let Err(error) = result {
+        warn!("Embedded ICC profile ignored: {error}");
+    }
+}
+
+/// Decodes an image checked by [`check_format`] and [`check_chunks`],
+/// along with its exact palette for indexed PNG files, see [`decode_indexed_png`].
+pub fn decode_with_palette<R: BufRead + Seek>(
+    image_reader: ImageReader<R>,
+    options: &Options,
+) -> Result<DecodedImage, SvgenError> {
+    #[cfg(feature = "png")]
+    let image_reader = match image_reader.format() {
+        Some(ImageFormat::Png) => {
+            let mut reader = image_reader.into_inner();
+            let start = reader.stream_position()?;
+            if let Some((image, palette)) = decode_indexed_png(&mut reader, options) {
+                return Ok((image, Some(palette)));
+            }
+            reader.seek(SeekFrom::Start(start))?;
+            let mut image_reader = ImageReader::new(reader);
+            image_reader.set_format(ImageFormat::Png);
+            image_reader
+        }
+        _ => image_reader,
+    };
+    Ok((decode_rgba_with_options(image_reader, options)?, None))
+}
+
+/// Decodes all `(frame, delay_ms)` pairs, composited to the full canvas.
+/// Formats without animation yield a single frame with no delay.
+pub fn decode_frames(
+    image_byte: &[u8],
+    options: &Options,
+) -> Result<Vec<(RgbaImage, f64)>, SvgenError> {
+    Ok(decode_frames_with_palette(image_byte, options)?.0)
+}
+
+/// Like [`decode_frames`], along with the exact palette of still indexed PNG files,
+/// see [`decode_with_palette`].
+pub fn decode_frames_with_palette(
+    image_byte: &[u8],
+    options: &Options,
+) -> Result<FramesWithPalette, SvgenError> {
+    let image_reader =
+        ImageReader::new(BufReader::new(Cursor::new(image_byte))).with_guessed_format()?;
+    check_format(image_reader.format(), options)?;
+    let image_reader = check_chunks(image_reader, options)?;
+
+    match image_reader.format() {
+        #[cfg(feature = "png")]
+        Some(ImageFormat::Png) if !options.ignore_png_crc => {
+            let decoder = PngDecoder::new(Cursor::new(image_byte))?;
+            if decoder.is_apng()? {
+                return Ok((collect_frames(decoder.apng()?.into_frames())?, None));
+            }
+        }
+        #[cfg(feature = "gif")]
+        Some(ImageFormat::Gif) => {
+            let frames = collect_frames(GifDecoder::new(Cursor::new(image_byte))?.into_frames())?;
+            return Ok((frames, None));
+        }
+        _ => {}
+    }
+
+    let (image, palette) = decode_with_palette(image_reader, options)?;
+    Ok((vec![(image, 0.0)], palette))
+}
+
+/// `(frame, delay_ms)` pairs of an animation.
+#[cfg(any(feature = "png", feature = "gif"))]
+fn collect_frames(frames: Frames) -> ImageResult<Vec<(RgbaImage, f64)>> {
+    frames
+        .map(|frame| {
+            let frame = frame?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            Ok((frame.into_buffer(), numer as f64 / denom as f64))
+        })
+        .collect()
+}
+
+/// Decodes an indexed (color type 3) PNG with at most [`INDEXED_PALETTE_MAX`] entries,
+/// returning the expanded pixels along with the RGBA palette (alpha from `tRNS`).
+///
+/// Returns `None` for any other input, so callers can fall back to regular decoding.
+#[cfg(feature = "png")]
+pub fn decode_indexed_png<R: Read>(
+    reader: R,
+    options: &Options,
+) -> Option<(RgbaImage, Vec<[u8; 4]>)> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.ignore_checksums(options.ignore_png_crc);
+    let mut reader = decoder.read_info().ok()?;
+
+    let info = reader.info();
+    let (width, height) = info.size();
+    if info.color_type != png::ColorType::Indexed
+        || info.is_animated()
+        || width as u64 * height as u64 > INDEXED_PIXELS_MAX
+    {
+        return None;
+    }
+
+    let palette: Vec<[u8; 4]> = {
+        let rgb = info.palette.as_ref()?;
+        let trns = info.trns.as_deref().unwrap_or(&[]);
+        rgb.chunks_exact(3)
+            .enumerate()
+            .map(|(i, c)| [c[0], c[1], c[2], trns.get(i).copied().unwrap_or(255)])
+            .collect()
+    };
+    // Every pixel is one of the palette colors, converting them converts the image.
+    #[cfg(feature = "icc")]
+    let palette = {
+        let mut palette = palette;
+        if let Some(icc_profile) = &info.icc_profile {
+            convert_to_srgb(icc_profile, palette.as_flattened_mut());
+        }
+        palette
+    };
+    if palette.len() > INDEXED_PALETTE_MAX {
+        return None;
+    }
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).ok()?;
+    let bit_depth = frame.bit_depth as usize;
+    // Indices are packed in whole bytes, and every row must have been decoded.
+    if !matches!(bit_depth, 1 | 2 | 4 | 8) || buffer.len() < frame.line_size * frame.height as usize
+    {
+        return None;
+    }
+    let pixels_per_byte = 8 / bit_depth;
+    let mask = ((1u16 << bit_depth) - 1) as u8;
+
+    let image = RgbaImage::from_fn(frame.width, frame.height, |x, y| {
+        // Indices narrower than a byte are packed most significant bits first.
+        let byte = buffer[y as usize * frame.line_size + x as usize / pixels_per_byte];
+        let shift = 8 - bit_depth * (x as usize % pixels_per_byte + 1);
+        let index = ((byte >> shift) & mask) as usize;
+        Rgba(palette.get(index).copied().unwrap_or([0, 0, 0, 0]))
+    });
+
+    Some((image, palette))
+}
+
+/// Collects the distinct opaque colors of an image,
+/// returning `None` when there are more than `max`.
+pub fn opaque_palette(image: &RgbaImage, max: usize) -> Option<Vec<[u8; 4]>> {
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    for pixel in image.pixels().filter(|p| p[3] == 255) {
+        if !palette.contains(&pixel.0) {
+            if palette.len() == max {
+                return None;
+            }
+            palette.push(pixel.0);
+        }
+    }
+    Some(palette)
+}
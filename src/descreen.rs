@@ -0,0 +1,142 @@
+//! Descreening of halftoned and dithered inputs, whose alternating pixels would otherwise
+//! be traced as thousands of tiny regions.
+//!
+//! Dithering is detected by the share of pixels differing from both their neighbors
+//! along a row or a column while these are alike, the `ABA` patterns that flat areas
+//! and the edges between them don't produce.
+
+use image::{Rgba, RgbaImage};
+
+use crate::structs::DescreenFilter;
+
+/// Squared RGB distance from which neighboring pixels count as different colors,
+/// so noise and anti-aliasing are not mistaken for dithering.
+const DIFFERENT_DISTANCE_SQUARED: u32 = 48 * 48;
+
+/// Share of alternating pixels from which an image is considered dithered.
+const DITHERED_RATIO: f64 = 0.15;
+
+/// Distances between the pixels of an `ABA` pattern, screens of two pixels wide dots
+/// alternating at a distance of two.
+const ALTERNATION_STEPS: [usize; 2] = [1, 2];
+
+fn is_different(a: &Rgba<u8>, b: &Rgba<u8>) -> bool {
+    let distance_squared = (0..3)
+        .map(|i| (a[i] as i32 - b[i] as i32).pow(2) as u32)
+        .sum::<u32>();
+    distance_squared >= DIFFERENT_DISTANCE_SQUARED || (a[3] == 0) != (b[3] == 0)
+}
+
+/// Share of the opaque pixels that differ from both their neighbors at one of the
+/// [`ALTERNATION_STEPS`], along their row or column, while these neighbors are alike.
+pub fn alternation_ratio(image: &RgbaImage) -> f64 {
+    let (width, height) = image.dimensions();
+    let (mut alternating, mut opaque) = (0usize, 0usize);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        opaque += 1;
+        let is_alternating = ALTERNATION_STEPS.iter().any(|&step| {
+            let step = step as u32;
+            let aba = |before: &Rgba<u8>, after: &Rgba<u8>| {
+                is_different(pixel, before)
+                    && is_different(pixel, after)
+                    && !is_different(before, after)
+            };
+            let along_row = x >= step
+                && x + step < width
+                && aba(image.get_pixel(x - step, y), image.get_pixel(x + step, y));
+            let along_column = y >= step
+                && y + step < height
+                && aba(image.get_pixel(x, y - step), image.get_pixel(x, y + step));
+            along_row || along_column
+        });
+        alternating += is_alternating as usize;
+    }
+    match opaque {
+        0 => 0.0,
+        opaque => alternating as f64 / opaque as f64,
+    }
+}
+
+/// Whether the image looks halftoned or dithered, see [`alternation_ratio`].
+pub fn is_dithered(image: &RgbaImage) -> bool {
+    alternation_ratio(image) >= DITHERED_RATIO
+}
+
+/// The image filtered over windows of `cell_size` × `cell_size` pixels around each pixel,
+/// clamped to the image, the width of the screen cells or dithering pattern.
+pub fn descreen(image: &RgbaImage, filter: DescreenFilter, cell_size: u32) -> RgbaImage {
+    let cell_size = cell_size.max(1);
+    let (width, height) = image.dimensions();
+    // Windows of even sizes have one pixel more before the center than after it.
+    let window = |v: u32, size: u32| {
+        let start = v.saturating_sub(cell_size / 2);
+        start..(v + cell_size - cell_size / 2).min(size)
+    };
+    match filter {
+        DescreenFilter::Average => {
+            let sums = summed_area(image);
+            let sum = |x: u32, y: u32| sums[y as usize * (width as usize + 1) + x as usize];
+            RgbaImage::from_fn(width, height, |x, y| {
+                let (xs, ys) = (window(x, width), window(y, height));
+                let area = ((xs.end - xs.start) * (ys.end - ys.start)) as u64;
+                Rgba(std::array::from_fn(|i| {
+                    let total = sum(xs.end, ys.end)[i] + sum(xs.start, ys.start)[i]
+                        - sum(xs.start, ys.end)[i]
+                        - sum(xs.end, ys.start)[i];
+                    ((total + area / 2) / area) as u8
+                }))
+            })
+        }
+        DescreenFilter::Mode => {
+            let mut counts: Vec<(Rgba<u8>, usize)> = Vec::new();
+            RgbaImage::from_fn(width, height, |x, y| {
+                counts.clear();
+                for wy in window(y, height) {
+                    for wx in window(x, width) {
+                        let color = *image.get_pixel(wx, wy);
+                        match counts.iter_mut().find(|(c, _)| *c == color) {
+                            Some((_, count)) => *count += 1,
+                            None => counts.push((color, 1)),
+                        }
+                    }
+                }
+                // Ties keep the pixel's own color, then the first one met.
+                let center = *image.get_pixel(x, y);
+                let center_count = counts.iter().find(|(c, _)| *c == center).map_or(0, |c| c.1);
+                let (mode, count) = counts
+                    .iter()
+                    .copied()
+                    .reduce(|best, color| match color.1 > best.1 {
+                        true => color,
+                        false => best,
+                    })
+                    .unwrap();
+                match center_count == count {
+                    true => center,
+                    false => mode,
+                }
+            })
+        }
+    }
+}
+
+/// Sums of every channel over the pixels above and left of each corner,
+/// `(width + 1) × (height + 1)` corners in rows.
+fn summed_area(image: &RgbaImage) -> Vec<[u64; 4]> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut sums = vec![[0u64; 4]; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row = [0u64; 4];
+        for x in 0..width {
+            let pixel = image.get_pixel(x as u32, y as u32);
+            for i in 0..4 {
+                row[i] += pixel[i] as u64;
+                sums[(y + 1) * (width + 1) + x + 1][i] = sums[y * (width + 1) + x + 1][i] + row[i];
+            }
+        }
+    }
+    sums
+}
@@ -0,0 +1,51 @@
+use std::{fmt, io};
+
+use image::ImageError;
+
+/// Why an input could not be traced.
+#[derive(Debug)]
+pub enum SvgenError {
+    /// The input format is not compiled in, or not in
+    /// [`Options::input_formats`](crate::structs::Options::input_formats).
+    /// Holds the name of the detected format, `unknown` when it was not recognized.
+    UnsupportedFormat(String),
+    /// The input could not be read or decoded.
+    Image(ImageError),
+    /// Raw pixels are not `width` × `height` × 4 bytes long.
+    BufferSize { expected: usize, actual: usize },
+}
+
+impl fmt::Display for SvgenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgenError::UnsupportedFormat(format) => {
+                write!(f, "unsupported input format: {format}")
+            }
+            SvgenError::Image(error) => write!(f, "invalid input image: {error}"),
+            SvgenError::BufferSize { expected, actual } => {
+                write!(f, "expected {expected} bytes of RGBA pixels, got {actual}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SvgenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SvgenError::UnsupportedFormat(_) | SvgenError::BufferSize { .. } => None,
+            SvgenError::Image(error) => Some(error),
+        }
+    }
+}
+
+impl From<ImageError> for SvgenError {
+    fn from(error: ImageError) -> Self {
+        SvgenError::Image(error)
+    }
+}
+
+impl From<io::Error> for SvgenError {
+    fn from(error: io::Error) -> Self {
+        SvgenError::Image(ImageError::IoError(error))
+    }
+}
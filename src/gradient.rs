@@ -0,0 +1,441 @@
+//! Detection of smoothly shaded regions, which quantization splits into bands of flat colors.
+//!
+//! Neighboring bands are grown into regions while a least squares fit of their colors
+//! by position stays within tolerance, linear for linear gradients and quadratic in the
+//! distance to a center for radial ones.
+
+use crate::{
+    mask::BitMask,
+    structs::{Gradient, GradientOptions, Point},
+};
+
+/// Linear gradients use the features `[1, x, y]`, radial ones add `x² + y²`.
+const LINEAR: usize = 3;
+const RADIAL: usize = 4;
+
+/// Radial gradients have to fit at least this much better than linear ones.
+const RADIAL_GAIN: f64 = 0.5;
+
+/// Colors sampled along the radius of radial gradients, their profile being quadratic.
+const RADIAL_STOPS: usize = 5;
+
+/// A region of the traced image filled with a gradient.
+pub struct GradientRegion {
+    pub mask: BitMask,
+    /// Mean color of the region.
+    pub color: [u8; 4],
+    /// In traced pixels.
+    pub gradient: Gradient,
+}
+
+/// Sums over the pixels of a region, enough to fit its colors by position,
+/// positions being normalized to `[-1, 1]` for the sums to stay well conditioned.
+#[derive(Copy, Clone, Default)]
+struct Moments {
+    /// Sums of `f fᵀ` for the features `f` of every pixel.
+    gram: [[f64; RADIAL]; RADIAL],
+    /// Sums of `f c` for every channel `c`.
+    cross: [[f64; RADIAL]; 3],
+    /// Sums of `c²` for every channel.
+    squares: [f64; 3],
+}
+
+/// Coefficients of the features for every channel, and the RMS error of the fit.
+type Fit<const N: usize> = ([[f64; N]; 3], f64);
+
+/// Radial coefficients, normalized center and RMS error.
+type RadialFit = ([[f64; RADIAL]; 3], (f64, f64), f64);
+
+impl Moments {
+    fn push(&mut self, f: [f64; RADIAL], color: [f64; 3]) {
+        for i in 0..RADIAL {
+            for j in 0..RADIAL {
+                self.gram[i][j] += f[i] * f[j];
+            }
+            for (cross, c) in self.cross.iter_mut().zip(color) {
+                cross[i] += f[i] * c;
+            }
+        }
+        for (square, c) in self.squares.iter_mut().zip(color) {
+            *square += c * c;
+        }
+    }
+
+    fn merge(&self, other: &Moments) -> Moments {
+        let mut merged = *self;
+        for i in 0..RADIAL {
+            for j in 0..RADIAL {
+                merged.gram[i][j] += other.gram[i][j];
+            }
+            for k in 0..3 {
+                merged.cross[k][i] += other.cross[k][i];
+            }
+        }
+        for k in 0..3 {
+            merged.squares[k] += other.squares[k];
+        }
+        merged
+    }
+
+    fn count(&self) -> f64 {
+        self.gram[0][0]
+    }
+
+    /// Mean of every channel.
+    fn mean(&self) -> [f64; 3] {
+        self.cross.map(|cross| cross[0] / self.count())
+    }
+
+    /// Least squares fit of the colors by the first `N` features,
+    /// `None` when they don't determine it, e.g. for pixels along a line.
+    fn fit<const N: usize>(&self) -> Option<Fit<N>> {
+        let mut coefficients = [[0.0; N]; 3];
+        let mut error = 0.0;
+        for (k, coefficients) in coefficients.iter_mut().enumerate() {
+            let mut system = [[0.0; N]; N];
+            let mut rhs = [0.0; N];
+            for i in 0..N {
+                system[i].copy_from_slice(&self.gram[i][..N]);
+                rhs[i] = self.cross[k][i];
+            }
+            let beta = solve(system, rhs)?;
+            // Residual sum of squares: Σc² - 2βᵀXᵀc + βᵀXᵀXβ.
+            let mut rss = self.squares[k];
+            for i in 0..N {
+                rss -= 2.0 * beta[i] * rhs[i];
+                for j in 0..N {
+                    rss += beta[i] * self.gram[i][j] * beta[j];
+                }
+            }
+            error += rss.max(0.0);
+            *coefficients = beta;
+        }
+        Some((coefficients, (error / (3.0 * self.count())).sqrt()))
+    }
+
+    /// Mean color predicted by the fit over the pixels of these moments.
+    fn predicted_mean<const N: usize>(&self, coefficients: &[[f64; N]; 3]) -> [f64; 3] {
+        coefficients
+            .map(|beta| (0..N).map(|i| beta[i] * self.gram[0][i]).sum::<f64>() / self.count())
+    }
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting.
+fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 * a[0][0].abs().max(1.0) {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..N {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col];
+            for (value, pivot) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let sum: f64 = (row + 1..N).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+fn color_distance(a: [u8; 4], b: [u8; 4]) -> f64 {
+    (0..3)
+        .map(|k| (a[k] as f64 - b[k] as f64).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+fn color_from(channels: [f64; 3]) -> [u8; 4] {
+    let [r, g, b] = channels.map(|c| c.round().clamp(0.0, 255.0) as u8);
+    [r, g, b, 255]
+}
+
+/// Finds the regions of `masks` forming gradients, clearing their pixels from the masks.
+///
+/// Bands are the 4-connected regions of a single mask, grown from the largest one
+/// over neighbors whose color differs by at most [`GradientOptions::max_step`].
+pub fn find_gradients(
+    masks: &mut [([u8; 4], BitMask)],
+    size: &[usize; 2],
+    options: &GradientOptions,
+) -> Vec<GradientRegion> {
+    const NONE: usize = usize::MAX;
+    let [width, height] = *size;
+    let scale = width.max(height) as f64 * 0.5;
+    // Pixel center to normalized position.
+    let normalize = |i: usize| {
+        let (x, y) = ((i % width) as f64 + 0.5, (i / width) as f64 + 0.5);
+        (
+            (x - width as f64 * 0.5) / scale,
+            (y - height as f64 * 0.5) / scale,
+        )
+    };
+    let denormalize = |(u, v): (f64, f64)| {
+        Point::new(
+            u * scale + width as f64 * 0.5,
+            v * scale + height as f64 * 0.5,
+        )
+    };
+
+    // ------- Label the bands -------
+    let mut owners = vec![NONE; width * height];
+    for (mask_index, (_color, mask)) in masks.iter().enumerate() {
+        for (owner, is_filled) in owners.iter_mut().zip(mask.iter()) {
+            if *owner == NONE && is_filled {
+                *owner = mask_index;
+            }
+        }
+    }
+    let mut labels = vec![NONE; width * height];
+    let mut bands: Vec<(usize, Moments, usize)> = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..labels.len() {
+        let mask_index = owners[start];
+        if labels[start] != NONE || mask_index == NONE {
+            continue;
+        }
+        let label = bands.len();
+        let color = masks[mask_index].0.map(|c| c as f64);
+        let color = [color[0], color[1], color[2]];
+        let (mut moments, mut area) = (Moments::default(), 0);
+        labels[start] = label;
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            let (u, v) = normalize(i);
+            moments.push([1.0, u, v, u * u + v * v], color);
+            area += 1;
+            let (x, y) = (i % width, i / width);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then(|| i + 1),
+                (y > 0).then(|| i - width),
+                (y + 1 < height).then(|| i + width),
+            ];
+            for j in neighbors.into_iter().flatten() {
+                if labels[j] == NONE && owners[j] == mask_index {
+                    labels[j] = label;
+                    stack.push(j);
+                }
+            }
+        }
+        bands.push((mask_index, moments, area));
+    }
+
+    // ------- Band adjacency graph -------
+    let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); bands.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let a = labels[x + y * width];
+            let right = (x + 1 < width).then(|| labels[x + 1 + y * width]);
+            let down = (y + 1 < height).then(|| labels[x + (y + 1) * width]);
+            for b in [right, down].into_iter().flatten() {
+                if a != NONE && b != NONE && a != b && !neighbors[a].contains(&b) {
+                    neighbors[a].push(b);
+                    neighbors[b].push(a);
+                }
+            }
+        }
+    }
+
+    // ------- Grow regions from the largest band -------
+    let band_color = |band: usize| masks[bands[band].0].0;
+    let mut order = (0..bands.len()).collect::<Vec<_>>();
+    // Ties are broken by label, keeping the growth order stable.
+    order.sort_by_key(|band| (std::cmp::Reverse(bands[*band].2), *band));
+
+    let mut region_of = vec![NONE; bands.len()];
+    // Seed of the region being grown a band was last tested for.
+    let mut tested_by = vec![NONE; bands.len()];
+    let mut regions: Vec<(Vec<usize>, Moments)> = Vec::new();
+    for seed in order {
+        if region_of[seed] != NONE {
+            continue;
+        }
+        // Bands along with the color step they joined the region by.
+        let mut members = vec![(seed, 0.0)];
+        let mut moments = bands[seed].1;
+        tested_by[seed] = seed;
+
+        let mut next = 0;
+        while next < members.len() {
+            let (band, _step) = members[next];
+            next += 1;
+            for &candidate in &neighbors[band] {
+                let step = color_distance(band_color(band), band_color(candidate));
+                if tested_by[candidate] == seed
+                    || region_of[candidate] != NONE
+                    || step > options.max_step
+                {
+                    continue;
+                }
+                let merged = moments.merge(&bands[candidate].1);
+                if fits(&merged, &bands[candidate].1, step, options) {
+                    tested_by[candidate] = seed;
+                    moments = merged;
+                    members.push((candidate, step));
+                }
+            }
+        }
+
+        let mut colors = members
+            .iter()
+            .map(|(band, _step)| band_color(*band))
+            .collect::<Vec<_>>();
+        colors.sort_unstable();
+        colors.dedup();
+        let is_gradient = colors.len() >= options.min_bands
+            && members
+                .iter()
+                .all(|(band, step)| fits(&moments, &bands[*band].1, *step, options));
+        if is_gradient {
+            for (band, _step) in &members {
+                region_of[*band] = regions.len();
+            }
+            regions.push((
+                members.into_iter().map(|(band, _step)| band).collect(),
+                moments,
+            ));
+        }
+    }
+
+    // ------- Fill the regions -------
+    let mut gradient_regions = Vec::with_capacity(regions.len());
+    for (region_index, (_members, moments)) in regions.iter().enumerate() {
+        let mut mask = BitMask::new(*size);
+        let pixels = (0..labels.len())
+            .filter(|i| labels[*i] != NONE && region_of[labels[*i]] == region_index)
+            .collect::<Vec<_>>();
+        for i in &pixels {
+            mask.set(i % width, i / width, true);
+        }
+        // Despeckled masks may overlap, the region is cleared from all of them.
+        for (_color, band_mask) in masks.iter_mut() {
+            for i in &pixels {
+                band_mask.set(i % width, i / width, false);
+            }
+        }
+
+        let gradient = match radial_fit(moments, options) {
+            Some((beta, center, _error)) => {
+                let radius = pixels
+                    .iter()
+                    .map(|i| {
+                        let (u, v) = normalize(*i);
+                        (u - center.0).hypot(v - center.1)
+                    })
+                    .fold(0.0, f64::max);
+                let (cu, cv) = center;
+                let at_center =
+                    beta.map(|b| b[0] + b[1] * cu + b[2] * cv + b[3] * (cu * cu + cv * cv));
+                let stops = (0..RADIAL_STOPS)
+                    .map(|s| {
+                        let t = s as f64 / (RADIAL_STOPS - 1) as f64;
+                        let r2 = (t * radius).powi(2);
+                        let channels = [0, 1, 2].map(|k| at_center[k] + beta[k][3] * r2);
+                        (t, color_from(channels))
+                    })
+                    .collect();
+                Gradient::Radial {
+                    center: denormalize(center),
+                    radius: radius * scale,
+                    stops,
+                }
+            }
+            None => {
+                let (beta, _error) = moments.fit::<LINEAR>().unwrap();
+                // Direction along which the channels change the most.
+                let (mut xx, mut xy, mut yy) = (0.0, 0.0, 0.0);
+                for b in &beta {
+                    xx += b[1] * b[1];
+                    xy += b[1] * b[2];
+                    yy += b[2] * b[2];
+                }
+                let angle = 0.5 * (2.0 * xy).atan2(xx - yy);
+                let direction = (angle.cos(), angle.sin());
+                let centroid = (
+                    moments.gram[0][1] / moments.count(),
+                    moments.gram[0][2] / moments.count(),
+                );
+                let (t_min, t_max) = pixels.iter().fold((0.0_f64, 0.0_f64), |(min, max), i| {
+                    let (u, v) = normalize(*i);
+                    let t = (u - centroid.0) * direction.0 + (v - centroid.1) * direction.1;
+                    (min.min(t), max.max(t))
+                });
+                let at = |t: f64| {
+                    let (u, v) = (centroid.0 + t * direction.0, centroid.1 + t * direction.1);
+                    (u, v, beta.map(|b| b[0] + b[1] * u + b[2] * v))
+                };
+                let (u0, v0, start_color) = at(t_min);
+                let (u1, v1, end_color) = at(t_max);
+                Gradient::Linear {
+                    start: denormalize((u0, v0)),
+                    end: denormalize((u1, v1)),
+                    stops: vec![(0.0, color_from(start_color)), (1.0, color_from(end_color))],
+                }
+            }
+        };
+
+        gradient_regions.push(GradientRegion {
+            mask,
+            color: color_from(moments.mean()),
+            gradient,
+        });
+    }
+
+    gradient_regions
+}
+
+/// Whether the region fit is within [`GradientOptions::tolerance`],
+/// and the mean color of one of its bands too, so a band standing out of a smooth region
+/// is not absorbed by it.
+///
+/// Quantization puts the colors of bands up to half a `step` away from the gradient,
+/// the step by which the band joined the region.
+fn fits(region: &Moments, band: &Moments, step: f64, options: &GradientOptions) -> bool {
+    let (predicted, error) = match radial_fit(region, options) {
+        Some((beta, _center, error)) => (band.predicted_mean(&beta), error),
+        None => match region.fit::<LINEAR>() {
+            Some((beta, error)) => (band.predicted_mean(&beta), error),
+            None => return false,
+        },
+    };
+    let mean = band.mean();
+    let band_error = (0..3)
+        .map(|k| (mean[k] - predicted[k]).powi(2))
+        .sum::<f64>()
+        .sqrt();
+    error <= options.tolerance && band_error <= options.tolerance + step * 0.5
+}
+
+/// Coefficients of the radial fit, its normalized center and its RMS error,
+/// when radial gradients are enabled and fit much better than a linear one.
+fn radial_fit(moments: &Moments, options: &GradientOptions) -> Option<RadialFit> {
+    if !options.radial {
+        return None;
+    }
+    let (beta, error) = moments.fit::<RADIAL>()?;
+    let linear_error = moments
+        .fit::<LINEAR>()
+        .map_or(f64::INFINITY, |(_, error)| error);
+    if error >= linear_error * RADIAL_GAIN {
+        return None;
+    }
+    // Least squares center of `c = a + q |p - center|²` over the channels.
+    let q2: f64 = beta.iter().map(|b| b[3] * b[3]).sum();
+    if q2 < 1e-12 {
+        return None;
+    }
+    let center = (
+        -beta.iter().map(|b| b[3] * b[1]).sum::<f64>() / (2.0 * q2),
+        -beta.iter().map(|b| b[3] * b[2]).sum::<f64>() / (2.0 * q2),
+    );
+    Some((beta, center, error))
+}
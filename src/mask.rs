@@ -0,0 +1,568 @@
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    min_heap::{MinHeap, NodeHandle},
+    structs::{DespeckleOptions, SeparationChannels, SeparationOptions, Threshold},
+};
+
+/// Binary image with one bit per pixel, each row starting on a new `u64` word.
+///
+/// Bits past the width of a row are always clear, so rows can be combined word by word.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitMask {
+    width: usize,
+    height: usize,
+    /// Words per row.
+    stride: usize,
+    words: Vec<u64>,
+}
+
+impl BitMask {
+    /// An empty mask of `[width, height]`.
+    pub fn new(size: [usize; 2]) -> Self {
+        let stride = size[0].div_ceil(64);
+        Self {
+            width: size[0],
+            height: size[1],
+            stride,
+            words: vec![0; stride * size[1]],
+        }
+    }
+
+    /// A mask of `[width, height]` from its pixels in row-major order.
+    pub fn from_bits(size: [usize; 2], bits: impl IntoIterator<Item = bool>) -> Self {
+        let mut mask = Self::new(size);
+        let mut bits = bits.into_iter();
+        for y in 0..mask.height {
+            for x in 0..mask.width {
+                if bits.next().unwrap_or(false) {
+                    mask.words[y * mask.stride + x / 64] |= 1 << (x % 64);
+                }
+            }
+        }
+        mask
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// `[width, height]`.
+    pub fn size(&self) -> [usize; 2] {
+        [self.width, self.height]
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        debug_assert!(x < self.width && y < self.height);
+        self.words[y * self.stride + x / 64] >> (x % 64) & 1 != 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        debug_assert!(x < self.width && y < self.height);
+        let (word, bit) = (&mut self.words[y * self.stride + x / 64], 1 << (x % 64));
+        match value {
+            true => *word |= bit,
+            false => *word &= !bit,
+        }
+    }
+
+    /// The words of row `y`, pixel `x` being bit `x % 64` of word `x / 64`.
+    pub fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.stride..(y + 1) * self.stride]
+    }
+
+    /// Number of filled pixels.
+    pub fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Number of filled pixels of row `y`.
+    pub fn count_row(&self, y: usize) -> usize {
+        self.row(y)
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Clears the pixels filled in `other`, of the same size.
+    pub fn subtract(&mut self, other: &BitMask) {
+        debug_assert_eq!(self.size(), other.size());
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word &= !other;
+        }
+    }
+
+    /// Fills the pixels filled in `other`, of the same size.
+    pub fn union(&mut self, other: &BitMask) {
+        debug_assert_eq!(self.size(), other.size());
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    /// The pixels in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| self.get(x, y)))
+    }
+}
+
+/// Index in [`palette_indices`] of pixels without a palette color.
+pub const NO_COLOR: u8 = u8::MAX;
+
+/// Index in the flat RGBA `palette` of the RGB color of every opaque pixel, in a single pass
+/// so the per-color masks are derived without reading the image again.
+/// Other pixels, and pixels of colors missing from the palette, are [`NO_COLOR`].
+///
+/// Of the colors sharing an RGB value the opaque one is used, or else the first one.
+/// The palette must have fewer than 255 colors.
+pub fn palette_indices(image: &RgbaImage, palette: &[u8]) -> Vec<u8> {
+    debug_assert!(palette.len() / 4 < NO_COLOR as usize);
+    let find = |rgb: [u8; 3]| {
+        let opaque = [rgb[0], rgb[1], rgb[2], 255];
+        palette
+            .chunks(4)
+            .position(|color| *color == opaque)
+            .or_else(|| palette.chunks(4).position(|color| color[..3] == rgb))
+            .map_or(NO_COLOR, |index| index as u8)
+    };
+    // Neighboring pixels mostly share their color.
+    let mut last: Option<([u8; 3], u8)> = None;
+    image
+        .pixels()
+        .map(|pixel| {
+            if pixel[3] != 255 {
+                return NO_COLOR;
+            }
+            let rgb = [pixel[0], pixel[1], pixel[2]];
+            match last {
+                Some((last_rgb, index)) if last_rgb == rgb => index,
+                _ => {
+                    let index = find(rgb);
+                    last = Some((rgb, index));
+                    index
+                }
+            }
+        })
+        .collect()
+}
+
+/// Ink coverage in `0..=255` of every channel of `channels` printing `rgb`,
+/// in the order of [`SeparationChannels::inks`].
+pub fn ink_coverage(channels: SeparationChannels, rgb: [u8; 3]) -> [u8; 4] {
+    let [r, g, b] = rgb;
+    match channels {
+        SeparationChannels::Cmyk => {
+            let max = r.max(g).max(b);
+            if max == 0 {
+                return [0, 0, 0, 255];
+            }
+            // Inks of the chromatic components, the key printing the rest.
+            let ink = |v: u8| ((max - v) as f64 * 255.0 / max as f64).round() as u8;
+            [ink(r), ink(g), ink(b), 255 - max]
+        }
+        SeparationChannels::Rgb => [255 - r, 255 - g, 255 - b, 0],
+    }
+}
+
+/// Builds the mask of opaque pixels with at least the level of ink of every channel,
+/// see [`ink_coverage`].
+pub fn mask_separation(image: &RgbaImage, separation: &SeparationOptions) -> Vec<BitMask> {
+    let size = [image.width() as usize, image.height() as usize];
+    let coverage = image
+        .pixels()
+        .map(|p| match p[3] {
+            255 => Some(ink_coverage(separation.channels, [p[0], p[1], p[2]])),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    (0..separation.channels.inks().len())
+        .map(|channel| {
+            let level = separation.levels[channel];
+            BitMask::from_bits(
+                size,
+                coverage
+                    .iter()
+                    .map(|ink| ink.is_some_and(|ink| ink[channel] >= level)),
+            )
+        })
+        .collect()
+}
+
+/// Builds the mask of opaque pixels darker than `threshold`.
+pub fn mask_threshold(image: &RgbaImage, threshold: &Threshold) -> BitMask {
+    let luma = image
+        .pixels()
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect::<Vec<f64>>();
+    let is_opaque = |i: usize| image.as_raw()[i * 4 + 3] == 255;
+    let size = [image.width() as usize, image.height() as usize];
+
+    match *threshold {
+        Threshold::Fixed(value) => BitMask::from_bits(
+            size,
+            (0..luma.len()).map(|i| is_opaque(i) && luma[i] < value as f64),
+        ),
+        Threshold::Otsu => {
+            let value = otsu_threshold(
+                luma.iter()
+                    .enumerate()
+                    .filter(|(i, _)| is_opaque(*i))
+                    .map(|(_, l)| *l as u8),
+            );
+            // Classified by their histogram bin, as the threshold was picked from them.
+            BitMask::from_bits(
+                size,
+                (0..luma.len()).map(|i| is_opaque(i) && luma[i] as u8 <= value),
+            )
+        }
+        Threshold::Mean { radius, offset } => BitMask::from_bits(
+            size,
+            window_stats(&luma, &size, radius as usize)
+                .into_iter()
+                .enumerate()
+                .map(|(i, (mean, _))| is_opaque(i) && luma[i] < mean - offset as f64),
+        ),
+        Threshold::Sauvola { radius, k } => {
+            // Dynamic range of the standard deviation for 8-bit values.
+            let range = 128.0;
+            BitMask::from_bits(
+                size,
+                window_stats(&luma, &size, radius as usize)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (mean, deviation))| {
+                        let value = mean * (1.0 + k as f64 * (deviation / range - 1.0));
+                        is_opaque(i) && luma[i] < value
+                    }),
+            )
+        }
+    }
+}
+
+/// The threshold maximizing the between class variance of the histogram,
+/// values at or below it form the darker class.
+fn otsu_threshold(values: impl Iterator<Item = u8>) -> u8 {
+    let mut histogram = [0usize; 256];
+    for value in values {
+        histogram[value as usize] += 1;
+    }
+
+    let total: usize = histogram.iter().sum();
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(i, count)| (i * count) as f64)
+        .sum();
+
+    let (mut best, mut best_variance) = (0, 0.0);
+    let (mut weight_dark, mut sum_dark) = (0usize, 0.0);
+    for (i, count) in histogram.iter().enumerate() {
+        weight_dark += count;
+        sum_dark += (i * count) as f64;
+        let weight_light = total - weight_dark;
+        if weight_dark == 0 || weight_light == 0 {
+            continue;
+        }
+        let mean_dark = sum_dark / weight_dark as f64;
+        let mean_light = (sum - sum_dark) / weight_light as f64;
+        let variance = weight_dark as f64 * weight_light as f64 * (mean_dark - mean_light).powi(2);
+        if variance > best_variance {
+            best = i as u8;
+            best_variance = variance;
+        }
+    }
+    best
+}
+
+/// Mean and standard deviation of every pixel's square window,
+/// windows are clipped at the image bounds.
+fn window_stats(values: &[f64], size: &[usize; 2], radius: usize) -> Vec<(f64, f64)> {
+    let [width, height] = *size;
+    debug_assert_eq!(values.len(), width * height);
+
+    // Summed area tables of values and squares, with an extra leading row and column of zeros.
+    let span = width + 1;
+    let mut table = vec![(0.0, 0.0); span * (height + 1)];
+    for y in 0..height {
+        let mut row_sum = (0.0, 0.0);
+        for x in 0..width {
+            let value = values[x + y * width];
+            row_sum.0 += value;
+            row_sum.1 += value * value;
+            let above = table[(x + 1) + y * span];
+            table[(x + 1) + (y + 1) * span] = (above.0 + row_sum.0, above.1 + row_sum.1);
+        }
+    }
+
+    let mut stats = Vec::with_capacity(values.len());
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+            let area = ((x1 - x0) * (y1 - y0)) as f64;
+            let sum = |c: fn(&(f64, f64)) -> f64| {
+                c(&table[x1 + y1 * span]) + c(&table[x0 + y0 * span])
+                    - c(&table[x0 + y1 * span])
+                    - c(&table[x1 + y0 * span])
+            };
+            let mean = sum(|t| t.0) / area;
+            let variance = (sum(|t| t.1) / area - mean * mean).max(0.0);
+            stats.push((mean, variance.sqrt()));
+        }
+    }
+    stats
+}
+
+/// Applies the mask filters of `options` in order: median, opening, closing,
+/// returning the number of regions (4-connected filled pixels) they removed entirely.
+///
+/// `scale` converts the radii from input pixels to mask pixels,
+/// when the image was upscaled before tracing.
+pub fn mask_despeckle(mask: &mut BitMask, options: &DespeckleOptions, scale: u32) -> usize {
+    // Closing only grows regions.
+    let original = (options.median_radius > 0 || options.open_radius > 0).then(|| mask.clone());
+    if options.median_radius > 0 {
+        *mask = mask_median(mask, (options.median_radius * scale) as usize);
+    }
+    if options.open_radius > 0 {
+        let radius = (options.open_radius * scale) as usize;
+        *mask = mask_dilate(&mask_erode(mask, radius), radius);
+    }
+    if options.close_radius > 0 {
+        let radius = (options.close_radius * scale) as usize;
+        *mask = mask_erode(&mask_dilate(mask, radius), radius);
+    }
+    original.map_or(0, |original| removed_regions(&original, mask))
+}
+
+/// Number of regions (4-connected filled pixels) of `original` without any pixel left in `mask`.
+fn removed_regions(original: &BitMask, mask: &BitMask) -> usize {
+    let [width, height] = original.size();
+    let mut is_visited = BitMask::new([width, height]);
+    let mut stack = Vec::new();
+    let mut removed = 0;
+    for y in 0..height {
+        for x in 0..width {
+            if !original.get(x, y) || is_visited.get(x, y) {
+                continue;
+            }
+            let mut is_removed = true;
+            is_visited.set(x, y, true);
+            stack.push((x, y));
+            while let Some((x, y)) = stack.pop() {
+                is_removed &= !mask.get(x, y);
+                let neighbors = [
+                    (x > 0).then(|| (x - 1, y)),
+                    (x + 1 < width).then_some((x + 1, y)),
+                    (y > 0).then(|| (x, y - 1)),
+                    (y + 1 < height).then_some((x, y + 1)),
+                ];
+                for (x, y) in neighbors.into_iter().flatten() {
+                    if original.get(x, y) && !is_visited.get(x, y) {
+                        is_visited.set(x, y, true);
+                        stack.push((x, y));
+                    }
+                }
+            }
+            removed += is_removed as usize;
+        }
+    }
+    removed
+}
+
+/// Sets each pixel to the majority of its square window.
+pub fn mask_median(mask: &BitMask, radius: usize) -> BitMask {
+    mask_window_map(mask, radius, |count, area| count * 2 > area)
+}
+
+/// Keeps pixels whose whole square window is filled.
+pub fn mask_erode(mask: &BitMask, radius: usize) -> BitMask {
+    mask_window_map(mask, radius, |count, area| count == area)
+}
+
+/// Fills pixels with any filled pixel in their square window.
+pub fn mask_dilate(mask: &BitMask, radius: usize) -> BitMask {
+    mask_window_map(mask, radius, |count, _area| count > 0)
+}
+
+/// Maps every pixel from the count of filled pixels in its square window
+/// and the window area, windows are clipped at the image bounds.
+fn mask_window_map(mask: &BitMask, radius: usize, f: impl Fn(usize, usize) -> bool) -> BitMask {
+    let [width, height] = mask.size();
+
+    // Summed area table, with an extra leading row and column of zeros.
+    let span = width + 1;
+    let mut table = vec![0usize; span * (height + 1)];
+    for y in 0..height {
+        let mut row_sum = 0;
+        for x in 0..width {
+            row_sum += usize::from(mask.get(x, y));
+            table[(x + 1) + (y + 1) * span] = table[(x + 1) + y * span] + row_sum;
+        }
+    }
+
+    let mut output = BitMask::new([width, height]);
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+            let count = table[x1 + y1 * span] + table[x0 + y0 * span]
+                - table[x0 + y1 * span]
+                - table[x1 + y0 * span];
+            output.set(x, y, f(count, (x1 - x0) * (y1 - y0)));
+        }
+    }
+    output
+}
+
+/// Merges the regions (4-connected opaque pixels of a single color) smaller than `min_area`
+/// into their largest neighboring region, e.g. quantization islands from JPEG artifacts.
+///
+/// Regions are merged from the smallest in a region adjacency graph,
+/// so a cluster of small regions grows until it reaches `min_area` or joins a larger one.
+/// Regions only touching non opaque pixels are kept.
+///
+/// Returns the number of regions merged.
+pub fn merge_small_regions(image: &mut RgbaImage, min_area: usize) -> usize {
+    const NONE: usize = usize::MAX;
+    let [width, height] = [image.width() as usize, image.height() as usize];
+    let pixels = image.as_raw();
+    let color_at = |i: usize| -> [u8; 4] { pixels[i * 4..i * 4 + 4].try_into().unwrap() };
+
+    // ------- Label the regions -------
+    let mut labels = vec![NONE; width * height];
+    let mut colors: Vec<[u8; 4]> = Vec::new();
+    let mut areas: Vec<usize> = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..labels.len() {
+        if labels[start] != NONE || color_at(start)[3] != 255 {
+            continue;
+        }
+        let (label, color) = (colors.len(), color_at(start));
+        let mut area = 0;
+        labels[start] = label;
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            area += 1;
+            let (x, y) = (i % width, i / width);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then(|| i + 1),
+                (y > 0).then(|| i - width),
+                (y + 1 < height).then(|| i + width),
+            ];
+            for j in neighbors.into_iter().flatten() {
+                if labels[j] == NONE && color_at(j) == color {
+                    labels[j] = label;
+                    stack.push(j);
+                }
+            }
+        }
+        colors.push(color);
+        areas.push(area);
+    }
+
+    // ------- Region adjacency graph -------
+    let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); colors.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let a = labels[x + y * width];
+            let right = (x + 1 < width).then(|| labels[x + 1 + y * width]);
+            let down = (y + 1 < height).then(|| labels[x + (y + 1) * width]);
+            for b in [right, down].into_iter().flatten() {
+                if a != NONE && b != NONE && a != b && !neighbors[a].contains(&b) {
+                    neighbors[a].push(b);
+                    neighbors[b].push(a);
+                }
+            }
+        }
+    }
+
+    // ------- Merge from the smallest region -------
+    let mut parent = (0..colors.len()).collect::<Vec<usize>>();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    // Ties are broken by label, keeping the merge order stable.
+    let mut heap = MinHeap::<(usize, usize), usize>::new();
+    let mut handles = vec![NodeHandle::INVALID; colors.len()];
+    for (region, area) in areas.iter().enumerate() {
+        if *area < min_area {
+            handles[region] = heap.insert((*area, region), region);
+        }
+    }
+    while let Some(region) = heap.pop_min() {
+        let mut region_neighbors = std::mem::take(&mut neighbors[region]);
+        for neighbor in &mut region_neighbors {
+            *neighbor = find(&mut parent, *neighbor);
+        }
+        region_neighbors.retain(|neighbor| *neighbor != region);
+        region_neighbors.sort_unstable();
+        region_neighbors.dedup();
+
+        let Some(target) = region_neighbors
+            .iter()
+            .copied()
+            .max_by_key(|neighbor| (areas[*neighbor], std::cmp::Reverse(*neighbor)))
+        else {
+            continue;
+        };
+
+        parent[region] = target;
+        areas[target] += areas[region];
+        region_neighbors.retain(|neighbor| *neighbor != target);
+        neighbors[target].extend(region_neighbors);
+        if heap.contains(handles[target]) {
+            if areas[target] < min_area {
+                heap.node_value_update(handles[target], (areas[target], target));
+            } else {
+                heap.remove(handles[target]);
+            }
+        }
+    }
+
+    // ------- Recolor the merged regions -------
+    let roots = (0..colors.len())
+        .map(|region| colors[find(&mut parent, region)])
+        .collect::<Vec<[u8; 4]>>();
+    for (pixel, label) in image.pixels_mut().zip(labels) {
+        if label != NONE {
+            pixel.0 = roots[label];
+        }
+    }
+    (0..colors.len())
+        .filter(|region| parent[*region] != *region)
+        .count()
+}
+
+/// Merges the regions of filled or empty pixels smaller than `min_area` into their surroundings,
+/// see [`merge_small_regions`], removing specks and filling pinholes.
+///
+/// Returns the number of specks and pinholes merged.
+pub fn mask_merge_small_regions(mask: &mut BitMask, min_area: usize) -> usize {
+    let [width, height] = mask.size();
+    let mut image = RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+        match mask.get(x as usize, y as usize) {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let merged = merge_small_regions(&mut image, min_area);
+    *mask = BitMask::from_bits([width, height], image.pixels().map(|pixel| pixel[0] == 0));
+    merged
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+
+use image::{Rgba, RgbaImage};
+use resvg::{tiny_skia, usvg};
+
+use crate::{
+    session::VectorizationSession,
+    structs::{Options, VectorizationResult},
+};
+
+/// Side of the square windows [`ImageDiff::ssim`] is averaged over.
+const SSIM_WINDOW: usize = 8;
+/// Stabilizing constants of SSIM, for values in `0..=1`.
+const SSIM_C1: f64 = 0.01 * 0.01;
+const SSIM_C2: f64 = 0.03 * 0.03;
+
+/// Range of the factor [`auto_tune`] scales the error and simplify thresholds by.
+const THRESHOLD_SCALES: (f64, f64) = (0.25, 8.0);
+/// Bisections of the threshold scale range.
+const TUNING_STEPS: usize = 8;
+
+/// Difference between a source image and the document traced from it, rendered back.
+///
+/// Both images are composited over white, as displayed on a page,
+/// errors compare their RGB channels in `0..=1`.
+#[derive(Clone, Debug)]
+pub struct ImageDiff {
+    /// Mean absolute difference of the channels.
+    pub mean_error: f64,
+    /// Root mean square difference of the channels.
+    pub rms_error: f64,
+    /// Largest difference of any channel.
+    pub max_error: f64,
+    /// Share of the pixels with a channel differing by more than 1/255, e.g. antialiased edges.
+    pub differing_pixels: f64,
+    /// Mean structural similarity of the luminance, in `-1..=1`, `1` when identical.
+    /// Closer to how a shifted edge or a lost texture is perceived than the per-pixel errors.
+    pub ssim: f64,
+    /// The rendered document, at the size of the source image.
+    pub rendered: RgbaImage,
+}
+
+#[derive(Debug)]
+pub enum CompareError {
+    /// The document could not be parsed.
+    Parse(usvg::Error),
+    /// The source image has no pixels.
+    EmptyImage,
+}
+
+impl fmt::Display for CompareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareError::Parse(error) => write!(f, "invalid SVG document: {error}"),
+            CompareError::EmptyImage => write!(f, "the source image is empty"),
+        }
+    }
+}
+
+impl std::error::Error for CompareError {}
+
+/// Renders `svg` at the size of `source`, stretching it over the whole image,
+/// and measures how far it is from `source`.
+pub fn rasterize_and_compare(svg: &str, source: &RgbaImage) -> Result<ImageDiff, CompareError> {
+    let (width, height) = source.dimensions();
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or(CompareError::EmptyImage)?;
+    let tree = usvg::Tree::from_str(svg, &usvg::Options::default()).map_err(CompareError::Parse)?;
+    let size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / size.width(),
+        height as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    let rendered = pixmap.pixels().iter().map(|pixel| {
+        let white = 1.0 - pixel.alpha() as f64 / 255.0;
+        [pixel.red(), pixel.green(), pixel.blue()].map(|v| v as f64 / 255.0 + white)
+    });
+    let expected = source.pixels().map(over_white);
+
+    let (mut sum, mut squared_sum, mut max_error, mut differing) = (0.0, 0.0, 0.0f64, 0);
+    let mut luminances = (Vec::new(), Vec::new());
+    for (a, b) in rendered.zip(expected) {
+        let mut is_different = false;
+        for (a, b) in a.iter().zip(b) {
+            let error = (a - b).abs();
+            sum += error;
+            squared_sum += error * error;
+            max_error = max_error.max(error);
+            is_different |= error > 1.0 / 255.0;
+        }
+        differing += is_different as usize;
+        luminances.0.push(luminance(a));
+        luminances.1.push(luminance(b));
+    }
+
+    let pixel_count = (width * height) as f64;
+    let channel_count = pixel_count * 3.0;
+    let channels = pixmap.pixels().iter().flat_map(|pixel| {
+        let color = pixel.demultiply();
+        [color.red(), color.green(), color.blue(), color.alpha()]
+    });
+    Ok(ImageDiff {
+        mean_error: sum / channel_count,
+        rms_error: (squared_sum / channel_count).sqrt(),
+        max_error,
+        differing_pixels: differing as f64 / pixel_count,
+        ssim: ssim(
+            &luminances.0,
+            &luminances.1,
+            width as usize,
+            height as usize,
+        ),
+        rendered: RgbaImage::from_raw(width, height, channels.collect()).unwrap(),
+    })
+}
+
+/// RGB channels of `pixel` composited over white, in `0..=1`.
+fn over_white(pixel: &Rgba<u8>) -> [f64; 3] {
+    let alpha = pixel[3] as f64 / 255.0;
+    [pixel[0], pixel[1], pixel[2]].map(|v| (v as f64 / 255.0 - 1.0) * alpha + 1.0)
+}
+
+/// Rec. 601 luminance of RGB channels.
+fn luminance([r, g, b]: [f64; 3]) -> f64 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+/// Mean SSIM of the [`SSIM_WINDOW`] windows of two luminance images, overlapping by half,
+/// the whole image being one window when smaller.
+fn ssim(a: &[f64], b: &[f64], width: usize, height: usize) -> f64 {
+    let starts = |len: usize| {
+        let window = SSIM_WINDOW.min(len);
+        (0..=len - window)
+            .step_by(window.div_ceil(2))
+            .map(move |start| (start, window))
+    };
+
+    let (mut total, mut count) = (0.0, 0);
+    for (y0, window_height) in starts(height) {
+        for (x0, window_width) in starts(width) {
+            let indices = (y0..y0 + window_height)
+                .flat_map(|y| (x0..x0 + window_width).map(move |x| y * width + x));
+            let n = (window_width * window_height) as f64;
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) =
+                (0.0, 0.0, 0.0, 0.0, 0.0);
+            for i in indices {
+                let (va, vb) = (a[i], b[i]);
+                sum_a += va;
+                sum_b += vb;
+                sum_aa += va * va;
+                sum_bb += vb * vb;
+                sum_ab += va * vb;
+            }
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let variance_a = sum_aa / n - mean_a * mean_a;
+            let variance_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+            total += (2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2)
+                / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1)
+                    * (variance_a + variance_b + SSIM_C2));
+            count += 1;
+        }
+    }
+    total / count as f64
+}
+
+/// Document picked by [`auto_tune`], with what it was traced with.
+#[derive(Clone, Debug)]
+pub struct AutoTuned {
+    pub svg: String,
+    /// The options the document was traced with.
+    pub options: Options,
+    /// How far the document is from the source image.
+    pub diff: ImageDiff,
+    /// Number of paths, curves with at least one segment.
+    pub path_count: usize,
+    /// Number of knots of these paths.
+    pub node_count: usize,
+}
+
+/// Traces the image with the error and simplify thresholds of `options` scaled
+/// so the document reaches `target_ssim` with the fewest path nodes,
+/// see [`ImageDiff::ssim`].
+///
+/// The fidelity is taken to drop as the thresholds grow, so the scale is binary searched.
+/// When even the smallest thresholds miss the target, the most faithful document is returned.
+///
+/// Regions are merged into their neighbors, doubling [`DespeckleOptions::merge_area`](crate::structs::DespeckleOptions::merge_area),
+/// until the image traces to at most `max_paths` paths.
+pub fn auto_tune(
+    image_byte: &[u8],
+    target_ssim: f64,
+    max_paths: usize,
+    options: &Options,
+) -> AutoTuned {
+    let mut session = VectorizationSession::new(image_byte, options.clone());
+    let (width, height) = session.image().dimensions();
+    let area = width * height;
+    while count_paths(session.result()).0 > max_paths
+        && session.options().despeckle.merge_area < area
+    {
+        let mut options = session.options().clone();
+        options.despeckle.merge_area = (options.despeckle.merge_area * 2).max(1);
+        session.set_options(options);
+    }
+
+    let base = session.options().clone();
+    let mut evaluate = |scale: f64| {
+        session.set_options(Options {
+            error_threshold: base.error_threshold * scale,
+            simplify_threshold: base.simplify_threshold * scale,
+            ..base.clone()
+        });
+        let (path_count, node_count) = count_paths(session.result());
+        let svg = session.svg().to_string();
+        let diff = rasterize_and_compare(&svg, session.image()).expect("traced documents render");
+        AutoTuned {
+            svg,
+            options: session.options().clone(),
+            diff,
+            path_count,
+            node_count,
+        }
+    };
+
+    let (mut low, mut high) = THRESHOLD_SCALES;
+    let mut candidates = vec![evaluate(low)];
+    if candidates[0].diff.ssim >= target_ssim {
+        candidates.push(evaluate(high));
+        if candidates[1].diff.ssim < target_ssim {
+            for _ in 0..TUNING_STEPS {
+                let scale = (low * high).sqrt();
+                let candidate = evaluate(scale);
+                if candidate.diff.ssim >= target_ssim {
+                    low = scale;
+                } else {
+                    high = scale;
+                }
+                candidates.push(candidate);
+            }
+        }
+    }
+
+    let passing = candidates
+        .iter()
+        .filter(|candidate| candidate.diff.ssim >= target_ssim)
+        .min_by_key(|candidate| candidate.node_count);
+    let best = passing.or_else(|| {
+        candidates
+            .iter()
+            .max_by(|a, b| a.diff.ssim.total_cmp(&b.diff.ssim))
+    });
+    best.unwrap().clone()
+}
+
+/// `(paths, nodes)` of a result, see [`AutoTuned`].
+fn count_paths(result: &VectorizationResult) -> (usize, usize) {
+    let paths = result.layers.iter().map(|layer| layer.paths.len()).sum();
+    let nodes = result
+        .layers
+        .iter()
+        .flat_map(|layer| &layer.curves)
+        .map(|(_is_cyclic, knots)| knots.len())
+        .sum();
+    (paths, nodes)
+}
@@ -1,43 +1,58 @@
-/// A min-heap, this differs from rusts BinaryHeap
-/// in that we can remove any item in the heap out-of-order and duplicates.
-///
-/// Characteristics:
-///
-/// - Uses {value: user_data}, where the 'value' must support PartialOrd
-///   for ordering in the heap.
-/// - Supported duplicate entries,
-///   (Note that the order, while not *undefined* is determined by the binary tree structure).
-///
-/// Overview:
-///
-/// Module:
-/// - MinHeap::new() -> MinHeap
-/// - MinHeap::with_capacity(capacity) -> MinHeap
-///
-/// Methods:
-/// - heap.insert(sort_value, user_data) -> handle
-/// - heap.insert_or_update(handle, sort_value, user_data)
-/// - heap.node_value_update(handle, sort_value) -> handle
-/// - heap.node_value_update_with_data(handle, sort_value, user_data) -> handle
-
-/// - heap.remove(handle)
-/// - heap.pop_min() -> Option(user_data)
-///
+//! A min-heap, this differs from rusts BinaryHeap
+//! in that we can remove any item in the heap out-of-order and duplicates.
+//!
+//! Characteristics:
+//!
+//! - Uses {value: user_data}, where the 'value' must support PartialOrd
+//!   for ordering in the heap.
+//! - Supported duplicate entries,
+//!   (Note that the order, while not *undefined* is determined by the binary tree structure).
+//! - Handles are generational, a handle to a node that was popped or removed
+//!   is detected as stale, even once its storage is reused.
+//!
+//! Overview:
+//!
+//! Module:
+//! - MinHeap::new() -> MinHeap
+//! - MinHeap::with_capacity(capacity) -> MinHeap
+//!
+//! Methods:
+//! - heap.insert(sort_value, user_data) -> handle
+//! - heap.insert_or_update(&mut handle, sort_value, user_data)
+//! - heap.node_value_update(handle, sort_value)
+//! - heap.node_value_update_with_data(handle, sort_value, user_data)
+//! - heap.contains(handle) -> bool
+//! - heap.remove(handle) -> Option(user_data)
+//! - heap.peek() -> Option((sort_value, user_data))
+//! - heap.pop_min() -> Option(user_data)
+//! - heap.iter() -> Iterator((handle, sort_value, user_data))
+
+use alloc::{vec, vec::Vec};
 
 /// Invalid index.
-const INVALID: usize = ::std::usize::MAX;
+const INVALID: usize = ::core::usize::MAX;
 
-/// Use only for: `self.nodes[NodeHandle]`
+/// Refers to a node of a [`MinHeap`].
+///
 /// While this is just an index internally `NodeHandle` is opaque
 /// to prevent external users mixing with other types.
+/// The generation of the node is stored along with the index,
+/// so handles to freed nodes don't silently refer to newer ones.
 
 // even though we don't want users of this struct to meddle with its internals
 // its useful to be able to compare them.
-#[derive(PartialEq, Debug, Copy, Clone)]
-pub struct NodeHandle(usize);
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
+pub struct NodeHandle {
+    index: usize,
+    generation: u32,
+}
 
 impl NodeHandle {
-    pub const INVALID: NodeHandle = NodeHandle(INVALID);
+    /// A handle that is never contained in a heap, e.g. for nodes not inserted yet.
+    pub const INVALID: NodeHandle = NodeHandle {
+        index: INVALID,
+        generation: 0,
+    };
 }
 
 pub trait HeapValue: PartialOrd + Copy {}
@@ -46,7 +61,7 @@ impl<TOrd> HeapValue for TOrd where TOrd: PartialOrd + Copy {}
 pub trait HeapData: Copy {}
 impl<TData> HeapData for TData where TData: Copy {}
 
-pub struct Node<TOrd: HeapValue, TData: HeapData> {
+struct Node<TOrd: HeapValue, TData: HeapData> {
     /// Value to order by.
     value: TOrd,
 
@@ -58,6 +73,13 @@ pub struct Node<TOrd: HeapValue, TData: HeapData> {
     /// When free'd doubles as a single-linked list into nodes,
     /// so we can re-use them.
     index: usize,
+
+    /// Incremented every time the node is free'd,
+    /// a handle is only valid while its generation matches.
+    generation: u32,
+
+    /// False while the node is in the free list.
+    is_used: bool,
 }
 
 pub struct MinHeap<TOrd: HeapValue, TData: HeapData> {
@@ -98,15 +120,21 @@ impl<TOrd: HeapValue, TData: HeapData> MinHeap<TOrd, TData> {
 
     // Debug only, does full search on data!
     // ensures we don't allow incorrect insertion/removal.
-    fn contains_node_handle(&self, nhandle: &NodeHandle) -> bool {
+    fn contains_node_index(&self, node_index: usize) -> bool {
         for i in &self.tree_index {
-            if *i == nhandle.0 {
+            if *i == node_index {
                 return true;
             }
         }
         return false;
     }
 
+    /// The node index of a handle, panicking on stale handles.
+    fn node_index(&self, nhandle: NodeHandle) -> usize {
+        assert!(self.contains(nhandle), "stale or invalid heap handle");
+        nhandle.index
+    }
+
     /// `self.tree(i)`, short for `self.node[self.tree_index[i]]`
     #[inline(always)]
     fn tree(&self, i: usize) -> &Node<TOrd, TData> {
@@ -177,29 +205,46 @@ impl<TOrd: HeapValue, TData: HeapData> MinHeap<TOrd, TData> {
     }
 
     // Small take/drop API to reuse nodes.
-    fn node_take(&mut self, node_data: Node<TOrd, TData>) -> NodeHandle {
+    fn node_take(&mut self, value: TOrd, user_data: TData, index: usize) -> NodeHandle {
         let nhandle;
+        let generation;
         if unlikely!(self.free == INVALID) {
             nhandle = self.node.len();
-            self.node.push(node_data);
+            generation = 0;
+            self.node.push(Node {
+                value,
+                user_data,
+                index,
+                generation,
+                is_used: true,
+            });
         } else {
             nhandle = self.free;
             let node = &mut self.node[nhandle];
             self.free = node.index;
-            *node = node_data;
+            generation = node.generation;
+            node.value = value;
+            node.user_data = user_data;
+            node.index = index;
+            node.is_used = true;
         }
 
         if cfg!(debug_assertions) {
-            debug_assert!(self.contains_node_handle(&NodeHandle(nhandle)) == false);
+            debug_assert!(self.contains_node_index(nhandle) == false);
         }
 
-        return NodeHandle(nhandle);
+        NodeHandle {
+            index: nhandle,
+            generation,
+        }
     }
 
     fn node_drop(&mut self, free_node: usize) -> TData {
         let node = &mut self.node[free_node];
         let user_data = node.user_data;
         node.index = self.free;
+        node.generation = node.generation.wrapping_add(1);
+        node.is_used = false;
         self.free = free_node;
         return user_data;
     }
@@ -207,17 +252,14 @@ impl<TOrd: HeapValue, TData: HeapData> MinHeap<TOrd, TData> {
     // -------------------------------------------------------------------
     // Public API
     //
+    /// Adds a node, returning the handle referring to it until it's popped or removed.
     pub fn insert(&mut self, value: TOrd, user_data: TData) -> NodeHandle {
         let tree_index = self.tree_index.len();
 
-        let nhandle = self.node_take(Node {
-            user_data: user_data,
-            value: value,
-            index: tree_index,
-        });
+        let nhandle = self.node_take(value, user_data, tree_index);
 
         let index = self.tree_index.len();
-        self.tree_index.push(nhandle.0);
+        self.tree_index.push(nhandle.index);
 
         self.heap_up(index);
 
@@ -225,6 +267,7 @@ impl<TOrd: HeapValue, TData: HeapData> MinHeap<TOrd, TData> {
         return nhandle;
     }
 
+    /// Removes the node with the smallest value, returning its data.
     pub fn pop_min(&mut self) -> Option<TData> {
         if self.tree_index.len() == 0 {
             return None;
@@ -233,7 +276,7 @@ impl<TOrd: HeapValue, TData: HeapData> MinHeap<TOrd, TData> {
         let free_node = self.tree_index[0];
 
         if cfg!(debug_assertions) {
-            debug_assert!(self.contains_node_handle(&NodeHandle(free_node)) == true);
+            debug_assert!(self.contains_node_index(free_node) == true);
         }
 
         let tree_index_len = self.tree_index.len() - 1;
@@ -248,6 +291,7 @@ impl<TOrd: HeapValue, TData: HeapData> MinHeap<TOrd, TData> {
         return Some(self.node_drop(free_node));
     }
 
+    /// Like [`MinHeap::pop_min`], also returning the value.
     pub fn pop_min_with_value(&mut self) -> Option<(TOrd, TData)> {
         // copied from pop_min
         if unlikely!(self.tree_index.len() == 0) {
@@ -257,7 +301,7 @@ impl<TOrd: HeapValue, TData: HeapData> MinHeap<TOrd, TData> {
         let free_node = self.tree_index[0];
 
         if cfg!(debug_assertions) {
-            debug_assert!(self.contains_node_handle(&NodeHandle(free_node)) == true);
+            debug_assert!(self.contains_node_index(free_node) == true);
         }
 
         let tree_index_len = self.tree_index.len() - 1;
@@ -273,70 +317,107 @@ impl<TOrd: HeapValue, TData: HeapData> MinHeap<TOrd, TData> {
         return Some((self.node[free_node].value, self.node_drop(free_node)));
     }
 
+    /// Updates the node of `nhandle_p` when it's in the heap,
+    /// otherwise inserts a node and stores its handle in `nhandle_p`.
     pub fn insert_or_update(&mut self, nhandle_p: &mut NodeHandle, value: TOrd, user_data: TData) {
-        if nhandle_p.0 == INVALID {
-            *nhandle_p = self.insert(value, user_data)
-        } else {
+        if self.contains(*nhandle_p) {
             self.node_value_update_with_data(*nhandle_p, value, user_data);
+        } else {
+            *nhandle_p = self.insert(value, user_data)
         }
     }
 
-    pub fn remove(&mut self, nhandle: NodeHandle) {
-        debug_assert!(self.tree_index.len() != 0);
-        debug_assert!(nhandle.0 < self.node.len());
-        let mut i = self.node[nhandle.0].index;
+    /// Removes a node out-of-order, returning its data,
+    /// or `None` when the handle is stale (the node was already popped or removed).
+    pub fn remove(&mut self, nhandle: NodeHandle) -> Option<TData> {
+        if !self.contains(nhandle) {
+            return None;
+        }
+        let mut i = self.node[nhandle.index].index;
         while i > 0 {
             let p = bin_parent(i);
 
             self.heap_swap(p, i);
             i = p;
         }
-        self.pop_min();
+        self.pop_min()
+    }
+
+    /// Whether the node of `nhandle` is in the heap.
+    pub fn contains(&self, nhandle: NodeHandle) -> bool {
+        self.node
+            .get(nhandle.index)
+            .is_some_and(|node| node.is_used && node.generation == nhandle.generation)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree_index.len()
     }
 
-    #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
-        return self.tree_index.len() == 0;
+        self.tree_index.is_empty()
+    }
+
+    /// The smallest value and its data, without removing it.
+    pub fn peek(&self) -> Option<(TOrd, TData)> {
+        if self.tree_index.is_empty() {
+            return None;
+        }
+        let node = self.tree(0);
+        Some((node.value, node.user_data))
+    }
+
+    /// Iterates over `(handle, value, user_data)` of every node, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeHandle, TOrd, TData)> + '_ {
+        self.tree_index.iter().map(move |&i| {
+            let node = &self.node[i];
+            let nhandle = NodeHandle {
+                index: i,
+                generation: node.generation,
+            };
+            (nhandle, node.value, node.user_data)
+        })
     }
 
+    /// Changes the value of a node, moving it to its new position.
+    ///
+    /// Panics when the handle is stale.
     pub fn node_value_update(&mut self, nhandle: NodeHandle, value: TOrd) {
-        debug_assert!(self.tree_index.len() != 0);
-        debug_assert!(nhandle.0 < self.node.len());
+        let node_index = self.node_index(nhandle);
         let (index, value_curr) = {
-            let node = &mut self.node[nhandle.0];
+            let node = &mut self.node[node_index];
             (node.index, node.value)
         };
         if value < value_curr {
-            self.node[nhandle.0].value = value;
+            self.node[node_index].value = value;
             self.heap_up(index);
         } else if value > value_curr {
-            self.node[nhandle.0].value = value;
+            self.node[node_index].value = value;
             self.heap_down(index);
         }
     }
 
+    /// Like [`MinHeap::node_value_update`], also replacing the data.
     pub fn node_value_update_with_data(
         &mut self,
         nhandle: NodeHandle,
         value: TOrd,
         user_data: TData,
     ) {
-        debug_assert!(self.tree_index.len() != 0);
-        debug_assert!(nhandle.0 < self.node.len());
-        self.node[nhandle.0].user_data = user_data;
+        let node_index = self.node_index(nhandle);
+        self.node[node_index].user_data = user_data;
         self.node_value_update(nhandle, value);
     }
 
-    #[allow(dead_code)]
+    /// Panics when the handle is stale.
     pub fn node_value(&self, nhandle: NodeHandle) -> TOrd {
-        return self.node[nhandle.0].value;
+        self.node[self.node_index(nhandle)].value
     }
-    #[allow(dead_code)]
+    /// Panics when the handle is stale.
     pub fn node_data(&self, nhandle: NodeHandle) -> TData {
-        return self.node[nhandle.0].user_data;
+        self.node[self.node_index(nhandle)].user_data
     }
 
-    #[allow(dead_code)]
     pub fn new() -> MinHeap<TOrd, TData> {
         MinHeap {
             tree_index: vec![],
@@ -0,0 +1,128 @@
+use std::fmt::Write;
+
+use crate::{
+    structs::{Options, VectorizationResult},
+    utils::{segments_flatten, trunc},
+};
+
+/// Writes a result as an ASCII DXF drawing for CAD/CAM tools.
+///
+/// Each color becomes a DXF layer named after its hex value, with the alpha channel
+/// appended for non opaque colors. Contours are
+/// written as closed `LWPOLYLINE`s (flattened with [`Options::flatten_tolerance`])
+/// or, with [`DxfOptions::splines`](crate::structs::DxfOptions::splines),
+/// as degree 3 `SPLINE`s holding the Bézier control points.
+/// DXF is y-up, so the image is flipped vertically.
+pub fn to_dxf(result: &VectorizationResult, options: &Options) -> String {
+    let height = result.height as f64;
+    let layers: Vec<_> = result
+        .layers
+        .iter()
+        .filter(|layer| !layer.curves.is_empty())
+        .filter(|layer| {
+            options.dxf.colors.as_ref().is_none_or(|colors| {
+                colors.contains(&[layer.color[0], layer.color[1], layer.color[2]])
+            })
+        })
+        .collect();
+
+    let layer_name = |color: &[u8; 4]| match color[3] {
+        255 => format!("{:02X}{:02X}{:02X}", color[0], color[1], color[2]),
+        _ => format!(
+            "{:02X}{:02X}{:02X}{:02X}",
+            color[0], color[1], color[2], color[3]
+        ),
+    };
+    let true_color =
+        |color: &[u8; 4]| (color[0] as u32) << 16 | (color[1] as u32) << 8 | color[2] as u32;
+
+    let mut output = String::new();
+
+    // ------- Header -------
+    let _ = write!(
+        output,
+        "0\nSECTION\n2\nHEADER\n\
+         9\n$ACADVER\n1\nAC1015\n\
+         9\n$INSUNITS\n70\n{}\n\
+         9\n$EXTMIN\n10\n0\n20\n0\n30\n0\n\
+         9\n$EXTMAX\n10\n{}\n20\n{}\n30\n0\n\
+         0\nENDSEC\n",
+        options.dxf.units as u8, result.width, result.height,
+    );
+
+    // ------- Layer table -------
+    let _ = write!(
+        output,
+        "0\nSECTION\n2\nTABLES\n0\nTABLE\n2\nLAYER\n70\n{}\n",
+        layers.len()
+    );
+    for layer in &layers {
+        let _ = write!(
+            output,
+            "0\nLAYER\n100\nAcDbSymbolTableRecord\n100\nAcDbLayerTableRecord\n\
+             2\n{}\n70\n0\n62\n7\n420\n{}\n6\nCONTINUOUS\n",
+            layer_name(&layer.color),
+            true_color(&layer.color),
+        );
+    }
+    output.push_str("0\nENDTAB\n0\nENDSEC\n");
+
+    // ------- Entities -------
+    output.push_str("0\nSECTION\n2\nENTITIES\n");
+    for layer in &layers {
+        let name = layer_name(&layer.color);
+
+        for (is_cyclic, segments) in layer.segments() {
+            let Some(first) = segments.first() else {
+                continue;
+            };
+            if options.dxf.splines {
+                // Bézier segments as a clamped B-spline: every inner knot has multiplicity 3.
+                let mut points = Vec::with_capacity(segments.len() * 3 + 1);
+                points.push(first.start());
+                for segment in &segments {
+                    let c = segment.to_cubic();
+                    points.extend([c.control1, c.control2, c.end]);
+                }
+
+                let _ = write!(
+                    output,
+                    "0\nSPLINE\n100\nAcDbEntity\n8\n{}\n100\nAcDbSpline\n\
+                     210\n0\n220\n0\n230\n1\n70\n8\n71\n3\n72\n{}\n73\n{}\n74\n0\n",
+                    name,
+                    points.len() + 4,
+                    points.len(),
+                );
+                for knot in std::iter::once(0)
+                    .chain((0..=segments.len()).flat_map(|i| std::iter::repeat_n(i, 3)))
+                    .chain(std::iter::once(segments.len()))
+                {
+                    let _ = write!(output, "40\n{}\n", knot);
+                }
+                for v in points {
+                    let _ = write!(
+                        output,
+                        "10\n{}\n20\n{}\n30\n0\n",
+                        trunc(v.x),
+                        trunc(height - v.y)
+                    );
+                }
+            } else {
+                let poly = segments_flatten(is_cyclic, &segments, options.flatten_tolerance);
+                let _ = write!(
+                    output,
+                    "0\nLWPOLYLINE\n100\nAcDbEntity\n8\n{}\n100\nAcDbPolyline\n90\n{}\n70\n{}\n",
+                    name,
+                    poly.len(),
+                    if is_cyclic { 1 } else { 0 },
+                );
+                for v in poly {
+                    let _ = write!(output, "10\n{}\n20\n{}\n", trunc(v.x), trunc(height - v.y));
+                }
+            }
+        }
+    }
+    output.push_str("0\nENDSEC\n0\nEOF\n");
+
+    output
+}
@@ -0,0 +1,74 @@
+use std::fmt::Write;
+
+use crate::{
+    structs::{Segment, VectorizationResult},
+    utils::trunc,
+};
+
+/// Writes a result as Encapsulated PostScript.
+///
+/// Each layer is one path, filled with the nonzero rule and stroked
+/// with a 1 unit line of the same color, matching the SVG output.
+pub fn to_eps(result: &VectorizationResult) -> String {
+    let mut output = String::new();
+
+    let _ = write!(
+        output,
+        "%!PS-Adobe-3.0 EPSF-3.0\n\
+         %%BoundingBox: 0 0 {w} {h}\n\
+         %%Creator: svgen\n\
+         %%Pages: 1\n\
+         %%EndComments\n\
+         /m {{moveto}} bind def\n\
+         /l {{lineto}} bind def\n\
+         /c {{curveto}} bind def\n\
+         /z {{closepath}} bind def\n\
+         gsave\n\
+         0 {h} translate 1 -1 scale\n\
+         1 setlinewidth\n",
+        w = result.width,
+        h = result.height,
+    );
+
+    for layer in &result.layers {
+        // Fully transparent layers (see `Options::transparent_layer`) are not painted.
+        if layer.curves.is_empty() || layer.color[3] == 0 {
+            continue;
+        }
+
+        let [r, g, b, _] = layer.color.map(|c| trunc(c as f64 / 255.0));
+        let _ = writeln!(output, "{r} {g} {b} setrgbcolor newpath");
+
+        for (is_cyclic, segments) in layer.segments() {
+            let Some(first) = segments.first() else {
+                continue;
+            };
+            let start = first.start();
+            let _ = writeln!(output, "{} {} m", trunc(start.x), trunc(start.y));
+            for segment in &segments {
+                let _ = match segment {
+                    Segment::Line { end, .. } => {
+                        writeln!(output, "{} {} l", trunc(end.x), trunc(end.y))
+                    }
+                    Segment::Cubic(c) => writeln!(
+                        output,
+                        "{} {} {} {} {} {} c",
+                        trunc(c.control1.x),
+                        trunc(c.control1.y),
+                        trunc(c.control2.x),
+                        trunc(c.control2.y),
+                        trunc(c.end.x),
+                        trunc(c.end.y),
+                    ),
+                };
+            }
+            if is_cyclic {
+                output.push_str("z\n");
+            }
+        }
+        output.push_str("gsave fill grestore stroke\n");
+    }
+
+    output.push_str("grestore\nshowpage\n%%EOF\n");
+    output
+}
@@ -0,0 +1,142 @@
+use std::fmt::Write;
+
+use crate::{
+    structs::{ContourSource, Options, VectorizationResult},
+    utils::{rgb_to_hex, ring_area2, ring_contains, segments_flatten, trunc},
+    vec2::DVec2,
+};
+
+/// Serializes a result as a GeoJSON `FeatureCollection`.
+///
+/// Each layer becomes a `Feature` with a `fill` property, holes are nested
+/// into the ring that contains them and rings follow the right-hand rule
+/// (exterior counter-clockwise, holes clockwise) in image coordinates.
+pub fn to_geojson(result: &VectorizationResult, options: &Options) -> String {
+    let mut output = String::from(r#"{"type":"FeatureCollection","features":["#);
+    let mut is_first_feature = true;
+
+    for layer in &result.layers {
+        let mut rings: Vec<Vec<DVec2>> = match options.contour_source {
+            ContourSource::Polygons => layer.polygons.iter().map(|(_, p)| p.clone()).collect(),
+            ContourSource::Curves => layer
+                .segments()
+                .iter()
+                .map(|(is_cyclic, s)| segments_flatten(*is_cyclic, s, options.flatten_tolerance))
+                .collect(),
+        };
+        rings.retain(|ring| ring.len() >= 3);
+
+        if rings.is_empty() {
+            continue;
+        }
+
+        let polygons = rings_nest(rings);
+
+        if !is_first_feature {
+            output.push(',');
+        }
+        is_first_feature = false;
+
+        let fill_opacity = match layer.color[3] {
+            255 => String::new(),
+            alpha => format!(r#","fill-opacity":{}"#, trunc(alpha as f64 / 255.0)),
+        };
+        let _ = write!(
+            output,
+            r#"{{"type":"Feature","properties":{{"fill":"{}"{}}},"geometry":{{"type":"{}","coordinates":"#,
+            rgb_to_hex(layer.color[0], layer.color[1], layer.color[2]),
+            fill_opacity,
+            if polygons.len() == 1 {
+                "Polygon"
+            } else {
+                "MultiPolygon"
+            },
+        );
+
+        if polygons.len() == 1 {
+            polygon_write(&mut output, &polygons[0]);
+        } else {
+            output.push('[');
+            for (i, polygon) in polygons.iter().enumerate() {
+                if i != 0 {
+                    output.push(',');
+                }
+                polygon_write(&mut output, polygon);
+            }
+            output.push(']');
+        }
+
+        output.push_str("}}");
+    }
+
+    output.push_str("]}");
+    output
+}
+
+/// Groups rings into polygons (exterior first, then its holes).
+///
+/// A ring nested inside an odd number of other rings is a hole,
+/// and belongs to the smallest exterior that contains it.
+fn rings_nest(mut rings: Vec<Vec<DVec2>>) -> Vec<Vec<Vec<DVec2>>> {
+    let areas: Vec<f64> = rings.iter().map(|r| ring_area2(r).abs()).collect();
+    // Edge midpoints never coincide with another contour's vertices.
+    let probes: Vec<DVec2> = rings.iter().map(|r| r[0].mid(r[1])).collect();
+
+    // For each ring, the rings that contain it.
+    let parents: Vec<Vec<usize>> = (0..rings.len())
+        .map(|i| {
+            (0..rings.len())
+                .filter(|&j| j != i && areas[j] > areas[i] && ring_contains(&rings[j], probes[i]))
+                .collect()
+        })
+        .collect();
+
+    let mut polygons: Vec<Vec<Vec<DVec2>>> = Vec::new();
+    let mut polygon_index = vec![usize::MAX; rings.len()];
+    let is_hole = |i: usize| parents[i].len() % 2 == 1;
+
+    for i in (0..rings.len()).filter(|&i| !is_hole(i)) {
+        let mut ring = std::mem::take(&mut rings[i]);
+        if ring_area2(&ring) < 0.0 {
+            ring.reverse();
+        }
+        polygon_index[i] = polygons.len();
+        polygons.push(vec![ring]);
+    }
+
+    for i in (0..rings.len()).filter(|&i| is_hole(i)) {
+        let parent = parents[i]
+            .iter()
+            .copied()
+            .filter(|&j| !is_hole(j))
+            .min_by(|&a, &b| areas[a].total_cmp(&areas[b]));
+        let mut ring = std::mem::take(&mut rings[i]);
+        if ring_area2(&ring) > 0.0 {
+            ring.reverse();
+        }
+        if let Some(parent) = parent {
+            polygons[polygon_index[parent]].push(ring);
+        }
+    }
+
+    polygons
+}
+
+fn polygon_write(output: &mut String, polygon: &[Vec<DVec2>]) {
+    output.push('[');
+    for (i, ring) in polygon.iter().enumerate() {
+        if i != 0 {
+            output.push(',');
+        }
+        output.push('[');
+        // GeoJSON rings repeat their first position.
+        for (j, v) in ring.iter().chain(ring.first()).enumerate() {
+            if j != 0 {
+                output.push(',');
+            }
+            let _ = write!(output, "[{},{}]", trunc(v.x), trunc(v.y));
+        }
+        output.push(']');
+    }
+    output.push(']');
+}
@@ -0,0 +1,7 @@
+//! Output backends, each serializing a [`VectorizationResult`](crate::structs::VectorizationResult).
+
+pub mod dxf;
+pub mod eps;
+pub mod geojson;
+pub mod pdf;
+pub mod svg;
@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+use crate::{
+    structs::{Segment, VectorizationResult},
+    utils::trunc,
+};
+
+/// Writes a result as a single-page PDF with one filled path per layer.
+///
+/// Paths are filled with the nonzero rule and stroked with a 1 unit line
+/// of the same color, matching the SVG output.
+pub fn to_pdf(result: &VectorizationResult) -> Vec<u8> {
+    let mut content = String::new();
+
+    // PDF space is y-up, flip to image coordinates.
+    let _ = writeln!(content, "1 0 0 -1 0 {} cm", result.height);
+    content.push_str("1 w\n");
+
+    for layer in &result.layers {
+        // Fully transparent layers (see `Options::transparent_layer`) are not painted.
+        if layer.curves.is_empty() || layer.color[3] == 0 {
+            continue;
+        }
+
+        let [r, g, b, _] = layer.color.map(|c| trunc(c as f64 / 255.0));
+        let _ = writeln!(content, "{r} {g} {b} rg {r} {g} {b} RG");
+
+        for (is_cyclic, segments) in layer.segments() {
+            let Some(first) = segments.first() else {
+                continue;
+            };
+            let start = first.start();
+            let _ = writeln!(content, "{} {} m", trunc(start.x), trunc(start.y));
+            for segment in &segments {
+                let _ = match segment {
+                    Segment::Line { end, .. } => {
+                        writeln!(content, "{} {} l", trunc(end.x), trunc(end.y))
+                    }
+                    Segment::Cubic(c) => writeln!(
+                        content,
+                        "{} {} {} {} {} {} c",
+                        trunc(c.control1.x),
+                        trunc(c.control1.y),
+                        trunc(c.control2.x),
+                        trunc(c.control2.y),
+                        trunc(c.end.x),
+                        trunc(c.end.y),
+                    ),
+                };
+            }
+            if is_cyclic {
+                content.push_str("h\n");
+            }
+        }
+        content.push_str("B\n");
+    }
+
+    let objects = [
+        String::from("<< /Type /Catalog /Pages 2 0 R >>"),
+        String::from("<< /Type /Pages /Kids [3 0 R] /Count 1 >>"),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << >> /Contents 4 0 R >>",
+            result.width, result.height
+        ),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ),
+    ];
+
+    let mut output = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(output.len());
+        let _ = write!(output, "{} 0 obj\n{}\nendobj\n", i + 1, object);
+    }
+
+    let xref_offset = output.len();
+    let _ = write!(
+        output,
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    );
+    for offset in offsets {
+        let _ = writeln!(output, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        output,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    );
+
+    output.into_bytes()
+}
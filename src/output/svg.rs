@@ -0,0 +1,1357 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    io, slice,
+};
+
+#[cfg(feature = "png")]
+use image::{ImageFormat, RgbaImage};
+use svg::{
+    node::{
+        element::{
+            Animate, Circle, Definitions, Description, Element, Ellipse, Group, Image,
+            LinearGradient, Path as SVGPath, Polygon, RadialGradient, Rectangle, Stop, Style,
+            Symbol, Title, Use,
+        },
+        Text,
+    },
+    Document, Node,
+};
+
+#[cfg(feature = "png")]
+use crate::utils::base64_encode;
+use crate::{
+    path_optimizer::OptimizedData,
+    polygon_simplifier::{poly_simplify_douglas_peucker, segment_distance_squared},
+    structs::{
+        BoundingBox, ColorMode, CoordinateMode, CurveType, Gradient, IdScheme, Options,
+        OutputFormatting, Point, Segment, SvgProfile, VectorizationResult,
+    },
+    telemetry::{info, stage},
+    utils::{
+        circle_fit, ellipse_deviation, ellipse_fit, generate_id, poly_subdivide_to_limit,
+        polygon_deviation, rect_corners, rect_fit, regular_polygon_fit, rgb_to_hex,
+        segments_flatten, trunc,
+    },
+};
+
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+const INKSCAPE_NAMESPACE: &str = "http://www.inkscape.org/namespaces/inkscape";
+
+/// Serializes a result as an SVG document, each layer's contours are
+/// defined once in `<defs>` and referenced by a stroke and a fill group.
+pub fn to_svg(result: &VectorizationResult, options: &Options) -> String {
+    to_svg_over(result, options, None)
+}
+
+/// Serializes a result as [`to_svg`] does, over an `<image>` of `raster` embedded as a PNG
+/// data URL and stretched over the [`VectorizationResult::canvas`], e.g. to annotate maps
+/// or diagrams that can't be fully traced with the shapes that could.
+///
+/// The raster stays under the paths it was traced to, whatever the [`Options::auto_crop`],
+/// [`Options::transform`] and [`SvgOptions::flip_y`](crate::structs::SvgOptions::flip_y).
+#[cfg(feature = "png")]
+pub fn to_hybrid_svg(
+    result: &VectorizationResult,
+    raster: &RgbaImage,
+    options: &Options,
+) -> String {
+    let mut png = Vec::new();
+    raster
+        .write_to(&mut io::Cursor::new(&mut png), ImageFormat::Png)
+        .expect("PNG encoding to memory");
+    let canvas = &result.canvas;
+    let [origin_x, origin_y] = options.svg.origin;
+    let mut image = Image::new()
+        .set("x", trunc(canvas.min.x - origin_x))
+        .set("width", trunc(canvas.width()))
+        .set("height", trunc(canvas.height()))
+        .set("preserveAspectRatio", "none")
+        .set(
+            options.svg.profile.href(),
+            format!("data:image/png;base64,{}", base64_encode(&png)),
+        );
+    // Mirrored like the paths, see `document_coordinates`.
+    image = match options.svg.flip_y {
+        true => image.set("y", trunc(canvas.min.y)).set(
+            "transform",
+            format!(
+                "matrix(1 0 0 -1 0 {})",
+                trunc(result.height as f64 - origin_y)
+            ),
+        ),
+        false => image.set("y", trunc(canvas.min.y - origin_y)),
+    };
+    to_svg_over(result, options, Some(image))
+}
+
+/// [`to_svg`] with `underlay` drawn below the paths.
+fn to_svg_over(result: &VectorizationResult, options: &Options, underlay: Option<Image>) -> String {
+    let _stage = stage!("emit");
+    // ------- SVG container created -------
+    let mut document = new_document(result.width, result.height, options);
+
+    let mut defs = Definitions::new();
+    let mut path_ids = PathIds::default();
+    let mut classes = Vec::new();
+    let groups = layers_to_groups(
+        result,
+        options,
+        &mut |node| defs.append(node),
+        &mut path_ids,
+        &mut classes,
+    );
+
+    append_style(&mut document, &classes, options);
+    document.append(defs);
+    if let Some(underlay) = underlay {
+        document.append(underlay);
+    }
+    for group in groups {
+        document.append(group);
+    }
+
+    let svg = serialize(&document, options.svg.formatting);
+    info!("SVG created! Byte: {}", svg.len());
+
+    svg
+}
+
+/// Writes the document of [`to_svg`] to `writer`, each path element as soon as its layer
+/// is processed, so outputs of tens of megabytes are never held in memory whole.
+///
+/// The `<style>` of [`SvgOptions::css_classes`](crate::structs::SvgOptions::css_classes)
+/// comes last instead of first, as the classes are only known once every layer is written.
+pub fn write_svg(
+    result: &VectorizationResult,
+    options: &Options,
+    mut writer: impl io::Write,
+) -> io::Result<()> {
+    let _stage = stage!("emit");
+    let formatting = options.svg.formatting;
+    let is_inline = formatting == OutputFormatting::Minified;
+    let document = new_document(result.width, result.height, options);
+
+    // Each element is serialized on its own before being written.
+    let mut buffer = String::new();
+    let mut written = 0;
+    let mut write = |buffer: &mut String| {
+        written += buffer.len();
+        let result = writer.write_all(buffer.as_bytes());
+        buffer.clear();
+        result
+    };
+
+    write_start_tag(&mut buffer, &document, formatting);
+    buffer.push('>');
+    let header = document.get_children().map_or(&[][..], Vec::as_slice);
+    for child in header {
+        if !is_inline {
+            new_line(&mut buffer, 1, formatting);
+        }
+        write_node(&mut buffer, child.as_ref(), 1, formatting, is_inline);
+    }
+    if !is_inline {
+        new_line(&mut buffer, 1, formatting);
+    }
+    buffer.push_str("<defs");
+    write(&mut buffer)?;
+
+    let mut defs_count = 0;
+    let mut defs_result = Ok(());
+    let mut path_ids = PathIds::default();
+    let mut classes = Vec::new();
+    let groups = layers_to_groups(
+        result,
+        options,
+        &mut |node| {
+            if defs_result.is_err() {
+                return;
+            }
+            if defs_count == 0 {
+                buffer.push('>');
+            }
+            defs_count += 1;
+            if !is_inline {
+                new_line(&mut buffer, 2, formatting);
+            }
+            write_node(&mut buffer, node.as_ref(), 2, formatting, is_inline);
+            defs_result = write(&mut buffer);
+        },
+        &mut path_ids,
+        &mut classes,
+    );
+    defs_result?;
+
+    if defs_count == 0 {
+        buffer.push_str("/>");
+    } else {
+        if !is_inline {
+            new_line(&mut buffer, 1, formatting);
+        }
+        buffer.push_str("</defs>");
+    }
+    let style = style_element(&classes, options);
+    let trailer = groups
+        .iter()
+        .map(|group| group as &dyn Node)
+        .chain(style.as_ref().map(|style| style as &dyn Node));
+    for node in trailer {
+        if !is_inline {
+            new_line(&mut buffer, 1, formatting);
+        }
+        write_node(&mut buffer, node, 1, formatting, is_inline);
+    }
+    if !is_inline {
+        new_line(&mut buffer, 0, formatting);
+    }
+    buffer.push_str("</svg>");
+    write(&mut buffer)?;
+
+    info!("SVG created! Byte: {}", written);
+    Ok(())
+}
+
+/// An empty document of the given size, with the units, origin, title, description,
+/// generator metadata and namespaces of [`Options::svg`](crate::structs::Options::svg).
+fn new_document(width: u32, height: u32, options: &Options) -> Document {
+    let svg_options = &options.svg;
+    let suffix = svg_options.units.map_or("", |units| units.suffix());
+    // Not `-v`, which would write `-0` for the default origin.
+    let [x, y] = svg_options.origin.map(|v| trunc(0.0 - v));
+    let mut document = Document::new()
+        .set("width", format!("{width}{suffix}"))
+        .set("height", format!("{height}{suffix}"))
+        .set("viewBox", (x, y, width, height));
+    if svg_options.profile != SvgProfile::Svg2 {
+        document = document
+            .set("version", "1.1")
+            .set("xmlns:xlink", XLINK_NAMESPACE);
+    }
+    // Separation plates are labeled Inkscape layers whatever the profile.
+    if svg_options.profile == SvgProfile::Inkscape || options.color_mode == ColorMode::Separation {
+        document = document.set("xmlns:inkscape", INKSCAPE_NAMESPACE);
+    }
+
+    if let Some(title) = &svg_options.title {
+        document.append(Title::new(title.clone()));
+    }
+    if let Some(description) = &svg_options.description {
+        document.append(Description::new().add(Text::new(description.clone())));
+    }
+    if svg_options.generator_metadata {
+        let mut metadata = Element::new("metadata");
+        metadata.append(Text::new(concat!(
+            env!("CARGO_PKG_NAME"),
+            " ",
+            env!("CARGO_PKG_VERSION")
+        )));
+        document.append(metadata);
+    }
+
+    document
+}
+
+/// Serializes `(result, delay_ms)` frames as one SVG document.
+///
+/// Every frame is a group that is only displayed during its delay,
+/// using discrete SMIL `<animate>` elements looping over the total duration.
+/// No frames give an empty document.
+pub fn to_animated_svg(frames: &[(VectorizationResult, f64)], options: &Options) -> String {
+    let (width, height) = frames
+        .first()
+        .map_or((0, 0), |(result, _)| (result.width, result.height));
+    let mut document = new_document(width, height, options);
+
+    let mut defs = Definitions::new();
+    let mut path_ids = PathIds::default();
+    let mut classes = Vec::new();
+    let mut frame_groups = Vec::with_capacity(frames.len());
+
+    let duration: f64 = frames.iter().map(|(_, delay)| delay).sum();
+    let mut time = 0.0;
+
+    for (i, (result, delay)) in frames.iter().enumerate() {
+        let groups = layers_to_groups(
+            result,
+            options,
+            &mut |node| defs.append(node),
+            &mut path_ids,
+            &mut classes,
+        );
+        let mut frame_group = inkscape_layer(Group::new(), &format!("frame-{i}"), options);
+
+        if frames.len() > 1 && duration > 0.0 {
+            // `calcMode="discrete"` keeps each value until the next key time.
+            let (start, end) = (time / duration, (time + delay) / duration);
+            let mut values = Vec::with_capacity(3);
+            let mut key_times = Vec::with_capacity(3);
+            if start > 0.0 {
+                values.push("none");
+                key_times.push(0.0);
+            }
+            values.push("inline");
+            key_times.push(start);
+            if end < 1.0 {
+                values.push("none");
+                key_times.push(end);
+            }
+
+            frame_group.append(
+                Animate::new()
+                    .set("attributeName", "display")
+                    .set("values", values.join(";"))
+                    .set(
+                        "keyTimes",
+                        key_times
+                            .iter()
+                            .map(|t| trunc(*t).to_string())
+                            .collect::<Vec<_>>()
+                            .join(";"),
+                    )
+                    .set("dur", format!("{}ms", trunc(duration)))
+                    .set("calcMode", "discrete")
+                    .set("repeatCount", "indefinite"),
+            );
+        }
+
+        for group in groups {
+            frame_group.append(group);
+        }
+        frame_groups.push(frame_group);
+        time += delay;
+    }
+
+    append_style(&mut document, &classes, options);
+    document.append(defs);
+    for frame_group in frame_groups {
+        document.append(frame_group);
+    }
+
+    serialize(&document, options.svg.formatting)
+}
+
+/// Serializes `(error_threshold, result)` levels of detail as one SVG document.
+///
+/// Every level is a group with an `id` of `level-<index>` and a `data-error-threshold`,
+/// only the first one is displayed, the others can be revealed by toggling `display`.
+/// No levels give an empty document.
+pub fn to_svg_levels(levels: &[(f64, VectorizationResult)], options: &Options) -> String {
+    let (width, height) = levels
+        .first()
+        .map_or((0, 0), |(_, result)| (result.width, result.height));
+    let mut document = new_document(width, height, options);
+
+    let mut defs = Definitions::new();
+    let mut path_ids = PathIds::default();
+    let mut classes = Vec::new();
+    let mut level_groups = Vec::with_capacity(levels.len());
+
+    for (i, (error_threshold, result)) in levels.iter().enumerate() {
+        let groups = layers_to_groups(
+            result,
+            options,
+            &mut |node| defs.append(node),
+            &mut path_ids,
+            &mut classes,
+        );
+        let level_id = format!("{}level-{i}", options.svg.id_prefix);
+        let mut level_group = inkscape_layer(Group::new(), &level_id, options)
+            .set("id", level_id)
+            .set("data-error-threshold", trunc(*error_threshold));
+        if i > 0 {
+            level_group = level_group.set("display", "none");
+        }
+
+        for group in groups {
+            level_group.append(group);
+        }
+        level_groups.push(level_group);
+    }
+
+    append_style(&mut document, &classes, options);
+    document.append(defs);
+    for level_group in level_groups {
+        document.append(level_group);
+    }
+
+    serialize(&document, options.svg.formatting)
+}
+
+/// Serializes `(name, result)` pairs as one SVG sprite document, e.g. the icons of a web
+/// icon system or the tiles of a spritesheet.
+///
+/// Every result is a `<symbol>` with its own `viewBox`, to be displayed with
+/// `<use href="#<name>"/>`, the document is as large as the largest result.
+/// Symbol ids are the names after [`SvgOptions::id_prefix`](crate::structs::SvgOptions::id_prefix),
+/// with the characters not allowed in an id replaced by `-`. Names used more than once
+/// get a `-2`, `-3`… suffix, and the ids of the paths skip the ids of the symbols.
+pub fn to_svg_symbols(symbols: &[(String, VectorizationResult)], options: &Options) -> String {
+    let width = symbols.iter().map(|(_, result)| result.width).max();
+    let height = symbols.iter().map(|(_, result)| result.height).max();
+    let mut document = new_document(width.unwrap_or(0), height.unwrap_or(0), options);
+
+    let symbol_ids = symbol_ids(symbols.iter().map(|(name, _)| name.as_str()), options);
+    let mut defs = Definitions::new();
+    let mut path_ids = PathIds {
+        reserved: symbol_ids.iter().cloned().collect(),
+        ..PathIds::default()
+    };
+    let mut classes = Vec::new();
+    let mut symbol_elements = Vec::with_capacity(symbols.len());
+
+    for ((_, result), id) in symbols.iter().zip(symbol_ids) {
+        let groups = layers_to_groups(
+            result,
+            options,
+            &mut |node| defs.append(node),
+            &mut path_ids,
+            &mut classes,
+        );
+        let mut symbol = Symbol::new()
+            .set("id", id)
+            .set("viewBox", (0, 0, result.width, result.height));
+
+        for group in groups {
+            symbol.append(group);
+        }
+        symbol_elements.push(symbol);
+    }
+
+    append_style(&mut document, &classes, options);
+    document.append(defs);
+    for symbol in symbol_elements {
+        document.append(symbol);
+    }
+
+    serialize(&document, options.svg.formatting)
+}
+
+/// Unique ids of the symbols named `names`, in order, see [`to_svg_symbols`].
+fn symbol_ids<'a>(names: impl Iterator<Item = &'a str>, options: &Options) -> Vec<String> {
+    let mut taken = HashSet::new();
+    names
+        .map(|name| {
+            let mut id: String = name
+                .chars()
+                .map(
+                    |c| match c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                        true => c,
+                        false => '-',
+                    },
+                )
+                .collect();
+            // Ids can't start with a digit, a hyphen or a period.
+            let is_valid_start = |c: char| c.is_alphabetic() || c == '_';
+            if options.svg.id_prefix.is_empty() && !id.starts_with(is_valid_start) {
+                id.insert(0, '_');
+            }
+            let id = format!("{}{id}", options.svg.id_prefix);
+            let unique = (1..)
+                .map(|n| match n {
+                    1 => id.clone(),
+                    n => format!("{id}-{n}"),
+                })
+                .find(|candidate| !taken.contains(candidate))
+                .unwrap();
+            taken.insert(unique.clone());
+            unique
+        })
+        .collect()
+}
+
+/// Serializes `document` with the whitespace and attribute spelling of `formatting`.
+fn serialize(document: &Document, formatting: OutputFormatting) -> String {
+    let mut output = String::new();
+    write_node(&mut output, document, 0, formatting, false);
+    output
+}
+
+/// Appends `node` and its descendants at nesting `depth`,
+/// without whitespace between elements when `is_inline`.
+fn write_node(
+    output: &mut String,
+    node: &dyn Node,
+    depth: usize,
+    formatting: OutputFormatting,
+    is_inline: bool,
+) {
+    if node.get_attributes().is_none() {
+        // Text, comments and blobs, which are escaped by their `Display`.
+        write!(output, "{node}").unwrap();
+        return;
+    }
+
+    write_start_tag(output, node, formatting);
+    let children = node.get_children().map_or(&[][..], Vec::as_slice);
+    if children.is_empty() {
+        output.push_str("/>");
+        return;
+    }
+    output.push('>');
+
+    // Whitespace next to text would become part of it.
+    let is_inline = is_inline
+        || formatting == OutputFormatting::Minified
+        || children
+            .iter()
+            .any(|child| child.get_attributes().is_none());
+    for child in children {
+        if !is_inline {
+            new_line(output, depth + 1, formatting);
+        }
+        write_node(output, child.as_ref(), depth + 1, formatting, is_inline);
+    }
+    if !is_inline {
+        new_line(output, depth, formatting);
+    }
+    write!(output, "</{}>", node.get_name()).unwrap();
+}
+
+/// Appends the name and attributes of the element `node`, leaving its start tag open.
+fn write_start_tag(output: &mut String, node: &dyn Node, formatting: OutputFormatting) {
+    write!(output, "<{}", node.get_name()).unwrap();
+    let mut attributes = node
+        .get_attributes()
+        .map_or_else(Vec::new, |attributes| attributes.iter().collect());
+    attributes.sort_by_key(|(name, _value)| name.as_str());
+    for (name, value) in attributes {
+        let value = match formatting {
+            OutputFormatting::Minified => shorten_numbers(value),
+            OutputFormatting::Lines | OutputFormatting::Pretty { .. } => value.to_string(),
+        };
+        write!(output, r#" {name}="{}""#, escape_attribute(&value)).unwrap();
+    }
+}
+
+/// Starts a line indented for nesting `depth`.
+fn new_line(output: &mut String, depth: usize, formatting: OutputFormatting) {
+    output.push('\n');
+    if let OutputFormatting::Pretty { indent } = formatting {
+        output.extend(std::iter::repeat_n(' ', indent * depth));
+    }
+}
+
+/// Escapes the markup characters and quotes of an attribute value.
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// `value` with the leading zero of every space separated number dropped,
+/// e.g. `0.5 -0.25` as `.5 -.25`.
+fn shorten_numbers(value: &str) -> String {
+    value
+        .split(' ')
+        .map(|token| match token.parse::<f64>() {
+            Ok(_) if token.starts_with("0.") => token[1..].to_string(),
+            Ok(_) if token.starts_with("-0.") => format!("-{}", &token[2..]),
+            _ => token.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Replaces the `d` attribute of every `<path>` element in `document` with `f(d)`.
+///
+/// This is a plain text scan rather than a full XML parse,
+/// so formatting, comments and unknown elements are preserved.
+pub fn map_path_data<E>(
+    document: &str,
+    mut f: impl FnMut(&str) -> Result<String, E>,
+) -> Result<String, E> {
+    let mut output = String::with_capacity(document.len());
+    let mut rest = document;
+
+    while let Some(tag_start) = rest.find("<path") {
+        let tag_len = rest[tag_start..]
+            .find('>')
+            .map_or(rest.len() - tag_start, |i| i + 1);
+        let tag = &rest[tag_start..tag_start + tag_len];
+        output.push_str(&rest[..tag_start]);
+
+        // Skip other elements sharing the prefix, e.g. `<pattern>`.
+        let is_path = tag[5..].starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>');
+        match path_data_range(tag).filter(|_| is_path) {
+            Some((start, end)) => {
+                output.push_str(&tag[..start]);
+                output.push_str(&f(&tag[start..end])?);
+                output.push_str(&tag[end..]);
+            }
+            None => output.push_str(tag),
+        }
+        rest = &rest[tag_start + tag_len..];
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// Byte range of the `d` attribute value within an element tag.
+fn path_data_range(tag: &str) -> Option<(usize, usize)> {
+    let bytes = tag.as_bytes();
+    for (i, _) in tag.match_indices('d') {
+        if i == 0 || !bytes[i - 1].is_ascii_whitespace() {
+            continue;
+        }
+        let after_name = tag[i + 1..].trim_start();
+        let Some(after_eq) = after_name.strip_prefix('=') else {
+            continue;
+        };
+        let after_eq = after_eq.trim_start();
+        let quote = after_eq
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')?;
+        let start = tag.len() - after_eq.len() + 1;
+        let end = start + tag[start..].find(quote)?;
+        return Some((start, end));
+    }
+    None
+}
+
+/// `(is_cyclic, segments)` of every contour of a path.
+type PathSegments = Vec<(bool, Vec<Segment>)>;
+
+/// `(layer, contour)` of the shape a contour belongs to, see [`shape_keys`].
+type ShapeKey = (usize, usize);
+
+/// `(color, segments, shape keys, gradient)` of a path to emit.
+type LayerPath = ([u8; 4], PathSegments, Vec<ShapeKey>, Option<Gradient>);
+
+/// `(id, position)` of a path referenced by a `<use>` element,
+/// placed at `position` when it is a shape defined at the origin.
+type Reference = (String, Option<Point>);
+
+/// A contour replaced with a shape element, see
+/// [`SvgOptions::detect_primitives`](crate::structs::SvgOptions::detect_primitives).
+enum Primitive {
+    /// `(center, radii, angle)` of an `<ellipse>`, or a `<circle>` when its radii are equal.
+    Ellipse(Point, [f64; 2], f64),
+    /// `(center, size, angle)` of a `<rect>`.
+    Rect(Point, [f64; 2], f64),
+    /// Vertices of a regular `<polygon>`.
+    Polygon(Vec<Point>),
+}
+
+/// Passes the path data of every layer to `defs` as it is produced,
+/// returning the stroke and fill groups referencing them.
+/// With [`SvgOptions::merge_paths`](crate::structs::SvgOptions::merge_paths)
+/// layers of the same fill share one path.
+///
+/// With [`ColorMode::Separation`] every ink layer is instead referenced by its own
+/// Inkscape layer labeled with its channel, multiplied with the layers below,
+/// and the paths are colored through attributes.
+///
+/// With [`SvgOptions::css_classes`](crate::structs::SvgOptions::css_classes)
+/// the paths get the class of their color instead, registered in `classes`,
+/// and are referenced by a single group.
+fn layers_to_groups(
+    result: &VectorizationResult,
+    options: &Options,
+    defs: &mut dyn FnMut(Box<dyn Node>),
+    path_ids: &mut PathIds,
+    classes: &mut Vec<(String, u8)>,
+) -> Vec<Group> {
+    let mut stroke_group =
+        inkscape_layer(Group::new(), "strokes", options).set("stroke-width", "1px");
+    let mut fill_group = inkscape_layer(Group::new(), "fills", options);
+    let inks = match options.color_mode {
+        ColorMode::Separation => options.separation.channels.inks(),
+        ColorMode::Black | ColorMode::Colored => &[],
+    };
+    let css_classes = options.svg.css_classes && inks.is_empty();
+    let mut plates = Vec::new();
+
+    // Keyed by color and alpha, in the order of the layers so the output is stable.
+    let mut strokes: Vec<((String, u8), Vec<Reference>)> = Vec::new();
+    let mut fills: Vec<((String, u8), Vec<Reference>)> = Vec::new();
+
+    let shape_errors = match options.svg.fit_error_attributes {
+        Some(_) => shape_fit_errors(result),
+        None => HashMap::new(),
+    };
+
+    let mut paths: Vec<LayerPath> = Vec::new();
+    for (layer, layer_keys) in result.layers.iter().zip(shape_keys(result)) {
+        let merged = paths
+            .iter_mut()
+            .find(|(color, _segments, _keys, gradient)| {
+                options.svg.merge_paths
+                    && layer.gradient.is_none()
+                    && gradient.is_none()
+                    && *color == layer.color
+            });
+        match merged {
+            Some((_color, segments, keys, _gradient)) => {
+                segments.extend(layer.segments());
+                keys.extend(layer_keys);
+            }
+            None => paths.push((
+                layer.color,
+                layer.segments(),
+                layer_keys,
+                layer.gradient.clone(),
+            )),
+        }
+    }
+
+    for (color, mut segments, mut keys, mut gradient) in paths {
+        let mut fill_color = (rgb_to_hex(color[0], color[1], color[2]), color[3]);
+
+        // Build SVG path data
+        if options.svg.flip_y || options.svg.origin != [0.0, 0.0] {
+            let to_document = document_coordinates(result.height, options);
+            for (_is_cyclic, contour) in &mut segments {
+                for segment in contour.iter_mut() {
+                    *segment = segment.map(&to_document);
+                }
+            }
+            gradient = gradient.map(|gradient| gradient.map(&to_document));
+        }
+        let primitives = match options.svg.detect_primitives {
+            Some(tolerance) if gradient.is_none() => {
+                take_primitives(&mut segments, &mut keys, tolerance)
+            }
+            _ => Vec::new(),
+        };
+        // Translated copies would move the gradient along.
+        let shapes = match options.svg.reuse_shapes && gradient.is_none() {
+            true => take_repeated_shapes(&mut segments, &mut keys, options),
+            false => Vec::new(),
+        };
+        let chunks = match options.svg.max_path_commands {
+            Some(max_commands) => split_shapes(segments, keys, max_commands, options),
+            None => vec![(segments, keys)],
+        };
+
+        let mut elements: Vec<Element> = Vec::new();
+        let mut references: Vec<Reference> = Vec::new();
+        for (segments, keys) in chunks {
+            let Some(data) = path_data(&segments, options) else {
+                continue;
+            };
+            let id = path_ids.next(color, options);
+
+            // Defined along the first path, and shared by the others.
+            if let Some(gradient) = gradient.take() {
+                let gradient_id = format!("{id}-gradient");
+                defs(gradient_element(&gradient, &gradient_id).into());
+                fill_color = (format!("url(#{gradient_id})"), 255);
+            }
+
+            let mut path = SVGPath::new().set("id", id.clone()).set("d", data);
+            if options.svg.bbox_attributes {
+                let bbox = segments
+                    .iter()
+                    .flat_map(|(_is_cyclic, contour)| contour.iter().map(Segment::bbox))
+                    .reduce(|a, b| a.union(&b));
+                if let Some(bbox) = bbox {
+                    let values = [bbox.min.x, bbox.min.y, bbox.width(), bbox.height()];
+                    path = path.set("data-bbox", values.map(|v| trunc(v).to_string()).join(" "));
+                }
+            }
+            if let Some(threshold) = options.svg.fit_error_attributes {
+                let error = keys
+                    .iter()
+                    .filter_map(|key| shape_errors.get(key))
+                    .fold(0.0, |a: f64, b| a.max(*b));
+                if error > threshold {
+                    path = path.set("data-fit-error", trunc(error));
+                }
+            }
+            elements.push(path.into());
+            references.push((id, None));
+        }
+        for primitive in primitives {
+            let id = path_ids.next(color, options);
+            let mut element = primitive_element(&primitive);
+            element.assign("id", id.clone());
+            if options.svg.bbox_attributes {
+                let bbox = primitive_bbox(&primitive);
+                let values = [bbox.min.x, bbox.min.y, bbox.width(), bbox.height()];
+                element.assign("data-bbox", values.map(|v| trunc(v).to_string()).join(" "));
+            }
+            elements.push(element);
+            references.push((id, None));
+        }
+        for (data, positions) in shapes {
+            let id = path_ids.next(color, options);
+            elements.push(SVGPath::new().set("id", id.clone()).set("d", data).into());
+            references.extend(positions.into_iter().map(|p| (id.clone(), Some(p))));
+        }
+        if references.is_empty() {
+            continue;
+        }
+
+        if let Some((label, _ink)) = inks.iter().find(|(_label, ink)| *ink == color) {
+            elements.into_iter().for_each(|path| defs(path.into()));
+            let mut plate = Group::new()
+                .set("inkscape:groupmode", "layer")
+                .set("inkscape:label", *label)
+                .set("style", "mix-blend-mode:multiply")
+                .set("fill", fill_color.0);
+            for reference in &references {
+                plate.append(use_element(reference, options));
+            }
+            plates.push(plate);
+            continue;
+        }
+
+        if css_classes {
+            let class = match classes.iter().position(|c| *c == fill_color) {
+                Some(class) => class,
+                None => {
+                    classes.push(fill_color);
+                    classes.len() - 1
+                }
+            };
+            let class = class_name(class, options);
+            for mut element in elements {
+                element.assign("class", class.clone());
+                defs(element.into());
+            }
+            for reference in &references {
+                stroke_group.append(use_element(reference, options));
+            }
+            continue;
+        }
+        elements.into_iter().for_each(|path| defs(path.into()));
+
+        for reference in references {
+            group_push(&mut strokes, fill_color.clone(), reference.clone());
+            group_push(&mut fills, fill_color.clone(), reference);
+        }
+    }
+
+    for ((stroke, alpha), references) in strokes.iter() {
+        let mut group = Group::new().set("stroke", stroke.clone());
+        if *alpha < 255 {
+            group = group.set("stroke-opacity", opacity(*alpha));
+        }
+
+        for reference in references {
+            group.append(use_element(reference, options));
+        }
+
+        stroke_group.append(group);
+    }
+
+    for ((fill, alpha), references) in fills.iter() {
+        let mut group = Group::new().set("fill", fill.clone());
+        if *alpha < 255 {
+            group = group.set("fill-opacity", opacity(*alpha));
+        }
+
+        for reference in references {
+            group.append(use_element(reference, options));
+        }
+
+        fill_group.append(group);
+    }
+
+    if !inks.is_empty() {
+        // Only the transparent layer is left out of the plates.
+        let mut groups = match fills.is_empty() {
+            true => Vec::new(),
+            false => vec![stroke_group, fill_group],
+        };
+        groups.extend(plates);
+        groups
+    } else if css_classes {
+        vec![stroke_group]
+    } else {
+        vec![stroke_group, fill_group]
+    }
+}
+
+/// A `<use>` element of a [`Reference`], see [`SvgProfile::href`].
+fn use_element((id, position): &Reference, options: &Options) -> Use {
+    let element = Use::new().set(options.svg.profile.href(), format!("#{id}"));
+    match position {
+        Some(position) => element
+            .set("x", trunc(position.x))
+            .set("y", trunc(position.y)),
+        None => element,
+    }
+}
+
+/// Optimized path data of the contours with the [`CoordinateMode`] of the options,
+/// `None` when there are none.
+fn path_data(segments: &[(bool, Vec<Segment>)], options: &Options) -> Option<String> {
+    let mut optimized_data = OptimizedData::from_segments(segments);
+    optimized_data.remove_degenerate();
+    if optimized_data.is_empty() {
+        return None;
+    }
+    if options.curve_type == CurveType::Quadratic {
+        optimized_data.to_quadratic(options.quadratic_tolerance);
+    }
+    Some(match options.svg.coordinate_mode {
+        CoordinateMode::Absolute => optimized_data.optimize(),
+        CoordinateMode::Relative => {
+            optimized_data.to_relative();
+            optimized_data.optimize()
+        }
+        CoordinateMode::Auto => optimized_data.optimize_shortest(),
+    })
+}
+
+/// Takes the contours repeated up to a translation out of `segments`, when that
+/// shortens the document, see
+/// [`SvgOptions::reuse_shapes`](crate::structs::SvgOptions::reuse_shapes).
+///
+/// Returns the path data of every repeated shape starting at the origin,
+/// along with the start points of its copies.
+///
+/// `keys` are the shapes of the contours, the ones of the contours taken are removed.
+fn take_repeated_shapes(
+    segments: &mut PathSegments,
+    keys: &mut Vec<ShapeKey>,
+    options: &Options,
+) -> Vec<(String, Vec<Point>)> {
+    let mut shapes: Vec<(String, Vec<Point>)> = Vec::new();
+    let mut indices: HashMap<String, usize> = HashMap::new();
+    let mut contour_shapes = Vec::with_capacity(segments.len());
+    for (is_cyclic, contour) in segments.iter() {
+        let Some(start) = contour.first().map(Segment::start) else {
+            contour_shapes.push(None);
+            continue;
+        };
+        let moved = contour.iter().map(|s| s.map(|p| p - start)).collect();
+        let Some(data) = path_data(&[(*is_cyclic, moved)], options) else {
+            contour_shapes.push(None);
+            continue;
+        };
+        let index = *indices.entry(data).or_insert_with_key(|data| {
+            shapes.push((data.clone(), Vec::new()));
+            shapes.len() - 1
+        });
+        shapes[index].1.push(start);
+        contour_shapes.push(Some(index));
+    }
+
+    let kept = contour_shapes
+        .into_iter()
+        .map(|index| {
+            index.is_none_or(|index| {
+                let (data, positions) = &shapes[index];
+                !is_worth_reusing(data, positions.len())
+            })
+        })
+        .collect::<Vec<_>>();
+    let (mut kept_segments, mut kept_keys) = (kept.iter(), kept.iter());
+    segments.retain(|_| *kept_segments.next().unwrap());
+    keys.retain(|_| *kept_keys.next().unwrap());
+    shapes.retain(|(data, positions)| is_worth_reusing(data, positions.len()));
+    shapes
+}
+
+/// Takes the closed contours standing alone as their shape and within `tolerance`
+/// of a circle or an ellipse out of `segments`, see
+/// [`SvgOptions::detect_primitives`](crate::structs::SvgOptions::detect_primitives).
+///
+/// `keys` are the shapes of the contours, the ones of the contours taken are removed.
+fn take_primitives(
+    segments: &mut PathSegments,
+    keys: &mut Vec<ShapeKey>,
+    tolerance: f64,
+) -> Vec<Primitive> {
+    let mut contour_counts: HashMap<ShapeKey, usize> = HashMap::new();
+    for key in keys.iter() {
+        *contour_counts.entry(*key).or_default() += 1;
+    }
+    let contour_primitives = segments
+        .iter()
+        .zip(keys.iter())
+        .map(|((is_cyclic, contour), key)| match contour_counts[key] {
+            1 if *is_cyclic => detect_primitive(contour, tolerance),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let (mut kept_segments, mut kept_keys) = (contour_primitives.iter(), contour_primitives.iter());
+    segments.retain(|_| kept_segments.next().unwrap().is_none());
+    keys.retain(|_| kept_keys.next().unwrap().is_none());
+    contour_primitives.into_iter().flatten().collect()
+}
+
+/// The circle, the ellipse, the rectangle or else the regular polygon
+/// of 3 to 12 vertices fitting the closed contour within `tolerance`.
+fn detect_primitive(contour: &[Segment], tolerance: f64) -> Option<Primitive> {
+    let points = segments_flatten(true, contour, tolerance / 4.0);
+    let perimeter = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.len_with(*b))
+        .sum::<f64>();
+    if perimeter <= 0.0 {
+        return None;
+    }
+    // Evenly spaced, so long straight edges are measured along their length.
+    let points = poly_subdivide_to_limit(true, &points, perimeter / 64.0);
+
+    let circle = circle_fit(&points).map(|(center, radius)| (center, [radius; 2], 0.0));
+    let ellipse = [circle, ellipse_fit(&points)]
+        .into_iter()
+        .flatten()
+        .find(|ellipse| ellipse_deviation(&points, *ellipse) <= tolerance);
+    if let Some((center, radii, angle)) = ellipse {
+        return Some(Primitive::Ellipse(center, radii, angle));
+    }
+
+    // The corners, without the vertices left along edges, e.g. the start of the points
+    // or the ends of cut corners.
+    let mut vertices = poly_simplify_douglas_peucker(true, &points, tolerance);
+    while vertices.len() > 3 {
+        let len = vertices.len();
+        let Some(i) = (0..len).find(|i| {
+            let (before, after) = (vertices[(i + len - 1) % len], vertices[(i + 1) % len]);
+            segment_distance_squared(vertices[*i], before, after) <= tolerance * tolerance
+        }) else {
+            break;
+        };
+        vertices.remove(i);
+    }
+    match vertices.len() {
+        4 => {
+            let bbox = BoundingBox::from_points(points.iter().copied())?;
+            let axis_aligned = (bbox.min.mid(bbox.max), [bbox.width(), bbox.height()], 0.0);
+            [Some(axis_aligned), rect_fit(&points)]
+                .into_iter()
+                .flatten()
+                .find(|rect| polygon_deviation(&points, &rect_corners(*rect)) <= tolerance)
+                .map(|(center, size, angle)| Primitive::Rect(center, size, angle))
+        }
+        3..=12 => {
+            let polygon = regular_polygon_fit(&vertices);
+            (polygon_deviation(&points, &polygon) <= tolerance)
+                .then_some(Primitive::Polygon(polygon))
+        }
+        _ => None,
+    }
+}
+
+/// The `<circle>`, `<ellipse>`, `<rect>` or `<polygon>` of the primitive,
+/// rotated around its center when it has an angle.
+fn primitive_element(primitive: &Primitive) -> Element {
+    let (mut element, center, angle): (Element, _, _) = match primitive {
+        Primitive::Ellipse(center, [rx, ry], _) if rx == ry => {
+            let circle = Circle::new()
+                .set("cx", trunc(center.x))
+                .set("cy", trunc(center.y))
+                .set("r", trunc(*rx));
+            return circle.into();
+        }
+        Primitive::Ellipse(center, [rx, ry], angle) => {
+            let ellipse = Ellipse::new()
+                .set("cx", trunc(center.x))
+                .set("cy", trunc(center.y))
+                .set("rx", trunc(*rx))
+                .set("ry", trunc(*ry));
+            (ellipse.into(), center, angle)
+        }
+        Primitive::Rect(center, [width, height], angle) => {
+            let rect = Rectangle::new()
+                .set("x", trunc(center.x - width / 2.0))
+                .set("y", trunc(center.y - height / 2.0))
+                .set("width", trunc(*width))
+                .set("height", trunc(*height));
+            (rect.into(), center, angle)
+        }
+        Primitive::Polygon(vertices) => {
+            let points = vertices
+                .iter()
+                .map(|v| format!("{},{}", trunc(v.x), trunc(v.y)))
+                .collect::<Vec<_>>();
+            return Polygon::new().set("points", points.join(" ")).into();
+        }
+    };
+    let degrees = trunc(angle.to_degrees());
+    if degrees != 0.0 {
+        let (cx, cy) = (trunc(center.x), trunc(center.y));
+        element.assign("transform", format!("rotate({degrees} {cx} {cy})"));
+    }
+    element
+}
+
+/// Bounds of the primitive.
+fn primitive_bbox(primitive: &Primitive) -> BoundingBox {
+    let points = match primitive {
+        Primitive::Ellipse(center, [rx, ry], angle) => {
+            let (sin, cos) = angle.sin_cos();
+            let extent = Point::new((rx * cos).hypot(ry * sin), (rx * sin).hypot(ry * cos));
+            vec![center.sub(extent), center.add(extent)]
+        }
+        Primitive::Rect(center, size, angle) => rect_corners((*center, *size, *angle)).to_vec(),
+        Primitive::Polygon(vertices) => vertices.clone(),
+    };
+    BoundingBox::from_points(points).unwrap()
+}
+
+/// `(layer, contour)` of the shape every contour of every layer belongs to, per layer:
+/// holes belong to the outer contour of their layer enclosing them, see
+/// [`VectorizationResult::contour_tree`], and the other contours to themselves.
+fn shape_keys(result: &VectorizationResult) -> Vec<Vec<ShapeKey>> {
+    let mut keys = result
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(i, layer)| (0..layer.curves.len()).map(|j| (i, j)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let nodes = &result.contour_tree;
+    for node in nodes {
+        let Some(parent) = node.parent.map(|parent| &nodes[parent]) else {
+            continue;
+        };
+        if node.is_hole && !parent.is_hole && parent.layer == node.layer {
+            if let Some(key) = keys
+                .get_mut(node.layer)
+                .and_then(|keys| keys.get_mut(node.contour))
+            {
+                *key = (parent.layer, parent.contour);
+            }
+        }
+    }
+    keys
+}
+
+/// Largest fit error of the contours of every shape, see [`shape_keys`]
+/// and [`Layer::fit_errors`](crate::structs::Layer::fit_errors).
+fn shape_fit_errors(result: &VectorizationResult) -> HashMap<ShapeKey, f64> {
+    let mut errors: HashMap<ShapeKey, f64> = HashMap::new();
+    for (layer, keys) in result.layers.iter().zip(shape_keys(result)) {
+        for (contour_errors, key) in layer.fit_errors().iter().zip(keys) {
+            let error = errors.entry(key).or_default();
+            *error = contour_errors.iter().fold(*error, |a, b| a.max(*b));
+        }
+    }
+    errors
+}
+
+/// Splits the contours into runs of whole shapes of at most `max_commands` path
+/// commands, a shape longer than that being a run of its own, see
+/// [`SvgOptions::max_path_commands`](crate::structs::SvgOptions::max_path_commands).
+///
+/// `keys` are the shapes of the contours, the contours of a shape are moved
+/// next to its first one so that each run starts with the `M` of a whole contour.
+/// Every run is returned along with the keys of its contours.
+fn split_shapes(
+    segments: PathSegments,
+    keys: Vec<ShapeKey>,
+    max_commands: usize,
+    options: &Options,
+) -> Vec<(PathSegments, Vec<ShapeKey>)> {
+    // `(contours, keys, commands)` of every shape, in the order of their first contour.
+    let mut shapes: Vec<(PathSegments, Vec<ShapeKey>, usize)> = Vec::new();
+    let mut indices: HashMap<ShapeKey, usize> = HashMap::new();
+    for (contour, key) in segments.into_iter().zip(keys) {
+        let mut data = OptimizedData::from_segments(slice::from_ref(&contour));
+        if options.curve_type == CurveType::Quadratic {
+            data.to_quadratic(options.quadratic_tolerance);
+        }
+        let index = *indices.entry(key).or_insert_with(|| {
+            shapes.push((Vec::new(), Vec::new(), 0));
+            shapes.len() - 1
+        });
+        shapes[index].0.push(contour);
+        shapes[index].1.push(key);
+        shapes[index].2 += data.len();
+    }
+
+    let mut runs: Vec<(PathSegments, Vec<ShapeKey>, usize)> = Vec::new();
+    for (contours, shape_keys, commands) in shapes {
+        match runs.last_mut() {
+            Some((run, run_keys, count)) if *count + commands <= max_commands => {
+                run.extend(contours);
+                run_keys.extend(shape_keys);
+                *count += commands;
+            }
+            _ => runs.push((contours, shape_keys, commands)),
+        }
+    }
+    runs.into_iter()
+        .map(|(run, run_keys, _count)| (run, run_keys))
+        .collect()
+}
+
+/// Whether defining a shape once along with the `<use>` elements placing its `copies`,
+/// about 40 bytes in each of the stroke and fill groups, is shorter than writing every copy.
+fn is_worth_reusing(data: &str, copies: usize) -> bool {
+    copies > 1 && copies * data.len() > data.len() + copies * 80
+}
+
+/// `group` marked as an Inkscape layer named `label` with [`SvgProfile::Inkscape`].
+fn inkscape_layer(group: Group, label: &str, options: &Options) -> Group {
+    match options.svg.profile {
+        SvgProfile::Inkscape => group
+            .set("inkscape:groupmode", "layer")
+            .set("inkscape:label", label),
+        SvgProfile::Svg11 | SvgProfile::Svg2 => group,
+    }
+}
+
+/// A `<linearGradient>` or `<radialGradient>` in user space, so it matches the path coordinates.
+fn gradient_element(gradient: &Gradient, id: &str) -> Element {
+    let mut element: Element = match gradient {
+        Gradient::Linear { start, end, .. } => LinearGradient::new()
+            .set("x1", trunc(start.x))
+            .set("y1", trunc(start.y))
+            .set("x2", trunc(end.x))
+            .set("y2", trunc(end.y))
+            .into(),
+        Gradient::Radial { center, radius, .. } => RadialGradient::new()
+            .set("cx", trunc(center.x))
+            .set("cy", trunc(center.y))
+            .set("r", trunc(*radius))
+            .into(),
+    };
+    element.assign("id", id);
+    element.assign("gradientUnits", "userSpaceOnUse");
+    for (offset, color) in gradient.stops() {
+        let mut stop = Stop::new()
+            .set("offset", trunc(*offset))
+            .set("stop-color", rgb_to_hex(color[0], color[1], color[2]));
+        if color[3] < 255 {
+            stop = stop.set("stop-opacity", opacity(color[3]));
+        }
+        element.append(stop);
+    }
+    element
+}
+
+/// Appends `id` to the ids of `key`, adding it last when new.
+fn group_push(
+    groups: &mut Vec<((String, u8), Vec<Reference>)>,
+    key: (String, u8),
+    reference: Reference,
+) {
+    match groups.iter_mut().find(|(k, _references)| *k == key) {
+        Some((_key, references)) => references.push(reference),
+        None => groups.push((key, vec![reference])),
+    }
+}
+
+/// Path ids handed out in a document, see [`SvgOptions::id_scheme`](crate::structs::SvgOptions::id_scheme).
+#[derive(Default)]
+struct PathIds {
+    count: usize,
+    /// Number of ids of each color with [`IdScheme::Color`].
+    colors: HashMap<[u8; 4], usize>,
+    /// Ids of other elements, e.g. of the symbols of [`to_svg_symbols`], skipped.
+    reserved: HashSet<String>,
+}
+
+impl PathIds {
+    fn next(&mut self, color: [u8; 4], options: &Options) -> String {
+        loop {
+            let id = self.next_candidate(color, options);
+            // Gradients are defined along their first path as `<id>-gradient`.
+            let gradient_id = format!("{id}-gradient");
+            if !self.reserved.contains(&id) && !self.reserved.contains(&gradient_id) {
+                return id;
+            }
+        }
+    }
+
+    fn next_candidate(&mut self, color: [u8; 4], options: &Options) -> String {
+        let prefix = &options.svg.id_prefix;
+        let id = match options.svg.id_scheme {
+            IdScheme::Counter => format!("{prefix}{}", generate_id(self.count)),
+            IdScheme::Color => {
+                let n = self.colors.entry(color).or_default();
+                let [r, g, b, a] = color;
+                let alpha = match a {
+                    255 => String::new(),
+                    a => format!("{a:02x}"),
+                };
+                let id = format!("{prefix}color-{r:02x}{g:02x}{b:02x}{alpha}-{n}");
+                *n += 1;
+                id
+            }
+        };
+        self.count += 1;
+        id
+    }
+}
+
+/// Maps image coordinates to the coordinate system of
+/// [`SvgOptions::flip_y`](crate::structs::SvgOptions::flip_y)
+/// and [`SvgOptions::origin`](crate::structs::SvgOptions::origin).
+fn document_coordinates(height: u32, options: &Options) -> impl Fn(Point) -> Point {
+    let [x, y] = options.svg.origin;
+    let origin = Point::new(x, y);
+    let (flip_y, height) = (options.svg.flip_y, height as f64);
+    move |point| {
+        let point = if flip_y {
+            Point::new(point.x, height - point.y)
+        } else {
+            point
+        };
+        point.sub(origin)
+    }
+}
+
+/// Opacity attribute value of an alpha channel.
+fn opacity(alpha: u8) -> f32 {
+    trunc(alpha as f64 / 255.0)
+}
+
+/// Name of the `class`-th color class, after [`SvgOptions::id_prefix`](crate::structs::SvgOptions::id_prefix)
+/// reduced to the characters a CSS selector takes unescaped.
+fn class_name(class: usize, options: &Options) -> String {
+    let mut name: String = (options.svg.id_prefix.chars())
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+                true => c,
+                false => '-',
+            },
+        )
+        .collect();
+    // Identifiers can't start with a digit or a hyphen.
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && !name.is_empty() {
+        name.insert(0, '_');
+    }
+    format!("{name}c{class}")
+}
+
+/// Appends a `<style>` filling and stroking every path with the color of its class.
+fn append_style(document: &mut Document, classes: &[(String, u8)], options: &Options) {
+    if let Some(style) = style_element(classes, options) {
+        document.append(style);
+    }
+}
+
+/// The `<style>` of [`append_style`], `None` without classes.
+fn style_element(classes: &[(String, u8)], options: &Options) -> Option<Style> {
+    if classes.is_empty() {
+        return None;
+    }
+    let rules = classes
+        .iter()
+        .enumerate()
+        .map(|(class, (color, alpha))| {
+            let class = class_name(class, options);
+            match *alpha {
+                255 => format!(".{class}{{fill:{color};stroke:{color}}}"),
+                alpha => format!(
+                    ".{class}{{fill:{color};stroke:{color};fill-opacity:{o};stroke-opacity:{o}}}",
+                    o = opacity(alpha)
+                ),
+            }
+        })
+        .collect::<Vec<_>>();
+    let separator = match options.svg.formatting {
+        OutputFormatting::Minified => "",
+        OutputFormatting::Lines | OutputFormatting::Pretty { .. } => "\n",
+    };
+    let mut style = Style::new(rules.join(separator));
+    if options.svg.profile != SvgProfile::Svg2 {
+        style = style.set("type", "text/css");
+    }
+    Some(style)
+}
@@ -1,13 +1,28 @@
-use std::{
-    num::ParseFloatError,
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    fmt, mem,
     ops::{Deref, DerefMut},
     str::FromStr,
 };
 
+#[cfg(feature = "std")]
 use svg::node::{element::path::Data, Value};
 
-use crate::utils::trunc;
+#[cfg(not(feature = "std"))]
+use crate::math::F64Ext;
+#[cfg(feature = "std")]
+use crate::structs::Segment;
+use crate::{
+    curve_fit_nd::{fit_poly_list, poly_list_subdivide_to_limit, CornerOptions},
+    vec2::DVec2,
+};
 
+/// Numeric arguments of a single command.
 #[derive(Clone, Debug)]
 pub struct Parameters(pub Vec<f64>);
 
@@ -37,12 +52,14 @@ impl From<Parameters> for String {
     }
 }
 
+/// Whether a command's coordinates are absolute or relative to the current point.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Position {
     Absolute,
     Relative,
 }
 
+/// A path command, named after its SVG letter.
 #[derive(Debug, Clone)]
 pub enum Command {
     M(Position, Parameters),
@@ -57,6 +74,123 @@ pub enum Command {
     Z,
 }
 
+impl Command {
+    /// Position and parameters, `None` for `Z`.
+    pub fn parts(&self) -> Option<(Position, &Parameters)> {
+        match self {
+            Command::M(pos, args)
+            | Command::L(pos, args)
+            | Command::H(pos, args)
+            | Command::V(pos, args)
+            | Command::C(pos, args)
+            | Command::S(pos, args)
+            | Command::Q(pos, args)
+            | Command::T(pos, args)
+            | Command::A(pos, args) => Some((*pos, args)),
+            Command::Z => None,
+        }
+    }
+
+    /// Mutable position and parameters, `None` for `Z`.
+    pub fn parts_mut(&mut self) -> Option<(&mut Position, &mut Parameters)> {
+        match self {
+            Command::M(pos, args)
+            | Command::L(pos, args)
+            | Command::H(pos, args)
+            | Command::V(pos, args)
+            | Command::C(pos, args)
+            | Command::S(pos, args)
+            | Command::Q(pos, args)
+            | Command::T(pos, args)
+            | Command::A(pos, args) => Some((pos, args)),
+            Command::Z => None,
+        }
+    }
+
+    /// Axis of every parameter in one group (`0` for x, `1` for y),
+    /// `None` for arc radii, rotation and flags.
+    ///
+    /// Parameters may hold several groups, repeating the command.
+    pub fn axes(&self) -> &'static [Option<usize>] {
+        const X: Option<usize> = Some(0);
+        const Y: Option<usize> = Some(1);
+        match self {
+            Command::M(..) | Command::L(..) | Command::T(..) => &[X, Y],
+            Command::H(..) => &[X],
+            Command::V(..) => &[Y],
+            Command::C(..) => &[X, Y, X, Y, X, Y],
+            Command::S(..) | Command::Q(..) => &[X, Y, X, Y],
+            Command::A(..) => &[None, None, None, None, None, X, Y],
+            Command::Z => &[],
+        }
+    }
+
+    /// The same command with other parameters.
+    fn with_parameters(&self, args: Parameters) -> Command {
+        match *self {
+            Command::M(pos, _) => Command::M(pos, args),
+            Command::L(pos, _) => Command::L(pos, args),
+            Command::H(pos, _) => Command::H(pos, args),
+            Command::V(pos, _) => Command::V(pos, args),
+            Command::C(pos, _) => Command::C(pos, args),
+            Command::S(pos, _) => Command::S(pos, args),
+            Command::Q(pos, _) => Command::Q(pos, args),
+            Command::T(pos, _) => Command::T(pos, args),
+            Command::A(pos, _) => Command::A(pos, args),
+            Command::Z => Command::Z,
+        }
+    }
+
+    /// The current point after this command, given the current point before it
+    /// and the start of the sub-path.
+    pub fn end_point(&self, cursor: DVec2, start: DVec2) -> DVec2 {
+        let Some((pos, args)) = self.parts() else {
+            return start;
+        };
+        let axes = self.axes();
+        let mut end = [cursor.x, cursor.y];
+        for values in args.chunks_exact(axes.len()) {
+            let origin = end;
+            for (value, axis) in values.iter().zip(axes) {
+                if let Some(axis) = *axis {
+                    end[axis] = match pos {
+                        Position::Absolute => *value,
+                        Position::Relative => origin[axis] + value,
+                    };
+                }
+            }
+        }
+        DVec2::new(end[0], end[1])
+    }
+}
+
+/// Whether a drawing command from `cursor` to `end` stays on `cursor`, control points included.
+///
+/// Smooth curves are never zero length, their implicit control point may lie elsewhere.
+fn is_zero_length(command: &Command, cursor: DVec2, end: DVec2) -> bool {
+    const EPSILON: f64 = 1e-9;
+    let Some((pos, args)) = command.parts() else {
+        return false;
+    };
+    let origin = match pos {
+        Position::Absolute => [0.0, 0.0],
+        Position::Relative => [cursor.x, cursor.y],
+    };
+    let cursor = [cursor.x, cursor.y];
+    match command {
+        Command::S(..) | Command::T(..) => false,
+        // Arcs ending where they start are not drawn.
+        Command::A(..) => end.sub(DVec2::new(cursor[0], cursor[1])).len() <= EPSILON,
+        _ => args
+            .iter()
+            .zip(command.axes().iter().cycle())
+            .all(|(value, axis)| {
+                axis.is_none_or(|axis| (value + origin[axis] - cursor[axis]).abs() <= EPSILON)
+            }),
+    }
+}
+
+/// SVG path data, parsed with [`str::parse`] and serialized with [`OptimizedData::optimize`].
 #[derive(Debug, Clone, Default)]
 pub struct OptimizedData(Vec<Command>);
 
@@ -94,151 +228,556 @@ impl OptimizedData {
         self.0.push(command);
     }
 
-    /// Convert all commands to relative.
+    /// Convert all commands to relative, except a leading move.
     pub fn to_relative(&mut self) {
-        let mut start = (0.0, 0.0);
-        let mut cursor = (0.0, 0.0);
-
-        for i in 0..self.0.len() {
-            // Take ownership of the command using `std::mem::replace`.
-            let command = std::mem::replace(&mut self.0[i], Command::Z);
-            let new_command = match command {
-                Command::M(pos, mut args) => {
-                    if pos == Position::Absolute && i != 0 {
-                        // Convert the coordinates to relative
-                        args.0[0] -= cursor.0;
-                        args.0[1] -= cursor.1;
-                        // Update cursor after conversion
-                        cursor.0 += args.0[0];
-                        cursor.1 += args.0[1];
-                        start = cursor;
-                        Command::M(Position::Relative, args)
-                    } else {
-                        // For the first M (or if already relative)
-                        cursor.0 += args.0[0];
-                        cursor.1 += args.0[1];
-                        start = cursor;
-                        Command::M(pos, args)
+        self.convert(Position::Relative);
+    }
+
+    /// Convert all commands to absolute.
+    pub fn to_absolute(&mut self) {
+        self.convert(Position::Absolute);
+    }
+
+    fn convert(&mut self, target: Position) {
+        let mut start = [0.0, 0.0];
+        let mut cursor = [0.0, 0.0];
+
+        for (i, command) in self.0.iter_mut().enumerate() {
+            let is_move = matches!(command, Command::M(..));
+            let axes = command.axes();
+            let Some((pos, args)) = command.parts_mut() else {
+                // Close path: reset the cursor to the starting point.
+                cursor = start;
+                continue;
+            };
+            // A leading move is the same either way, keep it absolute.
+            let is_convert = *pos != target && !(i == 0 && target == Position::Relative);
+
+            for (group, values) in args.0.chunks_exact_mut(axes.len()).enumerate() {
+                let origin = match *pos {
+                    Position::Absolute => [0.0, 0.0],
+                    Position::Relative => cursor,
+                };
+                let mut end = cursor;
+                for (value, axis) in values.iter_mut().zip(axes) {
+                    let Some(axis) = *axis else {
+                        continue;
+                    };
+                    let absolute = *value + origin[axis];
+                    end[axis] = absolute;
+                    if is_convert {
+                        *value = match target {
+                            Position::Absolute => absolute,
+                            Position::Relative => absolute - cursor[axis],
+                        };
                     }
                 }
-                Command::L(pos, mut args) => {
-                    if pos == Position::Absolute {
-                        args.0[0] -= cursor.0;
-                        args.0[1] -= cursor.1;
-                        cursor.0 += args.0[0];
-                        cursor.1 += args.0[1];
-                        Command::L(Position::Relative, args)
-                    } else {
-                        cursor.0 += args.0[0];
-                        cursor.1 += args.0[1];
-                        Command::L(pos, args)
+                cursor = end;
+                if is_move && group == 0 {
+                    start = cursor;
+                }
+            }
+            if is_convert {
+                *pos = target;
+            }
+        }
+    }
+
+    /// Split commands with repeated parameter groups into one command per group,
+    /// repeated move coordinates become lines.
+    pub fn split_groups(&mut self) {
+        let mut commands = Vec::with_capacity(self.0.len());
+        for command in self.0.drain(..) {
+            let arity = command.axes().len();
+            match command.parts() {
+                Some((pos, args)) if args.len() > arity => {
+                    for (group, values) in args.chunks_exact(arity).enumerate() {
+                        let values = Parameters(values.to_vec());
+                        commands.push(match command {
+                            Command::M(..) if group > 0 => Command::L(pos, values),
+                            _ => command.with_parameters(values),
+                        });
                     }
                 }
-                Command::H(pos, mut args) => {
-                    if pos == Position::Absolute {
-                        args.0[0] -= cursor.0;
-                        cursor.0 += args.0[0];
-                        Command::H(Position::Relative, args)
-                    } else {
-                        cursor.0 += args.0[0];
-                        Command::H(pos, args)
+                _ => commands.push(command),
+            }
+        }
+        self.0 = commands;
+    }
+
+    /// Remove segments drawing nothing, e.g. `c0 0 0 0 0 0`, then the sub-paths left
+    /// without any segment, e.g. `m1 1z`, along with trailing and repeated moves.
+    ///
+    /// Curves whose control point is reflected by a following `S` or `T` are kept,
+    /// and a relative move after a removed sub-path becomes absolute.
+    pub fn remove_degenerate(&mut self) {
+        self.split_groups();
+
+        let mut commands = Vec::with_capacity(self.0.len());
+        // Commands of the current sub-path, and whether one of them draws something.
+        let mut subpath = Vec::new();
+        let mut is_drawn = false;
+        let mut is_removed = false;
+        let mut start = DVec2::ZERO;
+        let mut cursor = DVec2::ZERO;
+
+        let mut input = mem::take(&mut self.0).into_iter().peekable();
+        while let Some(mut command) = input.next() {
+            let end = command.end_point(cursor, start);
+            match command {
+                Command::M(pos, _) => {
+                    match is_drawn {
+                        true => commands.append(&mut subpath),
+                        false => is_removed |= !subpath.is_empty(),
+                    }
+                    subpath.clear();
+                    is_drawn = false;
+                    if is_removed && pos == Position::Relative {
+                        command = Command::M(Position::Absolute, Parameters(vec![end.x, end.y]));
                     }
+                    is_removed = false;
+                    start = end;
                 }
-                Command::V(pos, mut args) => {
-                    if pos == Position::Absolute {
-                        args.0[0] -= cursor.1;
-                        cursor.1 += args.0[0];
-                        Command::V(Position::Relative, args)
-                    } else {
-                        cursor.1 += args.0[0];
-                        Command::V(pos, args)
+                Command::Z => {}
+                _ if is_zero_length(&command, cursor, end)
+                    && !matches!(input.peek(), Some(Command::S(..) | Command::T(..))) =>
+                {
+                    continue;
+                }
+                _ => is_drawn = true,
+            }
+            cursor = end;
+            subpath.push(command);
+        }
+        if is_drawn {
+            commands.append(&mut subpath);
+        }
+
+        self.0 = commands;
+    }
+
+    /// Merge consecutive straight segments (`L`, `H` and `V`) heading the same direction.
+    ///
+    /// The merged segment keeps the position of the first one,
+    /// so this can run before or after [`OptimizedData::to_relative`].
+    pub fn merge_lines(&mut self) {
+        self.split_groups();
+
+        let mut commands: Vec<Command> = Vec::with_capacity(self.0.len());
+        let mut start = DVec2::ZERO;
+        let mut cursor = DVec2::ZERO;
+        // Start point of the last pushed command when it is a straight segment.
+        let mut line_start: Option<DVec2> = None;
+
+        for command in self.0.drain(..) {
+            let end = command.end_point(cursor, start);
+            if !matches!(command, Command::L(..) | Command::H(..) | Command::V(..)) {
+                if let Command::M(..) = command {
+                    start = end;
+                }
+                cursor = end;
+                line_start = None;
+                commands.push(command);
+                continue;
+            }
+
+            if let Some(line_start) = line_start {
+                let a = cursor.sub(line_start);
+                let b = end.sub(cursor);
+                let cross = a.x * b.y - a.y * b.x;
+                let is_collinear = cross.abs() <= f64::EPSILON * a.len() * b.len() * 16.0;
+                if a.dot(b) > 0.0 && is_collinear {
+                    let merged = commands.last_mut().unwrap();
+                    let (pos, offset) = match merged {
+                        Command::L(pos, _) | Command::H(pos, _) | Command::V(pos, _) => (
+                            *pos,
+                            if *pos == Position::Absolute {
+                                DVec2::ZERO
+                            } else {
+                                line_start
+                            },
+                        ),
+                        _ => unreachable!(),
+                    };
+                    let rel = end.sub(offset);
+                    *merged = match (&*merged, &command) {
+                        (Command::H(..), Command::H(..)) => {
+                            Command::H(pos, Parameters(vec![rel.x]))
+                        }
+                        (Command::V(..), Command::V(..)) => {
+                            Command::V(pos, Parameters(vec![rel.y]))
+                        }
+                        _ => Command::L(pos, Parameters(vec![rel.x, rel.y])),
+                    };
+                    cursor = end;
+                    continue;
+                }
+            }
+
+            line_start = Some(cursor);
+            cursor = end;
+            commands.push(command);
+        }
+
+        self.0 = commands;
+    }
+
+    /// Convert all cubic commands to quadratic ones.
+    ///
+    /// Each cubic is split uniformly until every piece can be replaced by a
+    /// single quadratic within `tolerance`, the error of that replacement
+    /// shrinks with the cube of the piece count.
+    /// Quadratics whose control point mirrors the previous one become `T`.
+    ///
+    /// Output commands are absolute, call [`OptimizedData::to_relative`] afterwards.
+    pub fn to_quadratic(&mut self, tolerance: f64) {
+        let mut commands = Vec::with_capacity(self.0.len());
+        let mut start = DVec2::ZERO;
+        let mut cursor = DVec2::ZERO;
+        // Control point of the previous quadratic, used to detect `T` candidates.
+        let mut ctrl_prev: Option<DVec2> = None;
+
+        let point = |pos: Position, cursor: DVec2, x: f64, y: f64| -> DVec2 {
+            match pos {
+                Position::Absolute => DVec2::new(x, y),
+                Position::Relative => cursor.add(DVec2::new(x, y)),
+            }
+        };
+
+        for command in self.0.drain(..) {
+            let mut ctrl_next = None;
+            match command {
+                Command::C(pos, ref args) => {
+                    for c in args.chunks_exact(6) {
+                        let p0 = cursor;
+                        let p1 = point(pos, cursor, c[0], c[1]);
+                        let p2 = point(pos, cursor, c[2], c[3]);
+                        let p3 = point(pos, cursor, c[4], c[5]);
+
+                        for [q0, q1, q2] in cubic_to_quadratics([p0, p1, p2, p3], tolerance) {
+                            let is_smooth = ctrl_prev.is_some_and(|c| {
+                                let mirror = q0.add(q0.sub(c));
+                                trunc(mirror.x) == trunc(q1.x) && trunc(mirror.y) == trunc(q1.y)
+                            });
+                            commands.push(if is_smooth {
+                                Command::T(Position::Absolute, Parameters(vec![q2.x, q2.y]))
+                            } else {
+                                Command::Q(
+                                    Position::Absolute,
+                                    Parameters(vec![q1.x, q1.y, q2.x, q2.y]),
+                                )
+                            });
+                            ctrl_prev = Some(q1);
+                        }
+                        cursor = p3;
                     }
+                    continue;
                 }
-                Command::C(pos, mut args) => {
-                    if pos == Position::Absolute {
-                        args.0[0] -= cursor.0;
-                        args.0[1] -= cursor.1;
-                        args.0[2] -= cursor.0;
-                        args.0[3] -= cursor.1;
-                        args.0[4] -= cursor.0;
-                        args.0[5] -= cursor.1;
-                        cursor.0 += args.0[4];
-                        cursor.1 += args.0[5];
-                        Command::C(Position::Relative, args)
-                    } else {
-                        cursor.0 += args.0[4];
-                        cursor.1 += args.0[5];
-                        Command::C(pos, args)
+                Command::Q(pos, ref args) => {
+                    for q in args.chunks_exact(4) {
+                        ctrl_next = Some(point(pos, cursor, q[0], q[1]));
+                        cursor = point(pos, cursor, q[2], q[3]);
                     }
                 }
-                Command::S(pos, mut args) => {
-                    if pos == Position::Absolute {
-                        args.0[0] -= cursor.0;
-                        args.0[1] -= cursor.1;
-                        args.0[2] -= cursor.0;
-                        args.0[3] -= cursor.1;
-                        cursor.0 += args.0[2];
-                        cursor.1 += args.0[3];
-                        Command::S(Position::Relative, args)
-                    } else {
-                        cursor.0 += args.0[2];
-                        cursor.1 += args.0[3];
-                        Command::S(pos, args)
+                Command::M(pos, ref args) => {
+                    start = point(pos, cursor, args[0], args[1]);
+                    cursor = command.end_point(cursor, start);
+                }
+                _ => {
+                    cursor = command.end_point(cursor, start);
+                }
+            }
+            ctrl_prev = ctrl_next;
+            commands.push(command);
+        }
+
+        self.0 = commands;
+    }
+
+    /// Build absolute path data from fitted curves (as returned by `fit_poly_list`),
+    /// cyclic curves are closed with `Z`.
+    pub fn from_curves(curves: &[(bool, Vec<[DVec2; 3]>)]) -> Self {
+        let mut data = OptimizedData::new();
+        let coord = |v: DVec2| [trunc(v.x) as f64, trunc(v.y) as f64];
+
+        for (is_cyclic, p) in curves {
+            if p.is_empty() {
+                continue;
+            }
+            let mut v_prev = if *is_cyclic { p.last().unwrap() } else { &p[0] };
+            data.append(Command::M(
+                Position::Absolute,
+                Parameters(coord(v_prev[1]).to_vec()),
+            ));
+            for v_curr in &p[(if *is_cyclic { 0 } else { 1 })..] {
+                debug_assert!(v_curr[0].is_finite());
+                debug_assert!(v_curr[1].is_finite());
+                debug_assert!(v_curr[2].is_finite());
+
+                data.append(Command::C(
+                    Position::Absolute,
+                    Parameters([coord(v_prev[2]), coord(v_curr[0]), coord(v_curr[1])].concat()),
+                ));
+                v_prev = v_curr;
+            }
+            if *is_cyclic {
+                data.append(Command::Z);
+            }
+        }
+        data
+    }
+
+    /// Build absolute path data from polygons, closing cyclic ones with `Z`.
+    ///
+    /// Horizontal and vertical edges use `H` and `V`.
+    pub fn from_polygons(polygons: &[(bool, Vec<DVec2>)]) -> Self {
+        let mut data = OptimizedData::new();
+
+        for (is_cyclic, poly) in polygons {
+            let Some(first) = poly.first() else {
+                continue;
+            };
+            data.append(Command::M(
+                Position::Absolute,
+                Parameters(vec![first.x, first.y]),
+            ));
+            for pair in poly.windows(2) {
+                let [prev, curr] = [pair[0], pair[1]];
+                data.append(if prev.y == curr.y {
+                    Command::H(Position::Absolute, Parameters(vec![curr.x]))
+                } else if prev.x == curr.x {
+                    Command::V(Position::Absolute, Parameters(vec![curr.y]))
+                } else {
+                    Command::L(Position::Absolute, Parameters(vec![curr.x, curr.y]))
+                });
+            }
+            if *is_cyclic {
+                data.append(Command::Z);
+            }
+        }
+        data
+    }
+
+    /// Builds the path data of contours split into segments (see [`Layer::segments`]),
+    /// lines use `H`, `V` or `L` and the closing line of a cyclic contour is left to `Z`.
+    ///
+    /// [`Layer::segments`]: crate::structs::Layer::segments
+    #[cfg(feature = "std")]
+    pub fn from_segments(contours: &[(bool, Vec<Segment>)]) -> Self {
+        let mut data = OptimizedData::new();
+        let coord = |v: DVec2| [trunc(v.x) as f64, trunc(v.y) as f64];
+
+        for (is_cyclic, segments) in contours {
+            let Some(first) = segments.first() else {
+                continue;
+            };
+            data.append(Command::M(
+                Position::Absolute,
+                Parameters(coord(first.start()).to_vec()),
+            ));
+            let count = match segments.last() {
+                Some(Segment::Line { .. }) if *is_cyclic => segments.len() - 1,
+                _ => segments.len(),
+            };
+            for segment in &segments[..count] {
+                data.append(match segment {
+                    Segment::Line { start, end } => {
+                        let [prev, curr] = [coord(*start), coord(*end)];
+                        if prev[1] == curr[1] {
+                            Command::H(Position::Absolute, Parameters(vec![curr[0]]))
+                        } else if prev[0] == curr[0] {
+                            Command::V(Position::Absolute, Parameters(vec![curr[1]]))
+                        } else {
+                            Command::L(Position::Absolute, Parameters(curr.to_vec()))
+                        }
+                    }
+                    Segment::Cubic(c) => Command::C(
+                        Position::Absolute,
+                        Parameters([coord(c.control1), coord(c.control2), coord(c.end)].concat()),
+                    ),
+                });
+            }
+            if *is_cyclic {
+                data.append(Command::Z);
+            }
+        }
+        data
+    }
+
+    /// Flatten every sub-path into a polygon, curves and arcs are split into
+    /// line segments within `tolerance`.
+    ///
+    /// Sub-paths ending with `Z` are cyclic, the closing point is not repeated.
+    pub fn to_polygons(&self, tolerance: f64) -> Vec<(bool, Vec<DVec2>)> {
+        let mut poly_list = Vec::new();
+        let mut poly: Vec<DVec2> = Vec::new();
+        let mut start = DVec2::ZERO;
+        let mut cursor = DVec2::ZERO;
+        // Last control points, reflected by `S` and `T`.
+        let mut ctrl_cubic: Option<DVec2> = None;
+        let mut ctrl_quad: Option<DVec2> = None;
+
+        let point = |pos: Position, cursor: DVec2, x: f64, y: f64| -> DVec2 {
+            match pos {
+                Position::Absolute => DVec2::new(x, y),
+                Position::Relative => cursor.add(DVec2::new(x, y)),
+            }
+        };
+
+        let flush = |poly_list: &mut Vec<(bool, Vec<DVec2>)>, poly: &mut Vec<DVec2>, is_cyclic| {
+            if is_cyclic && poly.len() > 2 && poly.first() == poly.last() {
+                poly.pop();
+            }
+            if poly.len() > 1 {
+                poly_list.push((is_cyclic, core::mem::take(poly)));
+            } else {
+                poly.clear();
+            }
+        };
+
+        for command in &self.0 {
+            // Drawing after `Z` starts a new sub-path at the current point.
+            if poly.is_empty() {
+                poly.push(cursor);
+            }
+            let (mut ctrl_cubic_next, mut ctrl_quad_next) = (None, None);
+
+            match command {
+                Command::M(pos, args) => {
+                    flush(&mut poly_list, &mut poly, false);
+                    for (i, xy) in args.chunks_exact(2).enumerate() {
+                        cursor = point(*pos, cursor, xy[0], xy[1]);
+                        if i == 0 {
+                            start = cursor;
+                        }
+                        poly.push(cursor);
                     }
                 }
-                Command::Q(pos, mut args) => {
-                    if pos == Position::Absolute {
-                        args.0[0] -= cursor.0;
-                        args.0[1] -= cursor.1;
-                        args.0[2] -= cursor.0;
-                        args.0[3] -= cursor.1;
-                        cursor.0 += args.0[2];
-                        cursor.1 += args.0[3];
-                        Command::Q(Position::Relative, args)
-                    } else {
-                        cursor.0 += args.0[2];
-                        cursor.1 += args.0[3];
-                        Command::Q(pos, args)
+                Command::L(pos, args) => {
+                    for xy in args.chunks_exact(2) {
+                        cursor = point(*pos, cursor, xy[0], xy[1]);
+                        poly.push(cursor);
                     }
                 }
-                Command::T(pos, mut args) => {
-                    if pos == Position::Absolute {
-                        args.0[0] -= cursor.0;
-                        args.0[1] -= cursor.1;
-                        cursor.0 += args.0[0];
-                        cursor.1 += args.0[1];
-                        Command::T(Position::Relative, args)
-                    } else {
-                        cursor.0 += args.0[0];
-                        cursor.1 += args.0[1];
-                        Command::T(pos, args)
+                Command::H(pos, args) => {
+                    for x in args.iter() {
+                        cursor.x = if *pos == Position::Absolute {
+                            *x
+                        } else {
+                            cursor.x + x
+                        };
+                        poly.push(cursor);
                     }
                 }
-                Command::A(pos, mut args) => {
-                    if pos == Position::Absolute {
-                        // For an elliptical arc, only the last two parameters are adjusted
-                        args.0[5] -= cursor.0;
-                        args.0[6] -= cursor.1;
-                        cursor.0 += args.0[5];
-                        cursor.1 += args.0[6];
-                        Command::A(Position::Relative, args)
-                    } else {
-                        cursor.0 += args.0[5];
-                        cursor.1 += args.0[6];
-                        Command::A(pos, args)
+                Command::V(pos, args) => {
+                    for y in args.iter() {
+                        cursor.y = if *pos == Position::Absolute {
+                            *y
+                        } else {
+                            cursor.y + y
+                        };
+                        poly.push(cursor);
+                    }
+                }
+                Command::C(pos, args) => {
+                    for c in args.chunks_exact(6) {
+                        let p1 = point(*pos, cursor, c[0], c[1]);
+                        let p2 = point(*pos, cursor, c[2], c[3]);
+                        let p3 = point(*pos, cursor, c[4], c[5]);
+                        cubic_flatten(cursor, p1, p2, p3, tolerance, &mut poly);
+                        ctrl_cubic_next = Some(p2);
+                        cursor = p3;
+                    }
+                }
+                Command::S(pos, args) => {
+                    for c in args.chunks_exact(4) {
+                        let p1 = ctrl_cubic_next
+                            .or(ctrl_cubic)
+                            .map_or(cursor, |ctrl| cursor.add(cursor.sub(ctrl)));
+                        let p2 = point(*pos, cursor, c[0], c[1]);
+                        let p3 = point(*pos, cursor, c[2], c[3]);
+                        cubic_flatten(cursor, p1, p2, p3, tolerance, &mut poly);
+                        ctrl_cubic_next = Some(p2);
+                        cursor = p3;
+                    }
+                }
+                Command::Q(pos, args) => {
+                    for c in args.chunks_exact(4) {
+                        let q1 = point(*pos, cursor, c[0], c[1]);
+                        let q2 = point(*pos, cursor, c[2], c[3]);
+                        quadratic_flatten(cursor, q1, q2, tolerance, &mut poly);
+                        ctrl_quad_next = Some(q1);
+                        cursor = q2;
+                    }
+                }
+                Command::T(pos, args) => {
+                    for c in args.chunks_exact(2) {
+                        let q1 = ctrl_quad_next
+                            .or(ctrl_quad)
+                            .map_or(cursor, |ctrl| cursor.add(cursor.sub(ctrl)));
+                        let q2 = point(*pos, cursor, c[0], c[1]);
+                        quadratic_flatten(cursor, q1, q2, tolerance, &mut poly);
+                        ctrl_quad_next = Some(q1);
+                        cursor = q2;
+                    }
+                }
+                Command::A(pos, args) => {
+                    for a in args.chunks_exact(7) {
+                        let p1 = point(*pos, cursor, a[5], a[6]);
+                        for [c0, c1, c2, c3] in arc_to_cubics(
+                            cursor,
+                            DVec2::new(a[0], a[1]),
+                            a[2],
+                            a[3] != 0.0,
+                            a[4] != 0.0,
+                            p1,
+                        ) {
+                            cubic_flatten(c0, c1, c2, c3, tolerance, &mut poly);
+                        }
+                        cursor = p1;
                     }
                 }
                 Command::Z => {
-                    // Close path: reset the cursor to the starting point.
+                    flush(&mut poly_list, &mut poly, true);
                     cursor = start;
-                    Command::Z
                 }
-            };
-            // Put the processed command back into the vector.
-            self.0[i] = new_command;
+            }
+            ctrl_cubic = ctrl_cubic_next;
+            ctrl_quad = ctrl_quad_next;
+        }
+        flush(&mut poly_list, &mut poly, false);
+
+        poly_list
+    }
+
+    /// Re-fit the path with `curve_fit_nd`, returning absolute cubic path data
+    /// which deviates at most `tolerance` from the original outline.
+    pub fn simplify(&self, tolerance: f64) -> Self {
+        let corners = CornerOptions::default();
+
+        let mut poly_list = self.to_polygons(tolerance * 0.25);
+        // Ensure there are enough points to fit between corners.
+        poly_list_subdivide_to_limit(&mut poly_list, tolerance);
+
+        let curves = fit_poly_list(poly_list, tolerance, corners.angle(), corners.scale, true);
+        Self::from_curves(&curves)
+    }
+
+    /// [`OptimizedData::optimize`] the relative and the absolute commands,
+    /// returning the shorter serialization, the relative one on a tie.
+    pub fn optimize_shortest(&self) -> String {
+        let mut relative = self.clone();
+        relative.to_relative();
+        let mut absolute = self.clone();
+        absolute.to_absolute();
+        let (relative, absolute) = (relative.optimize(), absolute.optimize());
+        match absolute.len() < relative.len() {
+            true => absolute,
+            false => relative,
         }
     }
 
+    /// Serialize with two decimals, omitting repeated command letters and needless separators.
     pub fn optimize(&self) -> String {
         let mut output = String::with_capacity(self.0.len() * 4); // Preallocate estimated size
         let mut last_command: Option<char> = None;
@@ -257,6 +796,7 @@ impl OptimizedData {
                 Command::A(pos, params) => ('A', params, pos),
                 Command::Z => {
                     output.push('z');
+                    last_command = None;
                     last_char = Some('z');
                     continue;
                 }
@@ -268,8 +808,9 @@ impl OptimizedData {
                 cmd_char
             };
 
-            // Append command letter only if different from the last command
-            if Some(letter) != last_command {
+            // Append command letter only if different from the last command,
+            // a repeated move would be read back as a line.
+            if Some(letter) != last_command || cmd_char == 'M' {
                 output.push(letter);
                 last_command = Some(letter);
                 last_char = Some(letter);
@@ -301,27 +842,66 @@ impl OptimizedData {
     }
 }
 
+/// Error returned when path data is not valid SVG path syntax.
 #[derive(Debug, PartialEq, Eq)]
-pub struct ParseDataError;
+pub struct ParseDataError {
+    pub kind: ParseDataErrorKind,
+    /// Byte offset of the offending character, the input length at the end of the input.
+    pub position: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseDataErrorKind {
+    /// Not a path command letter.
+    UnknownCommand,
+    /// A command is missing parameters.
+    ExpectedNumber,
+    /// An arc flag is not `0` or `1`.
+    ExpectedFlag,
+    /// A number overflows to infinity, e.g. `1e999`.
+    NumberOutOfRange,
+}
+
+impl fmt::Display for ParseDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.kind {
+            ParseDataErrorKind::UnknownCommand => "unknown command",
+            ParseDataErrorKind::ExpectedNumber => "expected a number",
+            ParseDataErrorKind::ExpectedFlag => "expected an arc flag",
+            ParseDataErrorKind::NumberOutOfRange => "number out of range",
+        };
+        write!(
+            f,
+            "invalid SVG path data at byte {}: {reason}",
+            self.position
+        )
+    }
+}
+
+impl core::error::Error for ParseDataError {}
 
 impl FromStr for OptimizedData {
     type Err = ParseDataError;
 
+    /// Parses path data following the SVG path grammar,
+    /// implicitly repeated commands are split into one command per parameter group.
+    ///
+    /// Arc flags may be packed without separators, as in `a1 1 0 011 0`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Remove any leading/trailing whitespace.
-        let s = s.trim();
+        let bytes = s.as_bytes();
         let mut commands = Vec::new();
-        let mut chars = s.chars().peekable();
+        let mut i = 0;
 
-        while let Some(&ch) = chars.peek() {
+        loop {
             // Skip whitespace.
-            if ch.is_whitespace() {
-                chars.next();
-                continue;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
             }
-
-            // The command letter must be one of the expected letters.
-            let cmd_char = chars.next().ok_or_else(|| ParseDataError)?;
+            let Some(&cmd_byte) = bytes.get(i) else {
+                break;
+            };
+            i += 1;
+            let cmd_char = cmd_byte as char;
 
             // Special-case the Z/z command which takes no parameters.
             if cmd_char == 'Z' || cmd_char == 'z' {
@@ -330,55 +910,140 @@ impl FromStr for OptimizedData {
             }
 
             // Determine position: uppercase means Absolute, lowercase means Relative.
-            let position = if cmd_char.is_uppercase() {
+            let position = if cmd_char.is_ascii_uppercase() {
                 Position::Absolute
             } else {
                 Position::Relative
             };
+            let arity = command_arity(cmd_char.to_ascii_uppercase()).ok_or(ParseDataError {
+                kind: ParseDataErrorKind::UnknownCommand,
+                position: i - 1,
+            })?;
+            let is_arc = arity == 7;
 
-            // Accumulate characters that form the parameter part.
-            let mut param_str = String::new();
-            while let Some(&next_ch) = chars.peek() {
-                // If the next character is alphabetic, it might be the next command.
-                if next_ch.is_alphabetic() {
-                    break;
+            // Numbers are separated by whitespace, a comma,
+            // or nothing at all when the next one starts with a sign or a second '.'.
+            let mut numbers = Vec::new();
+            let is_flag = |count: usize| is_arc && matches!(count % arity, 3 | 4);
+            loop {
+                while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b',') {
+                    i += 1;
+                }
+                let start = i;
+                let number = if is_flag(numbers.len()) {
+                    parse_flag(bytes, &mut i)
+                } else {
+                    parse_number(s, &mut i)
+                };
+                match number {
+                    Some(number) if number.is_finite() => numbers.push(number),
+                    Some(_) => {
+                        return Err(ParseDataError {
+                            kind: ParseDataErrorKind::NumberOutOfRange,
+                            position: start,
+                        })
+                    }
+                    None => break,
                 }
-                param_str.push(next_ch);
-                chars.next();
             }
-            // Trim and split parameters on commas or whitespace.
-            let param_str = param_str.trim();
-            let numbers = if param_str.is_empty() {
-                Vec::new()
-            } else {
-                param_str
-                    .split(|c: char| c == ',' || c.is_whitespace())
-                    .filter(|s| !s.is_empty())
-                    .map(|num_str| num_str.parse::<f64>())
-                    .collect::<Result<Vec<f64>, ParseFloatError>>()
-                    .map_err(|_| ParseDataError)?
-            };
-            let parameters = Parameters(numbers);
-
-            // Depending on the command letter (normalized to uppercase) create the corresponding command.
-            let command = match cmd_char.to_ascii_uppercase() {
-                'M' => Command::M(position, parameters),
-                'L' => Command::L(position, parameters),
-                'H' => Command::H(position, parameters),
-                'V' => Command::V(position, parameters),
-                'C' => Command::C(position, parameters),
-                'S' => Command::S(position, parameters),
-                'Q' => Command::Q(position, parameters),
-                'T' => Command::T(position, parameters),
-                'A' => Command::A(position, parameters),
-                _ => return Err(ParseDataError),
-            };
-            commands.push(command);
+            if numbers.is_empty() || numbers.len() % arity != 0 {
+                return Err(ParseDataError {
+                    kind: if is_flag(numbers.len()) {
+                        ParseDataErrorKind::ExpectedFlag
+                    } else {
+                        ParseDataErrorKind::ExpectedNumber
+                    },
+                    position: i,
+                });
+            }
+
+            for (group, parameters) in numbers.chunks_exact(arity).enumerate() {
+                let parameters = Parameters(parameters.to_vec());
+                // Depending on the command letter (normalized to uppercase) create the corresponding command,
+                // coordinates repeated after a move are line segments.
+                let command = match cmd_char.to_ascii_uppercase() {
+                    'M' if group == 0 => Command::M(position, parameters),
+                    'M' | 'L' => Command::L(position, parameters),
+                    'H' => Command::H(position, parameters),
+                    'V' => Command::V(position, parameters),
+                    'C' => Command::C(position, parameters),
+                    'S' => Command::S(position, parameters),
+                    'Q' => Command::Q(position, parameters),
+                    'T' => Command::T(position, parameters),
+                    _ => Command::A(position, parameters),
+                };
+                commands.push(command);
+            }
         }
         Ok(OptimizedData(commands))
     }
 }
 
+/// Number of parameters in one group of an (uppercase) command.
+fn command_arity(cmd_char: char) -> Option<usize> {
+    match cmd_char {
+        'H' | 'V' => Some(1),
+        'M' | 'L' | 'T' => Some(2),
+        'S' | 'Q' => Some(4),
+        'C' => Some(6),
+        'A' => Some(7),
+        _ => None,
+    }
+}
+
+/// Parses the single `0` or `1` arc flag at `*i`, advancing past it.
+fn parse_flag(bytes: &[u8], i: &mut usize) -> Option<f64> {
+    let flag = match bytes.get(*i)? {
+        b'0' => 0.0,
+        b'1' => 1.0,
+        _ => return None,
+    };
+    *i += 1;
+    Some(flag)
+}
+
+/// Parses the number starting at `*i`, advancing past it.
+///
+/// Accepts an optional sign, a fraction and an exponent (`-1.5e-3`),
+/// returns `None` without advancing when there is no number.
+fn parse_number(s: &str, i: &mut usize) -> Option<f64> {
+    let bytes = s.as_bytes();
+    let digits = |mut j: usize| {
+        while j < bytes.len() && bytes[j].is_ascii_digit() {
+            j += 1;
+        }
+        j
+    };
+
+    let start = *i;
+    let mut j = start;
+    if j < bytes.len() && (bytes[j] == b'-' || bytes[j] == b'+') {
+        j += 1;
+    }
+    let int_end = digits(j);
+    let mut end = int_end;
+    if end < bytes.len() && bytes[end] == b'.' {
+        end = digits(end + 1);
+    }
+    // A mantissa needs at least one digit.
+    if end - j - usize::from(end > int_end) == 0 {
+        return None;
+    }
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut k = end + 1;
+        if k < bytes.len() && (bytes[k] == b'-' || bytes[k] == b'+') {
+            k += 1;
+        }
+        if digits(k) > k {
+            end = digits(k);
+        }
+    }
+
+    let number = s[start..end].parse().ok()?;
+    *i = end;
+    Some(number)
+}
+
 impl From<String> for OptimizedData {
     fn from(s: String) -> Self {
         // In this implementation we choose to panic on error.
@@ -388,6 +1053,7 @@ impl From<String> for OptimizedData {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<Data> for OptimizedData {
     fn from(data: Data) -> Self {
         let str_data: Value = data.into();
@@ -433,15 +1099,20 @@ implement! {
     A(Relative) => "a",
 }
 
-/// Formats a number with a maximum of two decimal places, removing trailing zeros.
+pub fn trunc(value: f64) -> f32 {
+    (f64::trunc(value * 100.0) / 100.0) as f32
+}
+
+/// Formats a number rounded to two decimal places, removing trailing zeros.
 /// If the number is between -1 and 1 (excluding 0), the leading zero is removed.
 /// Examples:
 ///   10.00 -> "10"
 ///   0.50  -> ".5"
 ///   -0.50 -> "-.5"
 fn format_num(n: f64) -> String {
-    // Format with two decimal places.
-    let mut s = format!("{}", trunc(n));
+    // Round rather than truncate, so relative coordinates don't drift.
+    // Adding zero turns `-0` into `0`.
+    let mut s = format!("{}", (n * 100.0).round() / 100.0 + 0.0);
     // Remove trailing zeros and the decimal point if unnecessary.
     if s.contains('.') {
         s = s.trim_end_matches('0').trim_end_matches('.').to_string();
@@ -454,3 +1125,168 @@ fn format_num(n: f64) -> String {
     }
     s
 }
+
+/// Approximates a cubic Bézier with a run of quadratics within `tolerance`.
+///
+/// The distance between a cubic and its mid-point quadratic is bounded by
+/// `sqrt(3) / 36 * |p3 - 3 * p2 + 3 * p1 - p0|`, splitting the cubic into `n`
+/// pieces scales that bound by `1 / n^3`.
+fn cubic_to_quadratics(cubic: [DVec2; 4], tolerance: f64) -> Vec<[DVec2; 3]> {
+    let [p0, p1, p2, p3] = cubic;
+    let d = p3.sub(p2.mul(3.0)).add(p1.mul(3.0)).sub(p0);
+    let error = (3.0_f64).sqrt() / 36.0 * d.len();
+    let pieces = if tolerance > 0.0 && error > tolerance {
+        ((error / tolerance).cbrt().ceil() as usize).max(1)
+    } else {
+        1
+    };
+
+    let mut quads = Vec::with_capacity(pieces);
+    let mut rest = cubic;
+    for i in 0..pieces {
+        // Split off the next piece, re-scaling `t` to the remaining curve.
+        let piece = if i + 1 == pieces {
+            rest
+        } else {
+            let t = 1.0 / (pieces - i) as f64;
+            let [a, b, c, d] = rest;
+            let ab = a.interp(b, t);
+            let bc = b.interp(c, t);
+            let cd = c.interp(d, t);
+            let abc = ab.interp(bc, t);
+            let bcd = bc.interp(cd, t);
+            let mid = abc.interp(bcd, t);
+            rest = [mid, bcd, cd, d];
+            [a, ab, abc, mid]
+        };
+        let [a, b, c, d] = piece;
+        let ctrl = b.add(c).mul(3.0).sub(a).sub(d).mul(0.25);
+        quads.push([a, ctrl, d]);
+    }
+    quads
+}
+
+// Flatten a cubic bezier into line segments within `tolerance`,
+// pushing every point after `p0`.
+pub fn cubic_flatten(
+    p0: DVec2,
+    p1: DVec2,
+    p2: DVec2,
+    p3: DVec2,
+    tolerance: f64,
+    out: &mut Vec<DVec2>,
+) {
+    fn recurse(p: [DVec2; 4], tolerance_sq: f64, depth: usize, out: &mut Vec<DVec2>) {
+        let [p0, p1, p2, p3] = p;
+        // Distance of the handles from the chord.
+        let chord = p3.sub(p0);
+        let chord_len_sq = chord.len_squared();
+        let dist_sq = |v: DVec2| -> f64 {
+            if chord_len_sq > 0.0 {
+                let cross = chord.x * (v.y - p0.y) - chord.y * (v.x - p0.x);
+                cross * cross / chord_len_sq
+            } else {
+                v.len_squared_with(p0)
+            }
+        };
+
+        if depth == 0 || (dist_sq(p1) <= tolerance_sq && dist_sq(p2) <= tolerance_sq) {
+            out.push(p3);
+            return;
+        }
+
+        let p01 = p0.mid(p1);
+        let p12 = p1.mid(p2);
+        let p23 = p2.mid(p3);
+        let p012 = p01.mid(p12);
+        let p123 = p12.mid(p23);
+        let mid = p012.mid(p123);
+        recurse([p0, p01, p012, mid], tolerance_sq, depth - 1, out);
+        recurse([mid, p123, p23, p3], tolerance_sq, depth - 1, out);
+    }
+
+    recurse([p0, p1, p2, p3], DVec2::sq(tolerance), 16, out);
+}
+
+// Flatten a quadratic bezier by elevating it to a cubic.
+fn quadratic_flatten(q0: DVec2, q1: DVec2, q2: DVec2, tolerance: f64, out: &mut Vec<DVec2>) {
+    let p1 = q0.interp(q1, 2.0 / 3.0);
+    let p2 = q2.interp(q1, 2.0 / 3.0);
+    cubic_flatten(q0, p1, p2, q2, tolerance, out);
+}
+
+/// Converts an SVG elliptical arc from `p0` to `p1` into cubics,
+/// each spanning at most a quarter turn.
+///
+/// Follows the endpoint to center conversion of the SVG implementation notes (F.6.5),
+/// radii too small to reach `p1` are scaled up.
+fn arc_to_cubics(
+    p0: DVec2,
+    radii: DVec2,
+    x_axis_rotation: f64,
+    large_arc: bool,
+    sweep: bool,
+    p1: DVec2,
+) -> Vec<[DVec2; 4]> {
+    let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+    if p0 == p1 {
+        return Vec::new();
+    }
+    if rx == 0.0 || ry == 0.0 {
+        return vec![[p0, p0, p1, p1]];
+    }
+
+    let (sin_phi, cos_phi) = x_axis_rotation.to_radians().sin_cos();
+    let rotate =
+        |v: DVec2| DVec2::new(cos_phi * v.x - sin_phi * v.y, sin_phi * v.x + cos_phi * v.y);
+
+    let d = p0.sub(p1).mul(0.5);
+    let x1 = cos_phi * d.x + sin_phi * d.y;
+    let y1 = -sin_phi * d.x + cos_phi * d.y;
+
+    let lambda = DVec2::sq(x1 / rx) + DVec2::sq(y1 / ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = DVec2::sq(rx * ry) - DVec2::sq(rx * y1) - DVec2::sq(ry * x1);
+    let den = DVec2::sq(rx * y1) + DVec2::sq(ry * x1);
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coef = sign * (num / den).max(0.0).sqrt();
+    let center_prime = DVec2::new(coef * rx * y1 / ry, -coef * ry * x1 / rx);
+    let center = rotate(center_prime).add(p0.mid(p1));
+
+    let angle = |u: DVec2, v: DVec2| (u.x * v.y - u.y * v.x).atan2(u.dot(v));
+    let u = DVec2::new((x1 - center_prime.x) / rx, (y1 - center_prime.y) / ry);
+    let v = DVec2::new((-x1 - center_prime.x) / rx, (-y1 - center_prime.y) / ry);
+    let theta = angle(DVec2::new(1.0, 0.0), u);
+    let mut delta = angle(u, v);
+    if !sweep && delta > 0.0 {
+        delta -= core::f64::consts::TAU;
+    } else if sweep && delta < 0.0 {
+        delta += core::f64::consts::TAU;
+    }
+
+    let segments = ((delta.abs() / core::f64::consts::FRAC_PI_2).ceil() as usize).max(1);
+    let step = delta / segments as f64;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+
+    let ellipse = |t: f64| center.add(rotate(DVec2::new(rx * t.cos(), ry * t.sin())));
+    let tangent = |t: f64| rotate(DVec2::new(-rx * t.sin(), ry * t.cos()));
+
+    (0..segments)
+        .map(|i| {
+            let a = theta + step * i as f64;
+            let b = a + step;
+            let start = if i == 0 { p0 } else { ellipse(a) };
+            let end = if i + 1 == segments { p1 } else { ellipse(b) };
+            [
+                start,
+                start.madd(tangent(a), k),
+                end.msub(tangent(b), k),
+                end,
+            ]
+        })
+        .collect()
+}
@@ -0,0 +1,2152 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Seek, Write},
+    path::Path,
+    thread,
+    time::Duration,
+};
+use wasm_bindgen::{prelude::*, Clamped};
+
+use image::{
+    imageops::{blur, crop_imm, resize, unsharpen, FilterType},
+    ImageReader, Rgba, RgbaImage,
+};
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::{
+    algo::extract_outline_seeded,
+    curve_fit_nd,
+    decoder::{
+        check_chunks, check_format, decode_frames_with_palette, decode_with_palette,
+        opaque_palette, DecodedImage, INDEXED_PALETTE_MAX,
+    },
+    descreen::{descreen as descreen_image, is_dithered},
+    error::SvgenError,
+    gradient::find_gradients,
+    mask::{
+        mask_despeckle, mask_merge_small_regions, mask_separation, mask_threshold,
+        merge_small_regions, palette_indices, BitMask,
+    },
+    output,
+    path_optimizer::{OptimizedData, ParseDataError},
+    polygon_simplifier::{poly_list_simplify_by, segment_distance_squared, shared_boundaries},
+    quantizer::{MedianCut, NeuQuant, Quantize},
+    session::VectorizationSession,
+    structs::{
+        BackgroundRemoval, BatchOutput, BorderContours, BoundingBox, ColorMode, DescreenFilter,
+        Gradient, Layer, LineFitting, Mode, OffsetOptions, Options, Orientation, OutputFormat,
+        PaletteColor, Quantizer, ResampleFilter, SpriteGrid, StageTimings, Stencil, Supersample,
+        SvgOutput, TraceStats, VectorizationResult, SUPERSAMPLE_MAX,
+    },
+    telemetry::{info, stage, trace, warn},
+    utils::{
+        contour_tree, curve_area, curve_is_simple, curve_offset, curve_offset_joined,
+        curve_reverse, curve_segments, curve_smooth, poly_list_subdivide,
+        poly_list_subdivide_adaptive, poly_split_straight, polygon_area, polygon_offset,
+        ring_contains, segments_flatten,
+    },
+    vec2::DVec2,
+};
+
+pub fn create_svg(image_byte: &[u8], color_mode: ColorMode) -> String {
+    create_svg_with_options(
+        image_byte,
+        &Options {
+            color_mode,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn create_svg_with_options(image_byte: &[u8], options: &Options) -> String {
+    trace!("SVG Creation");
+
+    output::svg::to_svg(&vectorize(image_byte, options), options)
+}
+
+/// Like [`create_svg_with_options`], along with what the trace produced and
+/// how long each stage took.
+pub fn create_svg_with_stats(image_byte: &[u8], options: &Options) -> (String, TraceStats) {
+    trace!("SVG Creation");
+
+    let mut timings = StageTimings::default();
+    let (image_reader, palette) = timed(&mut timings.decode, || {
+        decode_image(image_byte, options).unwrap_or_else(|error| panic!("{error}"))
+    });
+    let prepared = timed(&mut timings.prepare, || {
+        prepare_image(&image_reader, palette.as_deref(), options)
+    });
+    let masks = timed(&mut timings.masks, || build_masks(&prepared, options));
+    let outlines: Vec<Outline> = timed(&mut timings.trace, || {
+        masks
+            .iter()
+            .map(|mask| trace_outline(&mask.pixels, options))
+            .collect()
+    });
+    let contours = outlines.iter().map(Vec::len).sum();
+    let knots_before_simplification = outlines
+        .iter()
+        .flatten()
+        .map(|(_is_cyclic, points)| points.len())
+        .sum();
+    let fitted = timed(&mut timings.fit, || {
+        fit_layers(&prepared, &masks, outlines, options)
+    });
+    let result = timed(&mut timings.assemble, || {
+        assemble_result(&prepared, &masks, fitted, options)
+    });
+    let svg = timed(&mut timings.serialize, || {
+        output::svg::to_svg(&result, options)
+    });
+
+    let layers = &result.layers;
+    let stats = TraceStats {
+        contours,
+        contours_dropped: prepared.regions_merged
+            + masks.iter().map(|mask| mask.regions_removed).sum::<usize>()
+            + layers
+                .iter()
+                .flat_map(|layer| layer.segments())
+                .filter(|(_is_cyclic, segments)| segments.is_empty())
+                .count(),
+        knots_before_simplification,
+        knots_after_simplification: layers
+            .iter()
+            .flat_map(|layer| &layer.polygons)
+            .map(|(_is_cyclic, points)| points.len())
+            .sum(),
+        cubic_segments: layers
+            .iter()
+            .flat_map(|layer| layer.segments())
+            .map(|(_is_cyclic, segments)| segments.len())
+            .sum(),
+        paths_per_color: layers
+            .iter()
+            .map(|layer| (layer.color, layer.paths.len()))
+            .collect(),
+        timings,
+    };
+    (svg, stats)
+}
+
+/// Runs `f`, adding the time it took to `elapsed`.
+#[cfg(not(target_arch = "wasm32"))]
+fn timed<T>(elapsed: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let value = f();
+    *elapsed += start.elapsed();
+    value
+}
+
+/// Runs `f`, there is no clock to time it with on WASM.
+#[cfg(target_arch = "wasm32")]
+fn timed<T>(_elapsed: &mut Duration, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+/// Like [`create_svg_with_options`], returning an error for inputs that can't be decoded.
+pub fn try_create_svg(image_byte: &[u8], options: &Options) -> Result<String, SvgenError> {
+    trace!("SVG Creation");
+
+    Ok(output::svg::to_svg(
+        &try_vectorize(image_byte, options)?,
+        options,
+    ))
+}
+
+/// Like [`try_create_svg`], writing the document to `writer` as the layers are
+/// emitted instead of building it in memory, see [`output::svg::write_svg`].
+pub fn try_write_svg(
+    image_byte: &[u8],
+    options: &Options,
+    writer: impl Write,
+) -> Result<(), SvgenError> {
+    trace!("SVG Creation");
+
+    output::svg::write_svg(&try_vectorize(image_byte, options)?, options, writer)?;
+    Ok(())
+}
+
+/// Like [`try_create_svg`], over the input image itself, see [`output::svg::to_hybrid_svg`],
+/// e.g. with [`ColorMode::Black`] to only trace the dark lines of a scanned map.
+///
+/// The embedded image is downscaled to `max_raster_size` pixels on its longest side,
+/// `None` embeds it at full resolution.
+#[cfg(feature = "png")]
+pub fn try_create_hybrid_svg(
+    image_byte: &[u8],
+    options: &Options,
+    max_raster_size: Option<u32>,
+) -> Result<String, SvgenError> {
+    trace!("Hybrid SVG Creation");
+    let (image_reader, palette) = decode_image(image_byte, options)?;
+    let result = vectorize_with_palette(&image_reader, palette.as_deref(), options);
+
+    let (width, height) = image_reader.dimensions();
+    let raster = match max_raster_size {
+        Some(max_size) if width.max(height) > max_size => {
+            let scale = max_size.max(1) as f64 / width.max(height) as f64;
+            let [width, height] = [width, height].map(|v| ((v as f64 * scale) as u32).max(1));
+            resize(&image_reader, width, height, FilterType::Triangle)
+        }
+        _ => image_reader,
+    };
+    Ok(output::svg::to_hybrid_svg(&result, &raster, options))
+}
+
+/// Like [`create_svg_with_options`], with the error and simplify thresholds tuned
+/// to reach `target_ssim` with the fewest path nodes and at most `max_paths` paths,
+/// see [`metrics::auto_tune`].
+#[cfg(feature = "metrics")]
+pub fn create_svg_auto(
+    image_byte: &[u8],
+    target_ssim: f64,
+    max_paths: usize,
+    options: &Options,
+) -> metrics::AutoTuned {
+    metrics::auto_tune(image_byte, target_ssim, max_paths, options)
+}
+
+/// Like [`create_svg_with_options`], decoding the image straight from a file.
+pub fn create_svg_from_path(path: &Path, options: &Options) -> Result<String, SvgenError> {
+    create_svg_from_reader(BufReader::new(File::open(path)?), options)
+}
+
+/// Like [`create_svg_with_options`], decoding the image from a reader
+/// without buffering the encoded bytes in memory first.
+pub fn create_svg_from_reader<R: BufRead + Seek>(
+    reader: R,
+    options: &Options,
+) -> Result<String, SvgenError> {
+    trace!("SVG Creation");
+
+    let (image_reader, palette) = decode_image_from_reader(reader, options)?;
+    Ok(output::svg::to_svg(
+        &vectorize_with_palette(&image_reader, palette.as_deref(), options),
+        options,
+    ))
+}
+
+/// Like [`create_svg_with_options`], tracing raw pixels without decoding them:
+/// `width` × `height` pixels of 4 bytes in RGBA order, row after row,
+/// e.g. the `data` of a canvas `ImageData` or a game engine texture.
+pub fn create_svg_from_rgba(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    options: &Options,
+) -> Result<String, SvgenError> {
+    trace!("SVG Creation");
+
+    let image_reader = rgba_image(rgba, width, height)?;
+    Ok(output::svg::to_svg(
+        &vectorize_image(&image_reader, options),
+        options,
+    ))
+}
+
+/// Copies raw RGBA pixels into an image, see [`create_svg_from_rgba`].
+fn rgba_image(rgba: &[u8], width: u32, height: u32) -> Result<RgbaImage, SvgenError> {
+    let expected = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .unwrap_or(usize::MAX);
+    if rgba.len() != expected {
+        return Err(SvgenError::BufferSize {
+            expected,
+            actual: rgba.len(),
+        });
+    }
+    Ok(RgbaImage::from_raw(width, height, rgba.to_vec()).unwrap())
+}
+
+/// Serializes the traced contours as a GeoJSON `FeatureCollection`,
+/// one `MultiPolygon` feature per color layer.
+pub fn create_geojson(image_byte: &[u8], options: &Options) -> String {
+    trace!("GeoJSON Creation");
+
+    output::geojson::to_geojson(&vectorize(image_byte, options), options)
+}
+
+/// Writes the traced image as a single-page vector PDF.
+pub fn create_pdf(image_byte: &[u8], options: &Options) -> Vec<u8> {
+    trace!("PDF Creation");
+
+    output::pdf::to_pdf(&vectorize(image_byte, options))
+}
+
+/// Traces every frame of an animated PNG or GIF into a single SVG,
+/// switching between frames with SMIL animations at the original delays.
+///
+/// Still images produce a single, permanently visible frame.
+pub fn create_animated_svg(image_byte: &[u8], options: &Options) -> String {
+    trace!("Animated SVG Creation");
+
+    let (frames, palette) =
+        decode_frames_with_palette(image_byte, options).unwrap_or_else(|error| panic!("{error}"));
+    let frames = frames
+        .iter()
+        .map(|(frame, delay)| {
+            let result = vectorize_with_palette(frame, palette.as_deref(), options);
+            (result, *delay)
+        })
+        .collect::<Vec<_>>();
+
+    output::svg::to_animated_svg(&frames, options)
+}
+
+/// Traces every frame of an animated PNG or GIF into its own SVG document.
+pub fn create_svg_frames(image_byte: &[u8], options: &Options) -> Vec<String> {
+    trace!("SVG Frames Creation");
+
+    let (frames, palette) =
+        decode_frames_with_palette(image_byte, options).unwrap_or_else(|error| panic!("{error}"));
+    frames
+        .iter()
+        .map(|(frame, _delay)| {
+            let result = vectorize_with_palette(frame, palette.as_deref(), options);
+            output::svg::to_svg(&result, options)
+        })
+        .collect()
+}
+
+/// Traces the image once and fits it at every error threshold (e.g. `[5.0, 2.0, 0.5]`),
+/// returning one SVG document per level of detail, all sharing the same palette.
+pub fn create_svg_levels(
+    image_byte: &[u8],
+    options: &Options,
+    error_thresholds: &[f64],
+) -> Vec<String> {
+    trace!("SVG Levels Creation");
+
+    VectorizationSession::new(image_byte, options.clone())
+        .levels(error_thresholds)
+        .iter()
+        .map(|result| output::svg::to_svg(result, options))
+        .collect()
+}
+
+/// Like [`create_svg_levels`], nesting the levels as groups of a single SVG document
+/// where only the first level is displayed, see [`output::svg::to_svg_levels`].
+pub fn create_svg_with_levels(
+    image_byte: &[u8],
+    options: &Options,
+    error_thresholds: &[f64],
+) -> String {
+    trace!("SVG Levels Creation");
+
+    let levels = error_thresholds
+        .iter()
+        .copied()
+        .zip(VectorizationSession::new(image_byte, options.clone()).levels(error_thresholds))
+        .collect::<Vec<_>>();
+
+    output::svg::to_svg_levels(&levels, options)
+}
+
+/// Traces every image with one palette computed over all of them, so the same colors
+/// (e.g. of the icons of a set) are traced as the same layer colors in every document.
+///
+/// The shared palette is only computed with [`ColorMode::Colored`], see [`Options::palette`],
+/// a palette given in the options is used as is.
+pub fn create_svgs_batch(images: &[&[u8]], options: &Options) -> Result<BatchOutput, SvgenError> {
+    trace!("SVG Batch Creation");
+
+    let decoded = images
+        .iter()
+        .map(|image_byte| decode_image(image_byte, options))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (results, palette) = vectorize_batch(&decoded, options);
+    let svgs = results
+        .iter()
+        .map(|result| output::svg::to_svg(result, options))
+        .collect();
+
+    Ok(BatchOutput { svgs, palette })
+}
+
+/// Like [`create_svgs_batch`], over the tiles of a spritesheet decoded once,
+/// returning one SVG document per tile in the order of [`SpriteGrid::rects`].
+pub fn create_svg_sprites(
+    image_byte: &[u8],
+    grid: &SpriteGrid,
+    options: &Options,
+) -> Result<BatchOutput, SvgenError> {
+    trace!("SVG Sprites Creation");
+
+    let (results, palette) = vectorize_sprites(image_byte, grid, options)?;
+    let svgs = results
+        .iter()
+        .map(|result| output::svg::to_svg(result, options))
+        .collect();
+
+    Ok(BatchOutput { svgs, palette })
+}
+
+/// Like [`create_svg_sprites`], as one document of `<symbol>` elements,
+/// see [`output::svg::to_svg_symbols`].
+pub fn create_svg_spritesheet(
+    image_byte: &[u8],
+    grid: &SpriteGrid,
+    options: &Options,
+) -> Result<String, SvgenError> {
+    trace!("SVG Sprites Creation");
+
+    let (results, _palette) = vectorize_sprites(image_byte, grid, options)?;
+    let symbols = results
+        .into_iter()
+        .enumerate()
+        .map(|(i, result)| (format!("sprite-{i}"), result))
+        .collect::<Vec<_>>();
+    Ok(output::svg::to_svg_symbols(&symbols, options))
+}
+
+/// Results of the tiles of a spritesheet traced with one palette, along with that palette.
+fn vectorize_sprites(
+    image_byte: &[u8],
+    grid: &SpriteGrid,
+    options: &Options,
+) -> Result<(Vec<VectorizationResult>, Vec<PaletteColor>), SvgenError> {
+    let (image_reader, fixed_palette) = decode_image(image_byte, options)?;
+    let size = [image_reader.width(), image_reader.height()];
+    let tiles = grid
+        .rects(size)
+        .into_iter()
+        .map(|rect| {
+            let [x, y, width, height] = region_clamp(rect, size);
+            let tile = crop_imm(&image_reader, x, y, width, height).to_image();
+            (tile, fixed_palette.clone())
+        })
+        .collect::<Vec<_>>();
+
+    Ok(vectorize_batch(&tiles, options))
+}
+
+/// Results of every image traced with one palette, the [`Options::palette`] when given,
+/// otherwise computed over all of them in [`ColorMode::Colored`], along with that palette.
+fn vectorize_batch(
+    images: &[DecodedImage],
+    options: &Options,
+) -> (Vec<VectorizationResult>, Vec<PaletteColor>) {
+    let mut options = options.clone();
+    if options.color_mode == ColorMode::Colored && options.palette.is_none() {
+        options.palette = Some(shared_palette(images, &options));
+    }
+
+    let mut palette: Vec<PaletteColor> = options
+        .palette
+        .iter()
+        .flatten()
+        .map(|&color| PaletteColor {
+            color,
+            pixel_count: 0,
+        })
+        .collect();
+    let mut results = Vec::with_capacity(images.len());
+    for (image_reader, fixed_palette) in images {
+        let result = vectorize_with_palette(image_reader, fixed_palette.as_deref(), &options);
+        for entry in &result.palette {
+            match palette
+                .iter_mut()
+                .find(|shared| shared.color == entry.color)
+            {
+                Some(shared) => shared.pixel_count += entry.pixel_count,
+                None => palette.push(*entry),
+            }
+        }
+        results.push(result);
+    }
+
+    (results, palette)
+}
+
+/// Palette of the visible pixels of every image, their dominant colors when they
+/// cover most of the pixels, otherwise quantized colors.
+fn shared_palette(images: &[DecodedImage], options: &Options) -> Vec<[u8; 4]> {
+    let _stage = stage!("quantize");
+    let visible_pixels = images
+        .iter()
+        .flat_map(|(image_reader, _)| image_reader.pixels())
+        .filter(|pixel| pixel[3] != 0);
+    let mut hist: HashMap<[u8; 4], usize> = HashMap::new();
+    for pixel in visible_pixels.clone() {
+        *hist.entry(pixel.0).or_default() += 1;
+    }
+
+    let colors = 5;
+    let palette = match dominant_palette(&hist, colors) {
+        Some(palette) => palette,
+        None if hist.is_empty() => Vec::new(),
+        None => {
+            let pixels = visible_pixels
+                .flat_map(|pixel| pixel.0)
+                .collect::<Vec<u8>>();
+            new_quantizer(&pixels, colors, options).color_map_rgba()
+        }
+    };
+    palette
+        .chunks_exact(4)
+        .map(|color| [color[0], color[1], color[2], color[3]])
+        .collect()
+}
+
+/// Traces the image and serializes it as [`Options::output_format`].
+pub fn create_output(image_byte: &[u8], options: &Options) -> Vec<u8> {
+    let result = vectorize(image_byte, options);
+
+    match options.output_format {
+        OutputFormat::Svg => output::svg::to_svg(&result, options).into_bytes(),
+        OutputFormat::GeoJson => output::geojson::to_geojson(&result, options).into_bytes(),
+        OutputFormat::Pdf => output::pdf::to_pdf(&result),
+        OutputFormat::Eps => output::eps::to_eps(&result).into_bytes(),
+        OutputFormat::Dxf => output::dxf::to_dxf(&result, options).into_bytes(),
+    }
+}
+
+/// Re-fits existing SVG path data within `tolerance`,
+/// returning it as optimized relative path data.
+pub fn simplify_svg_path(path_data: &str, tolerance: f64) -> Result<String, ParseDataError> {
+    let mut data = path_data.parse::<OptimizedData>()?.simplify(tolerance);
+    data.remove_degenerate();
+    data.to_relative();
+    Ok(data.optimize())
+}
+
+/// Re-fits the `d` attribute of every `<path>` in an SVG document within `tolerance`,
+/// the rest of the document is kept as is.
+pub fn simplify_svg(document: &str, tolerance: f64) -> Result<String, ParseDataError> {
+    output::svg::map_path_data(document, |path_data| {
+        simplify_svg_path(path_data, tolerance)
+    })
+}
+
+/// Runs the tracing pipeline, returning the fitted contours of every color layer.
+///
+/// # Panics
+///
+/// When the input can't be decoded, see [`try_vectorize`].
+pub fn vectorize(image_byte: &[u8], options: &Options) -> VectorizationResult {
+    try_vectorize(image_byte, options).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Like [`vectorize`], returning an error for inputs in a format that is not accepted,
+/// see [`Options::input_formats`], or that can't be decoded.
+pub fn try_vectorize(
+    image_byte: &[u8],
+    options: &Options,
+) -> Result<VectorizationResult, SvgenError> {
+    let (image_reader, palette) = decode_image(image_byte, options)?;
+
+    Ok(vectorize_with_palette(
+        &image_reader,
+        palette.as_deref(),
+        options,
+    ))
+}
+
+/// Runs the tracing pipeline on already decoded pixels.
+pub fn vectorize_image(image_reader: &RgbaImage, options: &Options) -> VectorizationResult {
+    vectorize_with_palette(image_reader, None, options)
+}
+
+/// `(is_cyclic, points)` pixel outlines of a mask.
+pub(crate) type Outline = Vec<(bool, Vec<DVec2>)>;
+
+/// `(polygons, curves)` of a traced mask, as stored in a [`Layer`].
+pub(crate) type Contours = (Vec<(bool, Vec<DVec2>)>, Vec<(bool, Vec<[DVec2; 3]>)>);
+
+/// When `fixed_palette` is given every pixel is expected to use one of its colors,
+/// filtering and quantization are skipped so colors are preserved exactly.
+fn vectorize_with_palette(
+    image_reader: &RgbaImage,
+    fixed_palette: Option<&[[u8; 4]]>,
+    options: &Options,
+) -> VectorizationResult {
+    let prepared = prepare_image(image_reader, fixed_palette, options);
+    let masks = build_masks(&prepared, options);
+    let outlines = masks
+        .iter()
+        .map(|mask| trace_outline(&mask.pixels, options))
+        .collect();
+
+    build_result(&prepared, &masks, outlines, options)
+}
+
+/// Decodes an encoded image, along with its exact palette for indexed images.
+pub(crate) fn decode_image(
+    image_byte: &[u8],
+    options: &Options,
+) -> Result<DecodedImage, SvgenError> {
+    decode_image_from_reader(Cursor::new(image_byte), options)
+}
+
+/// Like [`decode_image`], decoding the image from a reader.
+fn decode_image_from_reader<R: BufRead + Seek>(
+    reader: R,
+    options: &Options,
+) -> Result<DecodedImage, SvgenError> {
+    let _stage = stage!("decode");
+    let image_reader = ImageReader::new(reader).with_guessed_format()?;
+    check_format(image_reader.format(), options)?;
+    let image_reader = check_chunks(image_reader, options)?;
+
+    // Indexed images keep their exact palette.
+    let (image_reader, palette) = decode_with_palette(image_reader, options)?;
+    if let Some(palette) = &palette {
+        info!("Indexed image with {} colors", palette.len());
+    }
+    Ok((image_reader, palette))
+}
+
+/// Pixels of the prepared image traced as one layer.
+pub(crate) struct Mask {
+    pub(crate) color: [u8; 4],
+    pub(crate) pixels: BitMask,
+    /// Fill of a smoothly shaded region, see [`Options::gradients`].
+    pub(crate) gradient: Option<Gradient>,
+    /// Regions removed by the despeckle filters, see [`TraceStats::contours_dropped`].
+    pub(crate) regions_removed: usize,
+}
+
+impl Mask {
+    fn new(color: [u8; 4], pixels: BitMask) -> Self {
+        Self {
+            color,
+            pixels,
+            gradient: None,
+            regions_removed: 0,
+        }
+    }
+}
+
+/// The image after filtering, upscaling and quantization, ready to be split into masks.
+pub(crate) struct PreparedImage {
+    pub(crate) image: RgbaImage,
+    /// Flat RGBA palette every pixel was quantized to,
+    /// empty in [`ColorMode::Black`] and [`ColorMode::Separation`].
+    pub(crate) palette: Vec<u8>,
+    /// Factor the image was upscaled by before tracing.
+    pub(crate) scale_factor: u32,
+    /// `[x, y]` of the traced region in the source image, in source pixels.
+    pub(crate) offset: [u32; 2],
+    /// `[width, height]` of the source image.
+    pub(crate) source_size: [u32; 2],
+    /// Filter the source was descreened with, see [`Options::descreen`].
+    pub(crate) descreen_filter: Option<DescreenFilter>,
+    /// Regions merged by the merge area of [`Options::despeckle`],
+    /// see [`TraceStats::contours_dropped`].
+    pub(crate) regions_merged: usize,
+}
+
+impl PreparedImage {
+    pub(crate) fn size(&self) -> [usize; 2] {
+        [self.image.width() as usize, self.image.height() as usize]
+    }
+}
+
+/// Clears the background, crops, descreens, filters, upscales and quantizes the image.
+///
+/// Depends on [`Options::background`], [`Options::stencil`], [`Options::region`],
+/// [`Options::mode`], [`Options::descreen`], [`Options::preprocess`],
+/// [`Options::supersample`], [`Options::resample_filter`], [`Options::sharpen`],
+/// [`Options::color_mode`], [`Options::quantizer`], the despeckle blur and merge area.
+pub(crate) fn prepare_image(
+    image_reader: &RgbaImage,
+    fixed_palette: Option<&[[u8; 4]]>,
+    options: &Options,
+) -> PreparedImage {
+    info!(
+        "Image readed {}x{}",
+        image_reader.width(),
+        image_reader.height()
+    );
+
+    // ------- Clear the background and the pixels outside the stencil -------
+    let masked_image;
+    let image_reader = match (&options.background, &options.stencil) {
+        (None, None) => image_reader,
+        (background, stencil) => {
+            let mut image = match background {
+                Some(background) => remove_background(image_reader, background),
+                None => image_reader.clone(),
+            };
+            if let Some(stencil) = stencil {
+                let size = [image.width(), image.height()];
+                apply_stencil(&mut image, [0, 0], size, stencil);
+            }
+            masked_image = image;
+            &masked_image
+        }
+    };
+    let source_size = [image_reader.width(), image_reader.height()];
+
+    // ------- Crop to the region of interest -------
+    let region_image;
+    let (image_reader, offset) = match options.region {
+        Some(region) => {
+            let [x, y, width, height] = region_clamp(region, source_size);
+            region_image = crop_imm(image_reader, x, y, width, height).to_image();
+            info!("Region cropped to {width}x{height} at {x},{y}");
+            (&region_image, [x, y])
+        }
+        None => (image_reader, [0, 0]),
+    };
+    let (width, height) = image_reader.dimensions();
+
+    let is_pixel_perfect = options.mode == Mode::PixelPerfect;
+    let is_preprocessed = options.preprocess && !is_pixel_perfect;
+
+    // Pixel art keeps its exact colors when there are few enough of them.
+    let exact_palette;
+    let fixed_palette = match fixed_palette {
+        None if is_pixel_perfect => {
+            exact_palette = opaque_palette(image_reader, INDEXED_PALETTE_MAX);
+            exact_palette.as_deref()
+        }
+        fixed_palette => fixed_palette,
+    };
+
+    // ------- Smooth halftones and dithering out -------
+    let descreened_image;
+    let descreen = options
+        .descreen
+        .filter(|descreen| !descreen.detect || is_dithered(image_reader));
+    let descreen_filter = descreen.map(|descreen| match fixed_palette {
+        Some(_) => DescreenFilter::Mode,
+        None => descreen.filter,
+    });
+    let image_reader = match (descreen, descreen_filter) {
+        (Some(descreen), Some(filter)) => {
+            info!("Descreened over {} pixel cells", descreen.cell_size);
+            descreened_image = descreen_image(image_reader, filter, descreen.cell_size);
+            &descreened_image
+        }
+        _ => image_reader,
+    };
+
+    // Colors of the source pixels, before filtering and interpolation blend them.
+    // Fully transparent pixels have arbitrary colors, they are left out of the palette.
+    let mut hist: HashMap<[u8; 4], usize> = HashMap::new();
+    for pix in image_reader.pixels().filter(|pix| pix[3] != 0) {
+        let key = [pix[0], pix[1], pix[2], pix[3]];
+        *hist.entry(key).or_default() += 1;
+    }
+
+    // ------- Upscale the image if necessary -------
+    let scale_factor = match options.supersample {
+        _ if is_pixel_perfect => 1,
+        Supersample::Auto if width * height < 512 * 512 && is_preprocessed => 3,
+        Supersample::Auto | Supersample::Off => 1,
+        Supersample::Factor(factor) => factor
+            .clamp(1, SUPERSAMPLE_MAX)
+            .min(u32::MAX / width.max(height).max(1)),
+    };
+    let mut image_reader =
+        filter_image(image_reader, fixed_palette.is_some(), scale_factor, options);
+    if scale_factor > 1 && options.supersample == Supersample::Auto {
+        warn!(
+            "Image size is small. Upscaled to {}x{}",
+            width * scale_factor,
+            height * scale_factor
+        );
+    }
+
+    let palette = quantize_image(&mut image_reader, fixed_palette, &hist, options);
+
+    let merge_area = options.despeckle.merge_area;
+    let mut regions_merged = 0;
+    if merge_area > 0 && !palette.is_empty() {
+        let min_area = merge_area as usize * (scale_factor * scale_factor) as usize;
+        regions_merged = merge_small_regions(&mut image_reader, min_area);
+    }
+
+    PreparedImage {
+        image: image_reader,
+        palette,
+        scale_factor,
+        offset,
+        source_size,
+        descreen_filter,
+        regions_merged,
+    }
+}
+
+/// The `image` with its background made fully transparent as set by `background`.
+pub(crate) fn remove_background(image: &RgbaImage, background: &BackgroundRemoval) -> RgbaImage {
+    match background {
+        BackgroundRemoval::ChromaKey { color, tolerance } => {
+            let mut image = image.clone();
+            let tolerance_squared = tolerance * tolerance;
+            for pixel in image.pixels_mut() {
+                let distance_squared = (0..3)
+                    .map(|i| (pixel[i] as f32 - color[i] as f32).powi(2))
+                    .sum::<f32>();
+                if distance_squared <= tolerance_squared {
+                    pixel[3] = 0;
+                }
+            }
+            image
+        }
+        BackgroundRemoval::Custom(filter) => filter.apply(image),
+    }
+}
+
+/// Makes the pixels of `image` outside the `stencil` fully transparent,
+/// `image` being the part at `origin` of a source image of `source_size`.
+pub(crate) fn apply_stencil(
+    image: &mut RgbaImage,
+    origin: [u32; 2],
+    source_size: [u32; 2],
+    stencil: &Stencil,
+) {
+    let (stencil_width, stencil_height) = stencil.image.dimensions();
+    if stencil_width == 0 || stencil_height == 0 {
+        return;
+    }
+    let sample = |v: u32, size: u32, stencil_size: u32| {
+        ((v as u64 * stencil_size as u64 / size.max(1) as u64) as u32).min(stencil_size - 1)
+    };
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let sx = sample(origin[0] + x, source_size[0], stencil_width);
+        let sy = sample(origin[1] + y, source_size[1], stencil_height);
+        if stencil.image.get_pixel(sx, sy)[0] <= stencil.threshold {
+            pixel[3] = 0;
+        }
+    }
+}
+
+/// Palette of the prepared image, the fixed one when given, otherwise the
+/// dominant colors of `hist` or quantized colors, snapping every pixel to it.
+fn quantize_image(
+    image_reader: &mut RgbaImage,
+    fixed_palette: Option<&[[u8; 4]]>,
+    hist: &HashMap<[u8; 4], usize>,
+    options: &Options,
+) -> Vec<u8> {
+    let _stage = stage!("quantize");
+    let shared_palette = options
+        .palette
+        .as_ref()
+        .filter(|_| options.color_mode == ColorMode::Colored);
+    if let Some(shared_palette) = shared_palette {
+        let palette = unique_colors(shared_palette);
+        snap_to_palette(image_reader, &palette);
+        return palette;
+    }
+    match fixed_palette {
+        Some(fixed_palette) => unique_colors(fixed_palette),
+        // Black and separation modes threshold the pixels instead of quantizing them.
+        None if options.color_mode != ColorMode::Colored => Vec::new(),
+        None => {
+            let colors = 5;
+
+            match dominant_palette(hist, colors) {
+                // Flat color images (e.g. logos) keep their exact dominant colors,
+                // anti-aliased edges are snapped to the nearest one.
+                Some(palette) => {
+                    snap_to_palette(image_reader, &palette);
+                    palette
+                }
+                None => {
+                    // --- Quantize the Image Colors ---
+                    let visible_pixels = image_reader
+                        .pixels()
+                        .filter(|pixel| pixel[3] != 0)
+                        .flat_map(|pixel| pixel.0)
+                        .collect::<Vec<u8>>();
+                    let pixels = match visible_pixels.is_empty() {
+                        true => image_reader.as_raw(),
+                        false => &visible_pixels,
+                    };
+                    let quantizer = new_quantizer(pixels, colors, options);
+                    let palette = quantizer.color_map_rgba();
+
+                    // Iterate through each pixel, quantize its color, and write it to the output image.
+                    for pixel in image_reader.pixels_mut() {
+                        // Get the index in the palette corresponding to this color.
+                        let idx = quantizer.index_of(&pixel.0);
+                        // Each color in the palette is 4 bytes (RGBAs).
+                        let r = palette[idx * 4];
+                        let g = palette[idx * 4 + 1];
+                        let b = palette[idx * 4 + 2];
+                        // Write the quantized color; we keep the original alpha.
+                        *pixel = Rgba([r, g, b, pixel.0[3]]);
+                    }
+
+                    palette
+                }
+            }
+        }
+    }
+}
+
+/// The colors without duplicates as a flat RGBA palette, in order.
+fn unique_colors(colors: &[[u8; 4]]) -> Vec<u8> {
+    let mut palette: Vec<u8> = Vec::with_capacity(colors.len() * 4);
+    for color in colors {
+        if !palette.chunks(4).any(|c| c == color) {
+            palette.extend_from_slice(color);
+        }
+    }
+    palette
+}
+
+/// The [`Options::quantizer`] learning a palette of `colors` from the flat RGBA `pixels`.
+fn new_quantizer(pixels: &[u8], colors: usize, options: &Options) -> Box<dyn Quantize> {
+    match options.quantizer {
+        Quantizer::NeuQuant {
+            sample_factor,
+            learning_cycles,
+        } => {
+            let sample_factor = sample_factor.clamp(1, 30);
+            let learning_cycles = learning_cycles.unwrap_or(NeuQuant::learning_cycles(colors));
+            let seed = options.seed.unwrap_or(0);
+            Box::new(NeuQuant::with_seed(
+                sample_factor,
+                colors,
+                learning_cycles,
+                seed,
+                pixels,
+            ))
+        }
+        Quantizer::MedianCut => Box::new(MedianCut::new(colors, pixels)),
+    }
+}
+
+/// Smooths, blurs, upscales by `scale_factor` and sharpens the cropped source image,
+/// as set by the options. Images with a fixed palette are only upscaled, keeping their colors.
+pub(crate) fn filter_image(
+    image_reader: &RgbaImage,
+    has_fixed_palette: bool,
+    scale_factor: u32,
+    options: &Options,
+) -> RgbaImage {
+    let _stage = stage!("preprocess");
+    let is_pixel_perfect = options.mode == Mode::PixelPerfect;
+    let mut image_reader = if has_fixed_palette || !options.preprocess || is_pixel_perfect {
+        image_reader.clone()
+    } else {
+        preprocess_image(image_reader)
+    };
+
+    // Blurring would introduce colors outside a fixed palette,
+    // and soften pixel edges.
+    let blur_sigma = options.despeckle.blur_sigma;
+    if blur_sigma > 0.0 && !has_fixed_palette && !is_pixel_perfect {
+        image_reader = blur(&image_reader, blur_sigma);
+    }
+
+    if scale_factor > 1 {
+        // Interpolating filters would introduce colors outside a fixed palette.
+        let filter = match options.resample_filter {
+            _ if has_fixed_palette => FilterType::Nearest,
+            ResampleFilter::Nearest => FilterType::Nearest,
+            ResampleFilter::Triangle => FilterType::Triangle,
+            ResampleFilter::CatmullRom => FilterType::CatmullRom,
+            ResampleFilter::Gaussian => FilterType::Gaussian,
+            ResampleFilter::Lanczos3 => FilterType::Lanczos3,
+        };
+        let (width, height) = image_reader.dimensions();
+        image_reader = resize(
+            &image_reader,
+            width * scale_factor,
+            height * scale_factor,
+            filter,
+        );
+
+        if let Some(sharpen) = options.sharpen.filter(|_| !has_fixed_palette) {
+            image_reader = unsharpen(&image_reader, sharpen.sigma, sharpen.threshold);
+        }
+    }
+    image_reader
+}
+
+/// Replaces every pixel color with the nearest color of the flat RGBA `palette`,
+/// keeping its alpha.
+pub(crate) fn snap_to_palette(image: &mut RgbaImage, palette: &[u8]) {
+    for pixel in image.pixels_mut() {
+        let color = palette
+            .chunks(4)
+            .min_by_key(|color| {
+                color
+                    .iter()
+                    .zip(pixel.0)
+                    .map(|(a, b)| (*a as i32 - b as i32).pow(2))
+                    .sum::<i32>()
+            })
+            .unwrap();
+        *pixel = Rgba([color[0], color[1], color[2], pixel.0[3]]);
+    }
+}
+
+/// Clamps a `[x, y, width, height]` region to an image of `size`,
+/// keeping at least one pixel.
+fn region_clamp(region: [u32; 4], size: [u32; 2]) -> [u32; 4] {
+    let [x, y, width, height] = region;
+    let x = x.min(size[0].saturating_sub(1));
+    let y = y.min(size[1].saturating_sub(1));
+    [
+        x,
+        y,
+        width.min(size[0] - x).max(1),
+        height.min(size[1] - y).max(1),
+    ]
+}
+
+/// Share of the pixels the most frequent colors must cover
+/// to be used as the palette instead of quantizing.
+const DOMINANT_COVERAGE: f64 = 0.9;
+
+/// Share of the pixels below which a color is left out of the dominant colors,
+/// e.g. the blended colors along anti-aliased edges.
+const DOMINANT_MIN_SHARE: f64 = 0.005;
+
+/// Up to `colors` of the most frequent colors of the histogram as a flat RGBA palette,
+/// when they cover [`DOMINANT_COVERAGE`] of the pixels.
+fn dominant_palette(hist: &HashMap<[u8; 4], usize>, colors: usize) -> Option<Vec<u8>> {
+    let total: usize = hist.values().sum();
+    let mut entries = hist
+        .iter()
+        .filter(|(_, count)| **count as f64 >= total as f64 * DOMINANT_MIN_SHARE)
+        .collect::<Vec<_>>();
+    // Ties are broken by color, the histogram order is arbitrary.
+    entries.sort_by(|(color_a, count_a), (color_b, count_b)| {
+        count_b.cmp(count_a).then(color_a.cmp(color_b))
+    });
+    entries.truncate(colors);
+
+    let covered: usize = entries.iter().map(|(_, count)| **count).sum();
+    if entries.is_empty() || (covered as f64) < total as f64 * DOMINANT_COVERAGE {
+        return None;
+    }
+
+    info!("Using {} dominant colors", entries.len());
+    Some(entries.iter().flat_map(|(color, _)| **color).collect())
+}
+
+/// Splits the prepared image into the masks of the layers of a [`ColorMode`],
+/// which are then traced and fitted alike.
+trait LayerSource {
+    /// Despeckled masks of the layers, in the order they are stacked.
+    fn masks(&self, prepared: &PreparedImage, options: &Options) -> Vec<Mask>;
+}
+
+/// The layers of `color_mode`.
+fn layer_source(color_mode: ColorMode) -> &'static dyn LayerSource {
+    match color_mode {
+        ColorMode::Black => &ThresholdLayers,
+        ColorMode::Colored => &PaletteLayers,
+        ColorMode::Separation => &SeparationLayers,
+    }
+}
+
+/// A single black layer of the pixels darker than [`Options::threshold`].
+struct ThresholdLayers;
+
+impl LayerSource for ThresholdLayers {
+    fn masks(&self, prepared: &PreparedImage, options: &Options) -> Vec<Mask> {
+        let despeckle = &options.despeckle;
+        let scale_factor = prepared.scale_factor;
+        let mut image = mask_threshold(&prepared.image, &options.threshold);
+
+        let mut regions_removed = mask_despeckle(&mut image, despeckle, scale_factor);
+        if despeckle.merge_area > 0 {
+            let min_area = despeckle.merge_area as usize * (scale_factor * scale_factor) as usize;
+            regions_removed += mask_merge_small_regions(&mut image, min_area);
+        }
+
+        vec![Mask {
+            regions_removed,
+            ..Mask::new([0, 0, 0, 255], image)
+        }]
+    }
+}
+
+/// One layer per ink channel of [`Options::separation`], in the order of the inks.
+struct SeparationLayers;
+
+impl LayerSource for SeparationLayers {
+    fn masks(&self, prepared: &PreparedImage, options: &Options) -> Vec<Mask> {
+        let despeckle = &options.despeckle;
+        let scale_factor = prepared.scale_factor;
+        let separation = &options.separation;
+        let inks = separation.channels.inks();
+
+        mask_separation(&prepared.image, separation)
+            .into_iter()
+            .zip(inks)
+            .map(|(mut image, (_label, color))| {
+                let mut regions_removed = mask_despeckle(&mut image, despeckle, scale_factor);
+                if despeckle.merge_area > 0 {
+                    let min_area =
+                        despeckle.merge_area as usize * (scale_factor * scale_factor) as usize;
+                    regions_removed += mask_merge_small_regions(&mut image, min_area);
+                }
+                Mask {
+                    regions_removed,
+                    ..Mask::new(*color, image)
+                }
+            })
+            .collect()
+    }
+}
+
+/// One layer per palette color, then one per gradient region with [`Options::gradients`].
+struct PaletteLayers;
+
+impl LayerSource for PaletteLayers {
+    fn masks(&self, prepared: &PreparedImage, options: &Options) -> Vec<Mask> {
+        let PreparedImage {
+            image: image_reader,
+            palette,
+            scale_factor,
+            ..
+        } = prepared;
+        let size = prepared.size();
+
+        // ------- Process each unique colors -------
+        let indices = palette_indices(image_reader, palette);
+        let mut color_masks = Vec::with_capacity(palette.len() / 4);
+        let mut regions_removed = Vec::with_capacity(palette.len() / 4);
+        for (index, color) in palette.chunks(4).enumerate() {
+            // Build a binary mask for the current color
+            let mut image = BitMask::from_bits(size, indices.iter().map(|i| *i as usize == index));
+
+            regions_removed.push(mask_despeckle(
+                &mut image,
+                &options.despeckle,
+                *scale_factor,
+            ));
+
+            color_masks.push(([color[0], color[1], color[2], color[3]], image));
+        }
+
+        // Smooth regions are taken out of the masks of their bands.
+        let regions = match options.gradients {
+            Some(gradients) if options.mode == Mode::Smooth => {
+                find_gradients(&mut color_masks, &size, &gradients)
+            }
+            _ => Vec::new(),
+        };
+        info!("{} gradient regions", regions.len());
+
+        let mut masks: Vec<Mask> = color_masks
+            .into_iter()
+            .zip(regions_removed)
+            .map(|((color, image), regions_removed)| Mask {
+                regions_removed,
+                ..Mask::new(color, image)
+            })
+            .collect();
+        masks.extend(regions.into_iter().map(|region| Mask {
+            gradient: Some(region.gradient),
+            ..Mask::new(region.color, region.mask)
+        }));
+        masks
+    }
+}
+
+/// Splits the prepared image into the masks of the [`LayerSource`] of [`Options::color_mode`],
+/// followed by the mask of fully transparent pixels with [`Options::transparent_layer`].
+///
+/// Depends on [`Options::color_mode`], [`Options::threshold`], [`Options::separation`],
+/// [`Options::gradients`], [`Options::transparent_layer`], [`Options::clip_layers`]
+/// and the despeckle filters.
+pub(crate) fn build_masks(prepared: &PreparedImage, options: &Options) -> Vec<Mask> {
+    let _stage = stage!("masks");
+    let mut masks = layer_source(options.color_mode).masks(prepared, options);
+
+    if options.transparent_layer {
+        let pixels = prepared.image.pixels().map(|pixel| pixel[3] == 0);
+        let mut image = BitMask::from_bits(prepared.size(), pixels);
+
+        let regions_removed = mask_despeckle(&mut image, &options.despeckle, prepared.scale_factor);
+
+        masks.push(Mask {
+            regions_removed,
+            ..Mask::new([0, 0, 0, 0], image)
+        });
+    }
+
+    if options.clip_layers && options.color_mode == ColorMode::Colored {
+        // From the top, the pixels of the masks above are taken out of each mask.
+        let mut above = BitMask::new(prepared.size());
+        for mask in masks.iter_mut().rev() {
+            mask.pixels.subtract(&above);
+            above.union(&mask.pixels);
+        }
+    }
+
+    masks
+}
+
+/// Extracts the pixel outlines of a mask.
+///
+/// Depends on [`Options::turn_policy`], [`Options::connectivity`] and [`Options::seed`].
+pub(crate) fn trace_outline(image: &BitMask, options: &Options) -> Outline {
+    let stage = stage!("extract");
+    let seed = options.seed.unwrap_or(0);
+    let outline = extract_outline_seeded(image, options.outline_turn_policy(), true, seed)
+        .iter_mut()
+        .map(|x| {
+            (
+                x.0,
+                x.1.iter_mut().map(|x| x.as_dvec2()).collect::<Vec<DVec2>>(),
+            )
+        })
+        .collect::<Vec<(bool, Vec<DVec2>)>>();
+    stage.record_contours(outline.len());
+    outline
+}
+
+/// [`curve_fit_nd::fit_poly_list`] with every polygon fitted on its own thread,
+/// returning the curves in the order of the polygons.
+fn fit_poly_list_parallel(
+    poly_list_src: Vec<(bool, Vec<DVec2>)>,
+    error_threshold: f64,
+    corner_angle: f64,
+    corner_scale: f64,
+    use_optimize_exhaustive: bool,
+) -> Vec<(bool, Vec<[DVec2; 3]>)> {
+    // Single threaded (we may want to allow users to force this).
+    if poly_list_src.len() <= 1 {
+        return curve_fit_nd::fit_poly_list(
+            poly_list_src,
+            error_threshold,
+            corner_angle,
+            corner_scale,
+            use_optimize_exhaustive,
+        );
+    }
+
+    // Sorted by length so the larger polygons are started first,
+    // the smaller ones keep the other processors busy meanwhile.
+    let mut poly_vec_src = poly_list_src.into_iter().enumerate().collect::<Vec<_>>();
+    poly_vec_src.sort_by_key(|(_index, (_is_cyclic, poly_src))| std::cmp::Reverse(poly_src.len()));
+
+    let mut curve_list_dst = thread::scope(|scope| {
+        let handles = poly_vec_src
+            .into_iter()
+            .map(|(index, (is_cyclic, poly_src))| {
+                let handle = scope.spawn(move || {
+                    curve_fit_nd::fit_poly_single(
+                        &poly_src,
+                        is_cyclic,
+                        error_threshold,
+                        corner_angle,
+                        corner_scale,
+                        use_optimize_exhaustive,
+                    )
+                });
+                (index, is_cyclic, handle)
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|(index, is_cyclic, handle)| (index, (is_cyclic, handle.join().unwrap())))
+            .collect::<Vec<_>>()
+    });
+
+    // Keep the order of the input polygons.
+    curve_list_dst.sort_by_key(|(index, _)| *index);
+    curve_list_dst
+        .into_iter()
+        .map(|(_index, curve)| curve)
+        .collect()
+}
+
+/// Simplifies outlines and fits them with curves,
+/// `size` is the size of the traced image.
+///
+/// Depends on [`Options::mode`], [`Options::pixel_scale`], [`Options::corners`],
+/// [`Options::smoothness`], [`Options::line_fitting`], [`Options::border_contours`] and the thresholds for the layer `color`,
+/// see [`Options::thresholds_for`].
+pub(crate) fn fit_outline(
+    outline: Outline,
+    color: [u8; 4],
+    size: &[usize; 2],
+    options: &Options,
+) -> Contours {
+    let border_contours = match options.border_contours {
+        // Pixel edges along the border are already straight.
+        BorderContours::Clip if options.mode == Mode::PixelPerfect => BorderContours::Closed,
+        border_contours => border_contours,
+    };
+    let thresholds = options.thresholds_for(color);
+    if border_contours == BorderContours::Closed {
+        return fit_polygons(outline, thresholds, options);
+    }
+
+    // Fit the inner runs of contours touching the border as open polygons.
+    let mut runs = Vec::new();
+    let mut joins = Vec::new();
+    for (is_cyclic, poly) in outline {
+        match split_at_border(&poly, size) {
+            Some(pieces) => {
+                let (pieces, chains): (Vec<_>, Vec<_>) = pieces.into_iter().unzip();
+                runs.extend(pieces.into_iter().map(|run| (false, run)));
+                joins.push(Some(chains));
+            }
+            None => {
+                runs.push((is_cyclic, poly));
+                joins.push(None);
+            }
+        }
+    }
+
+    let contours = fit_polygons(runs, thresholds, options);
+    match border_contours {
+        BorderContours::Clip => join_at_border(&joins, contours),
+        _ => contours,
+    }
+}
+
+/// Splits a contour touching the image border into open runs of its inner edges,
+/// each paired with the border vertices leading to the next run.
+///
+/// Returns `None` for contours away from the border, or lying entirely on it.
+fn split_at_border(poly: &[DVec2], size: &[usize; 2]) -> Option<Vec<(Vec<DVec2>, Vec<DVec2>)>> {
+    let (width, height) = (size[0] as f64, size[1] as f64);
+    let len = poly.len();
+    let is_border = (0..len)
+        .map(|i| {
+            let (a, b) = (poly[i], poly[(i + 1) % len]);
+            (a.x == b.x && (a.x == 0.0 || a.x == width))
+                || (a.y == b.y && (a.y == 0.0 || a.y == height))
+        })
+        .collect::<Vec<bool>>();
+
+    // Start at the first vertex leaving the border.
+    let start = (0..len).find(|i| is_border[(i + len - 1) % len] && !is_border[*i])?;
+
+    let mut pieces = Vec::new();
+    let (mut i, mut edges) = (start, 0);
+    while edges < len {
+        let mut run = vec![poly[i]];
+        while !is_border[i] {
+            i = (i + 1) % len;
+            edges += 1;
+            run.push(poly[i]);
+        }
+        let mut chain = Vec::new();
+        while is_border[i] {
+            i = (i + 1) % len;
+            edges += 1;
+            if is_border[i] {
+                chain.push(poly[i]);
+            }
+        }
+        pieces.push((run, chain));
+    }
+    Some(pieces)
+}
+
+/// Closes the fitted runs of every split contour with straight lines along the image border.
+///
+/// `joins` has the border chains following each run of a split contour,
+/// `None` for contours fitted whole.
+fn join_at_border(joins: &[Option<Vec<Vec<DVec2>>>], contours: Contours) -> Contours {
+    let (mut polygons, mut curves) = (contours.0.into_iter(), contours.1.into_iter());
+    let mut joined: Contours = (
+        Vec::with_capacity(joins.len()),
+        Vec::with_capacity(joins.len()),
+    );
+
+    for chains in joins {
+        let Some(chains) = chains else {
+            joined.0.extend(polygons.next());
+            joined.1.extend(curves.next());
+            continue;
+        };
+
+        let (mut polygon, mut curve) = (Vec::new(), Vec::new());
+        for chain in chains {
+            polygon.extend(polygons.next().unwrap().1);
+            polygon.extend(chain);
+
+            let mut knots = curves.next().unwrap().1;
+            // Leave and join the border in straight lines.
+            if let Some(first) = knots.first_mut() {
+                first[0] = first[1];
+            }
+            if let Some(last) = knots.last_mut() {
+                last[2] = last[1];
+            }
+            curve.extend(knots);
+            curve.extend(chain.iter().map(|v| [*v; 3]));
+        }
+        joined.0.push((true, polygon));
+        joined.1.push((true, curve));
+    }
+    joined
+}
+
+/// Simplifies polygons and fits them with curves, or only scales them with [`Mode::PixelPerfect`].
+/// With [`Mode::Polygon`] the simplified polygons are not fitted.
+///
+/// `thresholds` are the `(error_threshold, simplify_threshold)` of [`Options::thresholds_for`].
+fn fit_polygons(poly_list_to_fit: Outline, thresholds: (f64, f64), options: &Options) -> Contours {
+    if options.mode == Mode::PixelPerfect {
+        return pixel_contours(poly_list_to_fit, options.pixel_outline_scale());
+    }
+    match &options.line_fitting {
+        Some(line_fitting) if options.mode == Mode::Smooth => {
+            fit_lines_first(poly_list_to_fit, thresholds, options, line_fitting)
+        }
+        _ => fit_curves(poly_list_to_fit, thresholds, options),
+    }
+}
+
+/// Fits the straight runs of the outlines with lines, see [`Options::line_fitting`],
+/// and the curved runs between them with [`fit_curves`].
+fn fit_lines_first(
+    poly_list: Outline,
+    thresholds: (f64, f64),
+    options: &Options,
+    line_fitting: &LineFitting,
+) -> Contours {
+    // Outlines without straight runs are kept whole as `Err` and fitted alike.
+    let split = poly_list
+        .into_iter()
+        .map(|(is_cyclic, poly)| {
+            let pieces = poly_split_straight(
+                is_cyclic,
+                &poly,
+                line_fitting.tolerance,
+                line_fitting.min_length,
+            );
+            (is_cyclic, pieces.ok_or(poly))
+        })
+        .collect::<Vec<_>>();
+    let curved = split
+        .iter()
+        .flat_map(|(is_cyclic, split)| match split {
+            Ok(pieces) => pieces
+                .iter()
+                .filter(|(is_straight, _)| !is_straight)
+                .map(|(_, points)| (false, points.clone()))
+                .collect(),
+            Err(poly) => vec![(*is_cyclic, poly.clone())],
+        })
+        .collect();
+    let (polygons, curves) = fit_curves(curved, thresholds, options);
+    let mut fitted = polygons
+        .into_iter()
+        .zip(curves)
+        .map(|((_, polygon), (_, curve))| (polygon, curve));
+
+    let mut contours: Contours = (Vec::new(), Vec::new());
+    for (is_cyclic, split) in split {
+        let (polygon, curve) = match split {
+            Ok(pieces) => {
+                let pieces = pieces
+                    .into_iter()
+                    .map(|(is_straight, points)| match is_straight {
+                        true => {
+                            let knots = points.iter().map(|v| [*v; 3]).collect();
+                            (points, knots)
+                        }
+                        false => fitted.next().unwrap(),
+                    })
+                    .collect::<Vec<_>>();
+                join_pieces(is_cyclic, pieces)
+            }
+            Err(_) => fitted.next().unwrap(),
+        };
+        contours.0.push((is_cyclic, polygon));
+        contours.1.push((is_cyclic, curve));
+    }
+    contours
+}
+
+/// Simplifies the outlines and fits them with curves, or keeps the polygons with [`Mode::Polygon`].
+fn fit_curves(
+    mut poly_list_to_fit: Outline,
+    (error_threshold, simplify_threshold): (f64, f64),
+    options: &Options,
+) -> Contours {
+    let corner_threshold = options.corners.angle();
+    let corner_scale = options.corners.scale;
+    let use_optimize_exhaustive = true;
+    let length_threshold = 0.75; // 0.75
+                                 // Pixel outlines only touch themselves, kept for simplified polygons crossing themselves.
+    let outlines = (options.ensure_simple_paths && options.mode == Mode::Smooth)
+        .then(|| poly_list_to_fit.clone());
+
+    // Ensure we always have at least one knot between 'corners'
+    // this means theres always a middle tangent, giving us more possible
+    // tangents when fitting the curve.
+    poly_list_subdivide(&mut poly_list_to_fit);
+    poly_list_simplify_by(
+        &mut poly_list_to_fit,
+        options.simplifier,
+        |is_cyclic, poly| match &options.adaptive_simplify {
+            Some(adaptive) => adaptive.threshold(simplify_threshold, is_cyclic, poly),
+            None => simplify_threshold,
+        },
+    );
+    let mut polygons = poly_list_to_fit.clone();
+    if options.mode == Mode::Polygon {
+        let curves = straight_curves(&polygons);
+        return (polygons, curves);
+    }
+
+    // Setting the `length_threshold` around 1.0 around sharp turns
+    // gives the fitter enough points to follow them,
+    // while straight and gently curving parts only need a few.
+    poly_list_subdivide_adaptive(&mut poly_list_to_fit, length_threshold);
+    // Fitted again when their curve crosses itself.
+    let poly_list_to_refit = outlines.as_ref().map(|_| poly_list_to_fit.clone());
+
+    let mut curves = fit_poly_list_parallel(
+        poly_list_to_fit,
+        error_threshold,
+        corner_threshold,
+        corner_scale,
+        use_optimize_exhaustive,
+    );
+
+    let smoothness = options.smoothness;
+    if smoothness > 0.0 {
+        for (is_cyclic, curve) in &mut curves {
+            curve_smooth(*is_cyclic, curve, smoothness, smoothness * error_threshold);
+        }
+    }
+
+    if let (Some(outlines), Some(poly_list)) = (outlines, poly_list_to_refit) {
+        let fitted = outlines
+            .into_iter()
+            .zip(poly_list)
+            .zip(&mut polygons)
+            .zip(&mut curves);
+        for ((((_, outline), (is_cyclic, poly)), (_, polygon)), (_, curve)) in fitted {
+            let mut error_threshold = error_threshold;
+            for _ in 0..4 {
+                if curve_is_simple(is_cyclic, curve) {
+                    break;
+                }
+                error_threshold /= 2.0;
+                *curve = curve_fit_nd::fit_poly_single(
+                    &poly,
+                    is_cyclic,
+                    error_threshold,
+                    corner_threshold,
+                    corner_scale,
+                    use_optimize_exhaustive,
+                );
+                if smoothness > 0.0 {
+                    curve_smooth(is_cyclic, curve, smoothness, smoothness * error_threshold);
+                }
+            }
+            if !curve_is_simple(is_cyclic, curve) {
+                *curve = polygon.iter().map(|v| [*v; 3]).collect();
+            }
+            if !curve_is_simple(is_cyclic, curve) {
+                *curve = outline.iter().map(|v| [*v; 3]).collect();
+                *polygon = outline;
+            }
+        }
+    }
+
+    (polygons, curves)
+}
+
+/// Joins the `(points, knots)` of pieces each starting where the one before ends,
+/// the knots at the junctions taking the in handle of the piece before them
+/// and the out handle of the piece after.
+fn join_pieces(
+    is_cyclic: bool,
+    pieces: impl IntoIterator<Item = (Vec<DVec2>, Vec<[DVec2; 3]>)>,
+) -> (Vec<DVec2>, Vec<[DVec2; 3]>) {
+    let (mut polygon, mut curve) = (Vec::new(), Vec::<[DVec2; 3]>::new());
+    for (mut points, mut knots) in pieces {
+        if let Some(last) = polygon.pop() {
+            let knot = curve.pop().unwrap();
+            knots[0][0] = knot[0];
+            points[0] = last;
+        }
+        polygon.extend(points);
+        curve.extend(knots);
+    }
+    if is_cyclic {
+        polygon.pop();
+        if let Some(knot) = curve.pop() {
+            curve[0][0] = knot[0];
+        }
+    }
+    (polygon, curve)
+}
+
+/// Fits the boundaries between the outlines of every layer once,
+/// so the contours of neighboring layers follow the exact same points and curves.
+///
+/// Each boundary uses the smallest thresholds of the layers on its sides.
+fn fit_shared_outlines(
+    outlines: &[Outline],
+    colors: &[[u8; 4]],
+    options: &Options,
+) -> Vec<Contours> {
+    let shared = shared_boundaries(outlines);
+
+    let mut thresholds = vec![(f64::INFINITY, f64::INFINITY); shared.boundaries.len()];
+    for (polygons, color) in shared.polygons.iter().zip(colors) {
+        let (error_threshold, simplify_threshold) = options.thresholds_for(*color);
+        for (index, _is_reversed) in polygons.iter().flat_map(|(_, refs)| refs) {
+            let boundary_thresholds = &mut thresholds[*index];
+            boundary_thresholds.0 = boundary_thresholds.0.min(error_threshold);
+            boundary_thresholds.1 = boundary_thresholds.1.min(simplify_threshold);
+        }
+    }
+
+    // Fit the boundaries sharing the same thresholds together.
+    let mut groups: Vec<((f64, f64), Vec<usize>)> = Vec::new();
+    for (index, boundary_thresholds) in thresholds.iter().enumerate() {
+        match groups.iter_mut().find(|(t, _)| t == boundary_thresholds) {
+            Some((_, indices)) => indices.push(index),
+            None => groups.push((*boundary_thresholds, vec![index])),
+        }
+    }
+    let mut fitted = vec![(Vec::new(), Vec::new()); shared.boundaries.len()];
+    for (group_thresholds, indices) in groups {
+        let poly_list = indices
+            .iter()
+            .map(|index| shared.boundaries[*index].clone())
+            .collect();
+        let (polygons, curves) = fit_polygons(poly_list, group_thresholds, options);
+        for ((index, (_, polygon)), (_, curve)) in indices.into_iter().zip(polygons).zip(curves) {
+            fitted[index] = (polygon, curve);
+        }
+    }
+
+    shared
+        .polygons
+        .iter()
+        .map(|polygons| {
+            let mut contours: Contours = (Vec::new(), Vec::new());
+            for (is_cyclic, refs) in polygons {
+                let is_ring = refs
+                    .first()
+                    .is_some_and(|(index, _)| shared.boundaries[*index].0);
+                let mut pieces = refs.iter().map(|(index, is_reversed)| {
+                    let (mut points, mut knots) = fitted[*index].clone();
+                    if *is_reversed {
+                        points.reverse();
+                        knots.reverse();
+                        knots.iter_mut().for_each(|knot| knot.swap(0, 2));
+                    }
+                    (points, knots)
+                });
+                let (polygon, curve) = match is_ring {
+                    true => pieces.next().unwrap(),
+                    false => join_pieces(*is_cyclic, pieces),
+                };
+                contours.0.push((*is_cyclic, polygon));
+                contours.1.push((*is_cyclic, curve));
+            }
+            contours
+        })
+        .collect()
+}
+
+/// Fits the outline of every mask into the layers of the result,
+/// then applies [`Options::auto_crop`] and [`Options::transform`].
+pub(crate) fn build_result(
+    prepared: &PreparedImage,
+    masks: &[Mask],
+    outlines: Vec<Outline>,
+    options: &Options,
+) -> VectorizationResult {
+    let contours = fit_layers(prepared, masks, outlines, options);
+    assemble_result(prepared, masks, contours, options)
+}
+
+/// Fits the outline of every mask.
+///
+/// Depends on the options of [`fit_outline`] and [`Options::shared_boundaries`].
+pub(crate) fn fit_layers(
+    prepared: &PreparedImage,
+    masks: &[Mask],
+    outlines: Vec<Outline>,
+    options: &Options,
+) -> Vec<Contours> {
+    let stage = stage!("fit");
+    let mut contours = if options.shared_boundaries && options.mode != Mode::PixelPerfect {
+        let colors = masks.iter().map(|mask| mask.color).collect::<Vec<_>>();
+        fit_shared_outlines(&outlines, &colors, options)
+    } else {
+        let size = prepared.size();
+        masks
+            .iter()
+            .zip(outlines)
+            .map(|(mask, outline)| fit_outline(outline, mask.color, &size, options))
+            .collect()
+    };
+    match_orientation(&mut contours);
+    if options.ensure_simple_paths && options.mode == Mode::Smooth {
+        straighten_crossing_curves(&mut contours);
+    }
+    stage.record_contours(contours.iter().map(|(_, curves)| curves.len()).sum());
+    contours
+}
+
+/// Reverses the closed curves winding the other way around than the polygon they were fitted to,
+/// as fitting can turn small contours inside out.
+fn match_orientation(contours: &mut [Contours]) {
+    for (polygons, curves) in contours {
+        for ((is_cyclic, polygon), (_, curve)) in polygons.iter().zip(curves) {
+            if *is_cyclic && polygon_area(polygon) * curve_area(curve) < 0.0 {
+                curve_reverse(curve);
+            }
+        }
+    }
+}
+
+/// Joins the points of the polygons whose curve crosses itself with lines,
+/// e.g. after the pieces of a contour fitted on their own were joined,
+/// see [`Options::ensure_simple_paths`].
+fn straighten_crossing_curves(contours: &mut [Contours]) {
+    for (polygons, curves) in contours {
+        if polygons.len() != curves.len() {
+            continue;
+        }
+        for ((_, polygon), (is_cyclic, curve)) in polygons.iter().zip(curves) {
+            if !curve_is_simple(*is_cyclic, curve) {
+                *curve = polygon.iter().map(|v| [*v; 3]).collect();
+            }
+        }
+    }
+}
+
+/// Whether a closed contour was moved by `distance` past its middle, where it can turn
+/// inside out keeping its area sign: none of the `moved` points is as far from `original`.
+fn is_moved_past(original: &[DVec2], moved: &[DVec2], distance: f64) -> bool {
+    let min_distance_squared = (distance.abs() * (1.0 - 1e-6)).powi(2);
+    !moved.iter().any(|p| {
+        (0..original.len()).all(|i| {
+            let (a, b) = (original[i], original[(i + 1) % original.len()]);
+            segment_distance_squared(*p, a, b) >= min_distance_squared
+        })
+    })
+}
+
+/// Moves the polygons and curves of a layer as set by [`Options::offset`],
+/// removing the closed contours turned inside out along with their polygon or curve.
+fn offset_contours((polygons, curves): Contours, offset: &OffsetOptions) -> Contours {
+    let distance = offset.distance;
+    let polygons = polygons
+        .into_iter()
+        .map(|(is_cyclic, polygon)| {
+            let moved = polygon_offset(is_cyclic, &polygon, distance, offset);
+            let is_flipped = is_cyclic
+                && (polygon_area(&moved) * polygon_area(&polygon) <= 0.0
+                    || is_moved_past(&polygon, &moved, distance));
+            (is_flipped, (is_cyclic, moved))
+        })
+        .collect::<Vec<_>>();
+    let curves = curves
+        .into_iter()
+        .map(|(is_cyclic, curve)| {
+            let moved = curve_offset_joined(is_cyclic, &curve, distance, offset);
+            let is_flipped = is_cyclic && {
+                let outline = segments_flatten(true, &curve_segments(true, &curve), 0.01);
+                let knots = moved.iter().map(|knot| knot[1]).collect::<Vec<_>>();
+                curve_area(&moved) * curve_area(&curve) <= 0.0
+                    || is_moved_past(&outline, &knots, distance - 0.02f64.copysign(distance))
+            };
+            (is_flipped, (is_cyclic, moved))
+        })
+        .collect::<Vec<_>>();
+
+    // Holes grown out of their removed outer contours go along with them.
+    let polygon_rings = polygons
+        .iter()
+        .map(|(is_flipped, (is_cyclic, polygon))| (*is_flipped, *is_cyclic, polygon.clone()))
+        .collect::<Vec<_>>();
+    let curve_rings = curves
+        .iter()
+        .map(|(is_flipped, (is_cyclic, curve))| {
+            let knots = curve.iter().map(|knot| knot[1]).collect();
+            (*is_flipped, *is_cyclic, knots)
+        })
+        .collect::<Vec<_>>();
+    let removed_polygons = removed_with_orphaned_holes(&polygon_rings);
+    let removed_curves = removed_with_orphaned_holes(&curve_rings);
+
+    // Polygons and curves are removed together when they are fitted from the same contours.
+    let is_paired = polygons.len() == curves.len();
+    let removed_polygons = (0..polygons.len())
+        .map(|i| removed_polygons[i] || is_paired && removed_curves[i])
+        .collect::<Vec<_>>();
+    let removed_curves = (0..curves.len())
+        .map(|i| removed_curves[i] || is_paired && removed_polygons[i])
+        .collect::<Vec<_>>();
+    (
+        polygons
+            .into_iter()
+            .zip(removed_polygons)
+            .filter_map(|((_, polygon), is_removed)| (!is_removed).then_some(polygon))
+            .collect(),
+        curves
+            .into_iter()
+            .zip(removed_curves)
+            .filter_map(|((_, curve), is_removed)| (!is_removed).then_some(curve))
+            .collect(),
+    )
+}
+
+/// Which of the `(is_removed, is_cyclic, ring)` contours are removed, adding the holes
+/// no longer inside any of the outer contours kept, which would be filled on their own.
+fn removed_with_orphaned_holes(rings: &[(bool, bool, Vec<DVec2>)]) -> Vec<bool> {
+    let is_kept_outer = |(is_removed, is_cyclic, ring): &(bool, bool, Vec<DVec2>)| {
+        !is_removed && *is_cyclic && polygon_area(ring) < 0.0
+    };
+    rings
+        .iter()
+        .map(|(is_removed, is_cyclic, ring)| {
+            let is_hole = *is_cyclic && polygon_area(ring) > 0.0;
+            *is_removed
+                || is_hole
+                    && !rings
+                        .iter()
+                        .filter(|outer| is_kept_outer(outer))
+                        .any(|(_, _, outer)| ring_contains(outer, ring[0]))
+        })
+        .collect()
+}
+
+/// Builds the layers of the result from the fitted `contours` of every mask,
+/// then applies [`Options::region`], [`Options::grid`], [`Options::auto_crop`]
+/// and [`Options::transform`].
+///
+/// Depends on [`Options::supersample`], [`Options::offset`], [`Options::overlap`]
+/// and the options above.
+pub(crate) fn assemble_result(
+    prepared: &PreparedImage,
+    masks: &[Mask],
+    contours: Vec<Contours>,
+    options: &Options,
+) -> VectorizationResult {
+    let (mut width, mut height) = prepared.image.dimensions();
+
+    // Automatically upscaled images are brought back to the input resolution,
+    // before the curves are offset in output units.
+    let downscale = match options.supersample {
+        Supersample::Auto => prepared.scale_factor,
+        Supersample::Off | Supersample::Factor(_) => 1,
+    };
+    width /= downscale;
+    height /= downscale;
+
+    let layers: Vec<Layer> = masks
+        .iter()
+        .zip(contours)
+        .map(|(mask, (mut polygons, mut curves))| {
+            let mut gradient = mask.gradient.clone();
+            if downscale > 1 {
+                let scale = 1.0 / downscale as f64;
+                for (_, polygon) in &mut polygons {
+                    polygon.iter_mut().for_each(|p| *p = *p * scale);
+                }
+                for (_, curve) in &mut curves {
+                    curve.iter_mut().flatten().for_each(|p| *p = *p * scale);
+                }
+                if let Some(gradient) = &mut gradient {
+                    gradient.transform(scale, DVec2::ZERO);
+                }
+            }
+            if options.offset.distance != 0.0 {
+                (polygons, curves) = offset_contours((polygons, curves), &options.offset);
+            }
+            for (is_cyclic, curve) in &mut curves {
+                curve_offset(*is_cyclic, curve, options.overlap);
+            }
+            // Outlines are traced with outer contours counter-clockwise.
+            if options.orientation == Orientation::OuterClockwise {
+                for (_, polygon) in polygons.iter_mut().filter(|(is_cyclic, _)| *is_cyclic) {
+                    polygon.reverse();
+                }
+                for (_, curve) in curves.iter_mut().filter(|(is_cyclic, _)| *is_cyclic) {
+                    curve_reverse(curve);
+                }
+            }
+            let mut layer = Layer {
+                color: mask.color,
+                polygons,
+                curves,
+                gradient,
+                paths: Vec::new(),
+            };
+            layer.update_paths();
+            layer
+        })
+        .collect();
+
+    // Masks are counted in traced pixels, which are upscaled input pixels.
+    let area = (prepared.scale_factor * prepared.scale_factor) as usize;
+    let palette = masks
+        .iter()
+        .map(|mask| PaletteColor {
+            color: mask.color,
+            pixel_count: mask.pixels.count() / area,
+        })
+        .collect();
+
+    if options.mode == Mode::PixelPerfect {
+        let scale = options.pixel_outline_scale();
+        width = width.saturating_mul(scale);
+        height = height.saturating_mul(scale);
+    }
+
+    let contour_tree = contour_tree(&layers, options.orientation);
+    let mut result = VectorizationResult {
+        width,
+        height,
+        layers,
+        palette,
+        contour_tree,
+        canvas: BoundingBox {
+            min: DVec2::new(0.0, 0.0),
+            max: DVec2::new(width as f64, height as f64),
+        },
+    };
+
+    // The region was traced on its own, move it back to its place in the image.
+    if options.region.is_some() {
+        // Output units per source pixel.
+        let scale = width * prepared.scale_factor / prepared.image.width();
+        let [x, y] = prepared.offset;
+        result.transform(1.0, [(x * scale) as f64, (y * scale) as f64]);
+        result.width = prepared.source_size[0] * scale;
+        result.height = prepared.source_size[1] * scale;
+        result.canvas.min = DVec2::new(0.0, 0.0);
+        result.canvas.max = DVec2::new(result.width as f64, result.height as f64);
+    }
+
+    if let Some(step) = options.grid.filter(|step| *step > 0.0) {
+        result.round_to_grid(step);
+    }
+
+    if let Some(padding) = options.auto_crop {
+        result.crop(padding);
+    }
+
+    let transform = &options.transform;
+    let scale = transform.scale_for(result.width, result.height);
+    if scale != 1.0 || transform.translate != [0.0, 0.0] {
+        result.transform(scale, transform.translate);
+    }
+
+    result
+}
+
+/// Scales pixel outlines by `scale`, pairing them with straight-edged curves
+/// (knots whose handles sit on the point) for outputs that only read curves.
+fn pixel_contours(mut poly_list: Vec<(bool, Vec<DVec2>)>, scale: u32) -> Contours {
+    for (_is_cyclic, poly) in &mut poly_list {
+        for v in poly.iter_mut() {
+            *v = v.mul(scale as f64);
+        }
+    }
+    let curves = straight_curves(&poly_list);
+    (poly_list, curves)
+}
+
+/// Curves joining the points of every polygon with line segments,
+/// their knots having both handles on the point.
+fn straight_curves(poly_list: &[(bool, Vec<DVec2>)]) -> Vec<(bool, Vec<[DVec2; 3]>)> {
+    poly_list
+        .iter()
+        .map(|(is_cyclic, poly)| (*is_cyclic, poly.iter().map(|v| [*v; 3]).collect()))
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn create_svg_wasm(image_byte: Box<[u8]>, color_mode: ColorMode) -> JsValue {
+    JsValue::from_str(&create_svg(&image_byte, color_mode))
+}
+
+/// Like [`create_svg_wasm`], returning an object with the `svg` document
+/// and the `palette` colors with their pixel counts.
+#[wasm_bindgen]
+pub fn create_svg_wasm_with_palette(image_byte: Box<[u8]>, color_mode: ColorMode) -> SvgOutput {
+    let options = Options {
+        color_mode,
+        ..Default::default()
+    };
+    let result = vectorize(&image_byte, &options);
+
+    SvgOutput {
+        svg: output::svg::to_svg(&result, &options),
+        palette: result.palette,
+    }
+}
+
+/// Traces the `data` of a canvas `ImageData` (a `Uint8ClampedArray`) as [`create_svg_wasm_with_palette`] does,
+/// see [`create_svg_from_rgba`].
+#[wasm_bindgen]
+pub fn create_svg_from_rgba_wasm(
+    pixels: Clamped<Vec<u8>>,
+    width: u32,
+    height: u32,
+    color_mode: ColorMode,
+) -> Result<SvgOutput, JsError> {
+    let options = Options {
+        color_mode,
+        ..Default::default()
+    };
+    let result = vectorize_image(&rgba_image(&pixels, width, height)?, &options);
+
+    Ok(SvgOutput {
+        svg: output::svg::to_svg(&result, &options),
+        palette: result.palette,
+    })
+}
+
+fn preprocess_image(
+    img: &image::ImageBuffer<Rgba<u8>, Vec<u8>>,
+) -> image::ImageBuffer<Rgba<u8>, Vec<u8>> {
+    // Adaptive Kuwahara filter: adapts the window radius per-pixel based on
+    // local edge strength (Sobel gradient magnitude). Flat regions use larger
+    // windows; edge regions use smaller windows to preserve detail.
+    pub fn adaptive_kuwahara_filter(
+        src: &image::ImageBuffer<Rgba<u8>, Vec<u8>>,
+        r_min: f64,
+        r_max: f64,
+        gamma: f32,
+    ) -> image::ImageBuffer<Rgba<u8>, Vec<u8>> {
+        use image::{ImageBuffer, Rgba};
+
+        let (width, height) = src.dimensions();
+        let mut dst = ImageBuffer::new(width, height);
+
+        let w = width as usize;
+        let h = height as usize;
+
+        // 1) Build grayscale (luminance) buffer
+        let mut lum: Vec<f32> = vec![0.0; w * h];
+        for y in 0..height {
+            for x in 0..width {
+                let p = src.get_pixel(x, y).0;
+                let l = 0.299f32 * p[0] as f32 + 0.587f32 * p[1] as f32 + 0.114f32 * p[2] as f32;
+                lum[(y as usize) * w + (x as usize)] = l;
+            }
+        }
+
+        // Helper to clamp coordinates and fetch luminance
+        let get_lum = |xx: i32, yy: i32| -> f32 {
+            let cx = xx.clamp(0, (width as i32) - 1) as usize;
+            let cy = yy.clamp(0, (height as i32) - 1) as usize;
+            lum[cy * w + cx]
+        };
+
+        // 2) Compute Sobel gradient magnitude per pixel, track global max
+        let mut grad_mag: Vec<f32> = vec![0.0; w * h];
+        let mut max_mag: f32 = 0.0;
+        for y in 0..(height as i32) {
+            for x in 0..(width as i32) {
+                // Sobel kernels
+                let gx = -1.0 * get_lum(x - 1, y - 1)
+                    + 1.0 * get_lum(x + 1, y - 1)
+                    + -2.0 * get_lum(x - 1, y)
+                    + 2.0 * get_lum(x + 1, y)
+                    + -1.0 * get_lum(x - 1, y + 1)
+                    + 1.0 * get_lum(x + 1, y + 1);
+                let gy = 1.0 * get_lum(x - 1, y - 1)
+                    + 2.0 * get_lum(x, y - 1)
+                    + 1.0 * get_lum(x + 1, y - 1)
+                    + -1.0 * get_lum(x - 1, y + 1)
+                    - 2.0 * get_lum(x, y + 1)
+                    - 1.0 * get_lum(x + 1, y + 1);
+                let m = (gx * gx + gy * gy).sqrt();
+                let idx = (y as usize) * w + (x as usize);
+                grad_mag[idx] = m;
+                if m > max_mag {
+                    max_mag = m;
+                }
+            }
+        }
+
+        // 3) Map gradient magnitude to adaptive radius per pixel
+        // Normalized edge strength in [0,1]. Strong edges -> near 1.
+        // Use (1 - edge)^gamma to favor larger windows in flat regions.
+        let denom = if max_mag > 0.0 { max_mag } else { 1.0 };
+        let r_min_c = r_min.max(0.0);
+        let r_max_c = r_max.max(r_min_c);
+        let range = (r_max_c - r_min_c) as f64;
+        let mut r_map: Vec<u32> = vec![r_min_c.round() as u32; w * h];
+        for i in 0..grad_mag.len() {
+            let e = (grad_mag[i] / denom).clamp(0.0, 1.0);
+            let inv = (1.0 - e).powf(gamma);
+            let r = r_min_c + range * (inv as f64);
+            let r_clamped = r.clamp(0.0, r_max_c);
+            r_map[i] = r_clamped.round() as u32;
+        }
+
+        // 4) Apply classic Kuwahara per pixel with its own radius
+        for y in 0..height {
+            for x in 0..width {
+                let idx = (y as usize) * w + (x as usize);
+                let r = r_map[idx];
+
+                let mut best_var = f64::MAX;
+                let mut best_mean = [0f64; 4];
+
+                // four sub-windows: (0,0), (0,r), (r,0), (r,r)
+                for (dy, dx) in &[(0, 0), (0, r), (r, 0), (r, r)] {
+                    let mut sum = [0u64; 4];
+                    let mut sum_sq = [0u64; 4];
+                    let mut count = 0u64;
+
+                    let y0 = y.saturating_sub(*dy);
+                    let x0 = x.saturating_sub(*dx);
+                    for yy in y0..=(y0 + r).min(height - 1) {
+                        for xx in x0..=(x0 + r).min(width - 1) {
+                            let pix = src.get_pixel(xx, yy).0;
+                            for c in 0..4 {
+                                let v = pix[c] as u64;
+                                sum[c] += v;
+                                sum_sq[c] += v * v;
+                            }
+                            count += 1;
+                        }
+                    }
+
+                    let mut var = 0f64;
+                    let mut mean = [0f64; 4];
+                    for c in 0..4 {
+                        let s = sum[c] as f64;
+                        let ss = sum_sq[c] as f64;
+                        mean[c] = s / count as f64;
+                        var += (ss / count as f64) - (mean[c] * mean[c]);
+                    }
+                    var /= 4.0;
+
+                    if var < best_var {
+                        best_var = var;
+                        best_mean = mean;
+                    }
+                }
+
+                let out_pix = Rgba([
+                    best_mean[0].round().clamp(0.0, 255.0) as u8,
+                    best_mean[1].round().clamp(0.0, 255.0) as u8,
+                    best_mean[2].round().clamp(0.0, 255.0) as u8,
+                    best_mean[3].round().clamp(0.0, 255.0) as u8,
+                ]);
+                dst.put_pixel(x, y, out_pix);
+            }
+        }
+
+        dst
+    }
+
+    // Reasonable defaults: r in [1, 5], gamma = 1.2 (more weight to edges)
+    adaptive_kuwahara_filter(&img, 1.0, 1.5, 1.2)
+}
+
+#[cfg(test)]
+mod tests {}
@@ -1,3 +1,11 @@
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as HashMap;
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+#[cfg(not(feature = "std"))]
+use crate::math::F64Ext;
 use crate::{min_heap, vec2::DVec2};
 
 // 2d quadric
@@ -88,7 +96,7 @@ fn plane_from_point_normal(p: &DVec2, n: &DVec2) -> [f64; 3] {
     [n.x, n.y, -p.dot(*n)]
 }
 
-const INVALID: usize = ::std::usize::MAX;
+const INVALID: usize = ::core::usize::MAX;
 
 struct Edge {
     v1: usize,
@@ -113,7 +121,7 @@ fn edge_heap_insert(
     i: usize,
     simplify_threshold_sq: f64,
 ) {
-    use std::f64;
+    use core::f64;
 
     let q1 = &quadrics[e.v1];
     let q2 = &quadrics[e.v2];
@@ -168,7 +176,7 @@ fn edge_heap_update(
     );
 }
 
-const INVALID_CO: DVec2 = DVec2::splat(::std::f64::MAX);
+const INVALID_CO: DVec2 = DVec2::splat(::core::f64::MAX);
 
 fn edge_heap_collapse(
     poly_edit: &mut Vec<DVec2>,
@@ -351,8 +359,367 @@ pub fn poly_simplify(is_cyclic: bool, poly: &Vec<DVec2>, simplify_threshold: f64
     return poly_edit;
 }
 
-pub fn poly_list_simplify(poly_list_src: &mut Vec<(bool, Vec<DVec2>)>, simplify_threshold: f64) {
+/// Douglas–Peucker simplification, keeping the points farther than `epsilon`
+/// from the chord between the kept points around them.
+///
+/// Cyclic polygons always keep their first point and the one farthest from it.
+pub fn poly_simplify_douglas_peucker(is_cyclic: bool, poly: &[DVec2], epsilon: f64) -> Vec<DVec2> {
+    let len = poly.len();
+    let poly_minimum_len = if is_cyclic { 4 } else { 2 };
+    if len <= poly_minimum_len {
+        return poly.to_vec();
+    }
+
+    // Cyclic polygons are closed by their first point, at index `len`.
+    let point = |i: usize| poly[i % len];
+    let mut keep = vec![false; len];
+    let mut ranges = Vec::new();
+    keep[0] = true;
+    if is_cyclic {
+        let farthest = (1..len)
+            .max_by(|a, b| {
+                let (a, b) = (
+                    poly[0].len_squared_with(poly[*a]),
+                    poly[0].len_squared_with(poly[*b]),
+                );
+                a.total_cmp(&b)
+            })
+            .unwrap();
+        keep[farthest] = true;
+        ranges.push((0, farthest));
+        ranges.push((farthest, len));
+    } else {
+        keep[len - 1] = true;
+        ranges.push((0, len - 1));
+    }
+
+    let epsilon_sq = epsilon * epsilon;
+    while let Some((first, last)) = ranges.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| {
+                (
+                    i,
+                    segment_distance_squared(point(i), point(first), point(last)),
+                )
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance_sq)) = farthest {
+            if distance_sq > epsilon_sq {
+                keep[i] = true;
+                ranges.push((first, i));
+                ranges.push((i, last));
+            }
+        }
+    }
+
+    poly.iter()
+        .zip(keep)
+        .filter_map(|(v, keep)| keep.then_some(*v))
+        .collect()
+}
+
+/// Squared distance from `p` to the segment `a`-`b`.
+pub(crate) fn segment_distance_squared(p: DVec2, a: DVec2, b: DVec2) -> f64 {
+    let ab = b.sub(a);
+    let len_sq = ab.len_squared();
+    let t = if len_sq == 0.0 {
+        0.0
+    } else {
+        (p.sub(a).dot(ab) / len_sq).clamp(0.0, 1.0)
+    };
+    p.len_squared_with(a.madd(ab, t))
+}
+
+/// Visvalingam–Whyatt simplification, repeatedly removing the point forming
+/// the smallest triangle with its neighbors while its area is below `epsilon²`.
+///
+/// The area of a point never drops below the area of the points removed before it,
+/// so removing a point can't make its neighbors cheaper to remove.
+pub fn poly_simplify_visvalingam_whyatt(
+    is_cyclic: bool,
+    poly: &[DVec2],
+    epsilon: f64,
+) -> Vec<DVec2> {
+    let len = poly.len();
+    let poly_minimum_len = if is_cyclic { 4 } else { 2 };
+    if len <= poly_minimum_len {
+        return poly.to_vec();
+    }
+
+    let mut prev = (0..len).map(|i| (i + len - 1) % len).collect::<Vec<_>>();
+    let mut next = (0..len).map(|i| (i + 1) % len).collect::<Vec<_>>();
+    let area = |prev: usize, i: usize, next: usize| {
+        let (a, b) = (poly[prev].sub(poly[i]), poly[next].sub(poly[i]));
+        (a.x * b.y - a.y * b.x).abs() * 0.5
+    };
+
+    // Ends of open polygons are never removed.
+    let removable = if is_cyclic { 0..len } else { 1..len - 1 };
+    let mut heap = min_heap::MinHeap::<f64, usize>::with_capacity(len);
+    let mut handles = vec![min_heap::NodeHandle::INVALID; len];
+    for i in removable {
+        handles[i] = heap.insert(area(prev[i], i, next[i]), i);
+    }
+
+    let area_threshold = epsilon * epsilon;
+    let mut keep = vec![true; len];
+    let mut poly_remaining_len = len;
+    while poly_remaining_len > poly_minimum_len {
+        let Some((area_min, i)) = heap
+            .peek()
+            .filter(|(area_min, _)| *area_min < area_threshold)
+        else {
+            break;
+        };
+        heap.pop_min();
+        handles[i] = min_heap::NodeHandle::INVALID;
+        keep[i] = false;
+        poly_remaining_len -= 1;
+
+        let (p, n) = (prev[i], next[i]);
+        next[p] = n;
+        prev[n] = p;
+        for j in [p, n] {
+            if heap.contains(handles[j]) {
+                heap.node_value_update(handles[j], area(prev[j], j, next[j]).max(area_min));
+            }
+        }
+    }
+
+    poly.iter()
+        .zip(keep)
+        .filter_map(|(v, keep)| keep.then_some(*v))
+        .collect()
+}
+
+/// Algorithm reducing the points of the traced outlines before fitting,
+/// see [`Options::simplify_threshold`](crate::structs::Options::simplify_threshold).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Simplifier {
+    /// Collapses edges by their quadric error, moving the remaining points
+    /// to best follow the removed ones.
+    Quadric,
+    /// Douglas–Peucker, keeps the points farther than the threshold
+    /// from the chord between their kept neighbors.
+    DouglasPeucker,
+    /// Visvalingam–Whyatt, removes the points forming the smallest triangles
+    /// with their neighbors while their area is below the threshold squared.
+    VisvalingamWhyatt,
+}
+
+/// Simplifies every polygon of the list with `simplifier`, `simplify_threshold` being its epsilon.
+pub fn poly_list_simplify(
+    poly_list_src: &mut Vec<(bool, Vec<DVec2>)>,
+    simplifier: Simplifier,
+    simplify_threshold: f64,
+) {
+    poly_list_simplify_by(poly_list_src, simplifier, |_, _| simplify_threshold)
+}
+
+/// Simplifies every polygon of the list with `simplifier`,
+/// the epsilon of each being `simplify_threshold(is_cyclic, poly)`.
+pub fn poly_list_simplify_by(
+    poly_list_src: &mut Vec<(bool, Vec<DVec2>)>,
+    simplifier: Simplifier,
+    simplify_threshold: impl Fn(bool, &[DVec2]) -> f64,
+) {
     poly_list_src.iter_mut().for_each(|(is_cyclic, poly_src)| {
-        *poly_src = poly_simplify(*is_cyclic, poly_src, simplify_threshold)
+        let simplify_threshold = simplify_threshold(*is_cyclic, poly_src);
+        *poly_src = match simplifier {
+            Simplifier::Quadric => poly_simplify(*is_cyclic, poly_src, simplify_threshold),
+            Simplifier::DouglasPeucker => {
+                poly_simplify_douglas_peucker(*is_cyclic, poly_src, simplify_threshold)
+            }
+            Simplifier::VisvalingamWhyatt => {
+                poly_simplify_visvalingam_whyatt(*is_cyclic, poly_src, simplify_threshold)
+            }
+        }
     })
 }
+
+/// Polygons of several lists split into the boundaries between their regions,
+/// see [`shared_boundaries`].
+pub struct SharedBoundaries {
+    /// `(is_cyclic, points)` of every boundary, open boundaries run from junction to junction.
+    pub boundaries: Vec<(bool, Vec<DVec2>)>,
+    /// For every polygon of every list, `(is_cyclic, boundaries)` in order.
+    pub polygons: Vec<Vec<(bool, Vec<BoundaryRef>)>>,
+}
+
+/// `(boundary index, is_reversed)` of a boundary along a polygon.
+pub type BoundaryRef = (usize, bool);
+
+fn vertex_key(v: &DVec2) -> [u64; 2] {
+    // Adding zero turns -0.0 into 0.0.
+    [(v.x + 0.0).to_bits(), (v.y + 0.0).to_bits()]
+}
+
+/// Splits polygons that tile the plane (e.g. the pixel outlines of every color layer)
+/// into boundaries, each boundary between two regions is stored once and referenced
+/// by the polygons on both sides, so simplifying or fitting the boundaries
+/// instead of the polygons keeps them from overlapping or leaving gaps.
+///
+/// Boundaries are split at junctions, vertices where more than two edges meet,
+/// which requires neighboring polygons to share their vertices:
+/// edges are first split into unit steps, as pixel outlines are on the integer grid.
+pub fn shared_boundaries(poly_lists: &[Vec<(bool, Vec<DVec2>)>]) -> SharedBoundaries {
+    let poly_lists = poly_lists
+        .iter()
+        .map(|poly_list| {
+            poly_list
+                .iter()
+                .map(|(is_cyclic, poly)| (*is_cyclic, poly_split_unit(*is_cyclic, poly)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Undirected neighbors of every vertex, edges shared by two polygons count once.
+    let mut neighbors: HashMap<[u64; 2], Vec<[u64; 2]>> = HashMap::new();
+    for (is_cyclic, poly) in poly_lists.iter().flatten() {
+        let len = poly.len();
+        let edges = if *is_cyclic {
+            len
+        } else {
+            len.saturating_sub(1)
+        };
+        for i in 0..edges {
+            let (a, b) = (vertex_key(&poly[i]), vertex_key(&poly[(i + 1) % len]));
+            for (v, other) in [(a, b), (b, a)] {
+                let v_neighbors = neighbors.entry(v).or_default();
+                if !v_neighbors.contains(&other) {
+                    v_neighbors.push(other);
+                }
+            }
+        }
+    }
+    let is_junction = |v: &DVec2| neighbors[&vertex_key(v)].len() != 2;
+
+    let mut boundaries = BoundaryMap::default();
+
+    let polygons = poly_lists
+        .into_iter()
+        .map(|poly_list| {
+            poly_list
+                .into_iter()
+                .map(|(is_cyclic, mut poly)| {
+                    let len = poly.len();
+                    if len == 0 {
+                        return (is_cyclic, Vec::new());
+                    }
+                    let mut splits = (0..len)
+                        .filter(|i| is_junction(&poly[*i]))
+                        .collect::<Vec<usize>>();
+                    if !is_cyclic {
+                        splits.retain(|i| *i != 0 && *i != len - 1);
+                        splits.insert(0, 0);
+                        splits.push(len - 1);
+                    } else if splits.is_empty() {
+                        let mut refs = Vec::new();
+                        boundaries.add(true, poly, &mut refs);
+                        return (true, refs);
+                    } else {
+                        // Start at a junction, ending at it again.
+                        poly.rotate_left(splits[0]);
+                        poly.push(poly[0]);
+                        let start = splits[0];
+                        splits.iter_mut().for_each(|i| *i -= start);
+                        splits.push(len);
+                    }
+                    let mut refs = Vec::new();
+                    for w in splits.windows(2) {
+                        boundaries.add(false, poly[w[0]..=w[1]].to_vec(), &mut refs);
+                    }
+                    (is_cyclic, refs)
+                })
+                .collect()
+        })
+        .collect();
+
+    SharedBoundaries {
+        boundaries: boundaries.boundaries,
+        polygons,
+    }
+}
+
+/// Boundaries by their vertices, in the same order from both sides.
+#[derive(Default)]
+struct BoundaryMap {
+    boundaries: Vec<(bool, Vec<DVec2>)>,
+    indices: HashMap<Vec<[u64; 2]>, usize>,
+}
+
+impl BoundaryMap {
+    /// Pushes the `(index, is_reversed)` of the boundary along `points` to `refs`,
+    /// adding it when the other side didn't.
+    fn add(&mut self, is_cyclic: bool, mut points: Vec<DVec2>, refs: &mut Vec<BoundaryRef>) {
+        let mut keys = points.iter().map(vertex_key).collect::<Vec<_>>();
+        let mut keys_rev = keys.clone();
+        keys_rev.reverse();
+        let mut start = 0;
+        if is_cyclic {
+            // Both sides of a ring start at its smallest vertex.
+            start = (0..keys.len()).min_by_key(|i| keys[*i]).unwrap();
+            keys.rotate_left(start);
+            keys_rev.rotate_left(keys.len() - 1 - start);
+        }
+        let is_reversed = keys_rev < keys;
+
+        // A loop from a junction back to it could be simplified away,
+        // split it in two halves, in the same place from both sides.
+        if !is_cyclic && points.len() > 2 && keys[0] == keys[keys.len() - 1] {
+            if is_reversed {
+                points.reverse();
+            }
+            let mid = points.len() / 2;
+            let mut halves = [points[..=mid].to_vec(), points[mid..].to_vec()];
+            if is_reversed {
+                halves.reverse();
+                halves.iter_mut().for_each(|half| half.reverse());
+            }
+            for half in halves {
+                self.add(false, half, refs);
+            }
+            return;
+        }
+
+        let key = if is_reversed { keys_rev } else { keys };
+        let index = *self.indices.entry(key).or_insert_with(|| {
+            points.rotate_left(start);
+            match (is_cyclic, is_reversed) {
+                (true, true) => points[1..].reverse(),
+                (false, true) => points.reverse(),
+                _ => {}
+            }
+            self.boundaries.push((is_cyclic, points));
+            self.boundaries.len() - 1
+        });
+        refs.push((index, is_reversed));
+    }
+}
+
+/// Splits the edges of a polygon into steps of about unit length.
+fn poly_split_unit(is_cyclic: bool, poly: &[DVec2]) -> Vec<DVec2> {
+    let len = poly.len();
+    let edges = if is_cyclic {
+        len
+    } else {
+        len.saturating_sub(1)
+    };
+    let mut poly_dst = Vec::with_capacity(len * 2);
+    for i in 0..edges {
+        let (a, b) = (poly[i], poly[(i + 1) % len]);
+        let steps = a.len_with(b).round().max(1.0) as usize;
+        // Multiplying before dividing keeps integer steps exact.
+        poly_dst.extend((0..steps).map(|step| {
+            let t = step as f64;
+            DVec2::new(
+                a.x + (b.x - a.x) * t / steps as f64,
+                a.y + (b.y - a.y) * t / steps as f64,
+            )
+        }));
+    }
+    if !is_cyclic {
+        poly_dst.extend(poly.last());
+    }
+    poly_dst
+}
@@ -64,12 +64,15 @@ that this copyright notice remain intact.
 //!
 //! ```
 //! let data = vec![0; 40];
-//! let nq = color_quant::NeuQuant::new(10, 256, &data);
+//! let nq = svgen::quantizer::NeuQuant::new(10, 256, &data);
 //! let indixes: Vec<u8> = data.chunks(4).map(|pix| nq.index_of(pix) as u8).collect();
 //! let color_map = nq.color_map_rgba();
 //! ```
 
-use std::cmp::{max, min};
+use std::{
+    cmp::{max, min},
+    collections::HashMap,
+};
 
 const CHANNELS: usize = 4;
 
@@ -120,6 +123,8 @@ pub struct NeuQuant {
     freq: Vec<f64>,
     samplefac: i32,
     netsize: usize,
+    learning_cycles: usize,
+    seed: u64,
 }
 
 impl NeuQuant {
@@ -131,6 +136,38 @@ impl NeuQuant {
     /// range $[1, 30]$. A value of $1$ thus produces the best result but is also
     /// slowest. $10$ is a good compromise between speed and quality.
     pub fn new(samplefac: i32, colors: usize, pixels: &[u8]) -> Self {
+        Self::with_learning_cycles(samplefac, colors, Self::learning_cycles(colors), pixels)
+    }
+
+    /// Default number of learning cycles for a palette of `colors`,
+    /// half the palette size but at least 100.
+    pub fn learning_cycles(colors: usize) -> usize {
+        (colors >> 1).max(100)
+    }
+
+    /// Like [`Self::new`] with the number of `learning_cycles` instead of the default,
+    /// which is half the palette size but at least 100.
+    ///
+    /// The learning rate and radius decrease after every cycle,
+    /// more cycles spread the sampled pixels more evenly over the decay.
+    pub fn with_learning_cycles(
+        samplefac: i32,
+        colors: usize,
+        learning_cycles: usize,
+        pixels: &[u8],
+    ) -> Self {
+        Self::with_seed(samplefac, colors, learning_cycles, 0, pixels)
+    }
+
+    /// Like [`Self::with_learning_cycles`], sampling the pixels from a position
+    /// picked from `seed` instead of the first pixel.
+    pub fn with_seed(
+        samplefac: i32,
+        colors: usize,
+        learning_cycles: usize,
+        seed: u64,
+        pixels: &[u8],
+    ) -> Self {
         let netsize = colors;
         let mut this = NeuQuant {
             network: Vec::with_capacity(netsize),
@@ -140,6 +177,8 @@ impl NeuQuant {
             freq: Vec::with_capacity(netsize),
             samplefac: samplefac,
             netsize: colors,
+            learning_cycles: learning_cycles.max(1),
+            seed,
         };
         this.init(pixels);
         this
@@ -333,10 +372,7 @@ impl NeuQuant {
         let lengthcount = pixels.len() / CHANNELS;
         let samplepixels = lengthcount / self.samplefac as usize;
         // learning cycles
-        let n_cycles = match self.netsize >> 1 {
-            n if n <= 100 => 100,
-            n => n,
-        };
+        let n_cycles = self.learning_cycles;
         let delta = match samplepixels / n_cycles {
             0 => 1,
             n => n,
@@ -348,7 +384,10 @@ impl NeuQuant {
             rad = 0
         };
 
-        let mut pos = 0;
+        let mut pos = match lengthcount {
+            0 => 0,
+            n => (self.seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) % n as u64) as usize,
+        };
         let step = *PRIMES
             .iter()
             .find(|&&prime| lengthcount % prime != 0)
@@ -505,3 +544,181 @@ impl NeuQuant {
         best_pos
     }
 }
+
+/// Common interface of the quantizers, mapping pixels to the colors of their palette.
+pub trait Quantize {
+    /// Finds the best-matching index in the color map.
+    ///
+    /// `pixel` is assumed to be in RGBA format.
+    fn index_of(&self, pixel: &[u8]) -> usize;
+
+    /// Returns the RGBA color map.
+    fn color_map_rgba(&self) -> Vec<u8>;
+}
+
+impl Quantize for NeuQuant {
+    fn index_of(&self, pixel: &[u8]) -> usize {
+        NeuQuant::index_of(self, pixel)
+    }
+
+    fn color_map_rgba(&self) -> Vec<u8> {
+        NeuQuant::color_map_rgba(self)
+    }
+}
+
+/// Median cut quantizer, in its variance based variant (Wan, Wong & Prusinkiewicz, 1988).
+///
+/// Repeatedly splits the box of colors with the largest squared error along its channel
+/// of largest variance, at the cut minimizing the squared error of both halves,
+/// every box giving the mean color of its pixels.
+/// Unlike [`NeuQuant`] it is deterministic and suited to small palettes,
+/// images with at most `colors` distinct colors keep them exactly.
+pub struct MedianCut {
+    colormap: Vec<[u8; 4]>,
+}
+
+/// Distinct colors with their pixel counts.
+type ColorBox = Vec<([u8; 4], usize)>;
+
+/// Pixel count, sums and sums of squares of the channels of a set of colors.
+#[derive(Copy, Clone, Default)]
+struct Moments {
+    count: f64,
+    sum: [f64; 4],
+    sum_sq: [f64; 4],
+}
+
+impl Moments {
+    fn add(&mut self, color: &[u8; 4], count: usize) {
+        let count = count as f64;
+        self.count += count;
+        for (channel, c) in color.iter().enumerate() {
+            let c = *c as f64;
+            self.sum[channel] += c * count;
+            self.sum_sq[channel] += c * c * count;
+        }
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        Moments {
+            count: self.count - other.count,
+            sum: [0, 1, 2, 3].map(|channel| self.sum[channel] - other.sum[channel]),
+            sum_sq: [0, 1, 2, 3].map(|channel| self.sum_sq[channel] - other.sum_sq[channel]),
+        }
+    }
+
+    /// Squared error of a channel around its mean.
+    fn channel_error(&self, channel: usize) -> f64 {
+        if self.count == 0.0 {
+            return 0.0;
+        }
+        self.sum_sq[channel] - self.sum[channel] * self.sum[channel] / self.count
+    }
+
+    fn error(&self) -> f64 {
+        (0..CHANNELS)
+            .map(|channel| self.channel_error(channel))
+            .sum()
+    }
+
+    fn of(color_box: &[([u8; 4], usize)]) -> Self {
+        let mut moments = Moments::default();
+        for (color, count) in color_box {
+            moments.add(color, *count);
+        }
+        moments
+    }
+}
+
+impl MedianCut {
+    /// Builds a palette of at most `colors` colors from RGBA `pixels`.
+    pub fn new(colors: usize, pixels: &[u8]) -> Self {
+        let mut hist: HashMap<[u8; 4], usize> = HashMap::new();
+        for p in pixels.chunks_exact(CHANNELS) {
+            *hist.entry([p[0], p[1], p[2], p[3]]).or_default() += 1;
+        }
+        let mut entries: ColorBox = hist.into_iter().collect();
+        // The histogram order is arbitrary.
+        entries.sort_unstable();
+
+        let mut boxes: Vec<(ColorBox, Moments)> = Vec::with_capacity(colors);
+        if !entries.is_empty() {
+            let moments = Moments::of(&entries);
+            boxes.push((entries, moments));
+        }
+        while boxes.len() < colors {
+            let Some(index) = (0..boxes.len())
+                .filter(|i| boxes[*i].0.len() > 1)
+                .max_by(|a, b| boxes[*a].1.error().total_cmp(&boxes[*b].1.error()))
+            else {
+                break;
+            };
+
+            let (mut color_box, moments) = boxes.swap_remove(index);
+            let channel = (0..CHANNELS)
+                .max_by(|a, b| {
+                    moments
+                        .channel_error(*a)
+                        .total_cmp(&moments.channel_error(*b))
+                })
+                .unwrap();
+            color_box.sort_by_key(|(color, _)| color[channel]);
+
+            // Cut between distinct channel values, minimizing the error of both halves.
+            let mut lower = Moments::default();
+            let mut best = (f64::INFINITY, 1);
+            for i in 1..color_box.len() {
+                let (color, count) = &color_box[i - 1];
+                lower.add(color, *count);
+                if color[channel] == color_box[i].0[channel] {
+                    continue;
+                }
+                let error = lower.error() + moments.sub(&lower).error();
+                if error < best.0 {
+                    best = (error, i);
+                }
+            }
+            let upper = color_box.split_off(best.1);
+            let upper_moments = Moments::of(&upper);
+            boxes.push((color_box, moments.sub(&upper_moments)));
+            boxes.push((upper, upper_moments));
+        }
+
+        let colormap = boxes
+            .iter()
+            .map(|(_, moments)| {
+                [0, 1, 2, 3].map(|channel| {
+                    (moments.sum[channel] / moments.count)
+                        .round()
+                        .clamp(0.0, 255.0) as u8
+                })
+            })
+            .collect();
+
+        MedianCut { colormap }
+    }
+
+    /// Lookup pixel values for color at `idx` in the colormap.
+    pub fn lookup(&self, idx: usize) -> Option<[u8; 4]> {
+        self.colormap.get(idx).copied()
+    }
+}
+
+impl Quantize for MedianCut {
+    fn index_of(&self, pixel: &[u8]) -> usize {
+        assert!(pixel.len() == 4);
+        (0..self.colormap.len())
+            .min_by_key(|i| {
+                self.colormap[*i]
+                    .iter()
+                    .zip(pixel)
+                    .map(|(a, b)| (*a as i32 - *b as i32).pow(2))
+                    .sum::<i32>()
+            })
+            .unwrap_or(0)
+    }
+
+    fn color_map_rgba(&self) -> Vec<u8> {
+        self.colormap.concat()
+    }
+}
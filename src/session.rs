@@ -0,0 +1,536 @@
+//! Resumable tracing pipeline for interactive parameter tuning.
+
+use image::{
+    imageops::{crop_imm, replace},
+    RgbaImage,
+};
+
+use crate::{
+    apply_stencil, assemble_result, build_masks, build_result, decode_image,
+    descreen::descreen as descreen_image,
+    error::SvgenError,
+    filter_image, fit_layers, fit_outline,
+    mask::BitMask,
+    output, prepare_image, remove_background, snap_to_palette,
+    structs::{
+        BackgroundRemoval, BorderContours, BoundingBox, ColorMode, Mode, Options, Point, Threshold,
+        TurnPolicy, VectorizationResult,
+    },
+    trace_outline, Contours, Mask, Outline, PreparedImage,
+};
+
+/// Vertices around a changed mask pixel whose edges or turns may change,
+/// the turn policies look up to 4 pixels around a vertex.
+const TRACE_MARGIN: usize = 5;
+
+/// `[x0, y0, x1, y1]` pixel rectangle, excluding `x1` and `y1`.
+type Rect = [usize; 4];
+
+/// Keeps the intermediate stages of the pipeline
+/// (prepared image → masks → outlines → curves → SVG),
+/// so changing [`Options`] only re-runs the stages depending on what changed.
+///
+/// E.g. moving [`Options::error_threshold`] re-fits the cached outlines
+/// without decoding, filtering or tracing the image again.
+///
+/// Painting onto the image with [`VectorizationSession::update_region`]
+/// only re-traces and re-fits the contours around the painted pixels.
+pub struct VectorizationSession {
+    image: RgbaImage,
+    fixed_palette: Option<Vec<[u8; 4]>>,
+    options: Options,
+    prepared: Option<PreparedImage>,
+    masks: Option<Vec<Mask>>,
+    outlines: Option<Vec<Outline>>,
+    /// Fitted outlines of every layer, before the offsets and transforms of the result.
+    contours: Option<Vec<Contours>>,
+    result: Option<VectorizationResult>,
+    svg: Option<String>,
+}
+
+impl VectorizationSession {
+    /// Decodes the image, indexed images keep their exact palette as in [`crate::vectorize`].
+    ///
+    /// # Panics
+    ///
+    /// When the input can't be decoded, see [`VectorizationSession::try_new`].
+    pub fn new(image_byte: &[u8], options: Options) -> Self {
+        Self::try_new(image_byte, options).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Like [`VectorizationSession::new`], returning an error for inputs that can't be decoded.
+    pub fn try_new(image_byte: &[u8], options: Options) -> Result<Self, SvgenError> {
+        let (image, fixed_palette) = decode_image(image_byte, &options)?;
+        Ok(Self::with_palette(image, fixed_palette, options))
+    }
+
+    /// Starts from already decoded pixels, as in [`crate::vectorize_image`].
+    pub fn from_image(image: RgbaImage, options: Options) -> Self {
+        Self::with_palette(image, None, options)
+    }
+
+    fn with_palette(
+        image: RgbaImage,
+        fixed_palette: Option<Vec<[u8; 4]>>,
+        options: Options,
+    ) -> Self {
+        Self {
+            image,
+            fixed_palette,
+            options,
+            prepared: None,
+            masks: None,
+            outlines: None,
+            contours: None,
+            result: None,
+            svg: None,
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// The decoded source pixels, with the updates of [`VectorizationSession::update_region`].
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Replaces the options, dropping the cached stages affected by the change
+    /// and every stage after them.
+    pub fn set_options(&mut self, options: Options) {
+        let old = std::mem::replace(&mut self.options, options);
+        let new = &self.options;
+
+        let mut is_stale = old.region != new.region
+            || old.descreen != new.descreen
+            || old.stencil != new.stencil
+            || old.background != new.background
+            || old.mode != new.mode
+            || old.preprocess != new.preprocess
+            || old.supersample != new.supersample
+            || old.resample_filter != new.resample_filter
+            || old.sharpen != new.sharpen
+            || old.color_mode != new.color_mode
+            || old.quantizer != new.quantizer
+            || old.palette != new.palette
+            || old.seed != new.seed
+            || old.despeckle.blur_sigma != new.despeckle.blur_sigma
+            || old.despeckle.merge_area != new.despeckle.merge_area;
+        if is_stale {
+            self.prepared = None;
+        }
+        is_stale |= old.threshold != new.threshold
+            || old.separation != new.separation
+            || old.transparent_layer != new.transparent_layer
+            || old.clip_layers != new.clip_layers
+            || old.despeckle != new.despeckle
+            || old.gradients != new.gradients;
+        if is_stale {
+            self.masks = None;
+        }
+        is_stale |= old.outline_turn_policy() != new.outline_turn_policy();
+        is_stale |= old.seed != new.seed && new.outline_turn_policy() == TurnPolicy::Random;
+        if is_stale {
+            self.outlines = None;
+        }
+        is_stale |= old.error_threshold != new.error_threshold
+            || old.smoothness != new.smoothness
+            || old.simplifier != new.simplifier
+            || old.simplify_threshold != new.simplify_threshold
+            || old.adaptive_simplify != new.adaptive_simplify
+            || old.line_fitting != new.line_fitting
+            || old.ensure_simple_paths != new.ensure_simple_paths
+            || old.color_overrides != new.color_overrides
+            || old.corners != new.corners
+            || old.border_contours != new.border_contours
+            || old.shared_boundaries != new.shared_boundaries
+            || old.pixel_scale != new.pixel_scale;
+        if is_stale {
+            self.contours = None;
+        }
+        is_stale |= old.overlap != new.overlap
+            || old.offset != new.offset
+            || old.orientation != new.orientation
+            || old.grid != new.grid
+            || old.auto_crop != new.auto_crop
+            || old.transform != new.transform;
+        if is_stale {
+            self.result = None;
+        }
+        is_stale |= old != *new;
+        if is_stale {
+            self.svg = None;
+        }
+    }
+
+    /// The traced result, running the stages that are not cached.
+    pub fn result(&mut self) -> &VectorizationResult {
+        if self.result.is_none() {
+            self.trace();
+            let (prepared, masks) = (
+                self.prepared.as_ref().unwrap(),
+                self.masks.as_ref().unwrap(),
+            );
+            let contours = self.contours.get_or_insert_with(|| {
+                fit_layers(
+                    prepared,
+                    masks,
+                    self.outlines.clone().unwrap(),
+                    &self.options,
+                )
+            });
+            self.result = Some(assemble_result(
+                prepared,
+                masks,
+                contours.clone(),
+                &self.options,
+            ));
+        }
+        self.result.as_ref().unwrap()
+    }
+
+    /// Fits the cached outlines at every error threshold, sharing the palette and the masks,
+    /// from the least to the most detailed when thresholds are decreasing.
+    ///
+    /// Error thresholds set by [`Options::color_overrides`] take precedence.
+    pub fn levels(&mut self, error_thresholds: &[f64]) -> Vec<VectorizationResult> {
+        error_thresholds
+            .iter()
+            .map(|error_threshold| self.fit(*error_threshold))
+            .collect()
+    }
+
+    /// Runs the stages up to the outlines that are not cached.
+    fn trace(&mut self) {
+        let options = &self.options;
+        let prepared = self.prepared.get_or_insert_with(|| {
+            prepare_image(&self.image, self.fixed_palette.as_deref(), options)
+        });
+        let masks = self
+            .masks
+            .get_or_insert_with(|| build_masks(prepared, options));
+        self.outlines.get_or_insert_with(|| {
+            masks
+                .iter()
+                .map(|mask| trace_outline(&mask.pixels, options))
+                .collect()
+        });
+    }
+
+    fn fit(&mut self, error_threshold: f64) -> VectorizationResult {
+        self.trace();
+        let options = Options {
+            error_threshold,
+            ..self.options.clone()
+        };
+        build_result(
+            self.prepared.as_ref().unwrap(),
+            self.masks.as_ref().unwrap(),
+            self.outlines.clone().unwrap(),
+            &options,
+        )
+    }
+
+    /// Replaces the pixels at `[x, y]` with `pixels`, clipped to the image,
+    /// then updates the cached stages around them only: the prepared pixels and masks
+    /// within reach of the image filters, and the contours of every layer reaching them,
+    /// which are traced and fitted again and spliced into the cached ones.
+    ///
+    /// The palette is kept, painted pixels take the nearest palette color,
+    /// and the edge preserving filter of [`Options::preprocess`] only sees the pixels
+    /// around them. Changing the options re-runs the affected stages on the whole image.
+    ///
+    /// Stages depending on the whole image are run again from scratch instead:
+    /// with [`Options::region`], [`BackgroundRemoval::Custom`], [`Options::gradients`],
+    /// a merge area, [`Threshold::Otsu`] or [`TurnPolicy::Random`].
+    pub fn update_region(&mut self, x: u32, y: u32, pixels: &RgbaImage) {
+        replace(&mut self.image, pixels, x as i64, y as i64);
+        let (width, height) = self.image.dimensions();
+        let dirty = [
+            x.min(width),
+            y.min(height),
+            x.saturating_add(pixels.width()).min(width),
+            y.saturating_add(pixels.height()).min(height),
+        ]
+        .map(|v| v as usize);
+        if dirty[0] == dirty[2] || dirty[1] == dirty[3] {
+            return;
+        }
+        self.result = None;
+        self.svg = None;
+
+        if !self.is_patchable() {
+            self.prepared = None;
+            self.masks = None;
+            self.outlines = None;
+            self.contours = None;
+            return;
+        }
+        let options = &self.options;
+        let has_fixed_palette = self.fixed_palette.is_some();
+        let Some(prepared) = &mut self.prepared else {
+            return;
+        };
+        let changed = patch_prepared(&self.image, prepared, dirty, has_fixed_palette, options);
+        let Some(masks) = &mut self.masks else {
+            return;
+        };
+        let changed = patch_masks(prepared, masks, changed, options);
+        let Some(outlines) = &mut self.outlines else {
+            return;
+        };
+
+        // Fitted contours are spliced when each outline contour is fitted on its own.
+        let is_per_contour = !(options.shared_boundaries && options.mode != Mode::PixelPerfect)
+            && (options.border_contours == BorderContours::Closed
+                || options.mode == Mode::PixelPerfect);
+        if !is_per_contour {
+            self.contours = None;
+        }
+        let size = prepared.size();
+        for (layer, (mask, outline)) in masks.iter().zip(outlines.iter_mut()).enumerate() {
+            let (removed, added) = patch_outline(outline, &mask.pixels, changed, options);
+            if let Some(contours) = &mut self.contours {
+                let (polygons, curves) = &mut contours[layer];
+                remove_indices(polygons, &removed);
+                remove_indices(curves, &removed);
+                let (new_polygons, new_curves) = fit_outline(added, mask.color, &size, options);
+                polygons.extend(new_polygons);
+                curves.extend(new_curves);
+            }
+        }
+    }
+
+    /// Whether every stage only depends on the pixels around each pixel,
+    /// see [`VectorizationSession::update_region`].
+    fn is_patchable(&self) -> bool {
+        let options = &self.options;
+        options.region.is_none()
+            && !matches!(options.background, Some(BackgroundRemoval::Custom(_)))
+            && options.gradients.is_none()
+            && options.despeckle.merge_area == 0
+            && options.outline_turn_policy() != TurnPolicy::Random
+            && !(options.color_mode == ColorMode::Black && options.threshold == Threshold::Otsu)
+    }
+
+    /// The traced result as an SVG document.
+    pub fn svg(&mut self) -> &str {
+        if self.svg.is_none() {
+            self.result();
+            let result = self.result.as_ref().unwrap();
+            self.svg = Some(output::svg::to_svg(result, &self.options));
+        }
+        self.svg.as_deref().unwrap()
+    }
+}
+
+/// `rect` grown by `margin` on every side, clipped to `size`.
+fn expand(rect: Rect, margin: usize, size: [usize; 2]) -> Rect {
+    [
+        rect[0].saturating_sub(margin),
+        rect[1].saturating_sub(margin),
+        (rect[2] + margin).min(size[0]),
+        (rect[3] + margin).min(size[1]),
+    ]
+}
+
+/// Source pixels around a pixel whose prepared pixels depend on it,
+/// through the descreening window, the windows of the edge preserving filter, the blur,
+/// the upscaling kernel and the unsharp mask.
+fn filter_margin(options: &Options) -> usize {
+    let sharpen = options
+        .sharpen
+        .map_or(0.0, |sharpen| sharpen.sigma.max(0.0));
+    let descreen = options
+        .descreen
+        .map_or(0, |descreen| descreen.cell_size as usize / 2);
+    descreen
+        + 3
+        + (3.0 * options.despeckle.blur_sigma.max(0.0)).ceil() as usize
+        + 3
+        + (3.0 * sharpen).ceil() as usize
+}
+
+/// Traced pixels around a prepared pixel whose mask pixels depend on it,
+/// through the despeckle filters and the local thresholds.
+fn mask_margin(options: &Options, scale: u32) -> usize {
+    let despeckle = &options.despeckle;
+    let filters =
+        (despeckle.median_radius + 2 * despeckle.open_radius + 2 * despeckle.close_radius) * scale;
+    let threshold = match options.threshold {
+        Threshold::Mean { radius, .. } | Threshold::Sauvola { radius, .. } => radius,
+        Threshold::Fixed(_) | Threshold::Otsu => 0,
+    };
+    (filters + threshold) as usize
+}
+
+/// Prepares the source pixels around the `dirty` source rectangle again,
+/// returning the rectangle of prepared pixels that may have changed, in traced pixels.
+fn patch_prepared(
+    image: &RgbaImage,
+    prepared: &mut PreparedImage,
+    dirty: Rect,
+    has_fixed_palette: bool,
+    options: &Options,
+) -> Rect {
+    let size = [image.width() as usize, image.height() as usize];
+    let margin = filter_margin(options);
+    // Pixels within the margin of the changed ones are prepared from pixels within the margin of them.
+    let inner = expand(dirty, margin, size);
+    let outer = expand(inner, margin, size);
+    let mut crop = crop_imm(
+        image,
+        outer[0] as u32,
+        outer[1] as u32,
+        (outer[2] - outer[0]) as u32,
+        (outer[3] - outer[1]) as u32,
+    )
+    .to_image();
+    if let Some(background) = &options.background {
+        crop = remove_background(&crop, background);
+    }
+    if let Some(stencil) = &options.stencil {
+        let origin = [outer[0] as u32, outer[1] as u32];
+        apply_stencil(&mut crop, origin, [image.width(), image.height()], stencil);
+    }
+    if let (Some(filter), Some(descreen)) = (prepared.descreen_filter, options.descreen) {
+        crop = descreen_image(&crop, filter, descreen.cell_size);
+    }
+    let scale = prepared.scale_factor as usize;
+    let mut filtered = filter_image(&crop, has_fixed_palette, prepared.scale_factor, options);
+    if !prepared.palette.is_empty() {
+        snap_to_palette(&mut filtered, &prepared.palette);
+    }
+
+    let [x0, y0, x1, y1] = inner.map(|v| v * scale);
+    let (dx, dy) = (outer[0] * scale, outer[1] * scale);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let pixel = *filtered.get_pixel((x - dx) as u32, (y - dy) as u32);
+            prepared.image.put_pixel(x as u32, y as u32, pixel);
+        }
+    }
+    [x0, y0, x1, y1]
+}
+
+/// Builds the masks around the `changed` prepared pixels again,
+/// returning the rectangle of mask pixels that may have changed.
+fn patch_masks(
+    prepared: &PreparedImage,
+    masks: &mut [Mask],
+    changed: Rect,
+    options: &Options,
+) -> Rect {
+    let size = prepared.size();
+    let margin = mask_margin(options, prepared.scale_factor);
+    let inner = expand(changed, margin, size);
+    let outer = expand(inner, margin, size);
+    let crop = PreparedImage {
+        image: crop_imm(
+            &prepared.image,
+            outer[0] as u32,
+            outer[1] as u32,
+            (outer[2] - outer[0]) as u32,
+            (outer[3] - outer[1]) as u32,
+        )
+        .to_image(),
+        palette: prepared.palette.clone(),
+        ..*prepared
+    };
+    for (mask, crop_mask) in masks.iter_mut().zip(build_masks(&crop, options)) {
+        for y in inner[1]..inner[3] {
+            for x in inner[0]..inner[2] {
+                let is_filled = crop_mask.pixels.get(x - outer[0], y - outer[1]);
+                mask.pixels.set(x, y, is_filled);
+            }
+        }
+    }
+    inner
+}
+
+/// Removes the contours of `outline` reaching the `changed` mask pixels,
+/// and traces the contours of `mask` reaching them again.
+///
+/// Returns the indices of the removed contours, the new ones are appended
+/// to `outline` and returned.
+fn patch_outline(
+    outline: &mut Outline,
+    mask: &BitMask,
+    changed: Rect,
+    options: &Options,
+) -> (Vec<usize>, Outline) {
+    let [width, height] = mask.size();
+    let reach = BoundingBox {
+        min: Point::new(
+            changed[0] as f64 - TRACE_MARGIN as f64,
+            changed[1] as f64 - TRACE_MARGIN as f64,
+        ),
+        max: Point::new(
+            (changed[2] + TRACE_MARGIN) as f64,
+            (changed[3] + TRACE_MARGIN) as f64,
+        ),
+    };
+    let reaches = |points: &[Point]| {
+        BoundingBox::from_points(points.iter().copied()).is_some_and(|bbox| bbox.intersects(&reach))
+    };
+
+    // New contours reaching the changed pixels only follow their old contours elsewhere,
+    // they fit in the bounds of these along with the changed pixels.
+    let mut removed = Vec::new();
+    let mut bounds = reach;
+    for (index, (_is_cyclic, points)) in outline.iter().enumerate() {
+        if reaches(points) {
+            removed.push(index);
+            bounds = bounds.union(&BoundingBox::from_points(points.iter().copied()).unwrap());
+        }
+    }
+
+    // Trace a crop leaving room for the turn policies around the bounds.
+    let clamp = |v: f64, max: usize| (v.max(0.0) as usize).min(max);
+    let crop = [
+        clamp(bounds.min.x.floor() - TRACE_MARGIN as f64, width),
+        clamp(bounds.min.y.floor() - TRACE_MARGIN as f64, height),
+        clamp(bounds.max.x.ceil() + TRACE_MARGIN as f64, width),
+        clamp(bounds.max.y.ceil() + TRACE_MARGIN as f64, height),
+    ];
+    let crop_mask = BitMask::from_bits(
+        [crop[2] - crop[0], crop[3] - crop[1]],
+        (crop[1]..crop[3]).flat_map(|y| (crop[0]..crop[2]).map(move |x| mask.get(x, y))),
+    );
+    let [crop_width, crop_height] = crop_mask.size().map(|v| v as f64);
+    // Contours along the sides of the crop inside the image are cut by it.
+    let is_cut = |p: &Point| {
+        (p.x == 0.0 && crop[0] > 0)
+            || (p.y == 0.0 && crop[1] > 0)
+            || (p.x == crop_width && crop[2] < width)
+            || (p.y == crop_height && crop[3] < height)
+    };
+    let offset = Point::new(crop[0] as f64, crop[1] as f64);
+    let added = trace_outline(&crop_mask, options)
+        .into_iter()
+        .filter(|(_is_cyclic, points)| !points.iter().any(is_cut))
+        .map(|(is_cyclic, points)| {
+            (
+                is_cyclic,
+                points.into_iter().map(|p| p.add(offset)).collect(),
+            )
+        })
+        .filter(|(_is_cyclic, points): &(bool, Vec<Point>)| reaches(points))
+        .collect::<Outline>();
+
+    remove_indices(outline, &removed);
+    outline.extend(added.iter().cloned());
+    (removed, added)
+}
+
+/// Removes the items at the sorted `indices`, keeping the order of the others.
+fn remove_indices<T>(items: &mut Vec<T>, indices: &[usize]) {
+    let mut indices = indices.iter().peekable();
+    let mut index = 0;
+    items.retain(|_| {
+        let is_removed = indices.next_if_eq(&&index).is_some();
+        index += 1;
+        !is_removed
+    });
+}
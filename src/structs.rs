@@ -1,16 +1,1651 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use image::{GrayImage, RgbaImage};
 use wasm_bindgen::prelude::*;
 
-#[derive(Copy, Clone)]
+pub use crate::{
+    curve_fit_nd::{CornerOptions, CubicBezier},
+    polygon_simplifier::Simplifier,
+};
+use crate::{
+    path_optimizer::cubic_flatten,
+    utils::{curve_fit_errors, curve_segments},
+    vec2::DVec2,
+};
+
+/// How the outline tracer resolves ambiguous (diagonal) pixel configurations,
+/// following Potrace's turn policies.
+///
+/// Connecting filled pixels traces filled regions as 8-connected and empty ones as
+/// 4-connected, connecting empty pixels the other way around. Checkerboards of any
+/// size are traced with every policy.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum TurnPolicy {
+    /// Connect filled pixels.
     Black,
+    /// Connect empty pixels.
     White,
+    /// Connect the color most common in the surrounding area.
     Majority,
+    /// Connect the color least common in the surrounding area.
     Minority,
+    /// Always turn right along Potrace's direction of travel, which goes around holes
+    /// the other way: connects filled pixels on outer contours and empty pixels on holes.
+    Right,
+    /// Always turn left along Potrace's direction of travel: connects empty pixels
+    /// on outer contours and filled pixels on holes.
+    Left,
+    /// Pick a turn from a hash of the position, deterministic for a given image.
+    Random,
+}
+
+/// Whether filled pixels touching at a corner only are one region,
+/// see [`Options::connectivity`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Connectivity {
+    /// Diagonal pixels are separate regions, the empty pixels between them being joined.
+    Four,
+    /// Diagonal pixels are one region, splitting the empty pixels between them.
+    Eight,
+}
+
+impl From<Connectivity> for TurnPolicy {
+    fn from(connectivity: Connectivity) -> Self {
+        match connectivity {
+            Connectivity::Four => TurnPolicy::White,
+            Connectivity::Eight => TurnPolicy::Black,
+        }
+    }
 }
 
 #[wasm_bindgen]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ColorMode {
     Black,
     Colored,
+    /// One layer per ink channel, see [`Options::separation`].
+    Separation,
+}
+
+/// How contours are turned into paths.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    /// Filtered, simplified and fitted with Bézier curves.
+    Smooth,
+    /// The raw pixel outlines as axis-aligned line segments,
+    /// without filtering or curve fitting, for pixel art.
+    PixelPerfect,
+    /// Filtered and simplified as with [`Mode::Smooth`], then joined by line segments
+    /// without curve fitting, e.g. for physics engines and clipping libraries.
+    /// Much faster, and the curves of every [`Layer`] follow its polygons.
+    Polygon,
+}
+
+/// How the image is upscaled before tracing, see [`Options::supersample`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Supersample {
+    /// Inputs smaller than 512×512 pixels are upscaled 3 times when [`Options::preprocess`]
+    /// is set, and the result is scaled back down to the input resolution.
+    #[default]
+    Auto,
+    /// Traced at the input resolution.
+    Off,
+    /// Upscaled by the factor, the result keeping the upscaled coordinates.
+    /// Clamped to `1..=`[`SUPERSAMPLE_MAX`], and so the upscaled size fits in `u32`.
+    Factor(u32),
+}
+
+/// Interpolation of the upscaled pixels, see [`Options::resample_filter`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ResampleFilter {
+    /// Repeats the pixels, keeping hard edges and the exact colors, for pixel art.
+    Nearest,
+    /// Linear interpolation.
+    Triangle,
+    /// Cubic interpolation, sharper than linear.
+    #[default]
+    CatmullRom,
+    /// Gaussian weights, the smoothest edges.
+    Gaussian,
+    /// Windowed sinc, keeping the most detail of photos at the cost of ringing around edges.
+    Lanczos3,
+}
+
+/// Unsharp mask restoring the edges softened by the upscaling, see [`Options::sharpen`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SharpenOptions {
+    /// Standard deviation of the Gaussian blur the image is compared with, in traced pixels.
+    pub sigma: f32,
+    /// Smallest difference from the blurred pixel that is sharpened, in color units,
+    /// so flat areas keep their noise level.
+    pub threshold: i32,
+}
+
+impl Default for SharpenOptions {
+    fn default() -> Self {
+        Self {
+            sigma: 1.0,
+            threshold: 2,
+        }
+    }
+}
+
+/// Winding of the closed contours in image coordinates, with the Y axis pointing down,
+/// so fill rules tell shapes from their holes.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Orientation {
+    /// Outer contours clockwise and holes counter-clockwise.
+    #[default]
+    OuterClockwise,
+    /// Outer contours counter-clockwise and holes clockwise, as outlines are traced.
+    OuterCounterClockwise,
+}
+
+/// Curve primitive used when emitting path data.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CurveType {
+    /// Cubic Bézier `C` commands, as produced by the fitter.
+    Cubic,
+    /// Quadratic Bézier `Q`/`T` commands, for TrueType/font tooling.
+    Quadratic,
+}
+
+/// File format produced by [`create_output`](crate::create_output).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Svg,
+    GeoJson,
+    Pdf,
+    Eps,
+    Dxf,
+}
+
+/// Drawing units written to the DXF `$INSUNITS` header.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DxfUnits {
+    Unitless = 0,
+    Inches = 1,
+    Millimeters = 4,
+    Centimeters = 5,
+    Meters = 6,
+}
+
+/// DXF specific output parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DxfOptions {
+    pub units: DxfUnits,
+    /// Write `SPLINE` entities instead of flattened `LWPOLYLINE`s.
+    pub splines: bool,
+    /// Only export layers with these RGB colors, all layers when `None`.
+    pub colors: Option<Vec<[u8; 3]>>,
+}
+
+impl Default for DxfOptions {
+    fn default() -> Self {
+        Self {
+            units: DxfUnits::Unitless,
+            splines: false,
+            colors: None,
+        }
+    }
+}
+
+/// SVG specific output parameters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SvgOptions {
+    /// Prepended to every generated `id`,
+    /// so documents inlined into the same HTML page don't collide.
+    pub id_prefix: String,
+    pub id_scheme: IdScheme,
+    /// Text of a `<title>` element.
+    pub title: Option<String>,
+    /// Text of a `<desc>` element.
+    pub description: Option<String>,
+    /// Add a `<metadata>` element naming the generator and its version.
+    pub generator_metadata: bool,
+    /// Color paths through `c<index>` classes of a `<style>` element,
+    /// instead of `fill`/`stroke` attributes, so they can be recolored with CSS.
+    pub css_classes: bool,
+    /// Unit of the `width` and `height` attributes, e.g. `width="100mm"`,
+    /// one user unit of the paths then being one of these. Unitless when `None`.
+    pub units: Option<SvgUnits>,
+    /// Emit path coordinates with the Y axis pointing up from the bottom edge,
+    /// as expected by pen plotters and CNC tools.
+    /// Browsers render such documents upside down.
+    pub flip_y: bool,
+    /// `[x, y]` position of the coordinate system origin,
+    /// from the top left corner or the bottom left one with `flip_y`.
+    /// The `viewBox` is moved along so the whole drawing stays visible.
+    pub origin: [f64; 2],
+    /// Add a `data-bbox="x y width height"` attribute to every path,
+    /// the bounds of its contours in document coordinates, e.g. for hit-testing.
+    pub bbox_attributes: bool,
+    /// Write the layers sharing a fill color and opacity as a single path of their
+    /// combined contours, referenced once per group. Gradient fills are never merged.
+    pub merge_paths: bool,
+    /// Define contours repeated within a path up to a translation, e.g. bullet dots,
+    /// once at the origin and place every copy with a positioned `<use>`.
+    /// These shapes get no `data-bbox`, and gradient fills are never split.
+    pub reuse_shapes: bool,
+    /// Split the path of a layer into several `<path>` elements sharing its fill
+    /// when its data has more commands than this, for renderers choking on long
+    /// `d` attributes. Holes stay in the path of the outer contour enclosing them,
+    /// see [`VectorizationResult::contour_tree`], so a longer shape is never split.
+    pub max_path_commands: Option<usize>,
+    /// Replace the contours within this distance of a circle, an ellipse, a rectangle
+    /// or a regular polygon, in output units, with `<circle>`, `<ellipse>`, `<rect>` and
+    /// `<polygon>` elements, e.g. the marks of scanned diagrams, which design tools then
+    /// edit as shapes. Only shapes without holes are replaced, and never in gradient fills.
+    /// `None` keeps every contour a path.
+    pub detect_primitives: Option<f64>,
+    /// Add a `data-fit-error` attribute to the paths whose largest fit error is above
+    /// this distance, in output units, see [`Layer::fit_errors`], e.g. for QA tooling
+    /// highlighting where the trace deviates most. `None` adds none.
+    pub fit_error_attributes: Option<f64>,
+    pub coordinate_mode: CoordinateMode,
+    pub profile: SvgProfile,
+    pub formatting: OutputFormatting,
+}
+
+/// Whitespace and attribute spelling of the serialized document, see [`SvgOptions::formatting`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum OutputFormatting {
+    /// One element per line, without indentation.
+    #[default]
+    Lines,
+    /// No whitespace between elements and the shortest spelling of numeric attributes,
+    /// e.g. `.5` for `0.5`, for web delivery.
+    Minified,
+    /// One element per line, indented by `indent` spaces per nesting level.
+    Pretty { indent: usize },
+}
+
+/// Whether path data is written with absolute or relative commands,
+/// see [`SvgOptions::coordinate_mode`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum CoordinateMode {
+    /// Uppercase commands, e.g. `L10 20`.
+    Absolute,
+    /// Lowercase commands after the leading move, e.g. `l2 3`,
+    /// usually shorter as the offsets between nearby points have fewer digits.
+    #[default]
+    Relative,
+    /// Both are serialized for every path, keeping the shorter one.
+    Auto,
+}
+
+/// SVG version and dialect the document is written for, see [`SvgOptions::profile`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SvgProfile {
+    /// SVG 1.1, as expected by older renderers and PDF tools:
+    /// a `version` attribute, `xlink:href` references with the xlink namespace declared,
+    /// and typed `<style>` elements.
+    Svg11,
+    /// SVG 2, plain `href` references.
+    #[default]
+    Svg2,
+    /// SVG 1.1 with the groups of strokes, fills, levels and frames marked as
+    /// named Inkscape layers, in the Inkscape namespace.
+    Inkscape,
+}
+
+impl SvgProfile {
+    /// Name of the attribute referencing another element.
+    pub fn href(&self) -> &'static str {
+        match self {
+            SvgProfile::Svg2 => "href",
+            SvgProfile::Svg11 | SvgProfile::Inkscape => "xlink:href",
+        }
+    }
+}
+
+/// How the `id`s of SVG paths are generated, after [`SvgOptions::id_prefix`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum IdScheme {
+    /// Short ids from a counter (`a`, `b`, … `aa`), renumbered whenever a layer is added or removed.
+    #[default]
+    Counter,
+    /// `color-<rrggbb>-<n>` from the layer color, alpha included as `<rrggbbaa>` when translucent,
+    /// `n` counting the paths of that color in the document.
+    /// Ids persist across re-runs for colors that are still traced, so external CSS and scripts
+    /// can keep referencing them.
+    Color,
+}
+
+/// Absolute units of [`SvgOptions::units`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SvgUnits {
+    Millimeters,
+    Centimeters,
+    Inches,
+}
+
+impl SvgUnits {
+    /// Suffix of a length in this unit.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            SvgUnits::Millimeters => "mm",
+            SvgUnits::Centimeters => "cm",
+            SvgUnits::Inches => "in",
+        }
+    }
+}
+
+/// Ink channels of [`ColorMode::Separation`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SeparationChannels {
+    /// Cyan, magenta, yellow and key (black) inks, the key taking the gray component.
+    #[default]
+    Cmyk,
+    /// The negatives of the red, green and blue channels, printed in cyan, magenta and yellow.
+    Rgb,
+}
+
+impl SeparationChannels {
+    /// `(label, ink color)` of every channel, in the order of the layers.
+    pub fn inks(&self) -> &'static [(&'static str, [u8; 4])] {
+        const CYAN: [u8; 4] = [0, 255, 255, 255];
+        const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+        const YELLOW: [u8; 4] = [255, 255, 0, 255];
+        match self {
+            SeparationChannels::Cmyk => &[
+                ("C", CYAN),
+                ("M", MAGENTA),
+                ("Y", YELLOW),
+                ("K", [0, 0, 0, 255]),
+            ],
+            SeparationChannels::Rgb => &[("R", CYAN), ("G", MAGENTA), ("B", YELLOW)],
+        }
+    }
+}
+
+/// Channels and levels of [`ColorMode::Separation`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SeparationOptions {
+    pub channels: SeparationChannels,
+    /// Ink coverage in `0..=255` from which an opaque pixel is inked,
+    /// for every channel in the order of [`SeparationChannels::inks`].
+    pub levels: [u8; 4],
+}
+
+impl Default for SeparationOptions {
+    fn default() -> Self {
+        Self {
+            channels: SeparationChannels::Cmyk,
+            levels: [128; 4],
+        }
+    }
+}
+
+/// Grayscale image restricting tracing to part of the input, e.g. the mask of
+/// a foreground object segmented from a photo, see [`Options::stencil`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stencil {
+    /// Sampled at the position of every input pixel when its size differs from the input's.
+    pub image: GrayImage,
+    /// Input pixels are traced where the stencil is above this value.
+    pub threshold: u8,
+}
+
+/// Pixels of the input made fully transparent before quantization, see [`Options::background`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackgroundRemoval {
+    /// Pixels whose RGB color is within the Euclidean distance `tolerance` of `color`,
+    /// e.g. `[255, 255, 255]` for a white background.
+    ChromaKey { color: [u8; 3], tolerance: f32 },
+    /// A pass of your own replacing the input, e.g. a segmentation model
+    /// clearing the alpha of the background.
+    Custom(ImageFilter),
+}
+
+/// Image pass of [`BackgroundRemoval::Custom`], only equal to its clones.
+#[derive(Clone)]
+pub struct ImageFilter(Arc<dyn Fn(&RgbaImage) -> RgbaImage + Send + Sync>);
+
+impl ImageFilter {
+    pub fn new(filter: impl Fn(&RgbaImage) -> RgbaImage + Send + Sync + 'static) -> Self {
+        Self(Arc::new(filter))
+    }
+
+    pub fn apply(&self, image: &RgbaImage) -> RgbaImage {
+        (self.0)(image)
+    }
+}
+
+impl fmt::Debug for ImageFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ImageFilter").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for ImageFilter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// How [`ColorMode::Black`] separates ink from the background,
+/// pixels darker than the threshold luminance are traced.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Threshold {
+    /// A fixed luminance in `0..=255`.
+    Fixed(u8),
+    /// A global threshold picked by Otsu's method from the luminance histogram.
+    Otsu,
+    /// The mean luminance of a square window minus `offset`, for unevenly lit inputs.
+    Mean { radius: u32, offset: f32 },
+    /// Sauvola's local threshold over a square window, suited to documents.
+    /// `k` is typically around `0.2`, higher values trace less.
+    Sauvola { radius: u32, k: f32 },
+}
+
+/// Noise removal for scanned or photographed inputs, everything is disabled by default.
+///
+/// Radii and areas are in input pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DespeckleOptions {
+    /// Standard deviation of a Gaussian blur applied before quantization, `0.0` disables it.
+    pub blur_sigma: f32,
+    /// Radius of a median filter applied to every color mask, `0` disables it.
+    pub median_radius: u32,
+    /// Radius of a morphological opening of every color mask, removing specks.
+    pub open_radius: u32,
+    /// Radius of a morphological closing of every color mask, filling pinholes.
+    pub close_radius: u32,
+    /// Regions of a single color with fewer input pixels are merged into their
+    /// largest neighboring region after quantization, `0` disables it.
+    ///
+    /// With [`ColorMode::Black`], specks of ink and holes in the ink are both merged
+    /// into their surroundings after thresholding, like Potrace's `turdsize`.
+    pub merge_area: u32,
+}
+
+/// Filter of [`DescreenOptions`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum DescreenFilter {
+    /// The mean color of the window, a low-pass filter blending the screen into
+    /// the tones it renders, suited to halftoned scans.
+    #[default]
+    Average,
+    /// The most frequent color of the window, keeping the colors of the input,
+    /// suited to dithered images of a few colors.
+    Mode,
+}
+
+/// Smoothing of halftoned or dithered inputs before quantization, see [`Options::descreen`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DescreenOptions {
+    /// Only descreens inputs detected as halftoned or dithered,
+    /// see [`descreen::is_dithered`](crate::descreen::is_dithered).
+    pub detect: bool,
+    /// Width of the square window filtered over, in input pixels,
+    /// about the period of the screen or dithering pattern.
+    pub cell_size: u32,
+    /// Images with a fixed palette always use [`DescreenFilter::Mode`],
+    /// averaging would introduce colors outside of it.
+    pub filter: DescreenFilter,
+}
+
+impl Default for DescreenOptions {
+    fn default() -> Self {
+        Self {
+            detect: true,
+            cell_size: 4,
+            filter: DescreenFilter::Average,
+        }
+    }
+}
+
+/// Corners of contours moved outward by [`OffsetOptions`], where their edges part.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LineJoin {
+    /// The edges are extended until they meet, or beveled past [`OffsetOptions::miter_limit`].
+    #[default]
+    Miter,
+    /// An arc around the original corner, e.g. for sticker borders.
+    Round,
+    /// A straight line between the ends of the edges.
+    Bevel,
+}
+
+/// Inset or outset of every contour, see [`Options::offset`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OffsetOptions {
+    /// Distance contours are moved outward by, negative to move them inward,
+    /// in output units before [`Options::transform`]. `0.0` disables it.
+    pub distance: f64,
+    pub join: LineJoin,
+    /// Longest miter of [`LineJoin::Miter`] corners, relative to the distance,
+    /// as the SVG `stroke-miterlimit`. Also limits how far the corners the edges
+    /// overlap at move, where edges are trimmed at their crossing.
+    pub miter_limit: f64,
+}
+
+impl Default for OffsetOptions {
+    fn default() -> Self {
+        Self {
+            distance: 0.0,
+            join: LineJoin::Miter,
+            miter_limit: 4.0,
+        }
+    }
+}
+
+/// Which layer colors a [`ColorOverride`] applies to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorTarget {
+    /// Colors whose RGB channels are all within `tolerance` of `rgb`.
+    Color { rgb: [u8; 3], tolerance: u8 },
+    /// Colors whose luminance is in `min..=max`.
+    Luminance { min: u8, max: u8 },
+}
+
+impl ColorTarget {
+    pub fn matches(&self, color: [u8; 4]) -> bool {
+        match *self {
+            ColorTarget::Color { rgb, tolerance } => rgb
+                .iter()
+                .zip(color)
+                .all(|(a, b)| a.abs_diff(b) <= tolerance),
+            ColorTarget::Luminance { min, max } => {
+                let luma =
+                    0.299 * color[0] as f64 + 0.587 * color[1] as f64 + 0.114 * color[2] as f64;
+                (min as f64..=max as f64).contains(&luma)
+            }
+        }
+    }
+}
+
+/// Tracing parameters replacing the global ones for the layers matching `target`,
+/// e.g. a tighter fit for line work than for background areas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorOverride {
+    pub target: ColorTarget,
+    /// Replaces [`Options::error_threshold`] when set.
+    pub error_threshold: Option<f64>,
+    /// Replaces [`Options::simplify_threshold`] when set.
+    pub simplify_threshold: Option<f64>,
+}
+
+/// How contours of shapes touching the image border are emitted.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BorderContours {
+    /// Closed contours including the edges along the image border,
+    /// which are fitted like any other edge.
+    Closed,
+    /// Open paths of the inner edges only, leaving out the edges along the image border.
+    Open,
+    /// Closed contours whose edges along the image border are kept as straight lines,
+    /// only the inner edges are fitted.
+    Clip,
+}
+
+/// Scaling and translation of the result, applied to the coordinates themselves
+/// so outputs don't rely on a `transform` attribute.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransformOptions {
+    /// Uniform scale factor, ignored when `fit` is set.
+    pub scale: f64,
+    /// `[x, y]` offset applied after scaling, the document grows to keep the content visible.
+    pub translate: [f64; 2],
+    /// `[width, height]` the result is scaled to fit in, preserving its aspect ratio.
+    pub fit: Option<[f64; 2]>,
+}
+
+impl Default for TransformOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            translate: [0.0, 0.0],
+            fit: None,
+        }
+    }
+}
+
+impl TransformOptions {
+    /// The scale factor for a result of the given size.
+    pub fn scale_for(&self, width: u32, height: u32) -> f64 {
+        match self.fit {
+            Some([fit_width, fit_height]) if width > 0 && height > 0 => {
+                (fit_width / width as f64).min(fit_height / height as f64)
+            }
+            _ => self.scale,
+        }
+    }
+}
+
+/// Detection of the straight runs of the outlines, see [`Options::line_fitting`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineFitting {
+    /// Largest distance between the outline and the line replacing it, in traced pixels.
+    pub tolerance: f64,
+    /// Shortest run replaced with a line, in traced pixels.
+    pub min_length: f64,
+}
+
+impl Default for LineFitting {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.75,
+            min_length: 16.0,
+        }
+    }
+}
+
+/// Size of a contour, see [`AdaptiveSimplify::size`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ContourSize {
+    /// Diagonal of the bounding box.
+    Diagonal,
+    /// Length of the outline.
+    Perimeter,
+}
+
+/// Simplification threshold scaled by the size of each contour, see [`Options::adaptive_simplify`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AdaptiveSimplify {
+    pub size: ContourSize,
+    /// Size, in traced pixels, of the contours simplified with [`Options::simplify_threshold`]
+    /// unchanged, the threshold of the others is scaled by their size relative to it.
+    /// Thresholds are not scaled when it isn't positive.
+    pub reference: f64,
+    /// Smallest scaled threshold, ignored when NaN.
+    pub min: f64,
+    /// Largest scaled threshold, ignored when NaN and taking precedence over `min`.
+    pub max: f64,
+}
+
+impl AdaptiveSimplify {
+    /// The threshold simplifying the `(is_cyclic, poly)` contour, scaled from `simplify_threshold`.
+    pub fn threshold(&self, simplify_threshold: f64, is_cyclic: bool, poly: &[DVec2]) -> f64 {
+        let size = match self.size {
+            ContourSize::Diagonal => {
+                let (min, max) = poly.iter().fold(
+                    ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]),
+                    |(min, max), v| {
+                        (
+                            [min[0].min(v.x), min[1].min(v.y)],
+                            [max[0].max(v.x), max[1].max(v.y)],
+                        )
+                    },
+                );
+                match poly.is_empty() {
+                    true => 0.0,
+                    false => DVec2::new(min[0], min[1]).len_with(DVec2::new(max[0], max[1])),
+                }
+            }
+            ContourSize::Perimeter => {
+                let open = poly.windows(2).map(|w| w[0].len_with(w[1])).sum::<f64>();
+                match (is_cyclic, poly.first(), poly.last()) {
+                    (true, Some(first), Some(last)) => open + last.len_with(*first),
+                    _ => open,
+                }
+            }
+        };
+        let threshold = match self.reference > 0.0 {
+            true => simplify_threshold * size / self.reference,
+            false => simplify_threshold,
+        };
+        // Unlike `clamp`, never panics on crossed or NaN bounds.
+        threshold.max(self.min).min(self.max)
+    }
+}
+
+impl Default for AdaptiveSimplify {
+    fn default() -> Self {
+        Self {
+            size: ContourSize::Diagonal,
+            reference: 64.0,
+            min: 0.25,
+            max: 4.0,
+        }
+    }
+}
+
+/// Detection of smoothly shaded regions, whose quantized bands are traced
+/// as a single layer filled with a gradient, see [`Options::gradients`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GradientOptions {
+    /// Largest RMS distance between the colors of a region and its gradient, in RGB units.
+    /// Quantization banding alone adds about a third of the band steps.
+    pub tolerance: f64,
+    /// Largest RGB distance between the colors of neighboring bands of a gradient,
+    /// larger steps are edges between shapes.
+    pub max_step: f64,
+    /// Fewest bands of distinct colors forming a gradient.
+    pub min_bands: usize,
+    /// Detect radial gradients along with linear ones.
+    pub radial: bool,
+}
+
+impl Default for GradientOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 15.0,
+            max_step: 80.0,
+            min_bands: 3,
+            radial: true,
+        }
+    }
+}
+
+/// Potrace's tracing parameters, turned into [`Options`] by [`Options::potrace`],
+/// for users migrating from it. Defaults are Potrace's.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PotraceCompat {
+    /// Areas of at most this many pixels are removed, `-t`.
+    pub turdsize: u32,
+    /// Corner threshold, from `0` (polygons) to `4/3` (no corners), `-a`.
+    pub alphamax: f64,
+    /// Tolerance of the curve optimization in pixels, `None` for `--longcurve`, `-O`.
+    pub opttolerance: Option<f64>,
+    /// Output coordinates are quantized to `1/unit` pixels, `-u`.
+    pub unit: f64,
+    /// How ambiguous pixel configurations are resolved, `-z`.
+    pub turnpolicy: TurnPolicy,
+    /// Luminance in `0.0..=1.0` below which pixels are traced, `-k`.
+    pub blacklevel: f64,
+}
+
+impl Default for PotraceCompat {
+    fn default() -> Self {
+        Self {
+            turdsize: 2,
+            alphamax: 1.0,
+            opttolerance: Some(0.2),
+            unit: 10.0,
+            turnpolicy: TurnPolicy::Minority,
+            blacklevel: 0.5,
+        }
+    }
+}
+
+/// Color quantizer reducing the image to the palette of [`ColorMode::Colored`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Quantizer {
+    /// Kohonen neural network, see [`crate::quantizer::NeuQuant`].
+    NeuQuant {
+        /// One in `sample_factor` pixels is learned, in `1..=30`,
+        /// 1 being the slowest and most accurate.
+        sample_factor: i32,
+        /// Number of learning cycles, `None` for the default of the palette size.
+        learning_cycles: Option<usize>,
+    },
+    /// Median cut, see [`crate::quantizer::MedianCut`],
+    /// deterministic and better suited to small palettes.
+    MedianCut,
+}
+
+/// Contour stage used by polyline based outputs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ContourSource {
+    /// Simplified polygons, before curve fitting.
+    Polygons,
+    /// Fitted curves, flattened with [`Options::flatten_tolerance`].
+    Curves,
+}
+
+/// Encoded image formats accepted as input, each behind the cargo feature of the same name.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Bmp,
+    /// Decoded through the `dav1d` system library, the `avif` feature is not enabled by default.
+    Avif,
+}
+
+impl InputFormat {
+    pub const ALL: [InputFormat; 6] = [
+        InputFormat::Png,
+        InputFormat::Jpeg,
+        InputFormat::Gif,
+        InputFormat::Webp,
+        InputFormat::Bmp,
+        InputFormat::Avif,
+    ];
+
+    /// The input format of an `image` crate format, `None` for formats never accepted.
+    pub fn from_image_format(format: image::ImageFormat) -> Option<Self> {
+        match format {
+            image::ImageFormat::Png => Some(InputFormat::Png),
+            image::ImageFormat::Jpeg => Some(InputFormat::Jpeg),
+            image::ImageFormat::Gif => Some(InputFormat::Gif),
+            image::ImageFormat::WebP => Some(InputFormat::Webp),
+            image::ImageFormat::Bmp => Some(InputFormat::Bmp),
+            image::ImageFormat::Avif => Some(InputFormat::Avif),
+            _ => None,
+        }
+    }
+
+    /// Whether the decoder of this format is compiled in.
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            InputFormat::Png => cfg!(feature = "png"),
+            InputFormat::Jpeg => cfg!(feature = "jpeg"),
+            InputFormat::Gif => cfg!(feature = "gif"),
+            InputFormat::Webp => cfg!(feature = "webp"),
+            InputFormat::Bmp => cfg!(feature = "bmp"),
+            InputFormat::Avif => cfg!(feature = "avif"),
+        }
+    }
+}
+
+/// Largest [`Supersample::Factor`], a larger one only costs memory.
+pub const SUPERSAMPLE_MAX: u32 = 16;
+
+/// Largest [`Options::pixel_scale`], keeping the scaled size of large images within `u32`.
+pub const PIXEL_SCALE_MAX: u32 = 1024;
+
+/// Tracing and output parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// Formats inputs may be encoded in, others are rejected with
+    /// [`SvgenError::UnsupportedFormat`](crate::error::SvgenError::UnsupportedFormat)
+    /// before decoding, as are formats whose feature is disabled.
+    pub input_formats: Vec<InputFormat>,
+    /// Decodes PNG files whose chunk checksums don't match, e.g. written by a broken
+    /// encoder, instead of rejecting them. Animated PNGs are then decoded as their first frame.
+    pub ignore_png_crc: bool,
+    /// `[x, y, width, height]` of the only part of the input traced, in input pixels,
+    /// clamped to the image. The palette is computed from the region alone.
+    ///
+    /// Paths keep their position in the whole image, whose size the result keeps,
+    /// combine with [`Options::auto_crop`] to only cover the traced content.
+    pub region: Option<[u32; 4]>,
+    /// Pixels outside the stencil are made fully transparent before anything else,
+    /// leaving them out of the palette and of every layer, see [`Options::transparent_layer`].
+    pub stencil: Option<Stencil>,
+    /// Removes the background of the input before [`Options::stencil`] and anything else.
+    /// [`BackgroundRemoval::Custom`] passes may return an image of another size,
+    /// which is then traced instead.
+    pub background: Option<BackgroundRemoval>,
+    pub color_mode: ColorMode,
+    pub mode: Mode,
+    /// Smooths halftone screens and dithering out of the input before anything but
+    /// [`Options::region`] sees it, `None` traces them as they are.
+    pub descreen: Option<DescreenOptions>,
+    /// Smooths the input with an edge preserving filter before tracing,
+    /// and lets [`Supersample::Auto`] upscale it. Without it the pixels
+    /// are traced as they are, as Potrace does.
+    ///
+    /// Never used with [`Mode::PixelPerfect`], which traces the pixels as they are.
+    pub preprocess: bool,
+    /// Upscaling of the input before tracing, smoothing the outlines of small images.
+    /// [`Options::error_threshold`] and the other distances in traced pixels are
+    /// measured on the upscaled image.
+    ///
+    /// Never used with [`Mode::PixelPerfect`], see [`Options::pixel_scale`].
+    pub supersample: Supersample,
+    /// Filter the image is upscaled with by [`Options::supersample`].
+    /// Images traced with a fixed palette are always upscaled with [`ResampleFilter::Nearest`],
+    /// other filters blending their colors.
+    pub resample_filter: ResampleFilter,
+    /// Unsharp mask applied after upscaling, `None` keeps the resampled image.
+    /// Not used without upscaling, nor with a fixed palette.
+    pub sharpen: Option<SharpenOptions>,
+    /// Integer factor pixel outlines are scaled by, only used with [`Mode::PixelPerfect`].
+    /// Clamped to `1..=`[`PIXEL_SCALE_MAX`], see [`Options::pixel_outline_scale`].
+    pub pixel_scale: u32,
+    pub output_format: OutputFormat,
+    pub curve_type: CurveType,
+    /// Maximum distance a quadratic approximation may deviate from its cubic,
+    /// only used with [`CurveType::Quadratic`].
+    pub quadratic_tolerance: f64,
+    /// Which contours polyline outputs (e.g. GeoJSON) are built from.
+    pub contour_source: ContourSource,
+    /// Maximum deviation allowed when flattening curves to polylines.
+    pub flatten_tolerance: f64,
+    pub dxf: DxfOptions,
+    pub svg: SvgOptions,
+    pub quantizer: Quantizer,
+    /// Colors of the [`ColorMode::Colored`] layers, every pixel being snapped to the nearest
+    /// one instead of quantizing the image, e.g. the palette shared by a set of images,
+    /// see [`crate::create_svgs_batch`]. `None` computes the palette of every image.
+    pub palette: Option<Vec<[u8; 4]>>,
+    /// Adds a last layer of color `[0, 0, 0, 0]` tracing the fully transparent pixels,
+    /// which are otherwise left out of every layer and of the palette.
+    pub transparent_layer: bool,
+    /// Takes the pixels of the layers stacked above out of every layer after the
+    /// despeckle filters, which may grow masks over their neighbors, so each color only
+    /// covers its visible area. Only used with [`ColorMode::Colored`], whose layers
+    /// otherwise only overlap where the filters made them.
+    pub clip_layers: bool,
+    pub despeckle: DespeckleOptions,
+    /// Only used with [`ColorMode::Black`].
+    pub threshold: Threshold,
+    /// Only used with [`ColorMode::Separation`].
+    pub separation: SeparationOptions,
+    pub turn_policy: TurnPolicy,
+    /// Seed of the pseudo-random choices, the turns of [`TurnPolicy::Random`] and the
+    /// first pixel sampled by [`Quantizer::NeuQuant`]. Every stage is deterministic,
+    /// the same image and options always give the same output, other seeds give
+    /// different but equally valid ones. `None` is the same as `Some(0)`.
+    pub seed: Option<u64>,
+    /// Joins or splits diagonal pixels of every layer whatever their surroundings,
+    /// instead of [`Options::turn_policy`]. `None` leaves it to the turn policy.
+    pub connectivity: Option<Connectivity>,
+    /// Winding of the contours of every layer, see [`ContourNode::is_hole`].
+    pub orientation: Orientation,
+    /// Ignored with [`Options::shared_boundaries`].
+    pub border_contours: BorderContours,
+    /// Simplifies and fits each boundary between two layers once for both of them,
+    /// so neighboring layers tile exactly without seams or overlaps.
+    ///
+    /// Not used with [`Mode::PixelPerfect`], [`Options::border_contours`] is otherwise ignored.
+    pub shared_boundaries: bool,
+    /// Distance fitted curves are moved outward by, in output units before [`Options::transform`],
+    /// so adjacent layers slightly overlap instead of leaving hairline seams
+    /// where anti-aliased renderers blend both edges with the background.
+    ///
+    /// Only curves are moved, [`Layer::polygons`] keep the traced shape.
+    pub overlap: f64,
+    /// Moves every contour outward or inward with new geometry at the corners,
+    /// before [`Options::overlap`]. Unlike it, [`Layer::polygons`] are moved as well.
+    ///
+    /// Closed contours that an inset turns inside out, being thinner than
+    /// twice the distance, are removed along with the holes they enclosed.
+    pub offset: OffsetOptions,
+    /// Replaces the bands of smoothly shaded regions with one gradient filled layer each,
+    /// only used with [`ColorMode::Colored`] and [`Mode::Smooth`]. `None` keeps flat colors.
+    pub gradients: Option<GradientOptions>,
+    /// Step of the grid every point is rounded to, in output units before [`Options::transform`],
+    /// e.g. `0.1` for Potrace's default `unit` of 10. `None` keeps the fitted coordinates.
+    pub grid: Option<f64>,
+    /// Padding kept around the content when cropping the result to it,
+    /// in output units before [`Options::transform`].
+    ///
+    /// The coordinates are moved so the union of the [`Layer::bbox`]es starts at the padding,
+    /// and the size shrinks to fit. `None` keeps the whole canvas.
+    pub auto_crop: Option<f64>,
+    /// Scaling and translation applied to the coordinates of every output.
+    pub transform: TransformOptions,
+    pub corners: CornerOptions,
+    /// Maximum distance fitted curves may deviate from the outline, in traced pixels.
+    pub error_threshold: f64,
+    /// Smoothing in `0..=1` of the wobbles left by fitting pixel staircases, `0.0` disables it.
+    /// Smooth knots move toward the midpoint of their neighbors, by at most `smoothness`
+    /// times the error threshold, and the lengths of their handles are evened out.
+    ///
+    /// Only used with [`Mode::Smooth`].
+    pub smoothness: f64,
+    /// Replaces the straight runs of the outlines with lines before fitting,
+    /// only the curved runs between them are simplified and fitted with curves,
+    /// for technical drawings made mostly of straight edges. `None` fits whole outlines.
+    ///
+    /// Only used with [`Mode::Smooth`].
+    pub line_fitting: Option<LineFitting>,
+    pub simplifier: Simplifier,
+    /// Maximum distance removed outline points may be from the simplified polygon,
+    /// the epsilon of [`Options::simplifier`].
+    pub simplify_threshold: f64,
+    /// Scales the simplification threshold of every contour by its size, so small shapes
+    /// keep their details and large ones drop more points. With [`Options::shared_boundaries`]
+    /// each boundary is scaled by its own size. `None` simplifies every contour alike.
+    pub adaptive_simplify: Option<AdaptiveSimplify>,
+    /// Fits the contours whose curve crosses itself again with a smaller error threshold,
+    /// halved up to 4 times, then joins the points of their polygon with lines,
+    /// so the paths can go through boolean operations. Only checked before
+    /// [`Options::offset`] and [`Options::overlap`], which can make contours cross.
+    ///
+    /// With [`Options::shared_boundaries`] every boundary is fitted again on its own,
+    /// a contour still crossing itself once they are joined no longer follows its neighbors.
+    pub ensure_simple_paths: bool,
+    /// Per layer color threshold overrides, the first matching one is used.
+    pub color_overrides: Vec<ColorOverride>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            input_formats: InputFormat::ALL.to_vec(),
+            ignore_png_crc: false,
+            region: None,
+            stencil: None,
+            background: None,
+            color_mode: ColorMode::Colored,
+            mode: Mode::Smooth,
+            descreen: None,
+            preprocess: true,
+            supersample: Supersample::Auto,
+            resample_filter: ResampleFilter::CatmullRom,
+            sharpen: None,
+            pixel_scale: 1,
+            output_format: OutputFormat::Svg,
+            curve_type: CurveType::Cubic,
+            quadratic_tolerance: 0.5,
+            contour_source: ContourSource::Curves,
+            flatten_tolerance: 0.25,
+            dxf: DxfOptions::default(),
+            svg: SvgOptions::default(),
+            quantizer: Quantizer::NeuQuant {
+                sample_factor: 1,
+                learning_cycles: None,
+            },
+            palette: None,
+            transparent_layer: false,
+            clip_layers: false,
+            despeckle: DespeckleOptions::default(),
+            threshold: Threshold::Fixed(127),
+            separation: SeparationOptions::default(),
+            turn_policy: TurnPolicy::Majority,
+            seed: None,
+            connectivity: None,
+            orientation: Orientation::default(),
+            border_contours: BorderContours::Closed,
+            shared_boundaries: false,
+            overlap: 0.0,
+            offset: OffsetOptions::default(),
+            gradients: None,
+            grid: None,
+            auto_crop: None,
+            transform: TransformOptions::default(),
+            corners: CornerOptions::default(),
+            error_threshold: 1.5,
+            smoothness: 0.0,
+            simplifier: Simplifier::Quadric,
+            simplify_threshold: 2.0,
+            adaptive_simplify: None,
+            line_fitting: None,
+            ensure_simple_paths: false,
+            color_overrides: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    /// Black and white tracing driven by Potrace's parameters, the other options keeping their defaults.
+    ///
+    /// The pixels are traced as they are, see [`Options::preprocess`], and:
+    /// - `turdsize` maps to [`DespeckleOptions::merge_area`] `= turdsize + 1`,
+    ///   as Potrace removes the areas of at most `turdsize` pixels;
+    /// - `alphamax` maps to a corner [`CornerOptions::threshold`] of `alphamax · 3π/8`,
+    ///   keeping right angles as corners by default, and disables corners from `4/3`,
+    ///   above which Potrace never inserts one;
+    /// - `opttolerance` maps to [`Options::error_threshold`] `= 0.5 + opttolerance`,
+    ///   Potrace's polygons staying within half a pixel of the outline
+    ///   ([`Options::simplify_threshold`] `= 0.5`) before their curves are joined;
+    /// - `unit` maps to [`Options::grid`] `= 1 / unit`;
+    /// - `blacklevel` maps to a [`Threshold::Fixed`] luminance of `blacklevel · 255`.
+    pub fn potrace(params: &PotraceCompat) -> Self {
+        let alpha_corners = params.alphamax < 4.0 / 3.0;
+        Self {
+            color_mode: ColorMode::Black,
+            preprocess: false,
+            despeckle: DespeckleOptions {
+                merge_area: params.turdsize.saturating_add(1),
+                ..Default::default()
+            },
+            threshold: Threshold::Fixed((params.blacklevel.clamp(0.0, 1.0) * 255.0).round() as u8),
+            turn_policy: params.turnpolicy,
+            corners: CornerOptions {
+                enabled: alpha_corners,
+                threshold: params.alphamax.max(0.0) * 3.0 * std::f64::consts::PI / 8.0,
+                ..Default::default()
+            },
+            error_threshold: 0.5 + params.opttolerance.unwrap_or(0.0).max(0.0),
+            simplify_threshold: 0.5,
+            grid: (params.unit > 0.0).then(|| 1.0 / params.unit),
+            ..Default::default()
+        }
+    }
+
+    /// The turn policy outlines are traced with, that of [`Options::connectivity`] when set.
+    pub fn outline_turn_policy(&self) -> TurnPolicy {
+        self.connectivity.map_or(self.turn_policy, TurnPolicy::from)
+    }
+
+    /// The factor pixel outlines are scaled by, [`Options::pixel_scale`] clamped
+    /// to `1..=`[`PIXEL_SCALE_MAX`].
+    pub fn pixel_outline_scale(&self) -> u32 {
+        self.pixel_scale.clamp(1, PIXEL_SCALE_MAX)
+    }
+
+    /// The `(error_threshold, simplify_threshold)` used to fit a layer of `color`.
+    pub fn thresholds_for(&self, color: [u8; 4]) -> (f64, f64) {
+        match self
+            .color_overrides
+            .iter()
+            .find(|o| o.target.matches(color))
+        {
+            Some(o) => (
+                o.error_threshold.unwrap_or(self.error_threshold),
+                o.simplify_threshold.unwrap_or(self.simplify_threshold),
+            ),
+            None => (self.error_threshold, self.simplify_threshold),
+        }
+    }
+}
+
+/// A single color of the traced image.
+#[derive(Clone, Debug)]
+pub struct Layer {
+    /// RGBA fill color.
+    pub color: [u8; 4],
+    /// `(is_cyclic, points)` polygons, after simplification.
+    pub polygons: Vec<(bool, Vec<DVec2>)>,
+    /// `(is_cyclic, knots)` fitted curves, each knot is `[handle_in, point, handle_out]`.
+    pub curves: Vec<(bool, Vec<[DVec2; 3]>)>,
+    /// Fill replacing [`Layer::color`] in SVG outputs, the color is then its mean
+    /// for the outputs without gradients.
+    pub gradient: Option<Gradient>,
+    /// Placement of every fitted curve with at least one segment, in the same order,
+    /// see [`Layer::update_paths`].
+    pub paths: Vec<PathMetadata>,
+}
+
+impl Layer {
+    /// Recomputes [`Layer::paths`] from the curves.
+    pub fn update_paths(&mut self) {
+        self.paths = self
+            .segments()
+            .iter()
+            .filter_map(|(_is_cyclic, segments)| PathMetadata::from_segments(segments))
+            .collect();
+    }
+
+    /// Bounds of every path of the layer, `None` when it has none.
+    pub fn bbox(&self) -> Option<BoundingBox> {
+        self.paths
+            .iter()
+            .map(|path| path.bbox)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Largest distance of every polygon from each segment of its fitted curve,
+    /// in the order of [`Layer::segments`], e.g. to find where the trace deviates most
+    /// from the outlines, see [`utils::curve_fit_errors`](crate::utils::curve_fit_errors).
+    ///
+    /// Measured on the final curves, after smoothing, line fitting and the offsets,
+    /// empty when the polygons aren't the ones the curves were fitted to.
+    pub fn fit_errors(&self) -> Vec<Vec<f64>> {
+        if self.polygons.len() != self.curves.len() {
+            return Vec::new();
+        }
+        self.polygons
+            .iter()
+            .zip(&self.curves)
+            .map(|((_, polygon), (is_cyclic, curve))| curve_fit_errors(*is_cyclic, curve, polygon))
+            .collect()
+    }
+
+    /// `(is_cyclic, segments)` of every fitted curve, starting at its first knot,
+    /// where pieces whose handles lie on their chord are [`Segment::Line`]s.
+    pub fn segments(&self) -> Vec<(bool, Vec<Segment>)> {
+        self.curves
+            .iter()
+            .map(|(is_cyclic, curve)| (*is_cyclic, curve_segments(*is_cyclic, curve)))
+            .collect()
+    }
+}
+
+/// A point in image coordinates.
+pub type Point = DVec2;
+
+/// Color varying across a layer, see [`Options::gradients`].
+///
+/// `stops` are `(offset, color)` pairs, offsets increasing from 0 to 1.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Gradient {
+    /// Colors vary along the line from `start` to `end`, and are constant across it.
+    Linear {
+        start: Point,
+        end: Point,
+        stops: Vec<(f64, [u8; 4])>,
+    },
+    /// Colors vary with the distance from `center`, up to `radius`.
+    Radial {
+        center: Point,
+        radius: f64,
+        stops: Vec<(f64, [u8; 4])>,
+    },
+}
+
+impl Gradient {
+    pub fn stops(&self) -> &[(f64, [u8; 4])] {
+        match self {
+            Gradient::Linear { stops, .. } | Gradient::Radial { stops, .. } => stops,
+        }
+    }
+
+    /// Scales the gradient by `scale` then moves it by `offset`.
+    pub fn transform(&mut self, scale: f64, offset: Point) {
+        *self = self.map(|p| p.mul(scale).add(offset));
+        if let Gradient::Radial { radius, .. } = self {
+            *radius *= scale.abs();
+        }
+    }
+
+    /// The gradient with every point moved by `f`, which must preserve distances.
+    pub fn map(&self, f: impl Fn(Point) -> Point) -> Gradient {
+        match self {
+            Gradient::Linear { start, end, stops } => Gradient::Linear {
+                start: f(*start),
+                end: f(*end),
+                stops: stops.clone(),
+            },
+            Gradient::Radial {
+                center,
+                radius,
+                stops,
+            } => Gradient::Radial {
+                center: f(*center),
+                radius: *radius,
+                stops: stops.clone(),
+            },
+        }
+    }
+}
+
+/// An axis-aligned rectangle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingBox {
+    /// Top left corner.
+    pub min: Point,
+    /// Bottom right corner.
+    pub max: Point,
+}
+
+impl BoundingBox {
+    /// Bounds of the points, `None` when there are none.
+    pub fn from_points(points: impl IntoIterator<Item = Point>) -> Option<Self> {
+        points.into_iter().fold(None, |bbox, p| {
+            Some(match bbox {
+                None => BoundingBox { min: p, max: p },
+                Some(bbox) => bbox.union(&BoundingBox { min: p, max: p }),
+            })
+        })
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: Point::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Point::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max.x - self.min.x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max.y - self.min.y
+    }
+
+    /// Whether `p` is inside the box or on its edges.
+    pub fn contains(&self, p: Point) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x) && (self.min.y..=self.max.y).contains(&p.y)
+    }
+
+    /// Whether both boxes share a point, edges included.
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+}
+
+/// Where a traced shape sits, see [`Layer::paths`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PathMetadata {
+    /// Exact bounds of the curve, including its bulges past the knots.
+    pub bbox: BoundingBox,
+    /// Area enclosed by the curve, open curves being closed by their chord as when filled.
+    /// Holes are not subtracted from the shapes containing them.
+    pub area: f64,
+}
+
+impl PathMetadata {
+    /// Metadata of a contour, `None` when it has no segments.
+    pub fn from_segments(segments: &[Segment]) -> Option<Self> {
+        let bbox = segments
+            .iter()
+            .map(Segment::bbox)
+            .reduce(|a, b| a.union(&b))?;
+        let closing = Segment::Line {
+            start: segments[segments.len() - 1].end(),
+            end: segments[0].start(),
+        };
+        let area = segments
+            .iter()
+            .chain([&closing])
+            .map(Segment::signed_area)
+            .sum::<f64>();
+        Some(PathMetadata {
+            bbox,
+            area: area.abs(),
+        })
+    }
+}
+
+/// A piece of a contour, see [`Layer::segments`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Segment {
+    Line { start: Point, end: Point },
+    Cubic(CubicBezier),
+}
+
+impl Segment {
+    pub fn start(&self) -> Point {
+        match self {
+            Segment::Line { start, .. } => *start,
+            Segment::Cubic(cubic) => cubic.start,
+        }
+    }
+
+    pub fn end(&self) -> Point {
+        match self {
+            Segment::Line { end, .. } => *end,
+            Segment::Cubic(cubic) => cubic.end,
+        }
+    }
+
+    /// The segment as a cubic, lines get their control points on their ends.
+    pub fn to_cubic(&self) -> CubicBezier {
+        match *self {
+            Segment::Line { start, end } => CubicBezier {
+                start,
+                control1: start,
+                control2: end,
+                end,
+            },
+            Segment::Cubic(cubic) => cubic,
+        }
+    }
+
+    /// The segment with every point moved by `f`.
+    pub fn map(&self, f: impl Fn(Point) -> Point) -> Segment {
+        match *self {
+            Segment::Line { start, end } => Segment::Line {
+                start: f(start),
+                end: f(end),
+            },
+            Segment::Cubic(c) => Segment::Cubic(CubicBezier {
+                start: f(c.start),
+                control1: f(c.control1),
+                control2: f(c.control2),
+                end: f(c.end),
+            }),
+        }
+    }
+
+    /// Exact bounds of the segment, cubics are bound at the extrema of each axis.
+    pub fn bbox(&self) -> BoundingBox {
+        match *self {
+            Segment::Line { start, end } => BoundingBox::from_points([start, end]).unwrap(),
+            Segment::Cubic(c) => {
+                let [p0, p1, p2, p3] = [c.start, c.control1, c.control2, c.end];
+                // Roots of the derivative `a t² + b t + c`, for each axis.
+                let extrema = |p0: f64, p1: f64, p2: f64, p3: f64| -> [Option<f64>; 2] {
+                    let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+                    let b = 2.0 * (p0 - 2.0 * p1 + p2);
+                    let c = p1 - p0;
+                    if a.abs() < DVec2::EPS {
+                        return [(b.abs() >= DVec2::EPS).then(|| -c / b), None];
+                    }
+                    let discriminant = b * b - 4.0 * a * c;
+                    if discriminant < 0.0 {
+                        return [None, None];
+                    }
+                    let root = discriminant.sqrt();
+                    [Some((-b + root) / (2.0 * a)), Some((-b - root) / (2.0 * a))]
+                };
+                let roots = extrema(p0.x, p1.x, p2.x, p3.x)
+                    .into_iter()
+                    .chain(extrema(p0.y, p1.y, p2.y, p3.y))
+                    .flatten()
+                    .filter(|t| *t > 0.0 && *t < 1.0)
+                    .map(|t| {
+                        let s = 1.0 - t;
+                        p0.mul(s * s * s)
+                            .madd(p1, 3.0 * s * s * t)
+                            .madd(p2, 3.0 * s * t * t)
+                            .madd(p3, t * t * t)
+                    });
+                BoundingBox::from_points([p0, p3].into_iter().chain(roots)).unwrap()
+            }
+        }
+    }
+
+    /// Contribution of the segment to the signed area of a closed contour (Green's theorem),
+    /// positive for counter-clockwise contours in a Y up system.
+    pub fn signed_area(&self) -> f64 {
+        match *self {
+            Segment::Line { start, end } => (start.x * end.y - end.x * start.y) * 0.5,
+            Segment::Cubic(c) => {
+                let [p0, p1, p2, p3] = [c.start, c.control1, c.control2, c.end];
+                (p0.x * (6.0 * p1.y + 3.0 * p2.y + p3.y)
+                    + 3.0
+                        * (p1.x * (-2.0 * p0.y + p2.y + p3.y) - p2.x * (p0.y + p1.y - 2.0 * p3.y))
+                    - p3.x * (p0.y + 3.0 * p1.y + 6.0 * p2.y))
+                    / 20.0
+            }
+        }
+    }
+
+    /// Pushes the points of the segment after its start, within `tolerance`.
+    pub fn flatten(&self, tolerance: f64, out: &mut Vec<Point>) {
+        match self {
+            Segment::Line { end, .. } => out.push(*end),
+            Segment::Cubic(c) => {
+                cubic_flatten(c.start, c.control1, c.control2, c.end, tolerance, out)
+            }
+        }
+    }
+}
+
+/// A contour in the nesting of [`VectorizationResult::contour_tree`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContourNode {
+    /// Index of the layer in [`VectorizationResult::layers`].
+    pub layer: usize,
+    /// Index of the contour in the [`Layer::polygons`] and [`Layer::curves`] of its layer.
+    pub contour: usize,
+    /// Index of the smallest node enclosing this one, `None` for the outermost ones.
+    pub parent: Option<usize>,
+    /// Indices of the nodes whose parent is this one.
+    pub children: Vec<usize>,
+    /// Whether the contour bounds a hole of its layer rather than its outer edge,
+    /// from its winding, see [`Options::orientation`]. Open contours are never holes.
+    pub is_hole: bool,
+}
+
+/// A color of the final palette.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PaletteColor {
+    /// RGBA color.
+    #[wasm_bindgen(skip)]
+    pub color: [u8; 4],
+    /// Number of input pixels traced with this color.
+    pub pixel_count: usize,
+}
+
+#[wasm_bindgen]
+impl PaletteColor {
+    /// RGBA components, as a `Uint8Array` in JavaScript.
+    #[wasm_bindgen(getter)]
+    pub fn rgba(&self) -> Vec<u8> {
+        self.color.to_vec()
+    }
+}
+
+/// Output of the tracing pipeline, shared by all output backends.
+#[derive(Clone, Debug)]
+pub struct VectorizationResult {
+    pub width: u32,
+    pub height: u32,
+    pub layers: Vec<Layer>,
+    /// One entry per layer, in the same order.
+    pub palette: Vec<PaletteColor>,
+    /// One node per contour of every layer, across layers,
+    /// e.g. holes are children of the outer contour they belong to,
+    /// and shapes filling a hole of another layer are children of that hole.
+    ///
+    /// Contours sharing their shape, as a shape and the hole it exactly fills
+    /// with [`Options::shared_boundaries`], are siblings.
+    pub contour_tree: Vec<ContourNode>,
+    /// Where the input image lies in output coordinates, from `[0, 0]` to the size
+    /// until moved along with the paths by [`Options::auto_crop`] and [`Options::transform`],
+    /// e.g. to place the raster of [`to_hybrid_svg`](crate::output::svg::to_hybrid_svg).
+    pub canvas: BoundingBox,
+}
+
+impl VectorizationResult {
+    /// Scales every point by `scale` then moves it by `translate`,
+    /// the size grows to the bottom right corner of the transformed image, rounded up.
+    pub fn transform(&mut self, scale: f64, translate: [f64; 2]) {
+        let offset = DVec2::new(translate[0], translate[1]);
+        let apply = |v: &mut DVec2| *v = v.mul(scale).add(offset);
+        for layer in &mut self.layers {
+            for (_is_cyclic, polygon) in &mut layer.polygons {
+                polygon.iter_mut().for_each(apply);
+            }
+            for (_is_cyclic, curve) in &mut layer.curves {
+                curve.iter_mut().flatten().for_each(apply);
+            }
+            if let Some(gradient) = &mut layer.gradient {
+                gradient.transform(scale, offset);
+            }
+        }
+        apply(&mut self.canvas.min);
+        apply(&mut self.canvas.max);
+        self.layers.iter_mut().for_each(Layer::update_paths);
+        let size = |size: u32, offset: f64| (size as f64 * scale + offset).ceil().max(0.0) as u32;
+        self.width = size(self.width, translate[0]);
+        self.height = size(self.height, translate[1]);
+    }
+
+    /// Rounds every point of the paths to the nearest multiple of `step`.
+    pub fn round_to_grid(&mut self, step: f64) {
+        let apply =
+            |v: &mut DVec2| *v = DVec2::new((v.x / step).round(), (v.y / step).round()).mul(step);
+        for layer in &mut self.layers {
+            for (_is_cyclic, polygon) in &mut layer.polygons {
+                polygon.iter_mut().for_each(apply);
+            }
+            for (_is_cyclic, curve) in &mut layer.curves {
+                curve.iter_mut().flatten().for_each(apply);
+            }
+        }
+        self.layers.iter_mut().for_each(Layer::update_paths);
+    }
+
+    /// Moves the content to `padding` from the top left corner
+    /// and shrinks the size to the content plus `padding` on each side, rounded up.
+    ///
+    /// Results without any path are left unchanged.
+    pub fn crop(&mut self, padding: f64) {
+        let Some(bbox) = self
+            .layers
+            .iter()
+            .filter_map(Layer::bbox)
+            .reduce(|a, b| a.union(&b))
+        else {
+            return;
+        };
+        self.transform(1.0, [padding - bbox.min.x, padding - bbox.min.y]);
+        let size = |size: f64| (size + 2.0 * padding).ceil().max(0.0) as u32;
+        self.width = size(bbox.width());
+        self.height = size(bbox.height());
+    }
+}
+
+/// An SVG document along with the palette it was traced with.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct SvgOutput {
+    pub svg: String,
+    pub palette: Vec<PaletteColor>,
+}
+
+/// Tiles of a spritesheet, see [`crate::create_svg_sprites`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpriteGrid {
+    /// Tiles of `width` × `height` pixels from the top left corner, row by row,
+    /// the partial tiles along the right and bottom edges are left out.
+    Tiles { width: u32, height: u32 },
+    /// `[x, y, width, height]` of every tile, in input pixels, clamped to the image.
+    Rects(Vec<[u32; 4]>),
+}
+
+impl SpriteGrid {
+    /// `[x, y, width, height]` of the tiles of an image of `size`, in order.
+    pub fn rects(&self, size: [u32; 2]) -> Vec<[u32; 4]> {
+        match self {
+            SpriteGrid::Tiles { width, height } => {
+                let [width, height] = [(*width).max(1), (*height).max(1)];
+                let columns = size[0] / width;
+                (0..size[1] / height)
+                    .flat_map(|row| {
+                        (0..columns)
+                            .map(move |column| [column * width, row * height, width, height])
+                    })
+                    .collect()
+            }
+            SpriteGrid::Rects(rects) => rects.clone(),
+        }
+    }
+}
+
+/// SVG documents of a set of images traced with the same palette, see [`crate::create_svgs_batch`].
+#[derive(Clone, Debug)]
+pub struct BatchOutput {
+    /// One document per image, in the same order.
+    pub svgs: Vec<String>,
+    /// The shared palette, with the pixels traced with each color over every image.
+    pub palette: Vec<PaletteColor>,
+}
+
+/// What a trace produced and how long each stage took,
+/// to find out why a document is large or slow, see [`crate::create_svg_with_stats`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TraceStats {
+    /// Contours of the pixel outlines of every layer.
+    pub contours: usize,
+    /// Regions removed before tracing by the despeckle filters and
+    /// [`DespeckleOptions::merge_area`], not counted in [`TraceStats::contours`],
+    /// and fitted curves without any segment, left out of the paths.
+    pub contours_dropped: usize,
+    /// Vertices of the pixel outlines.
+    pub knots_before_simplification: usize,
+    /// Vertices of the simplified polygons, see [`Layer::polygons`].
+    pub knots_after_simplification: usize,
+    /// Segments of the fitted curves, see [`Layer::segments`].
+    pub cubic_segments: usize,
+    /// `(color, paths)` of every layer, in layer order, see [`Layer::paths`].
+    /// Each path is a subpath of the `<path>` of its layer in SVG documents.
+    pub paths_per_color: Vec<([u8; 4], usize)>,
+    pub timings: StageTimings,
+}
+
+/// Time spent in each stage of the pipeline, left at zero on WASM where there is no clock.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct StageTimings {
+    pub decode: Duration,
+    /// Filtering, upscaling and quantization.
+    pub prepare: Duration,
+    pub masks: Duration,
+    pub trace: Duration,
+    /// Simplification and curve fitting.
+    pub fit: Duration,
+    /// Layers, palette, contour tree and transforms of the result.
+    pub assemble: Duration,
+    pub serialize: Duration,
+}
+
+impl StageTimings {
+    pub fn total(&self) -> Duration {
+        self.decode
+            + self.prepare
+            + self.masks
+            + self.trace
+            + self.fit
+            + self.assemble
+            + self.serialize
+    }
 }
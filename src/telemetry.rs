@@ -0,0 +1,79 @@
+//! Diagnostics of the pipeline, through the `log` facade by default.
+//!
+//! With the `tracing` feature, messages are `tracing` events and every stage
+//! (decode, preprocess, quantize, masks, extract, fit, emit) runs in an info span
+//! recording its `elapsed_ms`, and for extract and fit the number of `contours`.
+//! Events are still forwarded to `log` while no `tracing` subscriber is set.
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use log::{info, trace, warn};
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{info, trace, warn};
+
+#[cfg(all(feature = "tracing", not(target_arch = "wasm32")))]
+use std::time::Instant;
+
+/// Enters the span of a pipeline stage until the returned [`Stage`] is dropped.
+#[cfg(feature = "tracing")]
+macro_rules! stage {
+    ($name:literal) => {
+        $crate::telemetry::Stage::enter(tracing::info_span!(
+            $name,
+            elapsed_ms = tracing::field::Empty,
+            contours = tracing::field::Empty,
+        ))
+    };
+}
+
+/// Enters the span of a pipeline stage until the returned [`Stage`] is dropped.
+#[cfg(not(feature = "tracing"))]
+macro_rules! stage {
+    ($name:literal) => {
+        $crate::telemetry::Stage
+    };
+}
+
+pub(crate) use stage;
+
+/// Span of a running pipeline stage, see [`stage!`].
+#[cfg(feature = "tracing")]
+pub(crate) struct Stage {
+    span: tracing::span::EnteredSpan,
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl Stage {
+    pub(crate) fn enter(span: tracing::Span) -> Self {
+        Self {
+            span: span.entered(),
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+        }
+    }
+
+    /// Records the number of contours the stage produced.
+    pub(crate) fn record_contours(&self, contours: usize) {
+        self.span.record("contours", contours);
+    }
+}
+
+/// Records how long the stage took, there is no clock to time it with on WASM.
+#[cfg(feature = "tracing")]
+impl Drop for Stage {
+    fn drop(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.span
+            .record("elapsed_ms", self.start.elapsed().as_secs_f64() * 1000.0);
+    }
+}
+
+/// Without the `tracing` feature stages are not recorded.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Stage;
+
+#[cfg(not(feature = "tracing"))]
+impl Stage {
+    pub(crate) fn record_contours(&self, _contours: usize) {}
+}
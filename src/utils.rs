@@ -1,4 +1,14 @@
-use crate::vec2::DVec2;
+pub use crate::{
+    curve_fit_nd::{poly_list_subdivide_to_limit, poly_subdivide_to_limit},
+    path_optimizer::{cubic_flatten, trunc},
+};
+use crate::{
+    polygon_simplifier::segment_distance_squared,
+    structs::{
+        BoundingBox, ContourNode, CubicBezier, Layer, LineJoin, OffsetOptions, Orientation, Segment,
+    },
+    vec2::DVec2,
+};
 
 pub fn generate_id(input: usize) -> String {
     let mut id = String::new();
@@ -28,8 +38,31 @@ pub fn rgba_to_hex(r: u8, g: u8, b: u8, a: u8) -> String {
     format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
 }
 
-pub fn trunc(value: f64) -> f32 {
-    (f64::trunc(value * 100.0) / 100.0) as f32
+/// Produces `#RRGGBB`, or the `#RGB` shorthand when it is equivalent.
+pub fn rgb_to_hex(r: u8, g: u8, b: u8) -> String {
+    if [r, g, b].iter().all(|c| c >> 4 == c & 0xF) {
+        format!("#{:X}{:X}{:X}", r & 0xF, g & 0xF, b & 0xF)
+    } else {
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    }
+}
+
+/// Standard base64 with padding, as in `data:` URLs.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            output.push(match i <= chunk.len() {
+                true => ALPHABET[(group >> (18 - 6 * i)) as usize & 63] as char,
+                false => '=',
+            });
+        }
+    }
+    output
 }
 
 // Subdivide
@@ -56,41 +89,1124 @@ pub fn poly_list_subdivide(poly_list_src: &mut Vec<(bool, Vec<DVec2>)>) {
         .for_each(|(is_cyclic, poly_src)| *poly_src = poly_subdivide(*is_cyclic, &poly_src))
 }
 
-// Subdivide until segments are smaller then the limit
-pub fn poly_subdivide_to_limit(is_cyclic: bool, poly_src: &Vec<DVec2>, limit: f64) -> Vec<DVec2> {
-    // target size isn't known. but will be at least as big as the source
-    let mut poly_dst: Vec<DVec2> = Vec::with_capacity(poly_src.len());
+/// Splits the polygon at its straight runs into `(is_straight, points)` open pieces,
+/// each starting where the one before ends. Runs are straight when every point is within
+/// `tolerance` of their least squares line, and at least `min_length` long.
+///
+/// A straight piece only keeps the ends of its run, moved onto its line,
+/// or to the crossing of the lines of two straight runs meeting at a corner.
+/// Cyclic polygons start at a straight run, so the curved runs are never split.
+/// `None` when the polygon has no straight run.
+pub fn poly_split_straight(
+    is_cyclic: bool,
+    poly_src: &[DVec2],
+    tolerance: f64,
+    min_length: f64,
+) -> Option<Vec<(bool, Vec<DVec2>)>> {
+    let mut points = poly_src.to_vec();
+    if is_cyclic {
+        points.extend(poly_src.first());
+    }
+    let mut runs = straight_runs(&points, tolerance, min_length);
+    if is_cyclic && !runs.is_empty() {
+        // Restart where the first run stops being straight, as a run
+        // may go on from the end of the cycle to its start.
+        points.pop();
+        points.rotate_left(runs[0].1 % poly_src.len());
+        points.push(points[0]);
+        runs = straight_runs(&points, tolerance, min_length);
+    }
+    if runs.is_empty() {
+        return None;
+    }
 
-    let limit_sq = DVec2::sq(limit);
-    let mut v_orig_prev = &poly_src[if is_cyclic { poly_src.len() - 1 } else { 0 }];
-    if !is_cyclic {
-        poly_dst.push(*v_orig_prev);
+    // Where the ends of the runs move to, the first and last points being the same when cyclic.
+    let last = points.len() - 1;
+    let mut moved = vec![None; points.len()];
+    for (i, &(start, end, line)) in runs.iter().enumerate() {
+        let previous = match i {
+            0 if is_cyclic => runs.last().filter(|run| run.1 == last && start == 0),
+            0 => None,
+            _ => Some(&runs[i - 1]).filter(|run| run.1 == start),
+        };
+        let start_point = match previous {
+            Some(&(_, _, previous_line)) => {
+                line_join(previous_line, line, points[start], tolerance)
+            }
+            None => line_project(line, points[start]),
+        };
+        moved[start] = Some(start_point);
+        moved[end].get_or_insert_with(|| line_project(line, points[end]));
     }
+    if is_cyclic {
+        let first = moved[0].or(moved[last]);
+        (moved[0], moved[last]) = (first, first);
+    }
+    let piece = |start: usize, end: usize| {
+        let mut piece = points[start..=end].to_vec();
+        piece[0] = moved[start].unwrap_or(piece[0]);
+        piece[end - start] = moved[end].unwrap_or(piece[end - start]);
+        piece
+    };
 
-    for v_orig_curr in &poly_src[(if is_cyclic { 0 } else { 1 })..] {
-        // subdivided point(s)
-        let len_sq = v_orig_prev.len_squared_with(*v_orig_curr);
-        if len_sq > limit_sq {
-            let len = len_sq.sqrt();
-            let sub = (len / limit).floor();
-            let inc = 1.0 / sub;
-            let mut step = inc;
-            for _ in 0..((sub as usize) - 1) {
-                poly_dst.push(v_orig_prev.interp(*v_orig_curr, step));
-                debug_assert!(step > 0.0 && step < 1.0);
-                step += inc;
+    let mut pieces = Vec::new();
+    let mut curved_start = 0;
+    for &(start, end, _) in &runs {
+        if start > curved_start {
+            pieces.push((false, piece(curved_start, start)));
+        }
+        pieces.push((true, vec![moved[start].unwrap(), moved[end].unwrap()]));
+        curved_start = end;
+    }
+    if curved_start < last {
+        pieces.push((false, piece(curved_start, last)));
+    }
+    if is_cyclic && !pieces[0].0 {
+        let (_, first) = pieces.remove(0);
+        match pieces.last_mut() {
+            Some((false, last)) => last.extend(&first[1..]),
+            _ => pieces.push((false, first)),
+        }
+    }
+    Some(pieces)
+}
+
+// `(center, direction)` of the least squares line through `points`.
+fn line_fit(points: &[DVec2]) -> (DVec2, DVec2) {
+    let n = points.len() as f64;
+    let center = points
+        .iter()
+        .fold(DVec2::new(0.0, 0.0), |sum, v| sum.add(*v))
+        .mul(1.0 / n);
+    let (mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0);
+    for v in points {
+        let d = v.sub(center);
+        xx += d.x * d.x;
+        yy += d.y * d.y;
+        xy += d.x * d.y;
+    }
+    let angle = 0.5 * (2.0 * xy).atan2(xx - yy);
+    (center, DVec2::new(angle.cos(), angle.sin()))
+}
+
+fn line_project((center, direction): (DVec2, DVec2), v: DVec2) -> DVec2 {
+    center.madd(direction, v.sub(center).dot(direction))
+}
+
+// Corner between two lines near `v`, their crossing unless they are
+// too close to parallel for it to be within `tolerance` of `v`.
+fn line_join(a: (DVec2, DVec2), b: (DVec2, DVec2), v: DVec2, tolerance: f64) -> DVec2 {
+    let cross = a.1.cross(b.1);
+    if cross != 0.0 {
+        let crossing = a.0.madd(a.1, b.0.sub(a.0).cross(b.1) / cross);
+        if crossing.len_with(v) <= 2.0 * tolerance {
+            return crossing;
+        }
+    }
+    line_project(a, v).mid(line_project(b, v))
+}
+
+// `(start, end, line)` of the straight runs of `points`, each grown greedily
+// from the end of the one before it.
+fn straight_runs(
+    points: &[DVec2],
+    tolerance: f64,
+    min_length: f64,
+) -> Vec<(usize, usize, (DVec2, DVec2))> {
+    let line = |start: usize, end: usize| {
+        let run = &points[start..=end];
+        let (center, direction) = line_fit(run);
+        run.iter()
+            .all(|v| direction.cross(v.sub(center)).abs() <= tolerance)
+            .then_some((center, direction))
+    };
+
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start + 1 < points.len() {
+        let mut end = start + 1;
+        let mut run_line = line(start, end).unwrap();
+        while let Some(longer) = points.get(end + 1).and_then(|_| line(start, end + 1)) {
+            (end, run_line) = (end + 1, longer);
+        }
+        match points[start].len_with(points[end]) >= min_length {
+            true => {
+                runs.push((start, end, run_line));
+                start = end;
             }
+            false => start += 1,
         }
-        // regular point
-        poly_dst.push(*v_orig_curr);
-        v_orig_prev = v_orig_curr;
     }
+    runs
+}
 
-    return poly_dst;
+/// Turn angle above which a vertex is subdivided with the full density of
+/// [`poly_subdivide_adaptive`], smaller turns allow proportionally longer steps.
+const SUBDIVIDE_SHARP_ANGLE: f64 = std::f64::consts::PI / 8.0;
+/// Distance turns of [`poly_subdivide_adaptive`] are measured over, as a multiple of its limit.
+const SUBDIVIDE_WINDOW_SCALE: f64 = 8.0;
+/// Longest step of [`poly_subdivide_adaptive`] along straight parts, as a multiple of its limit.
+const SUBDIVIDE_MAX_SCALE: f64 = 8.0;
+
+// Subdivide edges depending on how sharply the polygon turns at their ends,
+// densely (steps of `limit`) around sharp turns and sparsely along straight parts.
+pub fn poly_subdivide_adaptive(is_cyclic: bool, poly_src: &[DVec2], limit: f64) -> Vec<DVec2> {
+    let len = poly_src.len();
+    if len < 2 {
+        return poly_src.to_vec();
+    }
+
+    // Turn angle at every vertex, measured between the points about `window` away on both sides
+    // so the staircase of pixel outlines cancels out. The ends of open polygons count as sharp.
+    let window = limit * SUBDIVIDE_WINDOW_SCALE;
+    let walk = |i: usize, forward: bool| {
+        let (mut j, mut distance) = (i, 0.0);
+        for _ in 0..len / 2 {
+            let is_end = if forward { j == len - 1 } else { j == 0 };
+            if !is_cyclic && is_end {
+                break;
+            }
+            let j_next = if forward {
+                (j + 1) % len
+            } else {
+                (j + len - 1) % len
+            };
+            distance += poly_src[j].len_with(poly_src[j_next]);
+            j = j_next;
+            if distance >= window {
+                break;
+            }
+        }
+        poly_src[j]
+    };
+    let turns = (0..len)
+        .map(|i| {
+            if !is_cyclic && (i == 0 || i == len - 1) {
+                return std::f64::consts::PI;
+            }
+            let (d_in, d_out) = (
+                poly_src[i].normalized_diff(walk(i, false)),
+                walk(i, true).normalized_diff(poly_src[i]),
+            );
+            d_in.dot(d_out).clamp(-1.0, 1.0).acos()
+        })
+        .collect::<Vec<_>>();
+    let step = |turn: f64| {
+        (limit * SUBDIVIDE_SHARP_ANGLE / turn).clamp(limit, limit * SUBDIVIDE_MAX_SCALE)
+    };
+
+    let mut poly_dst: Vec<DVec2> = Vec::with_capacity(len * 2);
+    if !is_cyclic {
+        poly_dst.push(poly_src[0]);
+    }
+    for i in (if is_cyclic { 0 } else { 1 })..len {
+        let i_prev = (i + len - 1) % len;
+        let (v_prev, v_curr) = (poly_src[i_prev], poly_src[i]);
+        let turn = turns[i_prev].max(turns[i]);
+
+        let mut sub = (v_prev.len_with(v_curr) / step(turn)).floor() as usize;
+        // Keep a knot between sharp turns, so there's always a middle tangent.
+        if turn >= SUBDIVIDE_SHARP_ANGLE {
+            sub = sub.max(2);
+        }
+        for j in 1..sub {
+            poly_dst.push(v_prev.interp(v_curr, j as f64 / sub as f64));
+        }
+        poly_dst.push(v_curr);
+    }
+
+    poly_dst
 }
 
-pub fn poly_list_subdivide_to_limit(poly_list_src: &mut Vec<(bool, Vec<DVec2>)>, limit: f64) {
+pub fn poly_list_subdivide_adaptive(poly_list_src: &mut [(bool, Vec<DVec2>)], limit: f64) {
     poly_list_src.iter_mut().for_each(|(is_cyclic, poly_src)| {
-        *poly_src = poly_subdivide_to_limit(*is_cyclic, &poly_src, limit)
+        *poly_src = poly_subdivide_adaptive(*is_cyclic, poly_src, limit)
+    })
+}
+
+// Split a fitted curve (as returned by `fit_poly_list`) into segments starting at its first knot,
+// cubics whose handles lie on their chord become lines.
+pub fn curve_segments(is_cyclic: bool, curve: &[[DVec2; 3]]) -> Vec<Segment> {
+    let count = match is_cyclic {
+        true => curve.len(),
+        false => curve.len().saturating_sub(1),
+    };
+
+    (0..count)
+        .map(|i| {
+            let (k_prev, k_curr) = (&curve[i], &curve[(i + 1) % curve.len()]);
+            let cubic = CubicBezier {
+                start: k_prev[1],
+                control1: k_prev[2],
+                control2: k_curr[0],
+                end: k_curr[1],
+            };
+            if cubic_is_straight(&cubic) {
+                Segment::Line {
+                    start: cubic.start,
+                    end: cubic.end,
+                }
+            } else {
+                Segment::Cubic(cubic)
+            }
+        })
+        .collect()
+}
+
+// Whether both control points lie on the chord of the cubic, between its ends.
+fn cubic_is_straight(cubic: &CubicBezier) -> bool {
+    const EPSILON: f64 = 1e-6;
+    let chord = cubic.end.sub(cubic.start);
+    let chord_len_sq = chord.len_squared();
+    let on_chord = |v: DVec2| -> bool {
+        let d = v.sub(cubic.start);
+        if chord_len_sq == 0.0 {
+            return d.len_squared() <= EPSILON * EPSILON;
+        }
+        let cross = chord.x * d.y - chord.y * d.x;
+        let dot = chord.dot(d);
+        cross * cross <= EPSILON * EPSILON * chord_len_sq
+            && (-EPSILON..=chord_len_sq + EPSILON).contains(&dot)
+    };
+    on_chord(cubic.control1) && on_chord(cubic.control2)
+}
+
+// Largest distance of the vertices and edge midpoints of `polygon` from each segment
+// of the curve fitted to it, in the order of `curve_segments`,
+// every point counting towards the segment nearest to it.
+pub fn curve_fit_errors(is_cyclic: bool, curve: &[[DVec2; 3]], polygon: &[DVec2]) -> Vec<f64> {
+    let flattened = curve_segments(is_cyclic, curve)
+        .iter()
+        .map(|segment| {
+            let mut points = vec![segment.start()];
+            segment.flatten(0.01, &mut points);
+            let bbox = BoundingBox::from_points(points.iter().copied()).unwrap();
+            (bbox, points)
+        })
+        .collect::<Vec<_>>();
+    let mut errors = vec![0.0; flattened.len()];
+    if errors.is_empty() {
+        return errors;
+    }
+
+    let distance_squared = |point: DVec2, points: &[DVec2]| {
+        points
+            .windows(2)
+            .map(|w| segment_distance_squared(point, w[0], w[1]))
+            .fold(f64::INFINITY, f64::min)
+    };
+    let len = polygon.len();
+    let edges = match is_cyclic {
+        true => len,
+        false => len.saturating_sub(1),
+    };
+    let midpoints = (0..edges).map(|i| (polygon[i] + polygon[(i + 1) % len]) * 0.5);
+    // Neighboring points are usually nearest to the same segment, tried first.
+    let mut nearest = 0;
+    for point in polygon.iter().copied().chain(midpoints) {
+        let mut best = (distance_squared(point, &flattened[nearest].1), nearest);
+        for (i, (bbox, points)) in flattened.iter().enumerate() {
+            let dx = (bbox.min.x - point.x).max(point.x - bbox.max.x).max(0.0);
+            let dy = (bbox.min.y - point.y).max(point.y - bbox.max.y).max(0.0);
+            if i == nearest || dx * dx + dy * dy >= best.0 {
+                continue;
+            }
+            let distance = distance_squared(point, points);
+            if distance < best.0 {
+                best = (distance, i);
+            }
+        }
+        nearest = best.1;
+        errors[nearest] = f64::max(errors[nearest], best.0.sqrt());
+    }
+    errors
+}
+
+// Whether no part of a fitted curve crosses another one, including the loops of
+// single cubics. Parts only touching, e.g. at the knot between two segments
+// or along collapsed contours, don't cross.
+pub fn curve_is_simple(is_cyclic: bool, curve: &[[DVec2; 3]]) -> bool {
+    let flattened = curve_segments(is_cyclic, curve)
+        .iter()
+        .map(|segment| {
+            // Relative to the size of the segment, so the answer doesn't depend on the scale.
+            let bbox = segment.bbox();
+            let size = bbox.width().hypot(bbox.height());
+            let mut points = vec![segment.start()];
+            segment.flatten((size * 1e-3).max(1e-12), &mut points);
+            (bbox, points)
+        })
+        .collect::<Vec<_>>();
+
+    let overlaps = |a: &BoundingBox, b: &BoundingBox| {
+        a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+    };
+    let crosses = |a: [DVec2; 2], b: [DVec2; 2]| {
+        // Distances of a few ulps off a piece are rounding errors of points on it.
+        let side = |p: [DVec2; 2], q: DVec2| {
+            let d = p[1].sub(p[0]);
+            let distance = d.cross(q.sub(p[0])) / d.len_squared().sqrt();
+            match distance.abs() < 1e-9 {
+                true => 0.0,
+                false => distance,
+            }
+        };
+        side(a, b[0]) * side(a, b[1]) < 0.0 && side(b, a[0]) * side(b, a[1]) < 0.0
+    };
+    for (i, (bbox_a, points_a)) in flattened.iter().enumerate() {
+        for (bbox_b, points_b) in &flattened[i..] {
+            if !overlaps(bbox_a, bbox_b) {
+                continue;
+            }
+            let is_same = std::ptr::eq(points_a, points_b);
+            for (j, a) in points_a.windows(2).enumerate() {
+                // Neighboring pieces of a segment only share their end.
+                let skip = match is_same {
+                    true => j + 2,
+                    false => 0,
+                };
+                let mut pieces = points_b.windows(2).skip(skip);
+                if pieces.any(|b| crosses([a[0], a[1]], [b[0], b[1]])) {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+// Flatten segments into a polygon, cyclic polygons don't repeat their first point.
+pub fn segments_flatten(is_cyclic: bool, segments: &[Segment], tolerance: f64) -> Vec<DVec2> {
+    let mut poly: Vec<DVec2> = Vec::with_capacity(segments.len() * 4);
+    let Some(first) = segments.first() else {
+        return poly;
+    };
+
+    poly.push(first.start());
+    for segment in segments {
+        segment.flatten(tolerance, &mut poly);
+    }
+    if is_cyclic {
+        poly.pop();
+    }
+
+    poly
+}
+
+// Move a fitted curve by `distance` to the right of its direction,
+// which is outward for traced outlines as they keep the filled pixels on their left.
+// Knots move along with their handles, corners are mitered.
+pub fn curve_offset(is_cyclic: bool, curve: &mut [[DVec2; 3]], distance: f64) {
+    let len = curve.len();
+    if len < 2 || distance == 0.0 {
+        return;
+    }
+
+    // Straight knots have their handles on the point, fall back to the neighbors.
+    let first_non_zero = |vectors: [DVec2; 3]| -> DVec2 {
+        vectors
+            .into_iter()
+            .find(|v| v.len_squared() != 0.0)
+            .unwrap_or(DVec2::ZERO)
+    };
+    let normal = |tangent: DVec2| DVec2::new(-tangent.y, tangent.x).normalized();
+
+    let offsets = (0..len)
+        .map(|i| {
+            let [handle_in, point, handle_out] = curve[i];
+            let prev = &curve[(i + len - 1) % len];
+            let next = &curve[(i + 1) % len];
+            let tangent_in =
+                first_non_zero([point.sub(handle_in), point.sub(prev[2]), point.sub(prev[1])]);
+            let tangent_out = first_non_zero([
+                handle_out.sub(point),
+                next[0].sub(point),
+                next[1].sub(point),
+            ]);
+            let (normal_in, normal_out) = match (is_cyclic, i) {
+                (false, 0) => (normal(tangent_out), normal(tangent_out)),
+                (false, i) if i == len - 1 => (normal(tangent_in), normal(tangent_in)),
+                _ => (normal(tangent_in), normal(tangent_out)),
+            };
+
+            let mut miter = normal_in.add(normal_out);
+            if miter.normalize() == 0.0 {
+                miter = normal_in;
+            }
+            // Limit the miter of sharp corners to twice the distance.
+            miter.mul(distance / miter.dot(normal_in).max(0.5))
+        })
+        .collect::<Vec<DVec2>>();
+
+    for (knot, offset) in curve.iter_mut().zip(offsets) {
+        knot.iter_mut().for_each(|v| *v = v.add(offset));
+    }
+}
+
+/// `v` rotated by `angle` radians, from the X axis toward the Y axis.
+fn rotate(v: DVec2, angle: f64) -> DVec2 {
+    let (sin, cos) = angle.sin_cos();
+    DVec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// Normal to the right of a unit tangent, outward for traced outlines, see [`curve_offset`].
+fn right_normal(tangent: DVec2) -> DVec2 {
+    DVec2::new(-tangent.y, tangent.x)
+}
+
+/// Corner of a contour moved by a distance, see [`offset_corner`].
+enum OffsetCorner {
+    /// The moved edges meet at a point.
+    Point(DVec2),
+    /// The moved edges are joined by a line between the two points.
+    Bevel(DVec2, DVec2),
+    /// The moved edges are joined by an arc around the corner from the point,
+    /// turning by the signed angle.
+    Arc(DVec2, f64),
+}
+
+/// Corner at `point` of a contour moved by `distance` to its right,
+/// between the unit tangents of its incoming and outgoing edges.
+fn offset_corner(
+    point: DVec2,
+    tangent_in: DVec2,
+    tangent_out: DVec2,
+    distance: f64,
+    offset: &OffsetOptions,
+) -> OffsetCorner {
+    let (normal_in, normal_out) = (right_normal(tangent_in), right_normal(tangent_out));
+    let turn = tangent_in
+        .cross(tangent_out)
+        .atan2(tangent_in.dot(tangent_out));
+    let mut bisector = normal_in.add(normal_out);
+    if bisector.normalize() == 0.0 {
+        // The contour turns back on itself.
+        let (start, end) = (
+            point.madd(normal_in, distance),
+            point.madd(normal_out, distance),
+        );
+        return OffsetCorner::Bevel(start, end);
+    }
+    // Distance from the corner to where the moved edges cross, relative to `distance`.
+    let miter = 1.0 / (turn.abs() * 0.5).cos();
+    let miter_limit = offset.miter_limit.max(1.0);
+
+    // The edges part on the side they are moved to when the contour turns away from it.
+    let is_parting = turn * distance < 0.0 && turn.abs() > 1e-3;
+    match offset.join {
+        _ if !is_parting => {
+            OffsetCorner::Point(point.madd(bisector, distance * miter.min(miter_limit)))
+        }
+        LineJoin::Miter if miter <= miter_limit => {
+            OffsetCorner::Point(point.madd(bisector, distance * miter))
+        }
+        LineJoin::Round => OffsetCorner::Arc(point.madd(normal_in, distance), turn),
+        LineJoin::Miter | LineJoin::Bevel => OffsetCorner::Bevel(
+            point.madd(normal_in, distance),
+            point.madd(normal_out, distance),
+        ),
+    }
+}
+
+/// Moves the edges of a polygon by `distance` to their right, outward for traced outlines,
+/// negative distances moving them inward, joining them at the corners as set by `offset`.
+///
+/// Round joins are flattened within a tenth of a unit.
+pub fn polygon_offset(
+    is_cyclic: bool,
+    poly: &[DVec2],
+    distance: f64,
+    offset: &OffsetOptions,
+) -> Vec<DVec2> {
+    let mut points = poly.to_vec();
+    points.dedup();
+    if is_cyclic && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let len = points.len();
+    if len < 2 || distance == 0.0 {
+        return poly.to_vec();
+    }
+
+    let tangent = |i: usize| points[(i + 1) % len].sub(points[i]).normalized();
+    let max_step = match distance.abs() > 0.1 {
+        true => 2.0 * (1.0 - 0.1 / distance.abs()).acos(),
+        false => std::f64::consts::FRAC_PI_2,
+    };
+
+    let mut moved = Vec::with_capacity(len);
+    for (i, point) in points.iter().enumerate() {
+        let (tangent_in, tangent_out) = match (is_cyclic, i) {
+            (false, 0) => (tangent(0), tangent(0)),
+            (false, i) if i == len - 1 => (tangent(i - 1), tangent(i - 1)),
+            (_, i) => (tangent((i + len - 1) % len), tangent(i)),
+        };
+        match offset_corner(*point, tangent_in, tangent_out, distance, offset) {
+            OffsetCorner::Point(p) => moved.push(p),
+            OffsetCorner::Bevel(start, end) => moved.extend([start, end]),
+            OffsetCorner::Arc(start, turn) => {
+                let steps = (turn.abs() / max_step).ceil().max(1.0) as usize;
+                moved.extend((0..=steps).map(|step| {
+                    point.add(rotate(start.sub(*point), turn * step as f64 / steps as f64))
+                }));
+            }
+        }
+    }
+    moved
+}
+
+/// Moves a fitted curve of `[handle_in, point, handle_out]` knots by `distance` to its right,
+/// see [`polygon_offset`]. Round joins are arcs of cubics, and the handles of every
+/// segment are scaled along with its chord, following the change of curvature.
+pub fn curve_offset_joined(
+    is_cyclic: bool,
+    curve: &[[DVec2; 3]],
+    distance: f64,
+    offset: &OffsetOptions,
+) -> Vec<[DVec2; 3]> {
+    let len = curve.len();
+    if len < 2 || distance == 0.0 {
+        return curve.to_vec();
+    }
+
+    // Straight knots have their handles on the point, fall back to the neighbors.
+    let first_non_zero = |vectors: [DVec2; 3]| -> DVec2 {
+        vectors
+            .into_iter()
+            .find(|v| v.len_squared() != 0.0)
+            .unwrap_or(DVec2::ZERO)
+            .normalized()
+    };
+
+    // New knots of every knot, whose outer handles are set along with their segments below.
+    let mut knots = Vec::with_capacity(len);
+    for i in 0..len {
+        let [handle_in, point, handle_out] = curve[i];
+        let prev = &curve[(i + len - 1) % len];
+        let next = &curve[(i + 1) % len];
+        let tangent_in =
+            first_non_zero([point.sub(handle_in), point.sub(prev[2]), point.sub(prev[1])]);
+        let tangent_out = first_non_zero([
+            handle_out.sub(point),
+            next[0].sub(point),
+            next[1].sub(point),
+        ]);
+        let corner = match (is_cyclic, i) {
+            (false, 0) => OffsetCorner::Point(point.madd(right_normal(tangent_out), distance)),
+            (false, i) if i == len - 1 => {
+                OffsetCorner::Point(point.madd(right_normal(tangent_in), distance))
+            }
+            _ => offset_corner(point, tangent_in, tangent_out, distance, offset),
+        };
+        knots.push(match corner {
+            OffsetCorner::Point(p) => vec![[p; 3]],
+            OffsetCorner::Bevel(start, end) => vec![[start; 3], [end; 3]],
+            OffsetCorner::Arc(start, turn) => {
+                let steps = (turn.abs() / std::f64::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+                let step = turn / steps as f64;
+                let handle = 4.0 / 3.0 * (step.abs() / 4.0).tan() * distance.abs();
+                (0..=steps)
+                    .map(|j| {
+                        let p = point.add(rotate(start.sub(point), step * j as f64));
+                        let tangent = rotate(tangent_in, step * j as f64);
+                        [p.madd(tangent, -handle), p, p.madd(tangent, handle)]
+                    })
+                    .collect()
+            }
+        });
+    }
+
+    let segments = match is_cyclic {
+        true => len,
+        false => len - 1,
+    };
+    for i in 0..segments {
+        let j = (i + 1) % len;
+        let (start, end) = (knots[i].last().unwrap()[1], knots[j][0][1]);
+        let chord = curve[i][1].len_with(curve[j][1]);
+        let scale = match chord > 0.0 {
+            true => start.len_with(end) / chord,
+            false => 1.0,
+        };
+        knots[i].last_mut().unwrap()[2] = start.madd(curve[i][2].sub(curve[i][1]), scale);
+        knots[j][0][0] = end.madd(curve[j][0].sub(curve[j][1]), scale);
+    }
+    if !is_cyclic {
+        knots[0][0][0] = knots[0][0][1].add(curve[0][0].sub(curve[0][1]));
+        let last = knots[len - 1].last_mut().unwrap();
+        last[2] = last[1].add(curve[len - 1][2].sub(curve[len - 1][1]));
+    }
+    knots.into_iter().flatten().collect()
+}
+
+/// Signed area of a closed polygon, positive when it winds clockwise
+/// in image coordinates, with the Y axis pointing down.
+pub fn polygon_area(poly: &[DVec2]) -> f64 {
+    match poly.is_empty() {
+        true => 0.0,
+        false => ring_area2(poly) * 0.5,
+    }
+}
+
+/// Whether a closed polygon winds clockwise in image coordinates, see [`polygon_area`].
+/// Polygons without area are not.
+pub fn is_clockwise(poly: &[DVec2]) -> bool {
+    polygon_area(poly) > 0.0
+}
+
+/// Signed area of a closed curve of `[handle_in, point, handle_out]` knots,
+/// with the sign of [`polygon_area`].
+pub fn curve_area(curve: &[[DVec2; 3]]) -> f64 {
+    curve_segments(true, curve)
+        .iter()
+        .map(Segment::signed_area)
+        .sum()
+}
+
+/// Reverses the direction of a curve of `[handle_in, point, handle_out]` knots.
+pub fn curve_reverse(curve: &mut [[DVec2; 3]]) {
+    curve.reverse();
+    curve.iter_mut().for_each(|knot| knot.swap(0, 2));
+}
+
+/// Smooths a fitted curve of `[handle_in, point, handle_out]` knots by `smoothness` in `0..=1`.
+///
+/// Every smooth knot moves toward the midpoint of its neighbors, by at most `max_deviation`,
+/// and the lengths of its handles are drawn toward their mean. Corners, whose handles
+/// are not aligned, and the ends of open curves are kept as they are.
+pub fn curve_smooth(
+    is_cyclic: bool,
+    curve: &mut [[DVec2; 3]],
+    smoothness: f64,
+    max_deviation: f64,
+) {
+    let len = curve.len();
+    let smoothness = smoothness.clamp(0.0, 1.0);
+    if len < 3 || smoothness == 0.0 {
+        return;
+    }
+
+    let is_smooth = (0..len)
+        .map(|i| {
+            let [handle_in, point, handle_out] = curve[i];
+            let (tangent_in, tangent_out) = (point.sub(handle_in), handle_out.sub(point));
+            let lengths = tangent_in.len() * tangent_out.len();
+            (is_cyclic || (i != 0 && i != len - 1))
+                && lengths > 0.0
+                && tangent_in.dot(tangent_out) > 0.0
+                && tangent_in.cross(tangent_out).abs() <= 1e-3 * lengths
+        })
+        .collect::<Vec<bool>>();
+
+    // Moved from the knots before smoothing, so the result doesn't depend on the start.
+    let offsets = (0..len)
+        .map(|i| {
+            if !is_smooth[i] {
+                return DVec2::ZERO;
+            }
+            let midpoint = curve[(i + len - 1) % len][1]
+                .add(curve[(i + 1) % len][1])
+                .mul(0.5);
+            let offset = midpoint.sub(curve[i][1]).mul(smoothness);
+            let distance = offset.len();
+            match distance > max_deviation {
+                true => offset.mul(max_deviation / distance),
+                false => offset,
+            }
+        })
+        .collect::<Vec<DVec2>>();
+
+    for ((knot, offset), is_smooth) in curve.iter_mut().zip(offsets).zip(is_smooth) {
+        if !is_smooth {
+            continue;
+        }
+        let [handle_in, point, handle_out] = *knot;
+        let (length_in, length_out) = (point.len_with(handle_in), point.len_with(handle_out));
+        let mean = (length_in + length_out) * 0.5;
+        let point = point.add(offset);
+        *knot = [
+            point.sub(
+                point
+                    .sub(handle_in.add(offset))
+                    .normalized()
+                    .mul(length_in + (mean - length_in) * smoothness),
+            ),
+            point,
+            point.add(
+                handle_out
+                    .add(offset)
+                    .sub(point)
+                    .normalized()
+                    .mul(length_out + (mean - length_out) * smoothness),
+            ),
+        ];
+    }
+}
+
+/// Twice the signed area, positive for counter-clockwise rings with the Y axis pointing up,
+/// which are clockwise in image coordinates.
+pub fn ring_area2(ring: &[DVec2]) -> f64 {
+    let mut area = 0.0;
+    let mut v_prev = &ring[ring.len() - 1];
+    for v_curr in ring {
+        area += v_prev.x * v_curr.y - v_curr.x * v_prev.y;
+        v_prev = v_curr;
+    }
+    area
+}
+
+/// Even-odd point in polygon test.
+pub fn ring_contains(ring: &[DVec2], p: DVec2) -> bool {
+    let mut inside = false;
+    let mut v_prev = &ring[ring.len() - 1];
+    for v_curr in ring {
+        if (v_curr.y > p.y) != (v_prev.y > p.y)
+            && p.x < (v_prev.x - v_curr.x) * (p.y - v_curr.y) / (v_prev.y - v_curr.y) + v_curr.x
+        {
+            inside = !inside;
+        }
+        v_prev = v_curr;
+    }
+    inside
+}
+
+/// Vertices of a contour tested against the contours around it,
+/// most of them have to be inside for the contour to be nested.
+const CONTOUR_PROBES: usize = 7;
+
+/// Edges of a ring bucketed by rows, so point in polygon tests only visit the edges
+/// crossing the row of the point, the same as [`ring_contains`] otherwise.
+struct RingRows {
+    min_y: f64,
+    row_height: f64,
+    /// Indices `i` of the edges `ring[i - 1]..ring[i]` spanning each row.
+    rows: Vec<Vec<usize>>,
+}
+
+impl RingRows {
+    fn new(ring: &[DVec2], bbox: &BoundingBox) -> Self {
+        let count = ((ring.len() as f64).sqrt().ceil() as usize).max(1);
+        let row_height = (bbox.height() / count as f64).max(f64::MIN_POSITIVE);
+        let mut rows = vec![Vec::new(); count];
+        let row = |y: f64| (((y - bbox.min.y) / row_height) as usize).min(count - 1);
+        for i in 0..ring.len() {
+            let (a, b) = (ring[(i + ring.len() - 1) % ring.len()], ring[i]);
+            for edges in &mut rows[row(a.y.min(b.y))..=row(a.y.max(b.y))] {
+                edges.push(i);
+            }
+        }
+        Self {
+            min_y: bbox.min.y,
+            row_height,
+            rows,
+        }
+    }
+
+    fn contains(&self, ring: &[DVec2], p: DVec2) -> bool {
+        let row = ((p.y - self.min_y) / self.row_height) as usize;
+        let mut inside = false;
+        for &i in &self.rows[row.min(self.rows.len() - 1)] {
+            let (v_prev, v_curr) = (ring[(i + ring.len() - 1) % ring.len()], ring[i]);
+            if (v_curr.y > p.y) != (v_prev.y > p.y)
+                && p.x < (v_prev.x - v_curr.x) * (p.y - v_curr.y) / (v_prev.y - v_curr.y) + v_curr.x
+            {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+}
+
+/// Nests the contours of every layer into the smallest larger contour containing them,
+/// see [`crate::structs::VectorizationResult::contour_tree`].
+///
+/// Holes are told from outer contours by their winding, as set by `orientation`.
+pub fn contour_tree(layers: &[Layer], orientation: Orientation) -> Vec<ContourNode> {
+    struct Contour<'a> {
+        ring: &'a [DVec2],
+        is_cyclic: bool,
+        area: f64,
+        bbox: Option<BoundingBox>,
+        /// Built the first time another contour is tested against this one.
+        rows: Option<RingRows>,
+    }
+
+    let mut nodes = Vec::new();
+    let mut contours = Vec::new();
+    for (layer_index, layer) in layers.iter().enumerate() {
+        for (contour_index, (is_cyclic, ring)) in layer.polygons.iter().enumerate() {
+            let area2 = if ring.is_empty() {
+                0.0
+            } else {
+                ring_area2(ring)
+            };
+            nodes.push(ContourNode {
+                layer: layer_index,
+                contour: contour_index,
+                parent: None,
+                children: Vec::new(),
+                is_hole: *is_cyclic
+                    && area2 != 0.0
+                    && (area2 > 0.0) == (orientation == Orientation::OuterCounterClockwise),
+            });
+            contours.push(Contour {
+                ring,
+                is_cyclic: *is_cyclic,
+                area: area2.abs() * 0.5,
+                bbox: BoundingBox::from_points(ring.iter().copied()),
+                rows: None,
+            });
+        }
+    }
+
+    // From the smallest contour, the first larger one containing it is its parent.
+    let mut order = (0..contours.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| contours[*a].area.total_cmp(&contours[*b].area));
+
+    for (rank, &i) in order.iter().enumerate() {
+        let (ring, area) = (contours[i].ring, contours[i].area);
+        let Some(inner_bbox) = contours[i].bbox else {
+            continue;
+        };
+        let step = ring.len().div_ceil(CONTOUR_PROBES);
+        // Edge midpoints, as vertices may lie on the edges of neighboring layers.
+        let probes = (0..ring.len())
+            .step_by(step)
+            .map(|k| ring[k].mid(ring[(k + 1) % ring.len()]))
+            .collect::<Vec<_>>();
+
+        let parent = order[rank + 1..].iter().copied().find(|&j| {
+            let outer = &mut contours[j];
+            let Some(bbox) = outer.bbox else {
+                return false;
+            };
+            if !outer.is_cyclic
+                || outer.ring.len() < 3
+                || outer.area <= area
+                || !(bbox.contains(inner_bbox.min) && bbox.contains(inner_bbox.max))
+            {
+                return false;
+            }
+            let rows = outer
+                .rows
+                .get_or_insert_with(|| RingRows::new(outer.ring, &bbox));
+            let inside = probes
+                .iter()
+                .filter(|p| rows.contains(outer.ring, **p))
+                .count();
+            inside * 2 > probes.len()
+        });
+        if let Some(parent) = parent {
+            nodes[i].parent = Some(parent);
+            nodes[parent].children.push(i);
+        }
+    }
+
+    nodes
+}
+
+/// `(center, radius)` of the circle best fitting the points, the least squares
+/// solution of the algebraic circle equation `x² + y² + Dx + Ey + F = 0` (Kåsa fit).
+pub fn circle_fit(points: &[DVec2]) -> Option<(DVec2, f64)> {
+    let (mean, scale, points) = normalize_points(points)?;
+    let [d, e, f] = least_squares(points.iter().map(|v| ([v.x, v.y, 1.0], -v.len_squared())))?;
+    let radius_sq = (d * d + e * e) / 4.0 - f;
+    (radius_sq > 0.0).then(|| {
+        let center = DVec2::new(-d / 2.0, -e / 2.0);
+        (mean.madd(center, scale), radius_sq.sqrt() * scale)
+    })
+}
+
+/// `(center, radii, angle)` of the ellipse best fitting the points, the least squares solution
+/// of the algebraic conic equation `x² + Bxy + Cy² + Dx + Ey + F = 0`, `None` when that conic
+/// isn't an ellipse. The first radius is the larger one, `angle` is its direction
+/// from the X axis in radians, within `-PI / 2..PI / 2`.
+pub fn ellipse_fit(points: &[DVec2]) -> Option<(DVec2, [f64; 2], f64)> {
+    let (mean, scale, points) = normalize_points(points)?;
+    let [b, c, d, e, f] = least_squares(
+        points
+            .iter()
+            .map(|v| ([v.x * v.y, v.y * v.y, v.x, v.y, 1.0], -v.x * v.x)),
+    )?;
+    let det = 4.0 * c - b * b;
+    if det <= 0.0 {
+        return None;
+    }
+    let center = DVec2::new((b * e - 2.0 * c * d) / det, (b * d - 2.0 * e) / det);
+    // The conic around its center is `x² + Bxy + Cy² = -F'`.
+    let f = f + (d * center.x + e * center.y) / 2.0;
+    let angle = 0.5 * b.atan2(1.0 - c);
+    let (sin, cos) = angle.sin_cos();
+    let eigenvalues = [
+        cos * cos + b * cos * sin + c * sin * sin,
+        sin * sin - b * cos * sin + c * cos * cos,
+    ];
+    let radii = eigenvalues.map(|eigenvalue| -f / eigenvalue);
+    radii.iter().all(|radius_sq| *radius_sq > 0.0).then(|| {
+        let [a, b] = radii.map(|radius_sq| radius_sq.sqrt() * scale);
+        let (radii, angle) = match a >= b {
+            true => ([a, b], angle),
+            false if angle > 0.0 => ([b, a], angle - std::f64::consts::FRAC_PI_2),
+            false => ([b, a], angle + std::f64::consts::FRAC_PI_2),
+        };
+        (mean.madd(center, scale), radii, angle)
     })
 }
+
+/// Largest distance between the points and the `(center, radii, angle)` ellipse of
+/// [`ellipse_fit`], measured toward its center, infinite when the points leave
+/// more than an eighth of its turn uncovered, e.g. along an arc rather than around it.
+pub fn ellipse_deviation(points: &[DVec2], (center, radii, angle): (DVec2, [f64; 2], f64)) -> f64 {
+    let mut turns = Vec::with_capacity(points.len());
+    let mut deviation = 0.0f64;
+    for v in points {
+        let local = v.sub(center).rotate(-angle);
+        let (x, y) = (local.x / radii[0], local.y / radii[1]);
+        let scale = x.hypot(y);
+        deviation = deviation.max(match scale > 0.0 {
+            true => local.len() * (1.0 - 1.0 / scale).abs(),
+            false => radii[0].min(radii[1]),
+        });
+        turns.push(y.atan2(x));
+    }
+    turns.sort_by(f64::total_cmp);
+    let wrap = turns
+        .first()
+        .zip(turns.last())
+        .map(|(first, last)| first + std::f64::consts::TAU - last);
+    let largest_gap = turns
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .chain(wrap)
+        .fold(0.0, f64::max);
+    match largest_gap <= std::f64::consts::TAU / 8.0 {
+        true => deviation,
+        false => f64::INFINITY,
+    }
+}
+
+/// `(center, size, angle)` of the rectangle bounding the closed polygon, its sides along
+/// the mean direction of the edges modulo a quarter turn, weighted by their lengths.
+/// `angle` is the direction of its width within `-PI / 4..PI / 4`, `None` without points.
+pub fn rect_fit(poly: &[DVec2]) -> Option<(DVec2, [f64; 2], f64)> {
+    // Edge directions are averaged four times over, so that perpendicular ones agree.
+    let sum =
+        poly.iter()
+            .zip(poly.iter().cycle().skip(1))
+            .fold(DVec2::new(0.0, 0.0), |sum, (a, b)| {
+                let edge = b.sub(*a);
+                let angle = 4.0 * edge.y.atan2(edge.x);
+                sum.madd(DVec2::new(angle.cos(), angle.sin()), edge.len())
+            });
+    let angle = sum.y.atan2(sum.x) / 4.0;
+    let local = poly.iter().map(|v| v.rotate(-angle));
+    let bbox = BoundingBox::from_points(local)?;
+    let center = bbox.min.mid(bbox.max).rotate(angle);
+    Some((center, [bbox.width(), bbox.height()], angle))
+}
+
+/// The corners of the `(center, size, angle)` rectangle of [`rect_fit`].
+pub fn rect_corners((center, [width, height], angle): (DVec2, [f64; 2], f64)) -> [DVec2; 4] {
+    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .map(|(x, y)| center.add(DVec2::new(x * width / 2.0, y * height / 2.0).rotate(angle)))
+}
+
+/// The vertices of the regular polygon best following `vertices`, in the same order,
+/// around their mean at their mean distance from it.
+pub fn regular_polygon_fit(vertices: &[DVec2]) -> Vec<DVec2> {
+    let n = vertices.len() as f64;
+    let center = vertices
+        .iter()
+        .fold(DVec2::new(0.0, 0.0), |sum, v| sum.add(*v))
+        .mul(1.0 / n);
+    let radius = vertices.iter().map(|v| v.len_with(center)).sum::<f64>() / n;
+    let step = std::f64::consts::TAU / n * polygon_area(vertices).signum();
+    // Every vertex turned back by its step lands on the direction of the first one.
+    let first = vertices
+        .iter()
+        .enumerate()
+        .fold(DVec2::new(0.0, 0.0), |sum, (i, v)| {
+            sum.add(v.sub(center).rotate(-step * i as f64))
+        });
+    let first = first.normalized().mul(radius);
+    (0..vertices.len())
+        .map(|i| center.add(first.rotate(step * i as f64)))
+        .collect()
+}
+
+/// Largest distance between the points and the closed polygon of `vertices`,
+/// either from a point to the polygon or from a vertex to the nearest point.
+pub fn polygon_deviation(points: &[DVec2], vertices: &[DVec2]) -> f64 {
+    let edges = || vertices.iter().zip(vertices.iter().cycle().skip(1));
+    let to_polygon = points.iter().map(|v| {
+        edges()
+            .map(|(a, b)| segment_distance_squared(*v, *a, *b))
+            .fold(f64::INFINITY, f64::min)
+    });
+    let to_points = vertices.iter().map(|vertex| {
+        points
+            .iter()
+            .map(|v| v.len_squared_with(*vertex))
+            .fold(f64::INFINITY, f64::min)
+    });
+    to_polygon.chain(to_points).fold(0.0, f64::max).sqrt()
+}
+
+// `(mean, scale, points)` with the points moved around their mean
+// and scaled to a unit RMS distance from it, so the fits are well conditioned.
+fn normalize_points(points: &[DVec2]) -> Option<(DVec2, f64, Vec<DVec2>)> {
+    if points.len() < 5 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean = points
+        .iter()
+        .fold(DVec2::new(0.0, 0.0), |sum, v| sum.add(*v))
+        .mul(1.0 / n);
+    let scale = (points.iter().map(|v| v.len_squared_with(mean)).sum::<f64>() / n).sqrt();
+    (scale > 0.0).then(|| {
+        let points = points
+            .iter()
+            .map(|v| v.sub(mean).mul(1.0 / scale))
+            .collect();
+        (mean, scale, points)
+    })
+}
+
+// Least squares solution of the `(coefficients, value)` rows, from their normal equations
+// by Gaussian elimination, `None` when they are singular.
+fn least_squares<const N: usize>(rows: impl Iterator<Item = ([f64; N], f64)>) -> Option<[f64; N]> {
+    let mut a = [[0.0; N]; N];
+    let mut b = [0.0; N];
+    for (row, value) in rows {
+        for i in 0..N {
+            for j in 0..N {
+                a[i][j] += row[i] * row[j];
+            }
+            b[i] += row[i] * value;
+        }
+    }
+
+    for col in 0..N {
+        let pivot = (col..N).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col];
+        for row in col + 1..N {
+            let factor = a[row][col] / pivot_row[col];
+            for (value, pivot) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let sum = (row + 1..N).map(|k| a[row][k] * x[k]).sum::<f64>();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
@@ -1,3 +1,8 @@
+use core::ops::{Add, AddAssign, Index, Mul, Neg, Sub};
+
+#[cfg(not(feature = "std"))]
+use crate::math::F64Ext;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct DVec2 {
     pub x: f64,
@@ -53,24 +58,20 @@ impl DVec2 {
         self.x * other.x + self.y * other.y
     }
 
-    /// Adds two vectors.
+    /// Adds two vectors, as `self + other` without [`Add`] in scope.
+    #[allow(clippy::should_implement_trait)]
     #[inline]
     #[must_use]
     pub fn add(self, other: Self) -> Self {
-        Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
-        }
+        self + other
     }
 
-    /// Subtracts `other` from self.
+    /// Subtracts `other` from self, as `self - other` without [`Sub`] in scope.
+    #[allow(clippy::should_implement_trait)]
     #[inline]
     #[must_use]
     pub fn sub(self, other: Self) -> Self {
-        Self {
-            x: self.x - other.x,
-            y: self.y - other.y,
-        }
+        self - other
     }
 
     /// Returns the midpoint between two vectors.
@@ -114,14 +115,12 @@ impl DVec2 {
         }
     }
 
-    /// Multiplies the vector by a scalar.
+    /// Multiplies the vector by a scalar, as `self * f` without [`Mul`] in scope.
+    #[allow(clippy::should_implement_trait)]
     #[inline]
     #[must_use]
     pub fn mul(self, f: f64) -> Self {
-        Self {
-            x: self.x * f,
-            y: self.y * f,
-        }
+        self * f
     }
 
     /// Returns the squared length of the vector.
@@ -226,6 +225,42 @@ impl DVec2 {
     pub fn project_plane(self, plane: Self) -> Self {
         self.sub(self.project_onto_normalized(plane))
     }
+
+    /// Returns the vector rotated by 90 degrees counter-clockwise (y up).
+    #[inline]
+    #[must_use]
+    pub fn perp(self) -> Self {
+        Self {
+            x: -self.y,
+            y: self.x,
+        }
+    }
+
+    /// 2D cross product, the z component of the 3D cross product of the two vectors.
+    /// Positive when `other` is counter-clockwise of self (y up).
+    #[inline]
+    #[must_use]
+    pub fn cross(self, other: Self) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// Signed angle in radians from self to `other`, in `[-PI, PI]`.
+    #[inline]
+    #[must_use]
+    pub fn angle_between(self, other: Self) -> f64 {
+        self.cross(other).atan2(self.dot(other))
+    }
+
+    /// Returns the vector rotated by `angle` radians counter-clockwise (y up).
+    #[inline]
+    #[must_use]
+    pub fn rotate(self, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            x: self.x * cos - self.y * sin,
+            y: self.x * sin + self.y * cos,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -289,3 +324,213 @@ impl USizeVec2 {
         DVec2::new(self.x as f64, self.y as f64)
     }
 }
+
+impl Add for DVec2 {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for DVec2 {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl Mul<f64> for DVec2 {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, f: f64) -> Self {
+        Self {
+            x: self.x * f,
+            y: self.y * f,
+        }
+    }
+}
+
+impl Neg for DVec2 {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        self.negated()
+    }
+}
+
+impl AddAssign for DVec2 {
+    #[inline]
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Index<usize> for DVec2 {
+    type Output = f64;
+
+    /// `x` at 0 and `y` at 1, panics on other indices.
+    #[inline]
+    fn index(&self, index: usize) -> &f64 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("index out of bounds: DVec2 has 2 components but the index is {index}"),
+        }
+    }
+}
+
+impl From<(f64, f64)> for DVec2 {
+    #[inline]
+    fn from((x, y): (f64, f64)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<[f64; 2]> for DVec2 {
+    #[inline]
+    fn from([x, y]: [f64; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<DVec2> for [f64; 2] {
+    #[inline]
+    fn from(v: DVec2) -> Self {
+        [v.x, v.y]
+    }
+}
+
+impl Add for IVec2 {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for IVec2 {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl Mul<i32> for IVec2 {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, f: i32) -> Self {
+        Self {
+            x: self.x * f,
+            y: self.y * f,
+        }
+    }
+}
+
+impl Neg for IVec2 {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Self {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+impl AddAssign for IVec2 {
+    #[inline]
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Index<usize> for IVec2 {
+    type Output = i32;
+
+    /// `x` at 0 and `y` at 1, panics on other indices.
+    #[inline]
+    fn index(&self, index: usize) -> &i32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("index out of bounds: IVec2 has 2 components but the index is {index}"),
+        }
+    }
+}
+
+impl From<(i32, i32)> for IVec2 {
+    #[inline]
+    fn from((x, y): (i32, i32)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<[i32; 2]> for IVec2 {
+    #[inline]
+    fn from([x, y]: [i32; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<IVec2> for [i32; 2] {
+    #[inline]
+    fn from(v: IVec2) -> Self {
+        [v.x, v.y]
+    }
+}
+
+#[cfg(feature = "glam")]
+mod glam_interop {
+    use super::{DVec2, IVec2};
+
+    impl From<glam::DVec2> for DVec2 {
+        #[inline]
+        fn from(v: glam::DVec2) -> Self {
+            Self::new(v.x, v.y)
+        }
+    }
+
+    impl From<DVec2> for glam::DVec2 {
+        #[inline]
+        fn from(v: DVec2) -> Self {
+            Self::new(v.x, v.y)
+        }
+    }
+
+    impl From<glam::IVec2> for IVec2 {
+        #[inline]
+        fn from(v: glam::IVec2) -> Self {
+            Self::new(v.x, v.y)
+        }
+    }
+
+    impl From<IVec2> for glam::IVec2 {
+        #[inline]
+        fn from(v: IVec2) -> Self {
+            Self::new(v.x, v.y)
+        }
+    }
+}
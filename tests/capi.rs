@@ -0,0 +1,97 @@
+use std::{
+    env,
+    ffi::{c_char, CStr},
+    io::Cursor,
+    ptr,
+};
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use svgen::{
+    capi::{
+        svgen_create_svg, svgen_free_string, svgen_options_default, SvgenColorMode, SvgenStatus,
+    },
+    structs::{ColorMode, Options},
+    try_create_svg,
+};
+
+/// A black square on white, as a PNG.
+fn png() -> Vec<u8> {
+    let image = RgbaImage::from_fn(32, 32, |x, y| {
+        match (8..24).contains(&x) && (8..24).contains(&y) {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+#[test]
+fn create_svg() {
+    let png = png();
+    let mut options = svgen_options_default();
+    options.color_mode = SvgenColorMode::Black;
+    let mut svg: *mut c_char = ptr::null_mut();
+    let status = unsafe { svgen_create_svg(png.as_ptr(), png.len(), &options, &mut svg) };
+    assert_eq!(status, SvgenStatus::Ok);
+
+    let expected = try_create_svg(
+        &png,
+        &Options {
+            color_mode: ColorMode::Black,
+            ..Options::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(unsafe { CStr::from_ptr(svg) }.to_str().unwrap(), expected);
+    unsafe { svgen_free_string(svg) };
+
+    // Null options are the defaults.
+    let status = unsafe { svgen_create_svg(png.as_ptr(), png.len(), ptr::null(), &mut svg) };
+    assert_eq!(status, SvgenStatus::Ok);
+    let expected = try_create_svg(&png, &Options::default()).unwrap();
+    assert_eq!(unsafe { CStr::from_ptr(svg) }.to_str().unwrap(), expected);
+    unsafe { svgen_free_string(svg) };
+}
+
+#[test]
+fn create_svg_outside_the_repo() {
+    // Preprocessing is on by default and must not write next to the caller.
+    let dir = env::temp_dir().join(format!("svgen-capi-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    env::set_current_dir(&dir).unwrap();
+
+    let png = png();
+    let options = svgen_options_default();
+    assert!(Options::from(&options).preprocess);
+    let mut svg: *mut c_char = ptr::null_mut();
+    let status = unsafe { svgen_create_svg(png.as_ptr(), png.len(), &options, &mut svg) };
+    assert_eq!(status, SvgenStatus::Ok);
+    unsafe { svgen_free_string(svg) };
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    std::fs::remove_dir(&dir).unwrap();
+}
+
+#[test]
+fn create_svg_errors() {
+    let mut svg: *mut c_char = ptr::null_mut();
+    let bytes = b"not an image";
+    let status = unsafe { svgen_create_svg(bytes.as_ptr(), bytes.len(), ptr::null(), &mut svg) };
+    assert_eq!(status, SvgenStatus::UnsupportedFormat);
+    assert!(svg.is_null());
+
+    let status = unsafe { svgen_create_svg(ptr::null(), 0, ptr::null(), &mut svg) };
+    assert_eq!(status, SvgenStatus::NullArgument);
+    let status = unsafe { svgen_create_svg(bytes.as_ptr(), 0, ptr::null(), ptr::null_mut()) };
+    assert_eq!(status, SvgenStatus::NullArgument);
+
+    // A PNG cut short.
+    let png = png();
+    let status = unsafe { svgen_create_svg(png.as_ptr(), 40, ptr::null(), &mut svg) };
+    assert_eq!(status, SvgenStatus::InvalidImage);
+    assert!(svg.is_null());
+    unsafe { svgen_free_string(ptr::null_mut()) };
+}
@@ -0,0 +1,399 @@
+use std::f64::consts::PI;
+
+use svgen::{
+    curve_fit_nd::{
+        fit_points, fit_points_to_cubics, fit_poly_list, fit_poly_single,
+        fit_poly_single_with_errors, poly_list_subdivide_to_limit, CornerOptions, FitOptions,
+        Vector,
+    },
+    structs::{CubicBezier, Segment},
+    utils::{
+        curve_fit_errors, curve_is_simple, curve_segments, curve_smooth, poly_list_subdivide,
+        poly_list_subdivide_adaptive,
+    },
+    vec2::DVec2,
+};
+
+fn circle(radius: f64, degrees: std::ops::Range<usize>, step: usize) -> Vec<DVec2> {
+    degrees
+        .step_by(step)
+        .map(|d| (d as f64).to_radians())
+        .map(|a| DVec2::new(radius * a.cos(), radius * a.sin()))
+        .collect()
+}
+
+/// Largest distance from the points to the flattened curves.
+fn max_distance(points: &[DVec2], curves: &[CubicBezier]) -> f64 {
+    let mut flattened = vec![curves[0].start];
+    for curve in curves {
+        Segment::Cubic(*curve).flatten(0.01, &mut flattened);
+    }
+    points
+        .iter()
+        .map(|p| {
+            flattened
+                .windows(2)
+                .map(|w| {
+                    let ab = w[1].sub(w[0]);
+                    let t = (p.sub(w[0]).dot(ab) / ab.len_squared()).clamp(0.0, 1.0);
+                    p.len_with(w[0].madd(ab, t))
+                })
+                .fold(f64::MAX, f64::min)
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Whether the handles around the knot between two curves are aligned.
+fn is_smooth(curve: &CubicBezier, next: &CubicBezier) -> bool {
+    let (a, b) = (
+        curve.end.normalized_diff(curve.control2),
+        next.control1.normalized_diff(next.start),
+    );
+    a.dot(b) > 0.999
+}
+
+fn assert_chained(curves: &[CubicBezier]) {
+    for pair in curves.windows(2) {
+        assert_eq!(pair[0].end, pair[1].start);
+    }
+}
+
+#[test]
+fn open_arc() {
+    let points = circle(100.0, 0..181, 2);
+    let options = FitOptions::default();
+    let curves = fit_points_to_cubics(&points, false, &options);
+
+    assert!(!curves.is_empty() && curves.len() <= 4, "{}", curves.len());
+    assert_eq!(curves[0].start, points[0]);
+    assert_eq!(curves.last().unwrap().end, *points.last().unwrap());
+    assert_chained(&curves);
+    assert!(max_distance(&points, &curves) <= options.error_threshold);
+}
+
+#[test]
+fn cyclic_circle() {
+    let points = circle(50.0, 0..360, 5);
+    let options = FitOptions {
+        error_threshold: 0.5,
+        ..Default::default()
+    };
+    let curves = fit_points_to_cubics(&points, true, &options);
+
+    assert!(curves.len() >= 2 && curves.len() < points.len());
+    assert_chained(&curves);
+    assert_eq!(curves.last().unwrap().end, curves[0].start);
+    assert!(max_distance(&points, &curves) <= options.error_threshold);
+
+    // Closing the points explicitly gives the same curves.
+    let mut closed = points.clone();
+    closed.push(points[0]);
+    assert_eq!(fit_points_to_cubics(&closed, true, &options), curves);
+}
+
+#[test]
+fn square_corners() {
+    let corners = [
+        DVec2::new(0.0, 0.0),
+        DVec2::new(40.0, 0.0),
+        DVec2::new(40.0, 40.0),
+        DVec2::new(0.0, 40.0),
+    ];
+    let mut points = Vec::new();
+    for (i, corner) in corners.iter().enumerate() {
+        let next = corners[(i + 1) % corners.len()];
+        points.extend((0..10).map(|j| corner.interp(next, j as f64 / 10.0)));
+    }
+
+    let options = FitOptions::default();
+    let curves = fit_points_to_cubics(&points, true, &options);
+    assert!(max_distance(&corners, &curves) <= options.error_threshold);
+
+    // Without corners every knot is smooth, its handles are aligned.
+    let smooth = FitOptions {
+        corner_angle: None,
+        ..options
+    };
+    let curves = fit_points_to_cubics(&points, true, &smooth);
+    let mut knots = curves.iter().zip(curves.iter().cycle().skip(1));
+    assert!(knots.all(|(curve, next)| is_smooth(curve, next)));
+}
+
+#[test]
+fn sparse_points_subdivided() {
+    let points = circle(100.0, 0..91, 30);
+    let options = FitOptions {
+        subdivide_limit: Some(1.0),
+        ..Default::default()
+    };
+    let curves = fit_points_to_cubics(&points, false, &options);
+
+    assert_eq!(curves[0].start, points[0]);
+    assert_eq!(curves.last().unwrap().end, points[3]);
+    assert!(max_distance(&points, &curves) <= options.error_threshold);
+}
+
+#[test]
+fn degenerate_inputs() {
+    let options = FitOptions::default();
+    let point = DVec2::new(1.0, 2.0);
+    assert!(fit_points_to_cubics(&[], false, &options).is_empty());
+    assert!(fit_points_to_cubics(&[point], true, &options).is_empty());
+    assert!(fit_points_to_cubics(&[point; 5], false, &options).is_empty());
+
+    let end = DVec2::new(5.0, 2.0);
+    let curves = fit_points_to_cubics(&[point, point, end], false, &options);
+    assert_eq!(curves.len(), 1);
+    assert_eq!((curves[0].start, curves[0].end), (point, end));
+}
+
+/// Point of a cubic `[start, control1, control2, end]` at `t`.
+fn cubic_point<V: Vector>(cubic: &[V; 4], t: f64) -> V {
+    let [p0, p1, p2, p3] = *cubic;
+    let (p01, p12, p23) = (p0.interp(p1, t), p1.interp(p2, t), p2.interp(p3, t));
+    p01.interp(p12, t).interp(p12.interp(p23, t), t)
+}
+
+#[test]
+fn helix_3d() {
+    let points = (0..=144)
+        .map(|i| (i as f64 * 5.0).to_radians())
+        .map(|a| [40.0 * a.cos(), 40.0 * a.sin(), 10.0 * a])
+        .collect::<Vec<_>>();
+    let options = FitOptions {
+        error_threshold: 0.25,
+        ..Default::default()
+    };
+    let curves = fit_points(&points, false, &options);
+
+    assert!(curves.len() > 1 && curves.len() < points.len() / 4);
+    assert_eq!(curves[0][0], points[0]);
+    assert_eq!(curves.last().unwrap()[3], *points.last().unwrap());
+    for pair in curves.windows(2) {
+        assert_eq!(pair[0][3], pair[1][0]);
+    }
+
+    let samples = curves
+        .iter()
+        .flat_map(|cubic| (0..=200).map(|i| cubic_point(cubic, i as f64 / 200.0)))
+        .collect::<Vec<_>>();
+    for point in &points {
+        let distance = samples
+            .iter()
+            .map(|sample| sample.len_with(*point))
+            .fold(f64::MAX, f64::min);
+        // Sampling adds a little to the fitting error.
+        assert!(distance <= options.error_threshold * 1.1, "{distance}");
+    }
+}
+
+#[test]
+fn dimensions_agree() {
+    // A planar curve fits the same in 2D and 3D.
+    let points = circle(80.0, 0..270, 3);
+    let options = FitOptions::default();
+    let curves_2d = fit_points_to_cubics(&points, false, &options);
+    let points_3d = points.iter().map(|p| [p.x, p.y, 0.0]).collect::<Vec<_>>();
+    let curves_3d = fit_points(&points_3d, false, &options);
+
+    assert_eq!(curves_2d.len(), curves_3d.len());
+    for (cubic_2d, cubic_3d) in curves_2d.iter().zip(&curves_3d) {
+        let points_2d = [
+            cubic_2d.start,
+            cubic_2d.control1,
+            cubic_2d.control2,
+            cubic_2d.end,
+        ];
+        for (p_2d, p_3d) in points_2d.iter().zip(cubic_3d) {
+            assert!((p_2d.x - p_3d[0]).abs() < 1e-9 && (p_2d.y - p_3d[1]).abs() < 1e-9);
+            assert_eq!(p_3d[2], 0.0);
+        }
+    }
+}
+
+#[test]
+fn smoothing() {
+    // A circle of radius 10 whose knots alternate half a pixel in and out.
+    let knot = |i: usize, radius: f64| {
+        let a = (i as f64 * 22.5).to_radians();
+        let point = DVec2::new(radius * a.cos(), radius * a.sin());
+        let tangent = DVec2::new(-a.sin(), a.cos());
+        let (length_in, length_out) = (1.0 + (i % 3) as f64 * 0.5, 1.3);
+        [
+            point.madd(tangent, -length_in),
+            point,
+            point.madd(tangent, length_out),
+        ]
+    };
+    let wobbly = (0..16)
+        .map(|i| knot(i, 10.0 + if i % 2 == 0 { 0.5 } else { -0.5 }))
+        .collect::<Vec<_>>();
+    // Spread of the radii of the knots.
+    let wobble = |curve: &[[DVec2; 3]]| {
+        let radii = curve.iter().map(|[_, point, _]| point.len());
+        radii.clone().fold(0.0, f64::max) - radii.fold(f64::MAX, f64::min)
+    };
+
+    let mut smoothed = wobbly.clone();
+    curve_smooth(true, &mut smoothed, 1.0, 0.4);
+    assert!(wobble(&smoothed) < 0.5, "{}", wobble(&smoothed));
+    for (before, after) in wobbly.iter().zip(&smoothed) {
+        assert!(before[1].len_with(after[1]) <= 0.4 + 1e-9);
+        // Handles stay aligned, with lengths evened out.
+        let (length_in, length_out) = (after[1].len_with(after[0]), after[1].len_with(after[2]));
+        assert!(
+            (length_in - length_out).abs() < 1e-9,
+            "{length_in} {length_out}"
+        );
+        let (tangent_in, tangent_out) = (after[1].sub(after[0]), after[2].sub(after[1]));
+        assert!(tangent_in.cross(tangent_out).abs() < 1e-9);
+    }
+
+    // Corners and the ends of open curves stay in place.
+    let mut corner = wobbly.clone();
+    corner[3][2] = corner[3][1].add(DVec2::new(1.0, 1.0));
+    let mut smoothed = corner.clone();
+    curve_smooth(false, &mut smoothed, 1.0, 0.4);
+    for i in [0, 3, 15] {
+        assert_eq!(smoothed[i], corner[i]);
+    }
+    assert_ne!(smoothed[1], corner[1]);
+
+    let mut unchanged = wobbly.clone();
+    curve_smooth(true, &mut unchanged, 0.0, 0.4);
+    assert_eq!(unchanged, wobbly);
+}
+
+#[test]
+fn fit_errors() {
+    // A wobbly circle, not quite fitted by a few segments.
+    let points = (0..360)
+        .step_by(3)
+        .map(|d| (d as f64).to_radians())
+        .map(|a| DVec2::new(a.cos(), a.sin()) * (40.0 + (a * 7.0).sin()))
+        .collect::<Vec<_>>();
+    for (is_cyclic, points) in [(true, &points[..]), (false, &points[..60])] {
+        let points = points.to_vec();
+        let (curve, errors) = fit_poly_single_with_errors(&points, is_cyclic, 1.5, PI, 1.0, true);
+        assert_eq!(
+            curve,
+            fit_poly_single(&points, is_cyclic, 1.5, PI, 1.0, true)
+        );
+        assert_eq!(errors.len(), curve_segments(is_cyclic, &curve).len());
+        assert!(errors.iter().all(|error| *error <= 1.5), "{errors:?}");
+        assert!(errors.iter().any(|error| *error > 0.1), "{errors:?}");
+
+        // Measured on the curve, each point counting towards its nearest segment.
+        let measured = curve_fit_errors(is_cyclic, &curve, &points);
+        assert_eq!(measured.len(), errors.len());
+        let max = |errors: &[f64]| errors.iter().copied().fold(0.0, f64::max);
+        assert!(
+            (max(&measured) - max(&errors)).abs() < 0.1,
+            "{measured:?} {errors:?}"
+        );
+    }
+}
+
+#[test]
+fn simple_curves() {
+    let straight = |points: &[[f64; 2]]| {
+        let knots = points.iter().map(|&[x, y]| [DVec2::new(x, y); 3]);
+        knots.collect::<Vec<_>>()
+    };
+    let square = straight(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]);
+    assert!(curve_is_simple(true, &square));
+    let bow_tie = straight(&[[0.0, 0.0], [10.0, 10.0], [10.0, 0.0], [0.0, 10.0]]);
+    assert!(!curve_is_simple(true, &bow_tie));
+    // Open, the closing edge crossing the others is left out.
+    assert!(curve_is_simple(false, &bow_tie[..3]));
+    // Two squares touching at a corner only touch.
+    let touching = straight(&[
+        [0.0, 0.0],
+        [10.0, 0.0],
+        [10.0, 10.0],
+        [20.0, 10.0],
+        [20.0, 20.0],
+        [10.0, 20.0],
+        [10.0, 10.0],
+        [0.0, 10.0],
+    ]);
+    assert!(curve_is_simple(true, &touching));
+
+    // A single cubic looping over itself, whatever the scale.
+    for scale in [0.01, 1.0, 100.0] {
+        let knot = |x: f64, y: f64| DVec2::new(x, y) * scale;
+        let looping = vec![
+            [knot(0.0, 0.0), knot(0.0, 0.0), knot(10.0, 10.0)],
+            [knot(-5.0, 10.0), knot(5.0, 0.0), knot(5.0, 0.0)],
+        ];
+        assert!(!curve_is_simple(false, &looping), "{scale}");
+        let arc = vec![
+            [knot(0.0, 0.0), knot(0.0, 0.0), knot(2.0, 5.0)],
+            [knot(3.0, 5.0), knot(5.0, 0.0), knot(5.0, 0.0)],
+        ];
+        assert!(curve_is_simple(false, &arc), "{scale}");
+    }
+
+    // Fitted circles are simple.
+    let points = circle(40.0, 0..360, 5);
+    let curve = fit_poly_single(&points, true, 1.0, PI, 1.0, true);
+    assert!(curve_is_simple(true, &curve));
+}
+
+#[test]
+fn adaptive_subdivision() {
+    let limit = 0.75;
+    // `(points, fitted knots)` of a cyclic polygon subdivided evenly and adaptively.
+    let counts = |poly: &[DVec2]| {
+        let mut even = vec![(true, poly.to_vec())];
+        poly_list_subdivide(&mut even);
+        poly_list_subdivide_to_limit(&mut even, limit);
+        let mut adaptive = vec![(true, poly.to_vec())];
+        poly_list_subdivide_adaptive(&mut adaptive, limit);
+
+        let corners = CornerOptions::default();
+        [even, adaptive].map(|poly_list| {
+            let points = poly_list[0].1.len();
+            let curves = fit_poly_list(poly_list, 0.5, corners.angle(), corners.scale, true);
+            (points, curves[0].1.len())
+        })
+    };
+
+    // A large circle only needs sparse points to be fitted with about as many knots.
+    let [(even_points, even_knots), (adaptive_points, adaptive_knots)] =
+        counts(&circle(100.0, 0..360, 5));
+    assert!(
+        adaptive_points * 4 <= even_points,
+        "{adaptive_points} {even_points}"
+    );
+    assert!(
+        adaptive_knots <= even_knots + 1,
+        "{adaptive_knots} {even_knots}"
+    );
+
+    // A star keeps the full density around its sharp turns, and the knots to follow them.
+    let star: Vec<DVec2> = (0..16)
+        .map(|i| {
+            let radius = if i % 2 == 0 { 20.0 } else { 8.0 };
+            let angle = i as f64 * PI / 8.0;
+            DVec2::new(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+    let [(even_points, even_knots), (adaptive_points, adaptive_knots)] = counts(&star);
+    assert!(
+        adaptive_points >= even_points,
+        "{adaptive_points} {even_points}"
+    );
+    assert!(adaptive_knots >= star.len(), "{adaptive_knots}");
+    assert!(
+        adaptive_knots <= even_knots,
+        "{adaptive_knots} {even_knots}"
+    );
+    let mut adaptive = vec![(true, star.clone())];
+    poly_list_subdivide_adaptive(&mut adaptive, limit);
+    let poly = &adaptive[0].1;
+    assert!(star.iter().all(|v| poly.contains(v)));
+    assert!(
+        (poly.iter().zip(poly.iter().cycle().skip(1))).all(|(a, b)| a.len_with(*b) < 2.0 * limit)
+    );
+}
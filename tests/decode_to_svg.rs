@@ -31,5 +31,7 @@ fn main() {
 
     let svg_string = create_svg(&buffer, color_mode);
 
-    std::fs::write("assets/generated.svg", svg_string).expect("Unable to write file");
+    let out_path = env::temp_dir().join("svgen-generated.svg");
+    std::fs::write(&out_path, svg_string).expect("Unable to write file");
+    log::info!("Wrote {}", out_path.display());
 }
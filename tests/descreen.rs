@@ -0,0 +1,98 @@
+use image::{Rgba, RgbaImage};
+use svgen::{
+    descreen::{alternation_ratio, descreen, is_dithered},
+    output::svg::to_svg,
+    structs::{DescreenFilter, DescreenOptions, Options},
+    vectorize_image,
+};
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const RED: Rgba<u8> = Rgba([220, 40, 30, 255]);
+
+/// Black and white checkerboard, a 50% ordered dither.
+fn checkerboard(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| match (x + y) % 2 {
+        0 => BLACK,
+        _ => WHITE,
+    })
+}
+
+/// A red square on white.
+fn flat(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        match (8..24).contains(&x) && (8..24).contains(&y) {
+            true => RED,
+            false => WHITE,
+        }
+    })
+}
+
+#[test]
+fn dithering_detection() {
+    assert!(alternation_ratio(&checkerboard(16, 16)) > 0.9);
+    assert!(is_dithered(&checkerboard(16, 16)));
+    // Halftone dots of 2 × 2 pixels every 4 pixels.
+    let halftone = RgbaImage::from_fn(32, 32, |x, y| match x % 4 < 2 && y % 4 < 2 {
+        true => BLACK,
+        false => WHITE,
+    });
+    assert!(is_dithered(&halftone));
+
+    assert_eq!(alternation_ratio(&flat(32, 32)), 0.0);
+    assert!(!is_dithered(&flat(32, 32)));
+}
+
+#[test]
+fn descreen_filters() {
+    let averaged = descreen(&checkerboard(16, 16), DescreenFilter::Average, 2);
+    // Whole cells average to mid gray.
+    assert_eq!(averaged.get_pixel(8, 8).0, [128, 128, 128, 255]);
+
+    // Mostly black dithering: a white pixel in every 2 × 2 cell.
+    let dark = RgbaImage::from_fn(16, 16, |x, y| match x % 2 == 0 && y % 2 == 0 {
+        true => WHITE,
+        false => BLACK,
+    });
+    let mode = descreen(&dark, DescreenFilter::Mode, 3);
+    assert!(mode.pixels().skip(17).all(|pixel| *pixel == BLACK));
+
+    // Flat areas are kept, only convex corners are rounded.
+    let image = flat(32, 32);
+    let mode = descreen(&image, DescreenFilter::Mode, 3);
+    let differences = image.pixels().zip(mode.pixels()).filter(|(a, b)| a != b);
+    assert_eq!(differences.count(), 4);
+}
+
+#[test]
+fn descreened_trace() {
+    // A dithered gray band above a flat red square.
+    let image = RgbaImage::from_fn(64, 48, |x, y| match y < 16 {
+        true => checkerboard(64, 16).get_pixel(x, y).to_owned(),
+        false => flat(32, 32).get_pixel(x % 32, y - 16).to_owned(),
+    });
+    let contours = |descreen| {
+        let options = Options {
+            preprocess: false,
+            descreen,
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        result.layers.iter().map(|l| l.curves.len()).sum::<usize>()
+    };
+
+    let dithered = contours(None);
+    let descreened = contours(Some(DescreenOptions::default()));
+    assert!(descreened * 10 < dithered, "{descreened} {dithered}");
+
+    // Not detected in the flat image alone.
+    let trace = |descreen| {
+        let options = Options {
+            preprocess: false,
+            descreen,
+            ..Options::default()
+        };
+        to_svg(&vectorize_image(&flat(32, 32), &options), &options)
+    };
+    assert_eq!(trace(Some(DescreenOptions::default())), trace(None));
+}
@@ -0,0 +1,525 @@
+//! Fixture images traced to the documents in `tests/golden`,
+//! rewritten instead of compared with `UPDATE_GOLDEN=1 cargo test --test golden`.
+
+use std::{env, fs};
+
+use image::{Rgba, RgbaImage};
+use svgen::{
+    structs::{
+        AdaptiveSimplify, BoundingBox, ColorMode, ColorOverride, ColorTarget, ContourSize,
+        DespeckleOptions, GradientOptions, LineFitting, Mode, Options, Segment, Supersample,
+        VectorizationResult, SUPERSAMPLE_MAX,
+    },
+    try_create_svg, try_vectorize,
+    utils::{curve_is_simple, curve_segments},
+    vec2::DVec2,
+    vectorize_image,
+};
+
+/// Fixtures of `tests/fixtures` with the options they are traced with.
+fn fixtures() -> Vec<(&'static str, Options)> {
+    vec![
+        // A red disc overlapping a blue square, on white.
+        ("flat_logo", Options::default()),
+        // Blue to yellow from left to right.
+        (
+            "gradient",
+            Options {
+                gradients: Some(GradientOptions::default()),
+                ..Options::default()
+            },
+        ),
+        // A 12x12 outlined heart.
+        (
+            "pixel_art",
+            Options {
+                mode: Mode::PixelPerfect,
+                ..Options::default()
+            },
+        ),
+        // A dark "T" on grainy paper with specks.
+        (
+            "noisy_scan",
+            Options {
+                color_mode: ColorMode::Black,
+                despeckle: DespeckleOptions {
+                    merge_area: 8,
+                    ..DespeckleOptions::default()
+                },
+                ..Options::default()
+            },
+        ),
+        // A ring with a dot in its hole, and a square with two windows.
+        (
+            "holes",
+            Options {
+                color_mode: ColorMode::Black,
+                ..Options::default()
+            },
+        ),
+    ]
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    fs::read(format!("tests/fixtures/{name}.png")).unwrap()
+}
+
+fn trace(name: &str) -> VectorizationResult {
+    let (_name, options) = fixtures()
+        .into_iter()
+        .find(|(fixture, _options)| *fixture == name)
+        .unwrap();
+    try_vectorize(&fixture(name), &options).unwrap()
+}
+
+#[test]
+fn golden_documents() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+    for (name, options) in fixtures() {
+        let svg = try_create_svg(&fixture(name), &options).unwrap();
+        let path = format!("tests/golden/{name}.svg");
+        if update {
+            fs::write(&path, svg).unwrap();
+            continue;
+        }
+        let golden = fs::read_to_string(&path).unwrap_or_default();
+        if svg != golden {
+            let line = svg
+                .lines()
+                .zip(golden.lines())
+                .position(|(a, b)| a != b)
+                .unwrap_or(svg.lines().count().min(golden.lines().count()));
+            mismatches.push(format!("{path} differs from line {}", line + 1));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{mismatches:#?}\nrerun with UPDATE_GOLDEN=1 to accept the new documents"
+    );
+}
+
+/// Bounds of every path of the result in source pixels, which the result is `scale` times.
+fn source_bboxes(result: &VectorizationResult, scale: f64) -> Vec<[f64; 4]> {
+    result
+        .layers
+        .iter()
+        .flat_map(|layer| &layer.paths)
+        .map(|path| {
+            let BoundingBox { min, max } = path.bbox;
+            [min.x, min.y, max.x, max.y].map(|v| v / scale)
+        })
+        .collect()
+}
+
+/// Asserts every expected `[min_x, min_y, max_x, max_y]` box matches one of `bboxes`,
+/// within `tolerance` pixels.
+fn assert_bboxes(bboxes: &[[f64; 4]], expected: &[[f64; 4]], tolerance: f64) {
+    for expected in expected {
+        assert!(
+            bboxes.iter().any(|bbox| bbox
+                .iter()
+                .zip(expected)
+                .all(|(a, b)| (a - b).abs() <= tolerance)),
+            "no path bounded by {expected:?} in {bboxes:#.1?}"
+        );
+    }
+}
+
+#[test]
+fn flat_logo_geometry() {
+    let result = trace("flat_logo");
+    let scale = result.width as f64 / 64.0;
+    assert_eq!(result.layers.len(), 3);
+    for layer in &result.layers {
+        let paths = match layer.color {
+            // The canvas, and the hole the shapes fill.
+            [255, 255, 255, 255] => 2,
+            _ => 1,
+        };
+        assert_eq!(layer.paths.len(), paths, "{:?}", layer.color);
+    }
+    let bboxes = source_bboxes(&result, scale);
+    assert_bboxes(
+        &bboxes,
+        &[
+            [0.0, 0.0, 64.0, 64.0],
+            [23.0, 23.0, 54.0, 54.0],
+            [8.0, 8.0, 30.0, 30.0],
+        ],
+        1.0,
+    );
+}
+
+#[test]
+fn gradient_geometry() {
+    let result = trace("gradient");
+    assert!(result.layers.iter().any(|layer| layer.gradient.is_some()));
+    let scale = result.width as f64 / 64.0;
+    let bboxes = source_bboxes(&result, scale);
+    let union = bboxes.iter().fold([f64::MAX, f64::MAX, 0.0, 0.0], |a, b| {
+        [
+            a[0].min(b[0]),
+            a[1].min(b[1]),
+            a[2].max(b[2]),
+            a[3].max(b[3]),
+        ]
+    });
+    assert_bboxes(&[union], &[[0.0, 0.0, 64.0, 32.0]], 0.5);
+    // Bands of a single gradient are not traced separately.
+    assert!(bboxes.len() <= 3, "{bboxes:#.1?}");
+}
+
+#[test]
+fn pixel_art_geometry() {
+    let result = trace("pixel_art");
+    assert_eq!([result.width, result.height], [12, 12]);
+    let paths = |color: [u8; 4]| {
+        let layer = result.layers.iter().find(|layer| layer.color == color);
+        layer.unwrap().paths.len()
+    };
+    // Outline pixels touching at a corner only are separate paths.
+    assert_eq!(paths([30, 20, 40, 255]), 18);
+    assert_eq!(paths([230, 50, 80, 255]), 1);
+    assert_eq!(paths([250, 240, 220, 255]), 2);
+    // Pixel edges are kept exactly.
+    assert_bboxes(
+        &source_bboxes(&result, 1.0),
+        &[[1.0, 2.0, 11.0, 10.0], [5.0, 10.0, 7.0, 11.0]],
+        0.0,
+    );
+}
+
+#[test]
+fn noisy_scan_geometry() {
+    let result = trace("noisy_scan");
+    let scale = result.width as f64 / 64.0;
+    // Grain and specks are merged away.
+    let bboxes = source_bboxes(&result, scale);
+    assert_eq!(bboxes.len(), 1, "{bboxes:#.1?}");
+    assert_bboxes(&bboxes, &[[10.0, 10.0, 54.0, 54.0]], 1.0);
+}
+
+#[test]
+fn holes_geometry() {
+    let result = trace("holes");
+    let scale = result.width as f64 / 64.0;
+    let holes = result
+        .contour_tree
+        .iter()
+        .filter(|node| node.is_hole)
+        .count();
+    assert_eq!((result.contour_tree.len(), holes), (6, 3));
+    assert_bboxes(
+        &source_bboxes(&result, scale),
+        &[
+            // Ring, its hole and the dot in it.
+            [5.0, 6.0, 33.0, 34.0],
+            [11.0, 12.0, 27.0, 28.0],
+            [16.0, 17.0, 22.0, 23.0],
+            // Square and its windows.
+            [38.0, 9.0, 60.0, 31.0],
+            [42.0, 13.0, 47.0, 19.0],
+            [51.0, 13.0, 56.0, 19.0],
+        ],
+        1.0,
+    );
+}
+
+#[test]
+fn polygon_mode() {
+    let options = |mode| Options {
+        color_mode: ColorMode::Black,
+        mode,
+        ..Options::default()
+    };
+    let smooth = trace("holes");
+    let polygon = try_vectorize(&fixture("holes"), &options(Mode::Polygon)).unwrap();
+
+    // The same simplified polygons, joined by their own points.
+    for (smooth, polygon) in smooth.layers.iter().zip(&polygon.layers) {
+        assert_eq!(smooth.polygons, polygon.polygons);
+        for ((_, points), (_, knots)) in polygon.polygons.iter().zip(&polygon.curves) {
+            assert!(points.iter().zip(knots).all(|(p, knot)| *knot == [*p; 3]));
+        }
+    }
+    let scale = polygon.width as f64 / 64.0;
+    assert_bboxes(
+        &source_bboxes(&polygon, scale),
+        &source_bboxes(&smooth, scale),
+        1.0,
+    );
+
+    let svg = try_create_svg(&fixture("holes"), &options(Mode::Polygon)).unwrap();
+    let path_data = svg
+        .split(" d=\"")
+        .skip(1)
+        .map(|d| d.split('"').next().unwrap());
+    assert!(path_data.clone().count() > 0);
+    assert!(path_data
+        .into_iter()
+        .all(|d| !d.contains(['c', 'C', 's', 'S'])));
+}
+
+#[test]
+fn adaptive_simplify() {
+    // A large disc and a small one.
+    let image = RgbaImage::from_fn(96, 64, |x, y| {
+        let in_disc = |cx: f64, cy: f64, r: f64| (x as f64 - cx).hypot(y as f64 - cy) < r;
+        match in_disc(32.0, 32.0, 28.0) || in_disc(80.0, 32.0, 6.0) {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let point_counts = |adaptive_simplify| {
+        let options = Options {
+            color_mode: ColorMode::Black,
+            mode: Mode::Polygon,
+            supersample: Supersample::Off,
+            adaptive_simplify,
+            ..Options::default()
+        };
+        let mut polygons = vectorize_image(&image, &options).layers[0].polygons.clone();
+        polygons.sort_by_key(|(_, points)| points[0].x > 64.0);
+        polygons.iter().map(|(_, p)| p.len()).collect::<Vec<_>>()
+    };
+    let adaptive = AdaptiveSimplify {
+        reference: 24.0,
+        ..AdaptiveSimplify::default()
+    };
+    let [large, small] = point_counts(None)[..] else {
+        panic!()
+    };
+    let [adaptive_large, adaptive_small] = point_counts(Some(adaptive))[..] else {
+        panic!()
+    };
+    assert!(adaptive_large < large, "{adaptive_large} < {large}");
+    assert!(adaptive_small > small, "{adaptive_small} > {small}");
+
+    // Scaled by the size, within the clamps.
+    let square = [(0.0, 0.0), (3.0, 0.0), (3.0, 4.0), (0.0, 4.0)].map(|(x, y)| DVec2::new(x, y));
+    let perimeter = AdaptiveSimplify {
+        size: ContourSize::Perimeter,
+        reference: 7.0,
+        min: 0.0,
+        max: f64::INFINITY,
+    };
+    assert_eq!(perimeter.threshold(2.0, true, &square), 4.0);
+    assert_eq!(perimeter.threshold(2.0, false, &square), 20.0 / 7.0);
+    let diagonal = AdaptiveSimplify {
+        size: ContourSize::Diagonal,
+        ..perimeter
+    };
+    assert_eq!(diagonal.threshold(2.0, true, &square), 10.0 / 7.0);
+    let clamped = AdaptiveSimplify {
+        max: 1.0,
+        ..diagonal
+    };
+    assert_eq!(clamped.threshold(2.0, true, &square), 1.0);
+
+    // Crossed bounds give `max`, NaN bounds are ignored.
+    let crossed = AdaptiveSimplify {
+        min: 3.0,
+        max: 1.0,
+        ..diagonal
+    };
+    assert_eq!(crossed.threshold(2.0, true, &square), 1.0);
+    let unbounded = AdaptiveSimplify {
+        min: f64::NAN,
+        max: f64::NAN,
+        ..diagonal
+    };
+    assert_eq!(unbounded.threshold(2.0, true, &square), 10.0 / 7.0);
+    // Without a positive reference the threshold is not scaled.
+    for reference in [0.0, -1.0, f64::NAN] {
+        let unscaled = AdaptiveSimplify {
+            reference,
+            ..perimeter
+        };
+        assert_eq!(unscaled.threshold(2.0, true, &square), 2.0, "{reference}");
+    }
+    let options = Options {
+        color_mode: ColorMode::Black,
+        adaptive_simplify: Some(AdaptiveSimplify {
+            reference: 0.0,
+            min: f64::NAN,
+            max: -1.0,
+            ..adaptive
+        }),
+        ..Options::default()
+    };
+    assert!(!vectorize_image(&image, &options).layers[0]
+        .curves
+        .is_empty());
+}
+
+#[test]
+fn color_overrides() {
+    // A red disc and a blue one, on white.
+    let image = RgbaImage::from_fn(128, 64, |x, y| {
+        let in_disc = |cx: f64| (x as f64 + 0.5 - cx).hypot(y as f64 + 0.5 - 32.0) < 28.0;
+        match (in_disc(32.0), in_disc(96.0)) {
+            (true, _) => Rgba([255, 0, 0, 255]),
+            (_, true) => Rgba([0, 0, 255, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let knot_counts = |color_overrides| {
+        let options = Options {
+            supersample: Supersample::Off,
+            color_overrides,
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        [[255, 0, 0, 255], [0, 0, 255, 255]].map(|color| {
+            let layer = result.layers.iter().find(|l| l.color == color).unwrap();
+            layer
+                .curves
+                .iter()
+                .map(|(_, knots)| knots.len())
+                .sum::<usize>()
+        })
+    };
+    let red = |error_threshold| ColorOverride {
+        target: ColorTarget::Color {
+            rgb: [255, 0, 0],
+            tolerance: 8,
+        },
+        error_threshold: Some(error_threshold),
+        simplify_threshold: None,
+    };
+    let [red_knots, blue_knots] = knot_counts(Vec::new());
+
+    // Only the overridden layer is fitted tighter or looser.
+    let [tight_red, tight_blue] = knot_counts(vec![red(0.05)]);
+    assert!(tight_red > red_knots, "{tight_red} > {red_knots}");
+    assert_eq!(tight_blue, blue_knots);
+    let [loose_red, loose_blue] = knot_counts(vec![red(8.0)]);
+    assert!(loose_red < red_knots, "{loose_red} < {red_knots}");
+    assert_eq!(loose_blue, blue_knots);
+
+    // Both discs are dark enough, the first matching override is used.
+    let luminance = ColorOverride {
+        target: ColorTarget::Luminance { min: 0, max: 100 },
+        ..red(8.0)
+    };
+    let [_, loose_blue] = knot_counts(vec![luminance]);
+    assert!(loose_blue < blue_knots, "{loose_blue} < {blue_knots}");
+    assert_eq!(
+        knot_counts(vec![red(0.05), luminance]),
+        [tight_red, loose_blue]
+    );
+}
+
+#[test]
+fn line_fitting() {
+    // A tilted square and a disc.
+    let image = RgbaImage::from_fn(120, 64, |x, y| {
+        let (x, y) = (x as f64, y as f64);
+        let in_square = (x - 32.0 + (y - 32.0) * 0.3).abs() < 20.0
+            && (y - 32.0 - (x - 32.0) * 0.3).abs() < 20.0;
+        match in_square || (x - 90.0).hypot(y - 32.0) < 14.0 {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let segments = |line_fitting| {
+        let options = Options {
+            color_mode: ColorMode::Black,
+            supersample: Supersample::Off,
+            line_fitting,
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        let mut curves = result.layers[0].curves.clone();
+        curves.sort_by_key(|(_, knots)| knots[0][1].x > 60.0);
+        curves
+            .iter()
+            .map(|(is_cyclic, knots)| curve_segments(*is_cyclic, knots))
+            .collect::<Vec<_>>()
+    };
+    let is_line = |segment: &Segment| matches!(segment, Segment::Line { .. });
+
+    let curved = segments(None);
+    assert_eq!(curved.len(), 2);
+    assert!(!curved[0].iter().all(is_line));
+
+    // The square is drawn with its four edges, the disc is still fitted with curves.
+    let fitted = segments(Some(LineFitting::default()));
+    assert_eq!(fitted.len(), 2);
+    assert!(fitted[0].iter().all(is_line), "{:?}", fitted[0]);
+    assert_eq!(fitted[0].len(), 4);
+    assert!(!fitted[1].iter().any(is_line), "{:?}", fitted[1]);
+
+    // Along with the boundaries shared by the layers of colored images.
+    let options = Options {
+        line_fitting: Some(LineFitting::default()),
+        ..Options::default()
+    };
+    let result = try_vectorize(&fixture("flat_logo"), &options).unwrap();
+    let plain = trace("flat_logo");
+    assert_eq!(result.layers.len(), plain.layers.len());
+    let scale = result.width as f64 / 64.0;
+    assert_bboxes(
+        &source_bboxes(&result, scale),
+        &source_bboxes(&plain, scale),
+        1.0,
+    );
+}
+
+#[test]
+fn supersample_resolution() {
+    let trace = |supersample| {
+        let options = Options {
+            supersample,
+            ..Options::default()
+        };
+        try_vectorize(&fixture("flat_logo"), &options).unwrap()
+    };
+    let auto = trace(Supersample::Auto);
+    let factor = trace(Supersample::Factor(3));
+    let off = trace(Supersample::Off);
+    assert_eq!([auto.width, auto.height], [64, 64]);
+    assert_eq!([factor.width, factor.height], [192, 192]);
+    assert_eq!([off.width, off.height], [64, 64]);
+    // Factors are clamped to `1..=SUPERSAMPLE_MAX`.
+    let zero = trace(Supersample::Factor(0));
+    assert_eq!([zero.width, zero.height], [64, 64]);
+    let options = Options {
+        supersample: Supersample::Factor(u32::MAX),
+        ..Options::default()
+    };
+    let tiny = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+    let max = vectorize_image(&tiny, &options);
+    assert_eq!([max.width, max.height], [2 * SUPERSAMPLE_MAX; 2]);
+
+    // Auto traces the same upscaled image as a factor of 3, scaled back down.
+    let expected = source_bboxes(&factor, 3.0);
+    assert_bboxes(&source_bboxes(&auto, 1.0), &expected, 1e-6);
+    assert_bboxes(&source_bboxes(&off, 1.0), &expected, 1.0);
+}
+
+#[test]
+fn ensure_simple_paths() {
+    // The grain is traced as many tiny contours, some fitted crossing themselves.
+    let png = fixture("noisy_scan");
+    let crossing = |options: &Options| {
+        let result = try_vectorize(&png, options).unwrap();
+        let curves = result.layers.iter().flat_map(|layer| &layer.curves);
+        let crossing = curves.filter(|(is_cyclic, curve)| !curve_is_simple(*is_cyclic, curve));
+        (crossing.count(), result.layers.len())
+    };
+    let (before, layers) = crossing(&Options::default());
+    assert!(before > 0);
+
+    let options = Options {
+        ensure_simple_paths: true,
+        ..Options::default()
+    };
+    assert_eq!(crossing(&options), (0, layers));
+    let options = Options {
+        shared_boundaries: true,
+        ..options
+    };
+    assert_eq!(crossing(&options).0, 0);
+}
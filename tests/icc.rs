@@ -0,0 +1,51 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgb, RgbImage};
+use moxcms::ColorProfile;
+use svgen::{session::VectorizationSession, structs::Options};
+
+/// A flat 16x16 JPEG of `color`, with `icc_profile` embedded in an APP2 segment.
+fn jpeg(color: [u8; 3], icc_profile: Option<&[u8]>) -> Vec<u8> {
+    let mut jpeg = Vec::new();
+    RgbImage::from_pixel(16, 16, Rgb(color))
+        .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+        .unwrap();
+    if let Some(icc_profile) = icc_profile {
+        // The first of one chunk.
+        let mut app2 = vec![0xff, 0xe2];
+        app2.extend((icc_profile.len() as u16 + 16).to_be_bytes());
+        app2.extend(b"ICC_PROFILE\0\x01\x01");
+        app2.extend(icc_profile);
+        // Right after the SOI marker.
+        jpeg.splice(2..2, app2);
+    }
+    jpeg
+}
+
+/// The decoded color at the center of the image.
+fn decoded(jpeg: &[u8]) -> [u8; 4] {
+    let session = VectorizationSession::try_new(jpeg, Options::default()).unwrap();
+    session.image().get_pixel(8, 8).0
+}
+
+#[test]
+fn converted_to_srgb() {
+    let color = [200, 100, 50];
+    let untagged = decoded(&jpeg(color, None));
+
+    let srgb = ColorProfile::new_srgb().encode().unwrap();
+    let tagged = decoded(&jpeg(color, Some(&srgb)));
+    for (a, b) in tagged.iter().zip(untagged) {
+        assert!(a.abs_diff(b) <= 1, "{tagged:?} {untagged:?}");
+    }
+
+    // Display P3 has a wider gamut, the same values are more saturated in sRGB.
+    let p3 = ColorProfile::new_display_p3().encode().unwrap();
+    let wide = decoded(&jpeg(color, Some(&p3)));
+    assert!(wide[0] > untagged[0] + 5, "{wide:?} {untagged:?}");
+    assert!(wide[2] + 5 < untagged[2], "{wide:?} {untagged:?}");
+    assert_eq!(wide[3], 255);
+
+    // Broken profiles are ignored.
+    assert_eq!(decoded(&jpeg(color, Some(b"not a profile"))), untagged);
+}
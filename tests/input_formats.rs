@@ -0,0 +1,352 @@
+use std::{
+    env, fs,
+    io::{Cursor, ErrorKind},
+    path::Path,
+};
+
+use image::{
+    codecs::gif::GifEncoder, Delay, Frame, ImageError, ImageFormat, Rgb, RgbImage, Rgba, RgbaImage,
+};
+use svgen::{
+    create_animated_svg, create_svg_frames, create_svg_from_path, create_svg_from_reader,
+    create_svg_with_options,
+    error::SvgenError,
+    output::svg::to_svg,
+    structs::{ColorMode, InputFormat, Options},
+    try_create_svg, try_vectorize,
+    utils::rgb_to_hex,
+    vectorize_image,
+};
+
+fn encode(format: ImageFormat) -> Vec<u8> {
+    let image = RgbaImage::from_fn(16, 16, |x, _y| {
+        if x < 8 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .unwrap();
+    bytes
+}
+
+fn unsupported_format(result: Result<impl Sized, SvgenError>) -> String {
+    match result {
+        Err(SvgenError::UnsupportedFormat(format)) => format,
+        Err(error) => panic!("{error}"),
+        Ok(_) => panic!("accepted"),
+    }
+}
+
+#[test]
+fn input_format_allowlist() {
+    let (png, bmp) = (encode(ImageFormat::Png), encode(ImageFormat::Bmp));
+    let options = Options {
+        input_formats: vec![InputFormat::Png],
+        ..Options::default()
+    };
+
+    assert!(try_vectorize(&png, &options).is_ok());
+    assert!(try_vectorize(&bmp, &Options::default()).is_ok());
+    assert_eq!(unsupported_format(try_vectorize(&bmp, &options)), "bmp");
+    assert_eq!(
+        unsupported_format(create_svg_from_reader(Cursor::new(&bmp), &options)),
+        "bmp"
+    );
+}
+
+#[test]
+fn unknown_input_format() {
+    let error = try_vectorize(b"not an image", &Options::default()).unwrap_err();
+    assert_eq!(error.to_string(), "unsupported input format: unknown");
+}
+
+/// A 7x5 PNG of 2 bit palette indices.
+fn indexed_png() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, 7, 5);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Two);
+    encoder.set_palette(vec![0, 0, 0, 255, 0, 0, 0, 255, 0]);
+    let mut writer = encoder.write_header().unwrap();
+    writer
+        .write_image_data(&[0b0001_1000, 0b0110_0000].repeat(5))
+        .unwrap();
+    writer.finish().unwrap();
+    bytes
+}
+
+/// Fill colors of a document, sorted.
+fn fills(svg: &str) -> Vec<&str> {
+    let mut fills: Vec<&str> = (svg.split("fill=\"").skip(1))
+        .map(|fill| fill.split('"').next().unwrap())
+        .collect();
+    fills.sort_unstable();
+    fills.dedup();
+    fills
+}
+
+#[test]
+fn indexed_png_entry_points() {
+    // Close colors quantization would blend, in 4 bit indices.
+    let palette: [[u8; 3]; 6] = [
+        [250, 250, 250],
+        [12, 34, 56],
+        [18, 34, 56],
+        [200, 10, 90],
+        [90, 200, 10],
+        [10, 90, 200],
+    ];
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, 24, 24);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Four);
+    encoder.set_palette(palette.as_flattened().to_vec());
+    let mut writer = encoder.write_header().unwrap();
+    // A grid of 8x8 cells, one per index.
+    let index = |x: usize, y: usize| ((x / 8 + y / 8 * 3) % palette.len()) as u8;
+    let data: Vec<u8> = (0..24 * 24 / 2)
+        .map(|i| {
+            let (x, y) = (i * 2 % 24, i * 2 / 24);
+            index(x, y) << 4 | index(x + 1, y)
+        })
+        .collect();
+    writer.write_image_data(&data).unwrap();
+    writer.finish().unwrap();
+
+    let mut expected: Vec<String> = (palette.iter())
+        .map(|[r, g, b]| rgb_to_hex(*r, *g, *b))
+        .collect();
+    expected.sort_unstable();
+    let options = Options::default();
+    let svg = try_create_svg(&png, &options).unwrap();
+    assert_eq!(fills(&svg), expected);
+
+    let path = env::temp_dir().join("svgen-indexed-entry-points.png");
+    fs::write(&path, &png).unwrap();
+    let from_path = create_svg_from_path(&path, &options);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(from_path.unwrap(), svg);
+    assert_eq!(create_svg_with_options(&png, &options), svg);
+    assert_eq!(
+        create_svg_from_reader(Cursor::new(&png), &options).unwrap(),
+        svg
+    );
+    assert_eq!(create_svg_frames(&png, &options), [svg]);
+    assert_eq!(fills(&create_animated_svg(&png, &options)), expected);
+}
+
+#[test]
+fn gif_frames() {
+    // A black square moving right on white, shown 100, 200 then 100 ms.
+    let frames: Vec<RgbaImage> = (0..3)
+        .map(|i| {
+            RgbaImage::from_fn(24, 12, |x, y| {
+                match (i * 8..i * 8 + 8).contains(&x) && (2..10).contains(&y) {
+                    true => Rgba([0, 0, 0, 255]),
+                    false => Rgba([255, 255, 255, 255]),
+                }
+            })
+        })
+        .collect();
+    let mut gif = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut gif);
+        for (frame, delay) in frames.iter().zip([100, 200, 100]) {
+            let delay = Delay::from_numer_denom_ms(delay, 1);
+            (encoder.encode_frame(Frame::from_parts(frame.clone(), 0, 0, delay))).unwrap();
+        }
+    }
+
+    let options = Options::default();
+    let svgs = create_svg_frames(&gif, &options);
+    assert_eq!(svgs.len(), 3);
+    for (svg, frame) in svgs.iter().zip(&frames) {
+        assert_eq!(*svg, to_svg(&vectorize_image(frame, &options), &options));
+    }
+    assert_ne!(svgs[0], svgs[1]);
+
+    let animated = create_animated_svg(&gif, &options);
+    assert_eq!(animated.matches(r#"dur="400ms""#).count(), 3);
+    for key_times in ["0;0.25", "0;0.25;0.75", "0;0.75"] {
+        assert!(animated.contains(&format!(r#"keyTimes="{key_times}""#)));
+    }
+}
+
+#[test]
+fn path_entry_point() {
+    let options = Options {
+        color_mode: ColorMode::Black,
+        ..Options::default()
+    };
+    let path = Path::new("tests/fixtures/flat_logo.png");
+    let svg = create_svg_from_path(path, &options).unwrap();
+    assert_eq!(
+        svg,
+        create_svg_with_options(&fs::read(path).unwrap(), &options)
+    );
+
+    let missing = create_svg_from_path(Path::new("tests/fixtures/missing.png"), &options);
+    assert!(matches!(
+        missing,
+        Err(SvgenError::Image(ImageError::IoError(error))) if error.kind() == ErrorKind::NotFound
+    ));
+}
+
+#[test]
+fn truncated_inputs() {
+    for bytes in [
+        indexed_png(),
+        encode(ImageFormat::Png),
+        encode(ImageFormat::Bmp),
+    ] {
+        assert!(try_vectorize(&bytes, &Options::default()).is_ok());
+        for len in 0..bytes.len() - 1 {
+            // An error or the recovered pixels, but no panic.
+            let _ = try_vectorize(&bytes[..len], &Options::default());
+        }
+    }
+}
+
+/// CRC-32 of PNG chunks.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// A PNG chunk of `kind` holding `data`, with its checksum.
+fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let body = [kind.as_slice(), data].concat();
+    [
+        (data.len() as u32).to_be_bytes().as_slice(),
+        &body,
+        &crc32(&body).to_be_bytes(),
+    ]
+    .concat()
+}
+
+#[test]
+fn png_chunk_validation() {
+    let png = encode(ImageFormat::Png);
+    // The signature is followed by IHDR, 13 bytes long.
+    let (signature, ihdr, rest) = (&png[..8], &png[16..29], &png[33..]);
+    let with_chunk = |chunk: Vec<u8>| [signature, &png[8..33], &chunk, rest].concat();
+    let error = |bytes: &[u8]| {
+        try_vectorize(bytes, &Options::default())
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert!(try_vectorize(&with_chunk(chunk(b"abCd", b"skipped")), &Options::default()).is_ok());
+    assert!(error(&with_chunk(chunk(b"ABCD", b"unknown"))).contains("unknown critical chunk ABCD"));
+    assert!(create_svg_from_reader(
+        Cursor::new(with_chunk(chunk(b"ABCD", b""))),
+        &Options::default()
+    )
+    .is_err());
+
+    let short_ihdr = [signature, &chunk(b"IHDR", &ihdr[..12]), rest].concat();
+    assert!(error(&short_ihdr).contains("IHDR"));
+}
+
+/// `png` with a bit of the checksum of its first `kind` chunk flipped.
+fn corrupt_checksum(png: &[u8], kind: &[u8; 4]) -> Vec<u8> {
+    let start = png.windows(4).position(|window| window == kind).unwrap();
+    let length = u32::from_be_bytes(png[start - 4..start].try_into().unwrap()) as usize;
+    let mut corrupted = png.to_vec();
+    corrupted[start + 4 + length] ^= 1;
+    corrupted
+}
+
+#[test]
+fn png_checksums() {
+    let ignore_png_crc = Options {
+        ignore_png_crc: true,
+        ..Options::default()
+    };
+    for png in [encode(ImageFormat::Png), indexed_png()] {
+        let expected = try_create_svg(&png, &Options::default()).unwrap();
+        // Decoders stop before the IEND checksum, it is checked all the same.
+        for kind in [b"IHDR", b"IDAT", b"IEND"] {
+            let corrupted = corrupt_checksum(&png, kind);
+            let error = try_vectorize(&corrupted, &Options::default()).unwrap_err();
+            let name = String::from_utf8_lossy(kind);
+            assert!(error
+                .to_string()
+                .contains(&format!("checksum mismatch in chunk {name}")));
+            assert!(create_svg_from_reader(Cursor::new(&corrupted), &Options::default()).is_err());
+
+            let svg = try_create_svg(&corrupted, &ignore_png_crc).unwrap();
+            assert_eq!(svg, expected, "{name}");
+            let svg = create_svg_from_reader(Cursor::new(&corrupted), &ignore_png_crc).unwrap();
+            assert_eq!(svg, expected, "{name}");
+        }
+    }
+    // Still truncated.
+    let png = corrupt_checksum(&encode(ImageFormat::Png), b"IDAT");
+    assert!(try_vectorize(&png[..png.len() - 20], &ignore_png_crc).is_err());
+}
+
+/// A 24x16 JPEG, black on its left third, with an EXIF `orientation` tag.
+fn oriented_jpeg(orientation: u8) -> Vec<u8> {
+    let image = RgbImage::from_fn(24, 16, |x, _y| match x < 8 {
+        true => Rgb([0, 0, 0]),
+        false => Rgb([255, 255, 255]),
+    });
+    let mut jpeg = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+        .unwrap();
+
+    // Big endian TIFF header, then one IFD entry: tag 0x0112, SHORT, count 1.
+    let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+    exif.extend([0, orientation, 0, 0, 0, 0, 0, 0]);
+    let mut app1 = vec![0xff, 0xe1];
+    app1.extend((exif.len() as u16 + 2).to_be_bytes());
+    app1.extend(exif);
+    // Right after the SOI marker.
+    jpeg.splice(2..2, app1);
+    jpeg
+}
+
+#[test]
+fn exif_orientation() {
+    let options = Options {
+        color_mode: ColorMode::Black,
+        preprocess: false,
+        ..Options::default()
+    };
+    let black_bbox = |jpeg: &[u8]| {
+        let result = try_vectorize(jpeg, &options).unwrap();
+        let bbox = result.layers[0].bbox().unwrap();
+        let bbox = [bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y].map(f64::round);
+        ([result.width, result.height], bbox)
+    };
+
+    assert_eq!(
+        black_bbox(&oriented_jpeg(1)),
+        ([24, 16], [0.0, 0.0, 8.0, 16.0])
+    );
+    // Rotated 90° clockwise, the left third ends up on top.
+    assert_eq!(
+        black_bbox(&oriented_jpeg(6)),
+        ([16, 24], [0.0, 0.0, 16.0, 8.0])
+    );
+    // Mirrored.
+    assert_eq!(
+        black_bbox(&oriented_jpeg(2)),
+        ([24, 16], [16.0, 0.0, 24.0, 16.0])
+    );
+
+    let svg = create_svg_from_reader(Cursor::new(oriented_jpeg(8)), &options).unwrap();
+    assert!(svg.contains("viewBox=\"0 0 16 24\""), "{svg}");
+}
@@ -0,0 +1,169 @@
+use image::{Rgba, RgbaImage};
+use svgen::{
+    mask::{mask_despeckle, mask_threshold, BitMask},
+    structs::{DespeckleOptions, Options, Threshold},
+    vectorize_image,
+};
+
+/// Mask of `size` with the `[x, y, width, height]` rectangles filled.
+fn mask(size: usize, rects: &[[usize; 4]]) -> BitMask {
+    BitMask::from_bits(
+        [size, size],
+        (0..size * size).map(|i| {
+            let (px, py) = (i % size, i / size);
+            (rects.iter()).any(|&[x, y, width, height]| {
+                (x..x + width).contains(&px) && (y..y + height).contains(&py)
+            })
+        }),
+    )
+}
+
+fn despeckle(mut mask: BitMask, options: DespeckleOptions, scale: u32) -> (BitMask, usize) {
+    let removed = mask_despeckle(&mut mask, &options, scale);
+    (mask, removed)
+}
+
+#[test]
+fn despeckle_open_close() {
+    let block = [4, 4, 8, 8];
+    let speck = [1, 1, 1, 1];
+
+    // Opening removes a 1 px speck and keeps the block.
+    let options = DespeckleOptions {
+        open_radius: 1,
+        ..DespeckleOptions::default()
+    };
+    assert_eq!(
+        despeckle(mask(16, &[block, speck]), options, 1),
+        (mask(16, &[block]), 1)
+    );
+
+    // Closing fills a 1 px pinhole, without removing any region.
+    let options = DespeckleOptions {
+        close_radius: 1,
+        ..DespeckleOptions::default()
+    };
+    let mut punctured = mask(16, &[block]);
+    punctured.set(7, 7, false);
+    assert_eq!(despeckle(punctured, options, 1), (mask(16, &[block]), 0));
+
+    // A median does both, rounding the corners of the block.
+    let options = DespeckleOptions {
+        median_radius: 1,
+        ..DespeckleOptions::default()
+    };
+    let mut expected = mask(16, &[block]);
+    for (x, y) in [(4, 4), (11, 4), (4, 11), (11, 11)] {
+        expected.set(x, y, false);
+    }
+    let mut noisy = mask(16, &[block, speck]);
+    noisy.set(7, 7, false);
+    assert_eq!(despeckle(noisy, options, 1), (expected, 1));
+
+    // Radii are in input pixels, scaled to the mask.
+    let options = DespeckleOptions {
+        open_radius: 1,
+        ..DespeckleOptions::default()
+    };
+    let blob = mask(16, &[[1, 1, 3, 3]]);
+    assert_eq!(despeckle(blob.clone(), options, 1), (blob, 0));
+    assert_eq!(
+        despeckle(mask(16, &[[1, 1, 3, 3]]), options, 2),
+        (BitMask::new([16, 16]), 1)
+    );
+}
+
+#[test]
+fn despeckle_blur() {
+    // Red specks around a black square, mapped to black without blurring.
+    let mut image = RgbaImage::from_fn(32, 32, |x, y| {
+        match (8..24).contains(&x) && (8..24).contains(&y) {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    for (x, y) in [(2, 2), (28, 4), (3, 27), (29, 29)] {
+        image.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+    }
+    let contours = |blur_sigma| {
+        let options = Options {
+            preprocess: false,
+            despeckle: DespeckleOptions {
+                blur_sigma,
+                ..DespeckleOptions::default()
+            },
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        (result.layers.iter())
+            .map(|layer| (layer.color, layer.curves.len()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        contours(0.0),
+        [([255, 255, 255, 255], 6), ([0, 0, 0, 255], 5)]
+    );
+    assert_eq!(
+        contours(1.0),
+        [([255, 255, 255, 255], 2), ([0, 0, 0, 255], 1)]
+    );
+}
+
+fn gray(value: u8) -> Rgba<u8> {
+    Rgba([value, value, value, 255])
+}
+
+#[test]
+fn thresholds() {
+    // Pixels at or below the chosen threshold are traced.
+    let traced = |image: &RgbaImage, threshold| {
+        let mask = mask_threshold(image, &threshold);
+        (image.pixels().zip(mask.iter()))
+            .filter(|(_, is_filled)| *is_filled)
+            .map(|(pixel, _)| pixel[0])
+            .collect::<Vec<_>>()
+    };
+
+    // Fixed, without transparent pixels.
+    let mut image = RgbaImage::from_fn(4, 1, |x, _| gray([40, 120, 140, 220][x as usize]));
+    image.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+    assert_eq!(traced(&image, Threshold::Fixed(127)), [120]);
+    assert_eq!(traced(&image, Threshold::Fixed(200)), [120, 140]);
+
+    // Otsu splits a bimodal histogram between its modes, wherever they are.
+    for (dark, light) in [(50, 170), (150, 220)] {
+        let image = RgbaImage::from_fn(16, 16, |x, y| match (x + y) % 2 {
+            0 => gray(dark + (x % 8) as u8),
+            _ => gray(light + (y % 8) as u8),
+        });
+        let traced = traced(&image, Threshold::Otsu);
+        assert_eq!(traced.len(), 128, "{dark} {light}");
+        assert!(traced.iter().all(|value| *value < light), "{dark} {light}");
+    }
+    let light = RgbaImage::from_fn(16, 16, |x, y| gray(150 + ((x + y) % 2) as u8 * 70));
+    assert!(traced(&light, Threshold::Fixed(127)).is_empty());
+
+    // Local thresholds trace dots 60 levels darker than their surroundings
+    // under uneven lighting, where no global threshold can.
+    let background = |x: u32| 100 + x as u8 * 4;
+    let is_dot = |x: u32, y: u32| x % 6 == 3 && y % 6 == 3;
+    let image = RgbaImage::from_fn(36, 12, |x, y| match is_dot(x, y) {
+        true => gray(background(x) - 60),
+        false => gray(background(x)),
+    });
+    let dots = (image.enumerate_pixels())
+        .filter(|(x, y, _)| is_dot(*x, *y))
+        .map(|(_, _, pixel)| pixel[0])
+        .collect::<Vec<_>>();
+    assert_ne!(traced(&image, Threshold::Otsu), dots);
+    let local = [
+        Threshold::Mean {
+            radius: 2,
+            offset: 10.0,
+        },
+        Threshold::Sauvola { radius: 2, k: 0.2 },
+    ];
+    for threshold in local {
+        assert_eq!(traced(&image, threshold), dots, "{threshold:?}");
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use svgen::{
+    create_svg_auto, create_svg_with_options,
+    metrics::rasterize_and_compare,
+    output::svg::to_svg,
+    structs::{ColorMode, Options},
+    vectorize_image,
+};
+
+/// A disc of `color` on white.
+fn disc(color: [u8; 4]) -> RgbaImage {
+    RgbaImage::from_fn(64, 64, |x, y| {
+        let (dx, dy) = (x as f64 - 31.5, y as f64 - 31.5);
+        if dx * dx + dy * dy < 20.0 * 20.0 {
+            Rgba(color)
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    })
+}
+
+fn png(image: &RgbaImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .unwrap();
+    bytes
+}
+
+#[test]
+fn traced_document_matches_source() {
+    let image = disc([0, 0, 0, 255]);
+    let options = Options {
+        color_mode: ColorMode::Black,
+        ..Options::default()
+    };
+    let svg = to_svg(&vectorize_image(&image, &options), &options);
+    let diff = rasterize_and_compare(&svg, &image).unwrap();
+
+    assert_eq!(diff.rendered.dimensions(), image.dimensions());
+    assert!(diff.ssim > 0.9, "{diff:?}");
+    // Only antialiased pixels along the edge differ.
+    assert!(diff.differing_pixels < 0.1, "{diff:?}");
+    assert!(diff.mean_error < 0.02, "{diff:?}");
+}
+
+#[test]
+fn identical_and_unrelated_documents() {
+    let image = disc([20, 30, 120, 255]);
+    let svg = create_svg_with_options(&png(&image), &Options::default());
+    let traced = rasterize_and_compare(&svg, &image).unwrap();
+    let itself = rasterize_and_compare(&svg, &traced.rendered).unwrap();
+    // Rounding when unpremultiplying the rendered pixels.
+    assert!(itself.max_error <= 1.0 / 255.0, "{itself:?}");
+    assert!(itself.ssim > 0.999, "{itself:?}");
+
+    let blank = r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64"/>"#;
+    let unrelated = rasterize_and_compare(blank, &image).unwrap();
+    assert!(unrelated.ssim < traced.ssim, "{unrelated:?}");
+    assert!(unrelated.mean_error > traced.mean_error, "{unrelated:?}");
+
+    assert!(rasterize_and_compare("<svg", &image).is_err());
+}
+
+#[test]
+fn auto_tune_reaches_target() {
+    let image = png(&disc([20, 30, 120, 255]));
+    let options = Options::default();
+    let loose = create_svg_auto(&image, 0.9, usize::MAX, &options);
+    let strict = create_svg_auto(&image, 0.97, usize::MAX, &options);
+
+    assert!(loose.diff.ssim >= 0.9, "{:?}", loose.diff.ssim);
+    assert!(strict.diff.ssim >= 0.97, "{:?}", strict.diff.ssim);
+    assert!(loose.node_count <= strict.node_count);
+    assert!(loose.options.error_threshold >= strict.options.error_threshold);
+}
+
+#[test]
+fn auto_tune_merges_regions_over_path_budget() {
+    let mut image = disc([0, 0, 0, 255]);
+    for i in 0..6 {
+        for (x, y) in [(2, 2), (3, 2), (2, 3), (3, 3)] {
+            image.put_pixel(x + i * 8, y, Rgba([0, 0, 0, 255]));
+        }
+    }
+    let options = Options {
+        color_mode: ColorMode::Black,
+        ..Options::default()
+    };
+    let tuned = create_svg_auto(&png(&image), 0.5, 1, &options);
+    assert_eq!(tuned.path_count, 1);
+}
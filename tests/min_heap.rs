@@ -0,0 +1,198 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use proptest::{collection::vec, prelude::*};
+use svgen::min_heap::{MinHeap, NodeHandle};
+
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(i32),
+    PopMin,
+    /// Removes the n-th inserted node, which may already be gone.
+    Remove(usize),
+    /// Updates the n-th inserted node, inserting it again when gone.
+    Update(usize, i32),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (-50i32..50).prop_map(Op::Insert),
+        2 => Just(Op::PopMin),
+        2 => any::<usize>().prop_map(Op::Remove),
+        3 => (any::<usize>(), -50i32..50).prop_map(|(n, v)| Op::Update(n, v)),
+    ]
+}
+
+/// A `BinaryHeap` with lazy deletion, entries are skipped
+/// when they don't match the current value of their id.
+#[derive(Default)]
+struct Reference {
+    heap: BinaryHeap<Reverse<(i32, usize)>>,
+    live: HashMap<usize, i32>,
+}
+
+impl Reference {
+    fn insert(&mut self, id: usize, value: i32) {
+        self.live.insert(id, value);
+        self.heap.push(Reverse((value, id)));
+    }
+
+    fn remove(&mut self, id: usize) -> Option<usize> {
+        self.live.remove(&id).map(|_| id)
+    }
+
+    fn skip_stale(&mut self) {
+        while let Some(Reverse((value, id))) = self.heap.peek() {
+            if self.live.get(id) == Some(value) {
+                break;
+            }
+            self.heap.pop();
+        }
+    }
+
+    fn peek(&mut self) -> Option<((i32, usize), usize)> {
+        self.skip_stale();
+        self.heap
+            .peek()
+            .map(|Reverse((value, id))| ((*value, *id), *id))
+    }
+
+    fn pop_min(&mut self) -> Option<usize> {
+        self.skip_stale();
+        let Reverse((_, id)) = self.heap.pop()?;
+        self.live.remove(&id);
+        Some(id)
+    }
+}
+
+proptest! {
+    #[test]
+    fn matches_binary_heap(ops in vec(op(), 0..200)) {
+        // Ties are broken by id, so both heaps have a single valid order.
+        let mut heap = MinHeap::<(i32, usize), usize>::new();
+        let mut reference = Reference::default();
+        let mut handles: Vec<NodeHandle> = Vec::new();
+
+        for op in ops {
+            match op {
+                Op::Insert(value) => {
+                    let id = handles.len();
+                    handles.push(heap.insert((value, id), id));
+                    reference.insert(id, value);
+                }
+                Op::PopMin => {
+                    prop_assert_eq!(heap.pop_min(), reference.pop_min());
+                }
+                Op::Remove(n) if !handles.is_empty() => {
+                    let id = n % handles.len();
+                    prop_assert_eq!(heap.remove(handles[id]), reference.remove(id));
+                }
+                Op::Update(n, value) if !handles.is_empty() => {
+                    let id = n % handles.len();
+                    heap.insert_or_update(&mut handles[id], (value, id), id);
+                    reference.insert(id, value);
+                }
+                _ => {}
+            }
+
+            prop_assert_eq!(heap.len(), reference.live.len());
+            prop_assert_eq!(heap.peek(), reference.peek());
+            for (id, handle) in handles.iter().enumerate() {
+                prop_assert_eq!(heap.contains(*handle), reference.live.contains_key(&id));
+            }
+            let mut nodes = heap.iter().map(|(_, (value, _), id)| (id, value)).collect::<Vec<_>>();
+            nodes.sort();
+            let mut live = reference.live.iter().map(|(id, value)| (*id, *value)).collect::<Vec<_>>();
+            live.sort();
+            prop_assert_eq!(nodes, live);
+        }
+
+        let mut values = Vec::new();
+        while let Some((value, _)) = heap.pop_min_with_value() {
+            values.push(value);
+        }
+        prop_assert!(values.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
+
+#[test]
+fn decrease_key_stress() {
+    let count = 2000;
+    let mut heap = MinHeap::<i64, usize>::with_capacity(count);
+    let handles = (0..count)
+        .map(|i| heap.insert(i as i64 * 7 % 1009, i))
+        .collect::<Vec<_>>();
+
+    // Repeatedly decrease every key, interleaved with pops.
+    let mut popped = vec![false; count];
+    for round in 0..10 {
+        for (i, handle) in handles.iter().enumerate() {
+            if !popped[i] {
+                let value = heap.node_value(*handle) - (i as i64 * 13 + round) % 101;
+                heap.node_value_update(*handle, value);
+            }
+        }
+        for _ in 0..count / 20 {
+            let (value, i) = heap.pop_min_with_value().unwrap();
+            assert!(heap.iter().all(|(_, other, _)| value <= other));
+            popped[i] = true;
+        }
+    }
+
+    for (i, handle) in handles.iter().enumerate() {
+        assert_eq!(heap.contains(*handle), !popped[i]);
+        if popped[i] {
+            assert_eq!(heap.remove(*handle), None);
+        }
+    }
+    let mut last = i64::MIN;
+    while let Some((value, _)) = heap.pop_min_with_value() {
+        assert!(last <= value);
+        last = value;
+    }
+}
+
+#[test]
+fn stale_handles() {
+    let mut heap = MinHeap::<f64, u8>::new();
+    let a = heap.insert(1.0, 0);
+    assert_eq!(heap.pop_min(), Some(0));
+
+    // The freed node is reused, the old handle must not refer to it.
+    let b = heap.insert(2.0, 1);
+    assert!(!heap.contains(a));
+    assert!(heap.contains(b));
+    assert_eq!(heap.remove(a), None);
+    assert_eq!(heap.peek(), Some((2.0, 1)));
+    assert!(!heap.contains(NodeHandle::INVALID));
+
+    let mut c = a;
+    heap.insert_or_update(&mut c, 0.5, 2);
+    assert_ne!(c, a);
+    assert_eq!(heap.pop_min(), Some(2));
+}
+
+#[test]
+#[should_panic(expected = "stale")]
+fn stale_handle_update_panics() {
+    let mut heap = MinHeap::<f64, u8>::new();
+    let a = heap.insert(1.0, 0);
+    heap.remove(a);
+    heap.insert(2.0, 1);
+    heap.node_value_update(a, 0.0);
+}
+
+#[test]
+fn duplicates() {
+    let mut heap = MinHeap::<u8, u8>::new();
+    for i in 0..10 {
+        heap.insert(i % 3, i);
+    }
+    let mut values = Vec::new();
+    while let Some((value, _)) = heap.pop_min_with_value() {
+        values.push(value);
+    }
+    assert_eq!(values, [0, 0, 0, 0, 1, 1, 1, 2, 2, 2]);
+}
@@ -0,0 +1,157 @@
+use image::{Rgba, RgbaImage};
+use svgen::{
+    structs::{ColorMode, CubicBezier, LineJoin, OffsetOptions, Options, Segment},
+    utils::{curve_offset_joined, polygon_offset},
+    vec2::DVec2,
+    vectorize_image,
+};
+
+/// A square of `size` at the origin, counter-clockwise in image coordinates
+/// as traced outlines keep their filled pixels on the left.
+fn square(size: f64) -> Vec<DVec2> {
+    [(0.0, 0.0), (0.0, size), (size, size), (size, 0.0)]
+        .map(|(x, y)| DVec2::new(x, y))
+        .to_vec()
+}
+
+/// Distance from `p` to the outline of a square of `size` at the origin.
+fn square_distance(p: DVec2, size: f64) -> f64 {
+    let dx = (-p.x).max(p.x - size).max(0.0);
+    let dy = (-p.y).max(p.y - size).max(0.0);
+    match (dx, dy) {
+        (0.0, 0.0) => p.x.min(p.y).min(size - p.x).min(size - p.y),
+        _ => dx.hypot(dy),
+    }
+}
+
+fn offset(distance: f64, join: LineJoin) -> OffsetOptions {
+    OffsetOptions {
+        distance,
+        join,
+        ..OffsetOptions::default()
+    }
+}
+
+/// Points along the segments of a curve.
+fn curve_points(is_cyclic: bool, curve: &[[DVec2; 3]]) -> Vec<DVec2> {
+    let pairs = match is_cyclic {
+        true => curve.len(),
+        false => curve.len() - 1,
+    };
+    let mut points = Vec::new();
+    for i in 0..pairs {
+        let (a, b) = (curve[i], curve[(i + 1) % curve.len()]);
+        let cubic = CubicBezier {
+            start: a[1],
+            control1: a[2],
+            control2: b[0],
+            end: b[1],
+        };
+        Segment::Cubic(cubic).flatten(0.01, &mut points);
+    }
+    points
+}
+
+#[test]
+fn polygon_joins() {
+    let moved = polygon_offset(true, &square(10.0), 1.0, &offset(1.0, LineJoin::Miter));
+    let corners = [(-1.0, -1.0), (-1.0, 11.0), (11.0, 11.0), (11.0, -1.0)];
+    assert_eq!(moved.len(), 4);
+    for (p, (x, y)) in moved.iter().zip(corners) {
+        assert!(p.len_with(DVec2::new(x, y)) < 1e-9, "{moved:?}");
+    }
+
+    let moved = polygon_offset(true, &square(10.0), 1.0, &offset(1.0, LineJoin::Bevel));
+    assert_eq!(moved.len(), 8);
+    let moved = polygon_offset(true, &square(10.0), 1.0, &offset(1.0, LineJoin::Round));
+    assert!(moved.len() > 8);
+    for p in &moved {
+        assert!((square_distance(*p, 10.0) - 1.0).abs() < 1e-9, "{p:?}");
+    }
+
+    // Inward, the corners are where the edges cross whatever the join.
+    let moved = polygon_offset(true, &square(10.0), -1.0, &offset(-1.0, LineJoin::Round));
+    let corners = [(1.0, 1.0), (1.0, 9.0), (9.0, 9.0), (9.0, 1.0)];
+    for (p, (x, y)) in moved.iter().zip(corners) {
+        assert!(p.len_with(DVec2::new(x, y)) < 1e-9, "{moved:?}");
+    }
+
+    // Open polylines keep square ends.
+    let line = [DVec2::new(0.0, 0.0), DVec2::new(0.0, 10.0)];
+    let moved = polygon_offset(false, &line, 2.0, &offset(2.0, LineJoin::Round));
+    assert_eq!(moved, vec![DVec2::new(-2.0, 0.0), DVec2::new(-2.0, 10.0)]);
+}
+
+#[test]
+fn curve_joins() {
+    // A circle of radius 10 from four arcs.
+    let handle = 4.0 / 3.0 * (std::f64::consts::PI / 8.0).tan() * 10.0;
+    let circle = (0..4)
+        .map(|i| {
+            // Counter-clockwise in image coordinates.
+            let a = -(i as f64) * std::f64::consts::FRAC_PI_2;
+            let point = DVec2::new(10.0 * a.cos(), 10.0 * a.sin());
+            let tangent = DVec2::new(a.sin(), -a.cos());
+            [
+                point.madd(tangent, -handle),
+                point,
+                point.madd(tangent, handle),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let moved = curve_offset_joined(true, &circle, 2.0, &offset(2.0, LineJoin::Round));
+    assert_eq!(moved.len(), 4);
+    for p in curve_points(true, &moved) {
+        assert!((p.len() - 12.0).abs() < 0.01, "{}", p.len());
+    }
+
+    // Straight corners get round joins outward.
+    let polygon = square(10.0).into_iter().map(|p| [p; 3]).collect::<Vec<_>>();
+    let moved = curve_offset_joined(true, &polygon, 1.0, &offset(1.0, LineJoin::Round));
+    assert_eq!(moved.len(), 8);
+    for p in curve_points(true, &moved) {
+        assert!((square_distance(p, 10.0) - 1.0).abs() < 1e-3, "{p:?}");
+    }
+    let moved = curve_offset_joined(true, &polygon, 1.0, &offset(1.0, LineJoin::Miter));
+    assert_eq!(moved.len(), 4);
+    for p in moved[0] {
+        assert!(p.len_with(DVec2::new(-1.0, -1.0)) < 1e-9, "{p:?}");
+    }
+}
+
+#[test]
+fn traced_offset() {
+    // A black square with a window, on white.
+    let image = RgbaImage::from_fn(40, 40, |x, y| {
+        let square = (10..30).contains(&x) && (10..30).contains(&y);
+        let window = (18..22).contains(&x) && (18..22).contains(&y);
+        match square && !window {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let trace = |distance| {
+        let options = Options {
+            color_mode: ColorMode::Black,
+            preprocess: false,
+            offset: offset(distance, LineJoin::Miter),
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        let layer = &result.layers[0];
+        let bbox = layer.bbox().map(|b| [b.min.x, b.min.y, b.max.x, b.max.y]);
+        (
+            bbox.map(|b| b.map(f64::round)),
+            layer.polygons.len(),
+            layer.curves.len(),
+        )
+    };
+
+    assert_eq!(trace(0.0), (Some([10.0, 10.0, 30.0, 30.0]), 2, 2));
+    assert_eq!(trace(2.0).0, Some([8.0, 8.0, 32.0, 32.0]));
+    // The window closes.
+    assert_eq!(trace(2.5).1, 1);
+    assert_eq!(trace(-2.0), (Some([12.0, 12.0, 28.0, 28.0]), 2, 2));
+    // Thinner than twice the inset.
+    assert_eq!(trace(-11.0), (None, 0, 0));
+}
@@ -0,0 +1,76 @@
+use svgen::{
+    structs::{ColorMode, Options, Orientation, VectorizationResult},
+    try_vectorize,
+    utils::{curve_area, is_clockwise, polygon_area},
+    vec2::DVec2,
+};
+
+fn trace(fixture: &str, color_mode: ColorMode, orientation: Orientation) -> VectorizationResult {
+    let bytes = std::fs::read(format!("tests/fixtures/{fixture}.png")).unwrap();
+    let options = Options {
+        color_mode,
+        orientation,
+        ..Options::default()
+    };
+    try_vectorize(&bytes, &options).unwrap()
+}
+
+#[test]
+fn polygon_winding() {
+    // Right, then down the Y axis, as seen in an image.
+    let square = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)].map(|(x, y)| DVec2::new(x, y));
+    assert_eq!(polygon_area(&square), 4.0);
+    assert!(is_clockwise(&square));
+
+    let mut reversed = square;
+    reversed.reverse();
+    assert_eq!(polygon_area(&reversed), -4.0);
+    assert!(!is_clockwise(&reversed));
+
+    assert_eq!(polygon_area(&[]), 0.0);
+    assert!(!is_clockwise(&square[..2]));
+}
+
+#[test]
+fn contour_orientation() {
+    for (fixture, color_mode, orientation) in [
+        ("holes", ColorMode::Black, Orientation::OuterClockwise),
+        (
+            "holes",
+            ColorMode::Black,
+            Orientation::OuterCounterClockwise,
+        ),
+        // Grain traced as many small contours, some of which fitting used to turn inside out.
+        (
+            "noisy_scan",
+            ColorMode::Colored,
+            Orientation::OuterClockwise,
+        ),
+    ] {
+        let result = trace(fixture, color_mode, orientation);
+        let contours = result
+            .layers
+            .iter()
+            .flat_map(|layer| layer.polygons.iter().zip(&layer.curves));
+        let mut holes = 0;
+        for (node, ((is_cyclic, polygon), (_, curve))) in result.contour_tree.iter().zip(contours) {
+            let area = polygon_area(polygon);
+            if !is_cyclic || area == 0.0 {
+                continue;
+            }
+            let is_outer_clockwise = orientation == Orientation::OuterClockwise;
+            assert_eq!(is_clockwise(polygon), is_outer_clockwise != node.is_hole);
+            // Fitted curves keep the winding of their polygon, unless collapsed to a line.
+            let curve_area = curve_area(curve);
+            assert!(
+                curve_area.abs() < 1e-9 || curve_area * area > 0.0,
+                "{fixture}"
+            );
+            holes += node.is_hole as usize;
+        }
+        if fixture == "holes" {
+            // The ring, the square's two windows.
+            assert_eq!(holes, 3);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use image::{Rgba, RgbaImage};
+use proptest::{collection::vec, prelude::*};
+use svgen::{
+    algo::{direction_mask, extract_outline, extract_outline_seeded},
+    mask::BitMask,
+    output::svg::to_svg,
+    structs::{BorderContours, ColorMode, Connectivity, Mode, Options, TurnPolicy},
+    vec2::DVec2,
+    vectorize_image,
+};
+
+const TURN_POLICIES: [TurnPolicy; 7] = [
+    TurnPolicy::Black,
+    TurnPolicy::White,
+    TurnPolicy::Majority,
+    TurnPolicy::Minority,
+    TurnPolicy::Right,
+    TurnPolicy::Left,
+    TurnPolicy::Random,
+];
+
+/// Asserts the outline of `mask` has closed contours of unit steps
+/// covering every pixel edge once, with every turn policy.
+fn assert_outline(mask: &BitMask) {
+    let (_, edges) = direction_mask(mask, 1);
+    for turn_policy in TURN_POLICIES {
+        let outline = extract_outline(mask, turn_policy, false);
+        let mut steps = 0;
+        for (is_cyclic, poly) in &outline {
+            assert!(is_cyclic);
+            for (i, a) in poly.iter().enumerate() {
+                let b = poly[(i + 1) % poly.len()];
+                assert_eq!((a.x - b.x).abs() + (a.y - b.y).abs(), 1, "{turn_policy:?}");
+            }
+            steps += poly.len();
+        }
+        assert_eq!(steps, edges, "{turn_policy:?}");
+    }
+}
+
+#[test]
+fn checkerboards() {
+    // Every pair of diagonal pixels is ambiguous.
+    for size in [1, 2, 3, 8, 65] {
+        let checkerboard = (0..size * size).map(|i| (i % size + i / size) % 2 == 0);
+        assert_outline(&BitMask::from_bits([size, size], checkerboard));
+        let inverted = (0..size * size).map(|i| (i % size + i / size) % 2 == 1);
+        assert_outline(&BitMask::from_bits([size, size], inverted));
+    }
+    // Isolated single pixels.
+    let dots = (0..16 * 16).map(|i| i % 2 == 0 && (i / 16) % 2 == 0);
+    assert_outline(&BitMask::from_bits([16, 16], dots));
+}
+
+#[test]
+fn connectivity() {
+    // Two squares touching at a corner.
+    let is_filled = |x: usize, y: usize| (x < 4) == (y < 4);
+    let mask = BitMask::from_bits([8, 8], (0..64).map(|i| is_filled(i % 8, i / 8)));
+    let outline = |connectivity: Connectivity| extract_outline(&mask, connectivity.into(), true);
+    assert_eq!(outline(Connectivity::Four).len(), 2);
+    assert_eq!(outline(Connectivity::Eight).len(), 1);
+
+    // Whatever the turn policy, through the options.
+    let image = RgbaImage::from_fn(8, 8, |x, y| match is_filled(x as usize, y as usize) {
+        true => Rgba([0, 0, 0, 255]),
+        false => Rgba([255, 255, 255, 255]),
+    });
+    for turn_policy in TURN_POLICIES {
+        let paths = |connectivity| {
+            let options = Options {
+                color_mode: ColorMode::Black,
+                mode: Mode::PixelPerfect,
+                turn_policy,
+                connectivity,
+                ..Options::default()
+            };
+            vectorize_image(&image, &options).layers[0].paths.len()
+        };
+        assert_eq!(paths(Some(Connectivity::Four)), 2, "{turn_policy:?}");
+        assert_eq!(paths(Some(Connectivity::Eight)), 1, "{turn_policy:?}");
+    }
+}
+
+proptest! {
+    #[test]
+    fn random_masks(width in 1usize..24, bits in vec(any::<bool>(), 1..400)) {
+        let height = bits.len().div_ceil(width);
+        assert_outline(&BitMask::from_bits([width, height], bits));
+    }
+}
+
+#[test]
+fn random_turn_policy_seed() {
+    let size = 33;
+    let checkerboard = (0..size * size).map(|i| (i % size + i / size) % 2 == 0);
+    let mask = BitMask::from_bits([size, size], checkerboard);
+    let outline = |seed| extract_outline_seeded(&mask, TurnPolicy::Random, false, seed);
+    assert_eq!(
+        outline(0),
+        extract_outline(&mask, TurnPolicy::Random, false)
+    );
+    assert_eq!(outline(7), outline(7));
+    assert_ne!(outline(7), outline(0));
+
+    // The same seed gives the same document, through the options.
+    let image = RgbaImage::from_fn(48, 48, |x, y| {
+        let v = ((x * 37 + y * 91) ^ (x * y)) % 251;
+        Rgba([v as u8, (v * 3 % 256) as u8, (255 - v) as u8, 255])
+    });
+    let svg = |seed| {
+        let options = Options {
+            turn_policy: TurnPolicy::Random,
+            seed,
+            ..Options::default()
+        };
+        to_svg(&vectorize_image(&image, &options), &options)
+    };
+    assert_eq!(svg(Some(3)), svg(Some(3)));
+    assert_eq!(svg(None), svg(Some(0)));
+}
+
+#[test]
+fn right_left_turn_policies() {
+    // Rows from the top, `#` filled.
+    let mask = |rows: &[&str]| {
+        let bits = rows.iter().flat_map(|row| row.chars().map(|c| c == '#'));
+        BitMask::from_bits([rows[0].len(), rows.len()], bits.collect::<Vec<_>>())
+    };
+    let contours = |mask: &BitMask, turn_policy| extract_outline(mask, turn_policy, true).len();
+
+    // Diagonal pixels on an outer contour, Right connects them like Black.
+    let outer = mask(&["....", ".#..", "..#.", "...."]);
+    // On a hole, like White, Left connecting the filled pixels.
+    let hole = mask(&["######", "######", "##.###", "###.##", "######", "######"]);
+    // An island in a hole is an outer contour again.
+    let island = mask(&[
+        "##########",
+        "#........#",
+        "#........#",
+        "#...#....#",
+        "#....#...#",
+        "#........#",
+        "#........#",
+        "##########",
+    ]);
+    for (mask, right, left) in [(&outer, 1, 2), (&hole, 2, 3), (&island, 3, 4)] {
+        assert_eq!(contours(mask, TurnPolicy::Right), right);
+        assert_eq!(contours(mask, TurnPolicy::Left), left);
+        assert_outline(mask);
+    }
+    assert_eq!(contours(&hole, TurnPolicy::Black), 3);
+    assert_eq!(contours(&hole, TurnPolicy::White), 2);
+}
+
+#[test]
+fn border_contours() {
+    // A half disc on the left border, and a square away from it.
+    let image = RgbaImage::from_fn(32, 32, |x, y| {
+        let (dx, dy) = (x as f64 + 0.5, y as f64 - 16.0);
+        let is_filled = dx * dx + dy * dy < 100.0 || (20..28).contains(&x) && (4..12).contains(&y);
+        match is_filled {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let is_inner = |polygon: &[DVec2]| polygon.iter().all(|v| v.x > 0.0);
+    let trace = |border_contours| {
+        let options = Options {
+            color_mode: ColorMode::Black,
+            border_contours,
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        let svg = to_svg(&result, &options);
+        let mut polygons = result.layers[0].polygons.clone();
+        // The half disc first.
+        polygons.sort_by_key(|(_, polygon)| is_inner(polygon));
+        (polygons, svg)
+    };
+
+    // Only the inner edges of the half disc, as an open path from border to border.
+    let (polygons, svg) = trace(BorderContours::Open);
+    let (is_cyclic, disc) = &polygons[0];
+    assert!(!is_cyclic);
+    let (first, last) = (disc[0], disc[disc.len() - 1]);
+    assert_eq!(
+        [(first.x, first.y), (last.x, last.y)],
+        [(0.0, 26.0), (0.0, 7.0)]
+    );
+    assert!(is_inner(&disc[1..disc.len() - 1]));
+    // The square is still closed.
+    assert!(polygons[1].0);
+    assert_eq!(svg.matches('z').count(), 1);
+
+    // Closed, with the border edge kept as the straight line between its ends.
+    let (polygons, svg) = trace(BorderContours::Clip);
+    let (is_cyclic, disc) = &polygons[0];
+    assert!(is_cyclic);
+    let on_border: Vec<_> = (disc.iter()).filter(|v| v.x == 0.0).map(|v| v.y).collect();
+    assert_eq!(on_border.len(), 2);
+    assert!(on_border.contains(&7.0) && on_border.contains(&26.0));
+    assert!(polygons[1].0);
+    assert_eq!(svg.matches('z').count(), 2);
+}
+
+#[test]
+fn shared_boundaries() {
+    // A red disc on blue, the disc contour is the blue hole.
+    let image = RgbaImage::from_fn(32, 32, |x, y| {
+        let (dx, dy) = (x as f64 - 15.5, y as f64 - 15.5);
+        match dx * dx + dy * dy < 81.0 {
+            true => Rgba([255, 0, 0, 255]),
+            false => Rgba([0, 0, 255, 255]),
+        }
+    });
+    // Knots of the red disc missing from the blue hole, in either direction.
+    let seams = |shared_boundaries| {
+        let options = Options {
+            preprocess: false,
+            shared_boundaries,
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        let layer = |color| result.layers.iter().find(|l| l.color == color).unwrap();
+        let (red, blue) = (layer([255, 0, 0, 255]), layer([0, 0, 255, 255]));
+        assert_eq!((red.curves.len(), blue.curves.len()), (1, 2));
+        let hole = blue.curves.iter().flat_map(|(_, curve)| curve);
+        let hole: Vec<_> = hole.flat_map(|&[a, p, b]| [[a, p, b], [b, p, a]]).collect();
+        (red.curves[0].1.iter())
+            .filter(|knot| !hole.contains(knot))
+            .count()
+    };
+    assert!(seams(false) > 0);
+    assert_eq!(seams(true), 0);
+}
+
+#[test]
+fn contour_tree() {
+    // Blue with a red frame, holding a red dot in its window.
+    let within = |v: u32, start: u32, end: u32| (start..end).contains(&v);
+    let image = RgbaImage::from_fn(32, 32, |x, y| {
+        let is_inside = |start, end| within(x, start, end) && within(y, start, end);
+        match is_inside(4, 28) && !is_inside(10, 22) || is_inside(14, 18) {
+            true => Rgba([255, 0, 0, 255]),
+            false => Rgba([0, 0, 255, 255]),
+        }
+    });
+    for mode in [Mode::PixelPerfect, Options::default().mode] {
+        let options = Options {
+            color_mode: ColorMode::Colored,
+            mode,
+            preprocess: false,
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        assert_eq!(result.layers[0].color, [0, 0, 255, 255]);
+        let tree = &result.contour_tree;
+        // `(layer, contour, parent, is_hole)`, contours of both layers sharing their shape
+        // (the frame and the hole of the blue layer it fills) are siblings.
+        let nodes: Vec<_> = (tree.iter())
+            .map(|node| (node.layer, node.contour, node.parent, node.is_hole))
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                // Blue: the image, the frame's hole in it, the window and the dot's hole.
+                (0, 0, None, false),
+                (0, 1, Some(0), true),
+                (0, 2, Some(1), false),
+                (0, 3, Some(2), true),
+                // Red: the frame, its window and the dot.
+                (1, 0, Some(0), false),
+                (1, 1, Some(1), true),
+                (1, 2, Some(2), false),
+            ],
+            "{mode:?}"
+        );
+        for (i, node) in tree.iter().enumerate() {
+            let children = (0..tree.len()).filter(|&j| tree[j].parent == Some(i));
+            assert_eq!(node.children, children.collect::<Vec<_>>());
+        }
+    }
+}
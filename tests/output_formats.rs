@@ -0,0 +1,221 @@
+use std::fs;
+
+use image::{Rgba, RgbaImage};
+use svgen::{
+    create_geojson, create_output, create_pdf,
+    output::{dxf::to_dxf, eps::to_eps, geojson::to_geojson, pdf::to_pdf},
+    structs::{ColorMode, DxfOptions, DxfUnits, Mode, Options, OutputFormat},
+    vectorize_image,
+};
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+/// White image of `size` with the `(color, [x, y, width, height])` rectangles painted in order.
+fn bitmap(size: u32, rects: &[([u8; 4], [u32; 4])]) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(size, size, Rgba(WHITE));
+    for (color, [x, y, width, height]) in rects {
+        for py in *y..y + height {
+            for px in *x..x + width {
+                image.put_pixel(px, py, Rgba(*color));
+            }
+        }
+    }
+    image
+}
+
+fn pixel_options() -> Options {
+    Options {
+        color_mode: ColorMode::Black,
+        mode: Mode::PixelPerfect,
+        ..Options::default()
+    }
+}
+
+#[test]
+fn geojson() {
+    let options = pixel_options();
+
+    // A ring with a dot in its hole: the hole is nested into the ring's polygon,
+    // the dot is a polygon of its own, and every ring repeats its first position.
+    let nested = bitmap(
+        40,
+        &[
+            (BLACK, [5, 5, 30, 30]),
+            (WHITE, [10, 10, 20, 20]),
+            (BLACK, [17, 17, 6, 6]),
+        ],
+    );
+    assert_eq!(
+        to_geojson(&vectorize_image(&nested, &options), &options),
+        concat!(
+            r#"{"type":"FeatureCollection","features":[{"type":"Feature","#,
+            r##""properties":{"fill":"#000"},"geometry":{"type":"MultiPolygon","coordinates":["##,
+            "[[[35,5],[35,35],[5,35],[5,5],[35,5]],[[10,10],[10,30],[30,30],[30,10],[10,10]]],",
+            "[[[23,17],[23,23],[17,23],[17,17],[23,17]]]",
+            "]}}]}",
+        ),
+    );
+
+    // A single ring is a `Polygon`, and translucent layers have a `fill-opacity`.
+    let square = bitmap(8, &[(BLACK, [2, 2, 4, 4])]);
+    let mut result = vectorize_image(&square, &options);
+    result.layers[0].color = [255, 0, 0, 128];
+    assert!(to_geojson(&result, &options).contains(concat!(
+        r##""properties":{"fill":"#F00","fill-opacity":0.5},"##,
+        r#""geometry":{"type":"Polygon","coordinates":[[[6,2],[6,6],[2,6],[2,2],[6,2]]]}"#,
+    )));
+
+    // Picked through the output format.
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let options = Options {
+        output_format: OutputFormat::GeoJson,
+        ..Options::default()
+    };
+    assert_eq!(
+        create_output(&png, &options),
+        create_geojson(&png, &options).into_bytes()
+    );
+}
+
+#[test]
+fn pdf() {
+    let options = pixel_options();
+    let square = bitmap(8, &[(BLACK, [2, 2, 4, 4])]);
+    let mut result = vectorize_image(&square, &options);
+    let pdf = String::from_utf8(to_pdf(&result)).unwrap();
+    assert!(pdf.starts_with("%PDF-1.4\n"));
+    assert!(pdf.ends_with("%%EOF\n"));
+    assert!(pdf.contains("/MediaBox [0 0 8 8]"));
+
+    // One filled and stroked path per layer, flipped to image coordinates.
+    let content = concat!(
+        "1 0 0 -1 0 8 cm\n1 w\n",
+        "0 0 0 rg 0 0 0 RG\n",
+        "6 2 m\n6 6 l\n2 6 l\n2 2 l\n6 2 l\nh\nB\n",
+    );
+    assert!(pdf.contains(&format!(
+        "<< /Length {} >>\nstream\n{content}endstream",
+        content.len()
+    )));
+
+    // The cross-reference table points at each object.
+    let (body, xref) = pdf.split_once("xref\n").unwrap();
+    let startxref = pdf.lines().rev().nth(1).unwrap();
+    assert_eq!(startxref.parse::<usize>().unwrap(), body.len());
+    assert!(xref.starts_with("0 5\n0000000000 65535 f \n"));
+    for (i, line) in xref.lines().skip(2).take(4).enumerate() {
+        let offset: usize = line.strip_suffix(" 00000 n ").unwrap().parse().unwrap();
+        assert!(pdf[offset..].starts_with(&format!("{} 0 obj\n", i + 1)));
+    }
+
+    // Transparent layers are not painted.
+    result.layers[0].color[3] = 0;
+    let pdf = String::from_utf8(to_pdf(&result)).unwrap();
+    assert!(!pdf.contains(" m\n"));
+
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let options = Options {
+        output_format: OutputFormat::Pdf,
+        ..Options::default()
+    };
+    assert_eq!(create_output(&png, &options), create_pdf(&png, &options));
+}
+
+#[test]
+fn eps() {
+    let options = pixel_options();
+    let square = bitmap(8, &[(BLACK, [2, 2, 4, 4])]);
+    let mut result = vectorize_image(&square, &options);
+    result.layers[0].color = [255, 0, 0, 255];
+    let eps = to_eps(&result);
+    assert!(eps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 0 0 8 8\n"));
+    // Flipped to image coordinates, then one filled and stroked path per layer.
+    assert!(eps.ends_with(concat!(
+        "0 8 translate 1 -1 scale\n1 setlinewidth\n",
+        "1 0 0 setrgbcolor newpath\n",
+        "6 2 m\n6 6 l\n2 6 l\n2 2 l\n6 2 l\nz\n",
+        "gsave fill grestore stroke\n",
+        "grestore\nshowpage\n%%EOF\n",
+    )));
+
+    // Transparent layers are not painted.
+    result.layers[0].color[3] = 0;
+    assert!(!to_eps(&result).contains("newpath"));
+
+    // Curves are written with `curveto`, picked through the output format.
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let options = Options {
+        output_format: OutputFormat::Eps,
+        ..Options::default()
+    };
+    let eps = String::from_utf8(create_output(&png, &options)).unwrap();
+    assert!(eps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n"));
+    assert!(eps.lines().any(|line| line.ends_with(" c")));
+}
+
+#[test]
+fn dxf() {
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    let image = bitmap(8, &[(RED, [1, 1, 2, 2]), ([0, 0, 255, 255], [4, 4, 3, 3])]);
+    let options = Options {
+        color_mode: ColorMode::Colored,
+        ..pixel_options()
+    };
+    let mut result = vectorize_image(&image, &options);
+    assert_eq!(result.layers.len(), 3);
+    let layers = |dxf: &str| dxf.matches("\nAcDbLayerTableRecord\n").count();
+    assert_eq!(layers(&to_dxf(&result, &options)), 3);
+
+    // Units in the header, and only the layers of the listed colors.
+    let options = Options {
+        dxf: DxfOptions {
+            units: DxfUnits::Millimeters,
+            colors: Some(vec![[255, 0, 0]]),
+            ..DxfOptions::default()
+        },
+        ..options
+    };
+    let dxf = to_dxf(&result, &options);
+    assert!(dxf.contains("\n$INSUNITS\n70\n4\n"));
+    assert!(dxf.contains("\n$EXTMAX\n10\n8\n20\n8\n"));
+    assert_eq!(layers(&dxf), 1);
+    assert!(dxf.contains("\nLAYER\n70\n1\n"));
+    // A closed polyline on the layer named after the color, flipped to y-up.
+    assert!(dxf.contains(concat!(
+        "0\nLWPOLYLINE\n100\nAcDbEntity\n8\nFF0000\n100\nAcDbPolyline\n90\n4\n70\n1\n",
+        "10\n3\n20\n7\n10\n3\n20\n5\n10\n1\n20\n5\n10\n1\n20\n7\n0\n",
+    )));
+    assert_eq!(dxf.matches("\nLWPOLYLINE\n").count(), 1);
+    assert!(dxf.ends_with("0\nENDSEC\n0\nEOF\n"));
+
+    // Translucent colors append their alpha to the layer name.
+    result.layers[1].color[3] = 128;
+    assert!(to_dxf(&result, &options).contains("\n8\nFF000080\n"));
+    result.layers[1].color[3] = 255;
+
+    // Splines: a clamped knot vector with every inner knot repeated 3 times.
+    let options = Options {
+        dxf: DxfOptions {
+            splines: true,
+            ..options.dxf
+        },
+        ..options
+    };
+    let dxf = to_dxf(&result, &options);
+    assert!(!dxf.contains("LWPOLYLINE"));
+    // 4 segments: 13 control points and 17 knots.
+    assert!(dxf.contains("\n8\nFF0000\n100\nAcDbSpline\n"));
+    assert!(dxf.contains("\n71\n3\n72\n17\n73\n13\n74\n0\n"));
+    let knots: Vec<&str> = dxf
+        .split("\n40\n")
+        .skip(1)
+        .map(|knot| knot.lines().next().unwrap())
+        .collect();
+    assert_eq!(
+        knots,
+        ["0", "0", "0", "0", "1", "1", "1", "2", "2", "2", "3", "3", "3", "4", "4", "4", "4"]
+    );
+    // The control points, along with the extents in the header.
+    assert_eq!(dxf.matches("\n30\n0\n").count(), 13 + 2);
+}
@@ -0,0 +1,424 @@
+use proptest::{collection::vec, prelude::*};
+use svgen::{
+    path_optimizer::{Command, OptimizedData, Parameters, Position},
+    simplify_svg, simplify_svg_path,
+    vec2::DVec2,
+};
+
+/// Coordinates on a 0.01 grid, so two decimal serialization is exact.
+fn coord() -> impl Strategy<Value = f64> {
+    (-100_000i32..100_000).prop_map(|v| v as f64 / 100.0)
+}
+
+fn position() -> impl Strategy<Value = Position> {
+    prop_oneof![Just(Position::Absolute), Just(Position::Relative)]
+}
+
+fn command() -> impl Strategy<Value = Command> {
+    (0usize..10, position(), vec(coord(), 7)).prop_map(|(kind, pos, v)| {
+        let params = |n: usize| Parameters(v[..n].to_vec());
+        match kind {
+            0 => Command::M(pos, params(2)),
+            1 => Command::L(pos, params(2)),
+            2 => Command::H(pos, params(1)),
+            3 => Command::V(pos, params(1)),
+            4 => Command::C(pos, params(6)),
+            5 => Command::S(pos, params(4)),
+            6 => Command::Q(pos, params(4)),
+            7 => Command::T(pos, params(2)),
+            8 => Command::A(
+                pos,
+                Parameters(vec![
+                    v[0].abs(),
+                    v[1].abs(),
+                    v[2],
+                    f64::from(u8::from(v[3] < 0.0)),
+                    f64::from(u8::from(v[4] < 0.0)),
+                    v[5],
+                    v[6],
+                ]),
+            ),
+            _ => Command::Z,
+        }
+    })
+}
+
+fn path() -> impl Strategy<Value = OptimizedData> {
+    (coord(), coord(), vec(command(), 0..32)).prop_map(|(x, y, commands)| {
+        let mut data =
+            OptimizedData::new().add(Command::M(Position::Absolute, Parameters(vec![x, y])));
+        for command in commands {
+            data.append(command);
+        }
+        data
+    })
+}
+
+/// Paths of short axis aligned and diagonal lines, so runs of collinear segments are common.
+fn line_path() -> impl Strategy<Value = OptimizedData> {
+    let line = (0usize..4, position(), -3i32..4, -3i32..4).prop_map(|(kind, pos, x, y)| {
+        let (x, y) = (x as f64, y as f64);
+        match kind {
+            0 => Command::L(pos, Parameters(vec![x, y])),
+            1 => Command::H(pos, Parameters(vec![x])),
+            2 => Command::V(pos, Parameters(vec![y])),
+            _ => Command::L(Position::Relative, Parameters(vec![x, x])),
+        }
+    });
+    let segment = prop_oneof![
+        8 => line,
+        1 => Just(Command::Z),
+        1 => (-3i32..4, -3i32..4).prop_map(|(x, y)| {
+            Command::M(Position::Relative, Parameters(vec![x as f64, y as f64]))
+        }),
+    ];
+    vec(segment, 0..24).prop_map(|commands| {
+        let mut data =
+            OptimizedData::new().add(Command::M(Position::Absolute, Parameters(vec![0.0, 0.0])));
+        for command in commands {
+            data.append(command);
+        }
+        data
+    })
+}
+
+fn parts(command: &Command) -> (char, &[f64]) {
+    match command {
+        Command::M(_, params) => ('M', params),
+        Command::L(_, params) => ('L', params),
+        Command::H(_, params) => ('H', params),
+        Command::V(_, params) => ('V', params),
+        Command::C(_, params) => ('C', params),
+        Command::S(_, params) => ('S', params),
+        Command::Q(_, params) => ('Q', params),
+        Command::T(_, params) => ('T', params),
+        Command::A(_, params) => ('A', params),
+        Command::Z => ('Z', &[]),
+    }
+}
+
+/// Asserts both paths have the same commands with absolute parameters within `epsilon`.
+fn assert_same_geometry(a: &OptimizedData, b: &OptimizedData, epsilon: f64) {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    a.to_absolute();
+    b.to_absolute();
+    assert_eq!(a.len(), b.len(), "{a:?} != {b:?}");
+    for (command_a, command_b) in a.iter().zip(b.iter()) {
+        let ((letter_a, params_a), (letter_b, params_b)) = (parts(command_a), parts(command_b));
+        assert_eq!(letter_a, letter_b);
+        assert_eq!(params_a.len(), params_b.len());
+        for (value_a, value_b) in params_a.iter().zip(params_b) {
+            assert!(
+                (value_a - value_b).abs() <= epsilon,
+                "{command_a:?} != {command_b:?}"
+            );
+        }
+    }
+}
+
+/// Packs runs of the same command into one command with repeated parameter groups.
+fn pack(data: &OptimizedData) -> OptimizedData {
+    let mut packed = OptimizedData::new();
+    for command in data.iter() {
+        let is_same = packed.last().is_some_and(|last| {
+            !matches!(last, Command::M(..) | Command::Z)
+                && std::mem::discriminant(last) == std::mem::discriminant(command)
+                && last.parts().map(|(pos, _)| pos) == command.parts().map(|(pos, _)| pos)
+        });
+        if is_same {
+            let (_, args) = packed.last_mut().unwrap().parts_mut().unwrap();
+            args.0.extend_from_slice(command.parts().unwrap().1);
+        } else {
+            packed.append(command.clone());
+        }
+    }
+    packed
+}
+
+fn segment_distance(p: DVec2, a: DVec2, b: DVec2) -> f64 {
+    let ab = b.sub(a);
+    let len_sq = ab.len_squared();
+    if len_sq == 0.0 {
+        return p.len_with(a);
+    }
+    let t = (p.sub(a).dot(ab) / len_sq).clamp(0.0, 1.0);
+    p.len_with(a.interp(b, t))
+}
+
+proptest! {
+    #[test]
+    fn parse_malformed_text(text in "[MmLlHhVvCcSsQqTtAaZz0-9 ,.eE+-]{0,40}|\\PC{0,20}") {
+        // Malformed data is an error, parsed data is written back as valid data.
+        if let Ok(data) = text.parse::<OptimizedData>() {
+            data.optimize().parse::<OptimizedData>().unwrap();
+        }
+    }
+
+    #[test]
+    fn parse_round_trip(data in path()) {
+        let text = data
+            .iter()
+            .map(|command| String::from(command.clone()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let parsed: OptimizedData = text.parse().unwrap();
+        assert_same_geometry(&data, &parsed, 1e-9);
+    }
+
+    #[test]
+    fn parse_exponent_notation(mut data in path()) {
+        data.to_absolute();
+        let text = data
+            .iter()
+            .map(|command| {
+                let (letter, params) = parts(command);
+                let numbers = params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| match (letter, i) {
+                        // Arc flags are a single digit.
+                        ('A', 3 | 4) => v.to_string(),
+                        _ => format!("{v:e}"),
+                    })
+                    .collect::<Vec<_>>();
+                format!("{letter}{}", numbers.join(","))
+            })
+            .collect::<String>();
+        let parsed: OptimizedData = text.parse().unwrap();
+        assert_same_geometry(&data, &parsed, 1e-9);
+    }
+
+    #[test]
+    fn relative_absolute_round_trip(data in path()) {
+        let mut converted = data.clone();
+        converted.to_relative();
+        converted.to_absolute();
+        assert_same_geometry(&data, &converted, 1e-6);
+    }
+
+    #[test]
+    fn optimize_round_trip(data in path()) {
+        let mut relative = data.clone();
+        relative.to_relative();
+        let parsed: OptimizedData = relative.optimize().parse().unwrap();
+        assert_same_geometry(&data, &parsed, 1e-6);
+    }
+
+    #[test]
+    fn repeated_groups_convert(data in path()) {
+        let mut packed = pack(&data);
+        packed.to_relative();
+        packed.split_groups();
+        assert_same_geometry(&data, &packed, 1e-6);
+
+        let (mut quadratic, mut quadratic_packed) = (data.clone(), pack(&data));
+        quadratic.to_quadratic(0.5);
+        quadratic_packed.to_quadratic(0.5);
+        prop_assert_eq!(quadratic.optimize(), quadratic_packed.optimize());
+    }
+
+    #[test]
+    fn merge_lines_keeps_geometry(data in line_path()) {
+        let mut merged = pack(&data);
+        merged.merge_lines();
+        prop_assert!(merged.len() <= data.len());
+
+        let polys = data.to_polygons(0.1);
+        let polys_merged = merged.to_polygons(0.1);
+        prop_assert_eq!(polys.len(), polys_merged.len());
+        for ((is_cyclic, poly), (is_cyclic_merged, poly_merged)) in polys.iter().zip(&polys_merged) {
+            prop_assert_eq!(is_cyclic, is_cyclic_merged);
+            // Every merged vertex is an original one,
+            // and every original vertex lies on the merged outline.
+            for p in poly_merged {
+                prop_assert!(poly.iter().any(|q| p.len_with(*q) < 1e-9));
+            }
+            let mut outline = poly_merged.clone();
+            if *is_cyclic {
+                outline.push(poly_merged[0]);
+            }
+            for p in poly {
+                prop_assert!(outline
+                    .windows(2)
+                    .any(|w| segment_distance(*p, w[0], w[1]) < 1e-9));
+            }
+        }
+    }
+}
+
+#[test]
+fn parse_implicit_commands() {
+    let data: OptimizedData = "M0 0 10 10 20 0zm1,1h2 3".parse().unwrap();
+    let letters: String = data.iter().map(|command| parts(command).0).collect();
+    assert_eq!(letters, "MLLZMHH");
+}
+
+#[test]
+fn relative_repeated_lines() {
+    let mut data = OptimizedData::new()
+        .add(Command::M(Position::Absolute, Parameters(vec![5.0, 5.0])))
+        .add(Command::L(
+            Position::Absolute,
+            Parameters(vec![10.0, 10.0, 20.0, 20.0]),
+        ));
+    data.to_relative();
+    assert_eq!(data.optimize(), "M5 5l5 5 10 10");
+}
+
+#[test]
+fn optimize_shortest() {
+    let line = |start: [f64; 2], end: [f64; 2]| {
+        OptimizedData::new()
+            .add(Command::M(Position::Absolute, Parameters(start.to_vec())))
+            .add(Command::L(Position::Absolute, Parameters(end.to_vec())))
+    };
+    // Far from the origin, offsets are shorter.
+    assert_eq!(
+        line([100.0, 100.0], [101.0, 102.0]).optimize_shortest(),
+        "M100 100l1 2"
+    );
+    // Offsets between fractional coordinates can have more digits.
+    assert_eq!(
+        line([0.33, 0.25], [1.0, 1.0]).optimize_shortest(),
+        "M.33 .25L1 1"
+    );
+}
+
+#[test]
+fn remove_degenerate() {
+    let cleaned = |data: &str| {
+        let mut data: OptimizedData = data.parse().unwrap();
+        data.remove_degenerate();
+        data.optimize()
+    };
+    // Empty sub-paths go, and the move after them is made absolute.
+    assert_eq!(
+        cleaned("M0 0m5 5h0c0 0 0 0 0 0zm1 1L10 10 10 10c2 2 3 3 4 4M3 3"),
+        "M6 6L10 10c2 2 3 3 4 4"
+    );
+    assert_eq!(cleaned("M1 1z"), "");
+    // The control point reflected by `S` is kept.
+    assert_eq!(
+        cleaned("M0 0C0 0 0 0 0 0S5 5 10 0"),
+        "M0 0C0 0 0 0 0 0S5 5 10 0"
+    );
+    // A loop starting and ending at the same point is drawn.
+    assert_eq!(cleaned("M0 0c5 5-5 5 0 0z"), "M0 0c5 5-5 5 0 0z");
+}
+
+#[test]
+fn parse_compact_numbers() {
+    let data: OptimizedData = "M10-5.5.5e1 2L-1E-1+2".parse().unwrap();
+    assert_eq!(parts(&data[0]).1, [10.0, -5.5]);
+    assert_eq!(parts(&data[1]).1, [5.0, 2.0]);
+    assert_eq!(parts(&data[2]).1, [-0.1, 2.0]);
+}
+
+#[test]
+fn parse_packed_arc_flags() {
+    let data: OptimizedData = "M0 0a1 1 0 011 0A2,2,0,1,0,5,5".parse().unwrap();
+    assert_eq!(parts(&data[1]).1, [1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0]);
+    assert_eq!(parts(&data[2]).1, [2.0, 2.0, 0.0, 1.0, 0.0, 5.0, 5.0]);
+}
+
+#[test]
+fn parse_errors() {
+    use svgen::path_optimizer::ParseDataErrorKind::*;
+
+    for (text, kind, position) in [
+        ("M0", ExpectedNumber, 2),
+        ("X1 2", UnknownCommand, 0),
+        ("M0 0 L1 2 3", ExpectedNumber, 11),
+        ("M0 0 L", ExpectedNumber, 6),
+        ("M1e 2", ExpectedNumber, 2),
+        ("M0 0a1 1 0 21 0", ExpectedFlag, 11),
+        ("M0 0 10 10 20 0zm1,1h2 3 Q", ExpectedNumber, 26),
+        ("M0 0 L1e999 2", NumberOutOfRange, 6),
+    ] {
+        let error = text.parse::<OptimizedData>().unwrap_err();
+        assert_eq!((error.kind, error.position), (kind, position), "{text}");
+    }
+}
+
+#[test]
+fn simplify_path_data() {
+    // A circle of radius 50 drawn with 200 line segments.
+    let points: Vec<DVec2> = (0..200)
+        .map(|i| {
+            let angle = i as f64 * std::f64::consts::TAU / 200.0;
+            DVec2::new(60.0 + 50.0 * angle.cos(), 60.0 + 50.0 * angle.sin())
+        })
+        .collect();
+    let path_data = (points.iter().enumerate())
+        .map(|(i, p)| format!("{}{} {}", if i == 0 { 'M' } else { 'L' }, p.x, p.y))
+        .collect::<String>()
+        + "Z";
+    let original = path_data.parse::<OptimizedData>().unwrap();
+
+    let tolerance = 0.5;
+    let simplified = simplify_svg_path(&path_data, tolerance).unwrap();
+    let simplified = simplified.parse::<OptimizedData>().unwrap();
+    assert!(simplified.len() < original.len() / 10, "{simplified:?}");
+
+    // Both outlines stay within the tolerance of each other, both ways.
+    let distance = |p: &DVec2, outline: &[DVec2]| {
+        (outline.iter().zip(outline.iter().cycle().skip(1)))
+            .map(|(a, b)| segment_distance(*p, *a, *b))
+            .fold(f64::INFINITY, f64::min)
+    };
+    let polygons = simplified.to_polygons(0.01);
+    assert_eq!(polygons.len(), 1);
+    let (is_cyclic, outline) = &polygons[0];
+    assert!(is_cyclic);
+    for p in &points {
+        assert!(distance(p, outline) <= tolerance + 0.01);
+    }
+    for p in outline {
+        assert!(distance(p, &points) <= tolerance + 0.01);
+    }
+
+    // Within a document, every path is simplified and the rest is kept.
+    let document = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="4" height="4"/><path d="{path_data}" fill="red"/><path d="{path_data}"/></svg>"#
+    );
+    let expected = simplify_svg_path(&path_data, tolerance).unwrap();
+    assert_eq!(
+        simplify_svg(&document, tolerance).unwrap(),
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="4" height="4"/><path d="{expected}" fill="red"/><path d="{expected}"/></svg>"#
+        )
+    );
+    assert!(simplify_svg(r#"<svg><path d="M0 0L"/></svg>"#, tolerance).is_err());
+}
+
+#[test]
+fn to_quadratic() {
+    // Cubics elevated from quadratics, the second mirroring the control point of the first.
+    let mut data = "M0 0C20 40 50 40 90 0C130-40 160-40 180 0"
+        .parse::<OptimizedData>()
+        .unwrap();
+    data.to_quadratic(0.01);
+    let expected = "M0 0Q30 60 90 0T180 0".parse::<OptimizedData>().unwrap();
+    assert_same_geometry(&data, &expected, 1e-9);
+
+    // An S curve is split into quadratics staying within the tolerance.
+    let cubic = "M0 0C100 100 0 100 100 0".parse::<OptimizedData>().unwrap();
+    for tolerance in [2.0, 0.5, 0.1] {
+        let mut quadratic = cubic.clone();
+        quadratic.to_quadratic(tolerance);
+        assert!(quadratic.len() > 2);
+        assert!(quadratic
+            .iter()
+            .skip(1)
+            .all(|command| matches!(command, Command::Q(..) | Command::T(..))));
+
+        let (_, quadratic) = &quadratic.to_polygons(0.001)[0];
+        for p in &cubic.to_polygons(0.001)[0].1 {
+            let distance = quadratic
+                .windows(2)
+                .map(|edge| segment_distance(*p, edge[0], edge[1]))
+                .fold(f64::INFINITY, f64::min);
+            assert!(distance <= tolerance + 0.01, "{tolerance} {distance}");
+        }
+    }
+}
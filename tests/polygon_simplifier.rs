@@ -0,0 +1,133 @@
+use svgen::{
+    polygon_simplifier::{
+        poly_list_simplify, poly_simplify_douglas_peucker, poly_simplify_visvalingam_whyatt,
+        Simplifier,
+    },
+    vec2::DVec2,
+};
+
+fn points(coords: &[(f64, f64)]) -> Vec<DVec2> {
+    coords.iter().map(|&(x, y)| DVec2::new(x, y)).collect()
+}
+
+/// A peak between two slightly bumpy runs along the X axis.
+fn peak() -> Vec<DVec2> {
+    points(&[
+        (0.0, 0.0),
+        (1.0, 0.2),
+        (2.0, 0.0),
+        (3.0, 3.0),
+        (4.0, 0.0),
+        (5.0, 0.2),
+        (6.0, 0.0),
+    ])
+}
+
+/// A 4x4 square with a bump on its bottom and left edges.
+fn bumpy_square() -> Vec<DVec2> {
+    points(&[
+        (0.0, 0.0),
+        (2.0, 0.1),
+        (4.0, 0.0),
+        (4.0, 4.0),
+        (0.0, 4.0),
+        (0.1, 2.0),
+    ])
+}
+
+#[test]
+fn douglas_peucker() {
+    let peak = peak();
+    assert_eq!(poly_simplify_douglas_peucker(false, &peak, 0.1), peak);
+    // The base corners are 1.41 away from the chords around them.
+    assert_eq!(
+        poly_simplify_douglas_peucker(false, &peak, 1.0),
+        points(&[(0.0, 0.0), (2.0, 0.0), (3.0, 3.0), (4.0, 0.0), (6.0, 0.0)])
+    );
+    assert_eq!(
+        poly_simplify_douglas_peucker(false, &peak, 2.0),
+        points(&[(0.0, 0.0), (3.0, 3.0), (6.0, 0.0)])
+    );
+    // Whatever epsilon, open polygons keep their ends.
+    assert_eq!(
+        poly_simplify_douglas_peucker(false, &peak, 100.0),
+        points(&[(0.0, 0.0), (6.0, 0.0)])
+    );
+
+    // Cyclic polygons keep their first point and the opposite corner.
+    let square = bumpy_square();
+    let corners = points(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+    assert_eq!(poly_simplify_douglas_peucker(true, &square, 0.05), square);
+    assert_eq!(poly_simplify_douglas_peucker(true, &square, 0.5), corners);
+    assert_eq!(
+        poly_simplify_douglas_peucker(true, &square, 100.0),
+        points(&[(0.0, 0.0), (4.0, 4.0)])
+    );
+    assert_eq!(
+        poly_simplify_douglas_peucker(true, &corners, 100.0),
+        corners
+    );
+}
+
+#[test]
+fn visvalingam_whyatt() {
+    let peak = peak();
+    // The bumps form triangles of 0.2, the base corners of 1.6 and the peak of 3.
+    assert_eq!(poly_simplify_visvalingam_whyatt(false, &peak, 0.4), peak);
+    assert_eq!(
+        poly_simplify_visvalingam_whyatt(false, &peak, 1.0),
+        points(&[(0.0, 0.0), (2.0, 0.0), (3.0, 3.0), (4.0, 0.0), (6.0, 0.0)])
+    );
+    // Without the bumps the base corners form triangles of 3, then the peak one of 9.
+    assert_eq!(
+        poly_simplify_visvalingam_whyatt(false, &peak, 3.0),
+        points(&[(0.0, 0.0), (3.0, 3.0), (6.0, 0.0)])
+    );
+    assert_eq!(
+        poly_simplify_visvalingam_whyatt(false, &peak, 3.1),
+        points(&[(0.0, 0.0), (6.0, 0.0)])
+    );
+
+    // Cyclic polygons keep at least four points.
+    let square = bumpy_square();
+    let corners = points(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+    assert_eq!(poly_simplify_visvalingam_whyatt(true, &square, 0.1), square);
+    assert_eq!(
+        poly_simplify_visvalingam_whyatt(true, &square, 1.0),
+        corners
+    );
+    assert_eq!(
+        poly_simplify_visvalingam_whyatt(true, &square, 100.0),
+        corners
+    );
+}
+
+#[test]
+fn simplifiers() {
+    let poly_list = vec![(false, peak()), (true, bumpy_square())];
+    let simplified = |simplifier| {
+        let mut poly_list = poly_list.clone();
+        poly_list_simplify(&mut poly_list, simplifier, 1.0);
+        poly_list
+    };
+    assert_eq!(
+        simplified(Simplifier::DouglasPeucker),
+        [
+            (false, poly_simplify_douglas_peucker(false, &peak(), 1.0)),
+            (
+                true,
+                poly_simplify_douglas_peucker(true, &bumpy_square(), 1.0)
+            ),
+        ]
+    );
+    assert_eq!(
+        simplified(Simplifier::VisvalingamWhyatt),
+        [
+            (false, poly_simplify_visvalingam_whyatt(false, &peak(), 1.0)),
+            (
+                true,
+                poly_simplify_visvalingam_whyatt(true, &bumpy_square(), 1.0)
+            ),
+        ]
+    );
+}
@@ -0,0 +1,148 @@
+use image::{Rgba, RgbaImage};
+use svgen::{
+    structs::{Options, PotraceCompat, TurnPolicy, VectorizationResult},
+    vectorize_image,
+};
+
+const BLACK: u8 = 0;
+const WHITE: u8 = 255;
+
+/// White image of `size` with the `(gray, [x, y, width, height])` rectangles painted in order.
+fn bitmap(size: u32, rects: &[(u8, [u32; 4])]) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(size, size, Rgba([WHITE, WHITE, WHITE, 255]));
+    for (gray, [x, y, width, height]) in rects {
+        for py in *y..y + height {
+            for px in *x..x + width {
+                image.put_pixel(px, py, Rgba([*gray, *gray, *gray, 255]));
+            }
+        }
+    }
+    image
+}
+
+/// A ring with a dot in its hole.
+fn nested() -> RgbaImage {
+    bitmap(
+        40,
+        &[
+            (BLACK, [5, 5, 30, 30]),
+            (WHITE, [10, 10, 20, 20]),
+            (BLACK, [17, 17, 6, 6]),
+        ],
+    )
+}
+
+/// `(outer contours, holes, nesting depth)` of the traced contours.
+fn topology(result: &VectorizationResult) -> (usize, usize, usize) {
+    let tree = &result.contour_tree;
+    let holes = tree.iter().filter(|node| node.is_hole).count();
+    let depth = (0..tree.len())
+        .map(|mut i| {
+            let mut depth = 1;
+            while let Some(parent) = tree[i].parent {
+                (i, depth) = (parent, depth + 1);
+            }
+            depth
+        })
+        .max()
+        .unwrap_or(0);
+    (tree.len() - holes, holes, depth)
+}
+
+fn trace(image: &RgbaImage, params: PotraceCompat) -> VectorizationResult {
+    vectorize_image(image, &Options::potrace(&params))
+}
+
+/// Fixtures along with the topology Potrace traces them with for its parameters,
+/// following its rules: paths enclosing at most `turdsize` pixels are dropped,
+/// and the turn policy decides whether pixels touching at a corner are connected.
+#[test]
+fn potrace_topology() {
+    let specks = bitmap(
+        64,
+        &[
+            (BLACK, [5, 5, 1, 1]),
+            (BLACK, [10, 5, 1, 2]),
+            (BLACK, [15, 5, 2, 2]),
+            (BLACK, [20, 5, 3, 3]),
+            (BLACK, [30, 30, 20, 20]),
+            (WHITE, [35, 35, 1, 1]),
+            (WHITE, [42, 42, 3, 3]),
+        ],
+    );
+    let diagonal = bitmap(40, &[(BLACK, [10, 10, 8, 8]), (BLACK, [18, 18, 8, 8])]);
+    let nested = nested();
+
+    let defaults = PotraceCompat::default();
+    let cases = [
+        ("specks", &specks, defaults, (3, 1, 2)),
+        (
+            "specks turdsize 0",
+            &specks,
+            PotraceCompat {
+                turdsize: 0,
+                ..defaults
+            },
+            (5, 2, 2),
+        ),
+        (
+            "specks turdsize 10",
+            &specks,
+            PotraceCompat {
+                turdsize: 10,
+                ..defaults
+            },
+            (1, 0, 1),
+        ),
+        ("diagonal minority", &diagonal, defaults, (1, 0, 1)),
+        (
+            "diagonal majority",
+            &diagonal,
+            PotraceCompat {
+                turnpolicy: TurnPolicy::Majority,
+                ..defaults
+            },
+            (2, 0, 1),
+        ),
+        (
+            "diagonal black",
+            &diagonal,
+            PotraceCompat {
+                turnpolicy: TurnPolicy::Black,
+                ..defaults
+            },
+            (1, 0, 1),
+        ),
+        (
+            "diagonal white",
+            &diagonal,
+            PotraceCompat {
+                turnpolicy: TurnPolicy::White,
+                ..defaults
+            },
+            (2, 0, 1),
+        ),
+        ("nested", &nested, defaults, (2, 1, 3)),
+    ];
+    for (name, image, params, expected) in cases {
+        assert_eq!(topology(&trace(image, params)), expected, "{name}");
+    }
+}
+
+#[test]
+fn potrace_unit() {
+    let result = trace(&nested(), PotraceCompat::default());
+    let points = result
+        .layers
+        .iter()
+        .flat_map(|layer| &layer.curves)
+        .flat_map(|(_is_cyclic, curve)| curve.iter().flatten());
+    for point in points {
+        for value in [point.x, point.y] {
+            assert!(
+                (value * 10.0 - (value * 10.0).round()).abs() < 1e-9,
+                "{value}"
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use svgen::quantizer::{MedianCut, NeuQuant, Quantize};
+
+fn fixture(name: &str) -> Vec<u8> {
+    image::open(format!("assets/{name}.png"))
+        .unwrap()
+        .to_rgba8()
+        .into_raw()
+}
+
+/// Mean squared error per channel of the pixels mapped to their palette color.
+fn mean_squared_error(quantizer: &dyn Quantize, pixels: &[u8]) -> f64 {
+    let palette = quantizer.color_map_rgba();
+    let error: u64 = pixels
+        .chunks_exact(4)
+        .map(|pixel| {
+            let i = quantizer.index_of(pixel);
+            palette[i * 4..i * 4 + 4]
+                .iter()
+                .zip(pixel)
+                .map(|(a, b)| (*a as i64 - *b as i64).pow(2) as u64)
+                .sum::<u64>()
+        })
+        .sum();
+    error as f64 / pixels.len() as f64
+}
+
+#[test]
+fn median_cut_small_palettes() {
+    for name in ["albert_m", "hurricane"] {
+        let pixels = fixture(name);
+        for colors in [2, 5] {
+            let neuquant = mean_squared_error(&NeuQuant::new(1, colors, &pixels), &pixels);
+            let median_cut = mean_squared_error(&MedianCut::new(colors, &pixels), &pixels);
+            assert!(
+                median_cut <= neuquant,
+                "{name} with {colors} colors: median cut {median_cut} > NeuQuant {neuquant}"
+            );
+        }
+    }
+}
+
+#[test]
+fn more_colors_less_error() {
+    let pixels = fixture("hurricane");
+    let errors = [2, 5, 16].map(|colors| {
+        [
+            mean_squared_error(&NeuQuant::new(1, colors, &pixels), &pixels),
+            mean_squared_error(&MedianCut::new(colors, &pixels), &pixels),
+        ]
+    });
+    for pair in errors.windows(2) {
+        assert!(
+            pair[1][0] < pair[0][0] && pair[1][1] < pair[0][1],
+            "{errors:?}"
+        );
+    }
+}
+
+#[test]
+fn median_cut_exact_colors() {
+    let colors = [
+        [255, 0, 0, 255],
+        [0, 0, 255, 255],
+        [250, 250, 250, 255],
+        [0, 0, 0, 0],
+    ];
+    let pixels = (0..1000)
+        .flat_map(|i| colors[[0, 0, 0, 0, 1, 1, 1, 2, 2, 3][i % 10]])
+        .collect::<Vec<u8>>();
+
+    let quantizer = MedianCut::new(5, &pixels);
+    assert_eq!(mean_squared_error(&quantizer, &pixels), 0.0);
+    let mut palette = (0..colors.len())
+        .map(|i| quantizer.lookup(i).unwrap())
+        .collect::<Vec<_>>();
+    palette.sort();
+    let mut expected = colors.to_vec();
+    expected.sort();
+    assert_eq!(palette, expected);
+    assert_eq!(quantizer.lookup(colors.len()), None);
+}
+
+#[test]
+fn neuquant_default_learning_cycles() {
+    let pixels = fixture("hurricane");
+    // At least 100 cycles for palettes up to 200 colors.
+    assert_eq!(
+        NeuQuant::new(10, 5, &pixels).color_map_rgba(),
+        NeuQuant::with_learning_cycles(10, 5, 100, &pixels).color_map_rgba()
+    );
+}
+
+#[test]
+fn neuquant_seed() {
+    let pixels = fixture("hurricane");
+    let palette = |seed| NeuQuant::with_seed(10, 5, 100, seed, &pixels).color_map_rgba();
+    assert_eq!(
+        palette(0),
+        NeuQuant::with_learning_cycles(10, 5, 100, &pixels).color_map_rgba()
+    );
+    assert_eq!(palette(7), palette(7));
+    assert_ne!(palette(7), palette(0));
+    // Any seed gives a palette as good.
+    for seed in [1, 7, u64::MAX] {
+        let quantizer = NeuQuant::with_seed(10, 5, 100, seed, &pixels);
+        let reference = NeuQuant::new(10, 5, &pixels);
+        let error = mean_squared_error(&quantizer, &pixels);
+        assert!(
+            error < mean_squared_error(&reference, &pixels) * 1.5,
+            "{seed}"
+        );
+    }
+}
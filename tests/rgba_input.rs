@@ -0,0 +1,355 @@
+use std::io::Cursor;
+
+use image::{GrayImage, ImageFormat, Luma, Rgba, RgbaImage};
+use svgen::{
+    create_svg_from_reader, create_svg_from_rgba, create_svg_sprites, create_svg_spritesheet,
+    create_svgs_batch,
+    error::SvgenError,
+    structs::{
+        BackgroundRemoval, DespeckleOptions, ImageFilter, Options, SpriteGrid, Stencil, Supersample,
+    },
+    vectorize_image,
+};
+
+#[test]
+fn rgba_input_matches_decoded_input() {
+    let image = RgbaImage::from_fn(40, 30, |x, y| match (x / 10 + y / 10) % 3 {
+        0 => Rgba([200, 30, 40, 255]),
+        1 => Rgba([20, 120, 200, 255]),
+        _ => Rgba([250, 250, 250, 0]),
+    });
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let options = Options::default();
+
+    assert_eq!(
+        create_svg_from_rgba(image.as_raw(), 40, 30, &options).unwrap(),
+        create_svg_from_reader(Cursor::new(&png), &options).unwrap()
+    );
+}
+
+#[test]
+fn rgba_input_size_mismatch() {
+    let error = create_svg_from_rgba(&[0; 4 * 10 * 10], 10, 11, &Options::default()).unwrap_err();
+    assert!(matches!(
+        error,
+        SvgenError::BufferSize {
+            expected: 440,
+            actual: 400
+        }
+    ));
+}
+
+#[test]
+fn stencil() {
+    // A red square and a blue one, the stencil only covering the red one.
+    let image = RgbaImage::from_fn(60, 30, |x, y| {
+        Rgba(
+            match (x / 30, (5..25).contains(&(x % 30)) && (5..25).contains(&y)) {
+                (0, true) => [200, 30, 40, 255],
+                (1, true) => [20, 120, 200, 255],
+                _ => [250, 250, 250, 255],
+            },
+        )
+    });
+    let stencil = GrayImage::from_fn(60, 30, |x, y| {
+        Luma([match (2..28).contains(&x) && (2..28).contains(&y) {
+            true => 200,
+            false => 30,
+        }])
+    });
+    let options = Options {
+        preprocess: false,
+        stencil: Some(Stencil {
+            image: stencil,
+            threshold: 127,
+        }),
+        ..Options::default()
+    };
+    let result = vectorize_image(&image, &options);
+
+    let mut colors = result
+        .layers
+        .iter()
+        .map(|layer| layer.color)
+        .collect::<Vec<_>>();
+    colors.sort();
+    assert_eq!(colors, [[200, 30, 40, 255], [250, 250, 250, 255]]);
+    let bbox = |color| {
+        let layer = result.layers.iter().find(|l| l.color == color).unwrap();
+        let bbox = layer.bbox().unwrap();
+        [bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y].map(f64::round)
+    };
+    assert_eq!(bbox([200, 30, 40, 255]), [5.0, 5.0, 25.0, 25.0]);
+    assert_eq!(bbox([250, 250, 250, 255]), [2.0, 2.0, 28.0, 28.0]);
+    assert_eq!((result.width, result.height), (60, 30));
+}
+
+#[test]
+fn transparent_layer() {
+    // A red square and a blue one, the rest fully transparent over varying colors.
+    let image = RgbaImage::from_fn(40, 20, |x, y| {
+        Rgba(
+            match (x / 20, (5..15).contains(&(x % 20)) && (5..15).contains(&y)) {
+                (0, true) => [200, 30, 40, 255],
+                (1, true) => [20, 120, 200, 255],
+                _ => [(x * 37 % 256) as u8, (y * 53 % 256) as u8, 91, 0],
+            },
+        )
+    });
+    let trace = |transparent_layer| {
+        let options = Options {
+            preprocess: false,
+            transparent_layer,
+            ..Options::default()
+        };
+        vectorize_image(&image, &options)
+    };
+    let opaque = [[20, 120, 200, 255], [200, 30, 40, 255]];
+
+    for transparent_layer in [false, true] {
+        let result = trace(transparent_layer);
+        let mut palette = (result.palette.iter())
+            .map(|c| (c.color, c.pixel_count))
+            .collect::<Vec<_>>();
+        palette.sort();
+        // The transparent pixels are counted together whatever their RGB channels.
+        let mut expected = opaque.map(|color| (color, 100)).to_vec();
+        if transparent_layer {
+            expected.insert(0, ([0, 0, 0, 0], 600));
+        }
+        assert_eq!(palette, expected);
+
+        let colors = result.layers.iter().map(|l| l.color).collect::<Vec<_>>();
+        let (last, colors) = colors.split_last().unwrap();
+        match transparent_layer {
+            false => assert!(!result.layers.iter().any(|l| l.color[3] == 0)),
+            true => {
+                // Only added as the last layer, around both squares.
+                assert_eq!(*last, [0, 0, 0, 0]);
+                assert!(!colors.contains(&[0, 0, 0, 0]));
+                let bbox = result.layers.last().unwrap().bbox().unwrap();
+                assert_eq!(
+                    [bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y].map(f64::round),
+                    [0.0, 0.0, 40.0, 20.0]
+                );
+            }
+        }
+        let mut opaque_colors = (result.layers.iter())
+            .map(|l| l.color)
+            .filter(|color| color[3] != 0)
+            .collect::<Vec<_>>();
+        opaque_colors.sort();
+        assert_eq!(opaque_colors, opaque);
+    }
+}
+
+#[test]
+fn background_removal() {
+    // A red square on a slightly noisy white background.
+    let image = RgbaImage::from_fn(40, 30, |x, y| {
+        match (8..24).contains(&x) && (6..22).contains(&y) {
+            true => Rgba([200, 30, 40, 255]),
+            false => Rgba([250 - (x + y) as u8 % 8, 250, 248, 255]),
+        }
+    });
+    let trace = |background| {
+        let options = Options {
+            preprocess: false,
+            background: Some(background),
+            ..Options::default()
+        };
+        vectorize_image(&image, &options)
+    };
+
+    let result = trace(BackgroundRemoval::ChromaKey {
+        color: [255, 255, 255],
+        tolerance: 20.0,
+    });
+    assert_eq!(result.layers.len(), 1);
+    assert_eq!(result.layers[0].color, [200, 30, 40, 255]);
+    let bbox = result.layers[0].bbox().unwrap();
+    assert_eq!(
+        [bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y].map(f64::round),
+        [8.0, 6.0, 24.0, 22.0]
+    );
+
+    // A custom pass clearing the left half.
+    let filter = ImageFilter::new(|image: &RgbaImage| {
+        let mut image = image.clone();
+        for (x, _, pixel) in image.enumerate_pixels_mut() {
+            if x < 20 {
+                pixel[3] = 0;
+            }
+        }
+        image
+    });
+    let result = trace(BackgroundRemoval::Custom(filter));
+    assert!(!result.layers.is_empty());
+    for bbox in result.layers.iter().filter_map(|layer| layer.bbox()) {
+        assert!(bbox.min.x > 19.0, "{bbox:?}");
+    }
+}
+
+#[test]
+fn clip_layers() {
+    // Red stripes split by lines of blue, which the closing of the red mask fills.
+    let image = RgbaImage::from_fn(40, 30, |x, _y| match x % 5 == 4 {
+        true => Rgba([20, 120, 200, 255]),
+        false => Rgba([200, 30, 40, 255]),
+    });
+    let pixels = |clip_layers| {
+        let options = Options {
+            preprocess: false,
+            supersample: Supersample::Off,
+            despeckle: DespeckleOptions {
+                close_radius: 1,
+                ..DespeckleOptions::default()
+            },
+            clip_layers,
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        let counts = result.palette.iter().map(|color| color.pixel_count);
+        counts.collect::<Vec<_>>()
+    };
+    let overlapping = pixels(false);
+    let clipped = pixels(true);
+
+    assert_eq!(overlapping.len(), 2);
+    assert!(
+        overlapping.iter().sum::<usize>() > 40 * 30,
+        "{overlapping:?}"
+    );
+    assert_eq!(clipped.iter().sum::<usize>(), 40 * 30, "{clipped:?}");
+    // The top layer is kept whole, the one below loses what it covers.
+    assert_eq!(clipped[1], overlapping[1]);
+    assert!(clipped[0] < overlapping[0]);
+}
+
+#[test]
+fn batch_shared_palette() {
+    let colors = [
+        [200, 30, 40, 255],
+        [20, 120, 200, 255],
+        [30, 160, 60, 255],
+        [240, 200, 20, 255],
+        [120, 40, 160, 255],
+        [230, 120, 20, 255],
+    ];
+    // White icons with a square of their own color, the last two being the smallest.
+    let pngs = colors
+        .iter()
+        .enumerate()
+        .map(|(i, &color)| {
+            let size = 14 - i as u32;
+            let image = RgbaImage::from_fn(32, 32, |x, y| {
+                match (8..8 + size).contains(&x) && (8..8 + size).contains(&y) {
+                    true => Rgba(color),
+                    false => Rgba([255, 255, 255, 255]),
+                }
+            });
+            let mut png = Vec::new();
+            image
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .unwrap();
+            png
+        })
+        .collect::<Vec<_>>();
+    let images = pngs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let options = Options {
+        supersample: Supersample::Off,
+        ..Options::default()
+    };
+
+    let output = create_svgs_batch(&images, &options).unwrap();
+    let palette = output.palette.iter().map(|entry| entry.color);
+    assert_eq!(
+        palette.collect::<Vec<_>>(),
+        [
+            [255, 255, 255, 255],
+            colors[0],
+            colors[1],
+            colors[2],
+            colors[3]
+        ]
+    );
+    let pixel_count: usize = output.palette.iter().map(|entry| entry.pixel_count).sum();
+    assert_eq!(pixel_count, 6 * 32 * 32);
+    assert_eq!(output.svgs.len(), 6);
+    // The colors left out of the shared palette are traced with the nearest one.
+    for (svg, nearest) in output.svgs[4..].iter().zip(["#1478C8", "#F0C814"]) {
+        assert!(
+            !svg.contains("#7828A0") && !svg.contains("#E67814"),
+            "{svg}"
+        );
+        assert!(svg.contains(nearest), "{svg}");
+    }
+
+    // A palette given in the options is kept as is.
+    let options = Options {
+        palette: Some(vec![[255, 255, 255, 255], [0, 0, 0, 255]]),
+        ..options
+    };
+    let output = create_svgs_batch(&images, &options).unwrap();
+    assert_eq!(output.palette.len(), 2);
+    assert!(output.svgs[0].contains(r##"fill="#000""##));
+}
+
+#[test]
+fn sprites() {
+    let colors = [[200, 30, 40, 255], [20, 120, 200, 255], [30, 160, 60, 255]];
+    let tile = |color: [u8; 4]| {
+        RgbaImage::from_fn(32, 32, |x, y| {
+            match (8..24).contains(&x) && (6..26).contains(&y) {
+                true => Rgba(color),
+                false => Rgba([255, 255, 255, 255]),
+            }
+        })
+    };
+    let encode = |image: &RgbaImage| {
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    };
+    // Three tiles side by side, along with a partial one.
+    let sheet = RgbaImage::from_fn(3 * 32 + 10, 32, |x, y| match colors.get(x as usize / 32) {
+        Some(&color) => tile(color)[(x % 32, y)],
+        None => Rgba([0, 0, 0, 255]),
+    });
+    let options = Options {
+        supersample: Supersample::Off,
+        ..Options::default()
+    };
+
+    let grid = SpriteGrid::Tiles {
+        width: 32,
+        height: 32,
+    };
+    assert_eq!(
+        grid.rects([3 * 32 + 10, 32]),
+        [[0, 0, 32, 32], [32, 0, 32, 32], [64, 0, 32, 32]]
+    );
+    let sprites = create_svg_sprites(&encode(&sheet), &grid, &options).unwrap();
+    // The same documents as the tiles traced on their own with one palette.
+    let tiles = colors.map(|color| encode(&tile(color)));
+    let batch = create_svgs_batch(&tiles.each_ref().map(Vec::as_slice), &options).unwrap();
+    assert_eq!(sprites.svgs, batch.svgs);
+    assert_eq!(sprites.palette, batch.palette);
+
+    let rects = SpriteGrid::Rects(vec![[32, 0, 32, 32], [0, 0, 32, 32]]);
+    let sprites = create_svg_sprites(&encode(&sheet), &rects, &options).unwrap();
+    let batch = create_svgs_batch(&[&tiles[1], &tiles[0]], &options).unwrap();
+    assert_eq!(sprites.svgs, batch.svgs);
+
+    let spritesheet = create_svg_spritesheet(&encode(&sheet), &grid, &options).unwrap();
+    for i in 0..3 {
+        let symbol = format!(r#"<symbol id="sprite-{i}" viewBox="0 0 32 32">"#);
+        assert!(spritesheet.contains(&symbol), "{spritesheet}");
+    }
+    assert!(!spritesheet.contains("sprite-3"));
+}
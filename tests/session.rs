@@ -0,0 +1,242 @@
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use svgen::{
+    session::VectorizationSession,
+    structs::{
+        BackgroundRemoval, ColorMode, DescreenFilter, DescreenOptions, Options, ResampleFilter,
+        SharpenOptions, Stencil, Supersample, VectorizationResult,
+    },
+    vectorize_image,
+};
+
+const RED: [u8; 4] = [220, 40, 30, 255];
+const BLUE: [u8; 4] = [30, 60, 200, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+/// Image of `width` × `height` filled with `background`, with the `(color, [x, y, width, height])`
+/// rectangles painted in order.
+fn canvas(
+    width: u32,
+    height: u32,
+    background: [u8; 4],
+    rects: &[([u8; 4], [u32; 4])],
+) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(width, height, Rgba(background));
+    for (color, [x, y, w, h]) in rects {
+        for py in *y..y + h {
+            for px in *x..x + w {
+                image.put_pixel(px, py, Rgba(*color));
+            }
+        }
+    }
+    image
+}
+
+/// Every layer's color with its sorted polygons and curves, sorted by color:
+/// updates append contours and keep the palette order, which a whole trace ranks by frequency.
+fn shapes(result: &VectorizationResult) -> Vec<([u8; 4], Vec<String>, Vec<String>)> {
+    let mut layers = result
+        .layers
+        .iter()
+        .map(|layer| {
+            let mut polygons = layer
+                .polygons
+                .iter()
+                .map(|p| format!("{p:?}"))
+                .collect::<Vec<_>>();
+            let mut curves = layer
+                .curves
+                .iter()
+                .map(|c| format!("{c:?}"))
+                .collect::<Vec<_>>();
+            polygons.sort();
+            curves.sort();
+            (layer.color, polygons, curves)
+        })
+        .collect::<Vec<_>>();
+    layers.sort_by_key(|(color, _, _)| *color);
+    layers
+}
+
+/// Paints every patch into a session then checks it traces the same shapes
+/// as the whole painted image.
+fn assert_updates(image: RgbaImage, options: Options, patches: &[(u32, u32, RgbaImage)]) {
+    let mut session = VectorizationSession::from_image(image.clone(), options.clone());
+    session.result();
+    let mut painted = image;
+    for (x, y, pixels) in patches {
+        session.update_region(*x, *y, pixels);
+        image::imageops::replace(&mut painted, pixels, *x as i64, *y as i64);
+        let expected = vectorize_image(&painted, &options);
+        assert_eq!(
+            shapes(session.result()),
+            shapes(&expected),
+            "patch at {x}, {y}"
+        );
+    }
+}
+
+#[test]
+fn update_region_black() {
+    let image = canvas(
+        120,
+        90,
+        WHITE,
+        &[
+            ([0, 0, 0, 255], [10, 10, 30, 30]),
+            ([0, 0, 0, 255], [60, 15, 20, 50]),
+            ([0, 0, 0, 255], [95, 60, 25, 30]),
+        ],
+    );
+    let options = Options {
+        color_mode: ColorMode::Black,
+        preprocess: false,
+        ..Options::default()
+    };
+    let black = [0, 0, 0, 255];
+    assert_updates(
+        image,
+        options,
+        &[
+            // Joins the first two shapes.
+            (38, 20, canvas(24, 6, black, &[])),
+            // Cuts a hole in the second one.
+            (65, 30, canvas(8, 8, WHITE, &[])),
+            // A new shape along the image border.
+            (0, 70, canvas(15, 20, black, &[])),
+            // Clipped to the image.
+            (110, 0, canvas(30, 12, black, &[])),
+        ],
+    );
+}
+
+#[test]
+fn update_region_colored() {
+    let image = canvas(
+        100,
+        100,
+        WHITE,
+        &[
+            (RED, [10, 10, 40, 40]),
+            (BLUE, [55, 40, 35, 50]),
+            (RED, [20, 70, 15, 15]),
+        ],
+    );
+    let options = Options {
+        preprocess: false,
+        ..Options::default()
+    };
+    assert_updates(
+        image,
+        options,
+        &[
+            (30, 30, canvas(12, 12, BLUE, &[])),
+            (70, 60, canvas(6, 6, WHITE, &[(RED, [2, 2, 2, 2])])),
+        ],
+    );
+}
+
+#[test]
+fn update_region_resampled() {
+    let image = canvas(
+        80,
+        60,
+        WHITE,
+        &[(RED, [10, 10, 25, 25]), (BLUE, [45, 20, 25, 30])],
+    );
+    let options = Options {
+        preprocess: false,
+        supersample: Supersample::Factor(2),
+        resample_filter: ResampleFilter::Lanczos3,
+        sharpen: Some(SharpenOptions::default()),
+        ..Options::default()
+    };
+    assert_updates(
+        image,
+        options,
+        &[
+            (28, 28, canvas(10, 10, BLUE, &[])),
+            (50, 5, canvas(8, 8, RED, &[])),
+        ],
+    );
+}
+
+#[test]
+fn update_region_stenciled() {
+    let image = canvas(
+        80,
+        60,
+        WHITE,
+        &[(RED, [10, 10, 25, 25]), (BLUE, [15, 40, 10, 10])],
+    );
+    // Half the size of the image, covering its left half.
+    let stencil = GrayImage::from_fn(40, 30, |x, _| Luma([if x < 20 { 255 } else { 0 }]));
+    let options = Options {
+        stencil: Some(Stencil {
+            image: stencil,
+            threshold: 127,
+        }),
+        ..Options::default()
+    };
+    assert_updates(
+        image,
+        options,
+        &[
+            // Across the edge of the stencil.
+            (30, 30, canvas(20, 10, BLUE, &[])),
+            (60, 5, canvas(8, 8, RED, &[])),
+        ],
+    );
+}
+
+#[test]
+fn update_region_chroma_key() {
+    let image = canvas(
+        80,
+        60,
+        WHITE,
+        &[(RED, [10, 10, 25, 25]), (BLUE, [45, 20, 25, 30])],
+    );
+    let options = Options {
+        preprocess: false,
+        background: Some(BackgroundRemoval::ChromaKey {
+            color: [255, 255, 255],
+            tolerance: 10.0,
+        }),
+        ..Options::default()
+    };
+    assert_updates(
+        image,
+        options,
+        &[
+            (28, 28, canvas(10, 10, BLUE, &[])),
+            (50, 25, canvas(8, 8, WHITE, &[])),
+        ],
+    );
+}
+
+#[test]
+fn update_region_descreened() {
+    let image = canvas(
+        80,
+        60,
+        WHITE,
+        &[(RED, [10, 10, 25, 25]), (BLUE, [45, 20, 25, 30])],
+    );
+    let options = Options {
+        preprocess: false,
+        descreen: Some(DescreenOptions {
+            detect: false,
+            cell_size: 3,
+            filter: DescreenFilter::Mode,
+        }),
+        ..Options::default()
+    };
+    assert_updates(
+        image,
+        options,
+        &[
+            (28, 28, canvas(10, 10, BLUE, &[])),
+            (50, 25, canvas(8, 8, WHITE, &[(RED, [3, 3, 1, 1])])),
+        ],
+    );
+}
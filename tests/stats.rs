@@ -0,0 +1,100 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use svgen::{
+    create_svg_with_options, create_svg_with_stats,
+    structs::{ColorMode, DespeckleOptions, Options, Supersample},
+};
+
+#[test]
+fn trace_stats() {
+    // Two discs on white, and a single pixel speck.
+    let mut image = RgbaImage::from_fn(80, 60, |x, y| {
+        let inside = |cx: f64, cy: f64| (x as f64 - cx).hypot(y as f64 - cy) < 12.0;
+        if inside(20.0, 30.0) {
+            Rgba([200, 30, 40, 255])
+        } else if inside(55.0, 30.0) {
+            Rgba([20, 120, 200, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    image.put_pixel(75, 5, Rgba([200, 30, 40, 255]));
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let options = Options::default();
+
+    let (svg, stats) = create_svg_with_stats(&png, &options);
+    assert_eq!(svg, create_svg_with_options(&png, &options));
+
+    let paths = stats
+        .paths_per_color
+        .iter()
+        .map(|(_color, paths)| paths)
+        .sum::<usize>();
+    let drawn_layers = stats
+        .paths_per_color
+        .iter()
+        .filter(|(_color, paths)| *paths > 0)
+        .count();
+    assert_eq!(svg.matches("<path").count(), drawn_layers);
+    assert_eq!(stats.paths_per_color.len(), 3);
+    assert!(stats.contours >= paths);
+    assert!(stats.knots_after_simplification < stats.knots_before_simplification);
+    assert!(stats.cubic_segments >= paths);
+    assert!(stats.timings.total() > stats.timings.fit);
+}
+
+#[test]
+fn contours_dropped() {
+    // A disc and three single pixel specks, traced on their own without filters.
+    let specks = [(5, 5), (70, 8), (40, 52)];
+    let mut image = RgbaImage::from_fn(80, 60, |x, y| {
+        match (x as f64 - 40.0).hypot(y as f64 - 28.0) < 14.0 {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    for (x, y) in specks {
+        image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+    }
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let stats = |color_mode, despeckle| {
+        let options = Options {
+            color_mode,
+            despeckle,
+            supersample: Supersample::Off,
+            preprocess: false,
+            ..Options::default()
+        };
+        create_svg_with_stats(&png, &options).1
+    };
+
+    for color_mode in [ColorMode::Black, ColorMode::Colored] {
+        let unfiltered = stats(color_mode, DespeckleOptions::default());
+        let dropped = unfiltered.contours_dropped;
+        for despeckle in [
+            DespeckleOptions {
+                open_radius: 1,
+                ..DespeckleOptions::default()
+            },
+            DespeckleOptions {
+                merge_area: 2,
+                ..DespeckleOptions::default()
+            },
+        ] {
+            let filtered = stats(color_mode, despeckle);
+            assert_eq!(
+                filtered.contours_dropped,
+                dropped + specks.len(),
+                "{color_mode:?} {despeckle:?}"
+            );
+            assert!(filtered.contours < unfiltered.contours);
+        }
+    }
+}
@@ -0,0 +1,1105 @@
+use std::fs;
+
+use image::{Rgba, RgbaImage};
+use svgen::{
+    create_svg_with_levels,
+    mask::ink_coverage,
+    output::svg::{to_animated_svg, to_svg, to_svg_levels, to_svg_symbols, write_svg},
+    path_optimizer::{trunc, Command, OptimizedData},
+    structs::{
+        BoundingBox, ColorMode, CoordinateMode, CurveType, Gradient, GradientOptions, IdScheme,
+        LineFitting, Mode, Options, OutputFormatting, SeparationChannels, SeparationOptions,
+        Supersample, SvgOptions, SvgProfile, SvgUnits, TransformOptions, VectorizationResult,
+        PIXEL_SCALE_MAX,
+    },
+    try_create_hybrid_svg, try_create_svg, try_vectorize, try_write_svg,
+    utils::base64_encode,
+    vec2::DVec2,
+    vectorize_image,
+};
+
+#[test]
+fn merge_paths() {
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let mut result = try_vectorize(&png, &Options::default()).unwrap();
+    assert_eq!(result.layers.len(), 3);
+    // Two layers of the same color, as two palette colors mapped to one would be.
+    result.layers[2].color = result.layers[1].color;
+
+    let separate = to_svg(&result, &Options::default());
+    let options = Options {
+        svg: SvgOptions {
+            merge_paths: true,
+            ..SvgOptions::default()
+        },
+        ..Options::default()
+    };
+    let merged = to_svg(&result, &options);
+
+    assert_eq!(separate.matches("<path").count(), 3);
+    assert_eq!(merged.matches("<path").count(), 2);
+    assert_eq!(merged.matches("<use").count(), 4);
+    // The contours of both layers are subpaths of the merged one.
+    let subpaths = |svg: &str| svg.matches(['M', 'm']).count();
+    assert_eq!(subpaths(&merged), subpaths(&separate));
+    assert!(merged.len() < separate.len());
+}
+
+#[test]
+fn ink_separation() {
+    let cmyk = |rgb| ink_coverage(SeparationChannels::Cmyk, rgb);
+    assert_eq!(cmyk([255, 255, 255]), [0, 0, 0, 0]);
+    assert_eq!(cmyk([0, 0, 0]), [0, 0, 0, 255]);
+    assert_eq!(cmyk([255, 0, 0]), [0, 255, 255, 0]);
+    assert_eq!(cmyk([0, 128, 128]), [255, 0, 0, 127]);
+    let rgb = |rgb| ink_coverage(SeparationChannels::Rgb, rgb);
+    assert_eq!(rgb([255, 0, 100]), [0, 255, 155, 0]);
+}
+
+/// Bounds `[min_x, min_y, max_x, max_y]` of the paths of the layer labeled `label`.
+fn plate_bbox(result: &VectorizationResult, channels: SeparationChannels, label: &str) -> [f64; 4] {
+    let (_label, ink) = channels.inks().iter().find(|(l, _)| *l == label).unwrap();
+    let layer = result
+        .layers
+        .iter()
+        .find(|layer| layer.color == *ink)
+        .unwrap();
+    let bbox = layer.bbox().unwrap();
+    [bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y].map(f64::round)
+}
+
+#[test]
+fn separation_plates() {
+    // Red, cyan and black squares on white.
+    let image = RgbaImage::from_fn(60, 20, |x, y| {
+        Rgba(
+            match (x / 20, (4..16).contains(&(x % 20)) && (4..16).contains(&y)) {
+                (0, true) => [255, 0, 0, 255],
+                (1, true) => [0, 255, 255, 255],
+                (2, true) => [0, 0, 0, 255],
+                _ => [255, 255, 255, 255],
+            },
+        )
+    });
+    let options = |channels| Options {
+        color_mode: ColorMode::Separation,
+        preprocess: false,
+        separation: SeparationOptions {
+            channels,
+            ..SeparationOptions::default()
+        },
+        ..Options::default()
+    };
+
+    let cmyk = SeparationChannels::Cmyk;
+    let result = vectorize_image(&image, &options(cmyk));
+    assert_eq!(result.layers.len(), 4);
+    assert_eq!(plate_bbox(&result, cmyk, "C"), [24.0, 4.0, 36.0, 16.0]);
+    assert_eq!(plate_bbox(&result, cmyk, "M"), [4.0, 4.0, 16.0, 16.0]);
+    assert_eq!(plate_bbox(&result, cmyk, "Y"), [4.0, 4.0, 16.0, 16.0]);
+    assert_eq!(plate_bbox(&result, cmyk, "K"), [44.0, 4.0, 56.0, 16.0]);
+
+    let svg = to_svg(&result, &options(cmyk));
+    for label in ["C", "M", "Y", "K"] {
+        assert!(
+            svg.contains(&format!("inkscape:label=\"{label}\"")),
+            "{svg}"
+        );
+    }
+    assert!(svg.contains("xmlns:inkscape="));
+    assert_eq!(svg.matches("mix-blend-mode:multiply").count(), 4);
+
+    // The black square is printed with all three inks.
+    let rgb = SeparationChannels::Rgb;
+    let result = vectorize_image(&image, &options(rgb));
+    assert_eq!(result.layers.len(), 3);
+    assert_eq!(plate_bbox(&result, rgb, "R"), [24.0, 4.0, 56.0, 16.0]);
+    assert_eq!(plate_bbox(&result, rgb, "B"), [4.0, 4.0, 56.0, 16.0]);
+}
+
+#[test]
+fn reuse_shapes() {
+    // A row of identical dots, and a bar.
+    let image = RgbaImage::from_fn(120, 34, |x, y| {
+        let dot = (x as f64 - (x / 24 * 24) as f64 - 11.5).hypot(y as f64 - 11.5) < 10.0;
+        let bar = (26..31).contains(&y) && (5..115).contains(&x);
+        match dot || bar {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let options = |reuse_shapes| Options {
+        color_mode: ColorMode::Black,
+        mode: Mode::PixelPerfect,
+        svg: SvgOptions {
+            reuse_shapes,
+            ..SvgOptions::default()
+        },
+        ..Options::default()
+    };
+    let result = vectorize_image(&image, &options(false));
+    let separate = to_svg(&result, &options(false));
+    let reused = to_svg(&result, &options(true));
+
+    // The bar's path, and the dot defined once at the origin.
+    assert_eq!(separate.matches("<path").count(), 1);
+    assert_eq!(reused.matches("<path").count(), 2);
+    assert!(reused.contains("d=\"M0 0"), "{reused}");
+    // Every dot is placed in the stroke and fill groups.
+    let placements = reused
+        .split("<use")
+        .filter_map(|element| {
+            let attribute = |name| {
+                let value = element.split(&format!(" {name}=\"")).nth(1)?;
+                value.split('"').next()?.parse::<f64>().ok()
+            };
+            Some([attribute("x")?, attribute("y")?])
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(placements.len(), 10, "{reused}");
+    for (i, [x, y]) in placements.iter().enumerate() {
+        assert_eq!(
+            [*x, *y],
+            [placements[0][0] + (i % 5) as f64 * 24.0, placements[0][1]]
+        );
+    }
+    assert!(reused.len() < separate.len());
+
+    // Shapes shorter than their references are left in place.
+    let small = RgbaImage::from_fn(40, 10, |x, y| match x % 10 < 2 && y < 2 {
+        true => Rgba([0, 0, 0, 255]),
+        false => Rgba([255, 255, 255, 255]),
+    });
+    let svg = to_svg(&vectorize_image(&small, &options(true)), &options(true));
+    assert_eq!(svg.matches("<path").count(), 1);
+}
+
+#[test]
+fn max_path_commands() {
+    // A ring above a row of squares.
+    let image = RgbaImage::from_fn(100, 40, |x, y| {
+        let ring = (4..24).contains(&x)
+            && (2..22).contains(&y)
+            && !((10..18).contains(&x) && (8..16).contains(&y));
+        let square = (28..34).contains(&y) && x % 12 < 6;
+        match ring || square {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let options = |max_path_commands| Options {
+        color_mode: ColorMode::Black,
+        mode: Mode::PixelPerfect,
+        svg: SvgOptions {
+            max_path_commands,
+            ..SvgOptions::default()
+        },
+        ..Options::default()
+    };
+    let result = vectorize_image(&image, &options(None));
+    let path_data = |svg: &str| {
+        svg.split(" d=\"")
+            .skip(1)
+            .map(|data| {
+                data.split('"')
+                    .next()
+                    .unwrap()
+                    .parse::<OptimizedData>()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>()
+    };
+    let subpaths = |data: &OptimizedData| data.to_polygons(0.1).len();
+
+    let whole = path_data(&to_svg(&result, &options(None)));
+    assert_eq!(whole.len(), 1);
+    assert_eq!(subpaths(&whole[0]), 11);
+
+    let svg = to_svg(&result, &options(Some(12)));
+    let split = path_data(&svg);
+    assert!(split.len() > 1, "{svg}");
+    assert!(split.iter().all(|data| data.len() <= 12), "{svg}");
+    assert_eq!(split.iter().map(subpaths).sum::<usize>(), 11);
+    // Every path is filled and stroked.
+    assert_eq!(svg.matches("<use").count(), 2 * split.len());
+    // The hole stays in the path of the ring.
+    let ring = split
+        .iter()
+        .map(|data| data.to_polygons(0.1))
+        .find(|polygons| {
+            polygons
+                .iter()
+                .any(|(_, ring)| ring.iter().any(|p| p.x == 4.0))
+        })
+        .unwrap();
+    assert!(ring
+        .iter()
+        .any(|(_, hole)| hole.iter().any(|p| p.x == 10.0)));
+
+    // A shape longer than the limit is kept whole.
+    let svg = to_svg(&result, &options(Some(1)));
+    assert_eq!(path_data(&svg).len(), 10, "{svg}");
+}
+
+#[test]
+fn coordinate_mode() {
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let result = try_vectorize(&png, &Options::default()).unwrap();
+    let svg = |coordinate_mode| {
+        let options = Options {
+            svg: SvgOptions {
+                coordinate_mode,
+                ..SvgOptions::default()
+            },
+            ..Options::default()
+        };
+        to_svg(&result, &options)
+    };
+    let path_data = |svg: &str| {
+        svg.split(" d=\"")
+            .skip(1)
+            .map(|data| data.split('"').next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    let absolute = path_data(&svg(CoordinateMode::Absolute));
+    let relative = path_data(&svg(CoordinateMode::Relative));
+    let auto = path_data(&svg(CoordinateMode::Auto));
+
+    assert!(absolute
+        .iter()
+        .all(|data| !data.contains(['l', 'c', 'h', 'v'])));
+    assert_eq!(auto.len(), relative.len());
+    for ((auto, absolute), relative) in auto.iter().zip(&absolute).zip(&relative) {
+        assert_eq!(auto.len(), absolute.len().min(relative.len()));
+        assert!(auto == absolute || auto == relative);
+        // The same outline either way.
+        let points = |data: &str| {
+            let polygons = data.parse::<OptimizedData>().unwrap().to_polygons(0.1);
+            polygons
+                .into_iter()
+                .flat_map(|(_, polygon)| polygon)
+                .collect::<Vec<_>>()
+        };
+        let (absolute, relative) = (points(absolute), points(relative));
+        assert_eq!(absolute.len(), relative.len());
+        assert!(absolute
+            .iter()
+            .zip(&relative)
+            .all(|(a, r)| a.len_with(*r) < 1e-6));
+    }
+}
+
+/// A black 4x2 bar at (2, 1), on a white 20x10 image.
+fn bar() -> RgbaImage {
+    RgbaImage::from_fn(20, 10, |x, y| {
+        match (2..6).contains(&x) && (1..3).contains(&y) {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    })
+}
+
+/// Traces [`bar`] to its exact pixel outline.
+fn bar_options() -> Options {
+    Options {
+        color_mode: ColorMode::Black,
+        mode: Mode::Polygon,
+        supersample: Supersample::Off,
+        ..Options::default()
+    }
+}
+
+/// `width`, `height` and `viewBox` of the root element.
+fn root_size(svg: &str) -> [String; 3] {
+    let root = &svg[svg.find("<svg").unwrap()..];
+    ["width", "height", "viewBox"].map(|name| {
+        let value = root.split(&format!(" {name}=\"")).nth(1).unwrap();
+        value[..value.find('"').unwrap()].to_string()
+    })
+}
+
+/// Bounds of the first path in document coordinates.
+fn path_bounds(svg: &str) -> [f64; 4] {
+    let data = svg
+        .split(" d=\"")
+        .nth(1)
+        .unwrap()
+        .split('"')
+        .next()
+        .unwrap();
+    let polygons = data.parse::<OptimizedData>().unwrap().to_polygons(0.1);
+    let points = polygons.into_iter().flat_map(|(_, polygon)| polygon);
+    let bbox = BoundingBox::from_points(points).unwrap();
+    [bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y].map(|v| (v * 1e3).round() / 1e3)
+}
+
+#[test]
+fn document_coordinates() {
+    let svg = |svg| {
+        let options = Options {
+            svg,
+            ..bar_options()
+        };
+        to_svg(&vectorize_image(&bar(), &options), &options)
+    };
+
+    let default = svg(SvgOptions::default());
+    assert_eq!(root_size(&default), ["20", "10", "0 0 20 10"]);
+    assert_eq!(path_bounds(&default), [2.0, 1.0, 6.0, 3.0]);
+
+    // The Y axis points up from the bottom edge.
+    let flipped = svg(SvgOptions {
+        flip_y: true,
+        ..SvgOptions::default()
+    });
+    assert_eq!(root_size(&flipped), ["20", "10", "0 0 20 10"]);
+    assert_eq!(path_bounds(&flipped), [2.0, 7.0, 6.0, 9.0]);
+
+    // The origin moves the coordinates and the viewBox along.
+    let moved = svg(SvgOptions {
+        flip_y: true,
+        origin: [1.0, 2.5],
+        ..SvgOptions::default()
+    });
+    assert_eq!(root_size(&moved), ["20", "10", "-1 -2.5 20 10"]);
+    assert_eq!(path_bounds(&moved), [1.0, 4.5, 5.0, 6.5]);
+
+    // Only the size of the root element gets the units.
+    for (units, suffix) in [
+        (SvgUnits::Millimeters, "mm"),
+        (SvgUnits::Centimeters, "cm"),
+        (SvgUnits::Inches, "in"),
+    ] {
+        let svg = svg(SvgOptions {
+            units: Some(units),
+            ..SvgOptions::default()
+        });
+        assert_eq!(
+            root_size(&svg),
+            [
+                format!("20{suffix}"),
+                format!("10{suffix}"),
+                "0 0 20 10".into()
+            ]
+        );
+        assert_eq!(path_bounds(&svg), path_bounds(&default));
+    }
+}
+
+#[test]
+fn auto_crop() {
+    let svg = |auto_crop| {
+        let options = Options {
+            auto_crop,
+            ..bar_options()
+        };
+        to_svg(&vectorize_image(&bar(), &options), &options)
+    };
+    assert_eq!(root_size(&svg(None)), ["20", "10", "0 0 20 10"]);
+
+    // The empty margins are cut down to the padding, the bar moving along.
+    let tight = svg(Some(0.0));
+    assert_eq!(root_size(&tight), ["4", "2", "0 0 4 2"]);
+    assert_eq!(path_bounds(&tight), [0.0, 0.0, 4.0, 2.0]);
+    let padded = svg(Some(1.5));
+    assert_eq!(root_size(&padded), ["7", "5", "0 0 7 5"]);
+    assert_eq!(path_bounds(&padded), [1.5, 1.5, 5.5, 3.5]);
+}
+
+#[test]
+fn color_ids() {
+    // A red disc, a blue square and a small green square, on white.
+    let mut image = RgbaImage::from_fn(64, 40, |x, y| {
+        let (fx, fy) = (x as f64 + 0.5, y as f64 + 0.5);
+        if (fx - 16.0).hypot(fy - 20.0) < 10.0 {
+            Rgba([255, 0, 0, 255])
+        } else if (36..46).contains(&x) && (10..30).contains(&y) {
+            Rgba([0, 0, 255, 255])
+        } else if (52..60).contains(&x) && (4..12).contains(&y) {
+            Rgba([0, 160, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    let options = Options {
+        supersample: Supersample::Off,
+        svg: SvgOptions {
+            id_prefix: "icon-".to_string(),
+            id_scheme: IdScheme::Color,
+            ..SvgOptions::default()
+        },
+        ..Options::default()
+    };
+    // Path data by id.
+    let paths = |image: &RgbaImage| {
+        let svg = to_svg(&vectorize_image(image, &options), &options);
+        let mut paths = (svg.split("<path").skip(1))
+            .map(|path| {
+                let attribute = |name: &str| {
+                    let value = path.split(&format!(" {name}=\"")).nth(1).unwrap();
+                    value[..value.find('"').unwrap()].to_string()
+                };
+                (attribute("id"), attribute("d"))
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    };
+
+    let first = paths(&image);
+    assert_eq!(first, paths(&image));
+    let ids = first.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>();
+    for id in [
+        "icon-color-ff0000-0",
+        "icon-color-0000ff-0",
+        "icon-color-00a000-0",
+    ] {
+        assert!(ids.contains(&id), "{id} in {ids:?}");
+    }
+
+    // Growing the green square keeps every id, and the paths of the other shapes.
+    for y in 4..12 {
+        image.put_pixel(51, y, Rgba([0, 160, 0, 255]));
+    }
+    let edited = paths(&image);
+    assert_eq!(
+        edited.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+        first.iter().map(|(id, _)| id).collect::<Vec<_>>()
+    );
+    for ((id, data), (_, edited_data)) in first.iter().zip(&edited) {
+        match id.as_str() {
+            "icon-color-ff0000-0" | "icon-color-0000ff-0" => assert_eq!(data, edited_data),
+            "icon-color-00a000-0" => assert_ne!(data, edited_data),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn svg_profiles() {
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let result = try_vectorize(&png, &Options::default()).unwrap();
+    let svg = |profile, css_classes| {
+        let options = Options {
+            svg: SvgOptions {
+                profile,
+                css_classes,
+                ..SvgOptions::default()
+            },
+            ..Options::default()
+        };
+        to_svg(&result, &options)
+    };
+    let root = |svg: &str| {
+        svg[svg.find("<svg").unwrap()..]
+            .split('>')
+            .next()
+            .unwrap()
+            .to_string()
+    };
+    let uses = |svg: &str| svg.matches("<use").count();
+
+    let svg2 = svg(SvgProfile::Svg2, false);
+    assert!(uses(&svg2) > 0);
+    assert_eq!(svg2.matches("<use href=\"#").count(), uses(&svg2));
+    assert!(!svg2.contains("xlink") && !svg2.contains("inkscape"));
+    assert!(!root(&svg2).contains("version="));
+    assert!(svg(SvgProfile::Svg2, true).contains("<style>"));
+
+    // Every reference through the declared xlink namespace.
+    let svg11 = svg(SvgProfile::Svg11, false);
+    let root11 = root(&svg11);
+    assert!(root11.contains(r#" version="1.1""#));
+    assert!(root11.contains(r#" xmlns:xlink="http://www.w3.org/1999/xlink""#));
+    assert_eq!(svg11.matches("<use xlink:href=\"#").count(), uses(&svg2));
+    assert_eq!(svg11.matches(" href=").count(), 0);
+    assert!(!svg11.contains("inkscape"));
+    assert!(svg(SvgProfile::Svg11, true).contains(r#"<style type="text/css">"#));
+
+    // SVG 1.1 with the stroke and fill groups as named layers.
+    let inkscape = svg(SvgProfile::Inkscape, false);
+    let root_inkscape = root(&inkscape);
+    assert!(root_inkscape.contains(r#" version="1.1""#));
+    assert!(root_inkscape.contains(r#" xmlns:xlink="http://www.w3.org/1999/xlink""#));
+    assert!(
+        root_inkscape.contains(r#" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape""#)
+    );
+    assert_eq!(inkscape.matches("<use xlink:href=\"#").count(), uses(&svg2));
+    let layers = (inkscape.split("<g").skip(1))
+        .map(|group| group.split('>').next().unwrap())
+        .filter(|group| group.contains(r#"inkscape:groupmode="layer""#))
+        .collect::<Vec<_>>();
+    assert_eq!(layers.len(), 2, "{layers:?}");
+    assert!(layers[0].contains(r#"inkscape:label="strokes""#));
+    assert!(layers[1].contains(r#"inkscape:label="fills""#));
+}
+
+#[test]
+fn hybrid_svg() {
+    assert_eq!(base64_encode(b"Man"), "TWFu");
+    assert_eq!(base64_encode(b"Ma"), "TWE=");
+    assert_eq!(base64_encode(b"M"), "TQ==");
+    assert_eq!(base64_encode(b""), "");
+
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let options = Options::default();
+    let svg = try_create_hybrid_svg(&png, &options, Some(16)).unwrap();
+
+    // The input is embedded below the paths, the rest of the document is unchanged.
+    let start = svg.find("<image").unwrap();
+    let end = start + svg[start..].find("/>").unwrap() + 2;
+    assert!(start < svg.find("<g").unwrap());
+    let vector = format!("{}{}", &svg[..start], &svg[end..]);
+    let expected = try_create_svg(&png, &options).unwrap();
+    assert_eq!(
+        vector
+            .lines()
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>(),
+        expected.lines().collect::<Vec<_>>()
+    );
+
+    let image = &svg[start..end];
+    assert!(image.contains(r#" width="64""#) && image.contains(r#" height="64""#));
+    let data = image.split("href=\"data:image/png;base64,").nth(1).unwrap();
+    let data = &data[..data.find('"').unwrap()];
+    // The PNG signature, and the header of a 16 by 16 image.
+    assert!(
+        data.starts_with("iVBORw0KGgoAAAANSUhEUgAAABAAAAAQ"),
+        "{data}"
+    );
+    let full = try_create_hybrid_svg(&png, &options, None).unwrap();
+    assert!(full.len() > svg.len());
+
+    // The raster moves along with the paths.
+    let image_attributes = |options: &Options| {
+        let svg = try_create_hybrid_svg(&png, options, Some(16)).unwrap();
+        let image = svg
+            .split("<image")
+            .nth(1)
+            .unwrap()
+            .split("/>")
+            .next()
+            .unwrap();
+        ["x", "y", "width", "height", "transform"].map(|name| {
+            let value = image.split(&format!(" {name}=\"")).nth(1);
+            value.map(|value| value[..value.find('"').unwrap()].to_string())
+        })
+    };
+    let uncropped = try_vectorize(&png, &Options::default()).unwrap();
+    let content = (uncropped.layers.iter().filter_map(|layer| layer.bbox()))
+        .reduce(|a, b| a.union(&b))
+        .unwrap();
+    let cropped = Options {
+        auto_crop: Some(2.0),
+        ..Options::default()
+    };
+    let result = try_vectorize(&png, &cropped).unwrap();
+    let [x, y] = [2.0 - content.min.x, 2.0 - content.min.y];
+    assert_eq!([result.canvas.min.x, result.canvas.min.y], [x, y]);
+    let number = |v: f64| Some(format!("{}", trunc(v)));
+    assert_eq!(
+        image_attributes(&cropped),
+        [number(x), number(y), number(64.0), number(64.0), None]
+    );
+
+    let scaled = Options {
+        transform: TransformOptions {
+            scale: 2.0,
+            translate: [10.0, 0.0],
+            ..TransformOptions::default()
+        },
+        ..cropped.clone()
+    };
+    assert_eq!(
+        image_attributes(&scaled),
+        [
+            number(2.0 * x + 10.0),
+            number(2.0 * y),
+            number(128.0),
+            number(128.0),
+            None
+        ]
+    );
+
+    // Mirrored with the paths around the document height.
+    let flipped = Options {
+        svg: SvgOptions {
+            flip_y: true,
+            ..SvgOptions::default()
+        },
+        ..cropped
+    };
+    let height = result.height as f64;
+    assert_eq!(
+        image_attributes(&flipped),
+        [
+            number(x),
+            number(y),
+            number(64.0),
+            number(64.0),
+            Some(format!("matrix(1 0 0 -1 0 {})", trunc(height)))
+        ]
+    );
+}
+
+#[test]
+fn write_svg_streams() {
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let result = try_vectorize(&png, &Options::default()).unwrap();
+    let streamed = |options: &Options| {
+        let mut svg = Vec::new();
+        write_svg(&result, options, &mut svg).unwrap();
+        String::from_utf8(svg).unwrap()
+    };
+
+    let formattings = [
+        OutputFormatting::Lines,
+        OutputFormatting::Minified,
+        OutputFormatting::Pretty { indent: 2 },
+    ];
+    for formatting in formattings {
+        let options = Options {
+            svg: SvgOptions {
+                formatting,
+                title: Some("Logo".to_string()),
+                ..SvgOptions::default()
+            },
+            ..Options::default()
+        };
+        assert_eq!(streamed(&options), to_svg(&result, &options));
+    }
+
+    // Without layers `<defs>` is empty.
+    let empty = VectorizationResult {
+        layers: Vec::new(),
+        ..result.clone()
+    };
+    let mut svg = Vec::new();
+    write_svg(&empty, &Options::default(), &mut svg).unwrap();
+    assert_eq!(
+        String::from_utf8(svg).unwrap(),
+        to_svg(&empty, &Options::default())
+    );
+
+    let mut svg = Vec::new();
+    try_write_svg(&png, &Options::default(), &mut svg).unwrap();
+    assert_eq!(
+        String::from_utf8(svg).unwrap(),
+        try_create_svg(&png, &Options::default()).unwrap()
+    );
+
+    // The style moves after the groups.
+    let options = Options {
+        svg: SvgOptions {
+            css_classes: true,
+            ..SvgOptions::default()
+        },
+        ..Options::default()
+    };
+    let (streamed, svg) = (streamed(&options), to_svg(&result, &options));
+    let style = |svg: &str| {
+        let start = svg.find("<style").unwrap();
+        let end = svg.find("</style>").unwrap() + "</style>".len();
+        let style = &svg[start..end];
+        (style.to_string(), svg.replace(&format!("\n{style}"), ""))
+    };
+    assert_eq!(style(&streamed), style(&svg));
+    assert!(streamed.find("<style") > streamed.find("<defs"));
+}
+
+#[test]
+fn css_class_names() {
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let class_names = |id_prefix: &str| {
+        let options = Options {
+            svg: SvgOptions {
+                css_classes: true,
+                id_prefix: id_prefix.to_string(),
+                ..SvgOptions::default()
+            },
+            ..Options::default()
+        };
+        let svg = try_create_svg(&png, &options).unwrap();
+        let style = &svg[svg.find("<style").unwrap()..svg.find("</style>").unwrap()];
+        let selectors: Vec<String> = (style.split('.').skip(1))
+            .map(|rule| rule[..rule.find('{').unwrap()].to_string())
+            .collect();
+        let mut classes: Vec<String> = (svg.split(" class=\"").skip(1))
+            .map(|rest| rest[..rest.find('"').unwrap()].to_string())
+            .collect();
+        classes.sort_unstable();
+        classes.dedup();
+        // Every class is a single plain CSS identifier its selector matches.
+        assert!(!selectors.is_empty());
+        assert_eq!(classes, selectors);
+        selectors
+    };
+    assert_eq!(class_names("")[0], "c0");
+    assert_eq!(class_names("icon-")[0], "icon-c0");
+    assert_eq!(class_names("my.icon: 1")[0], "my-icon--1c0");
+    assert_eq!(class_names("2x")[0], "_2xc0");
+    assert_eq!(class_names("-x")[0], "_-xc0");
+}
+
+#[test]
+fn detect_primitives() {
+    // A disc, a rotated ellipse, a square and a ring, black on white.
+    let image = RgbaImage::from_fn(200, 60, |x, y| {
+        let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+        let (u, v) = (x - 80.0, y - 30.0);
+        let (u, v) = (u * 0.8 + v * 0.6, v * 0.8 - u * 0.6);
+        let ring = (x - 170.0).hypot(y - 30.0);
+        let is_filled = (x - 30.0).hypot(y - 30.0) < 20.0
+            || (u / 24.0).hypot(v / 12.0) < 1.0
+            || ((110.0..140.0).contains(&x) && (15.0..45.0).contains(&y))
+            || (12.0..24.0).contains(&ring);
+        match is_filled {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let svg = |detect_primitives| {
+        let options = Options {
+            color_mode: ColorMode::Black,
+            svg: SvgOptions {
+                detect_primitives,
+                bbox_attributes: true,
+                ..SvgOptions::default()
+            },
+            ..Options::default()
+        };
+        to_svg(&vectorize_image(&image, &options), &options)
+    };
+    let attribute = |element: &str, name: &str| -> f64 {
+        let value = element.split(&format!(" {name}=\"")).nth(1).unwrap();
+        value.split('"').next().unwrap().parse().unwrap()
+    };
+
+    let paths = svg(None);
+    assert_eq!(paths.matches("<path").count(), 1);
+    assert!(!paths.contains("<circle") && !paths.contains("<ellipse"));
+
+    // The ring stays in the path.
+    let primitives = svg(Some(1.0));
+    assert_eq!(primitives.matches("<path").count(), 1);
+    assert_eq!(primitives.matches("<use").count(), 8);
+    let circle = primitives.split("<circle").nth(1).unwrap();
+    assert!((attribute(circle, "cx") - 30.0).abs() < 0.2);
+    assert!((attribute(circle, "cy") - 30.0).abs() < 0.2);
+    assert!((attribute(circle, "r") - 20.0).abs() < 0.3);
+    assert!(circle.contains(" data-bbox=\""));
+    let ellipse = primitives.split("<ellipse").nth(1).unwrap();
+    assert!((attribute(ellipse, "cx") - 80.0).abs() < 0.2);
+    assert!((attribute(ellipse, "rx") - 24.0).abs() < 0.3);
+    assert!((attribute(ellipse, "ry") - 12.0).abs() < 0.3);
+    let transform = ellipse.split(" transform=\"rotate(").nth(1).unwrap();
+    let degrees: f64 = transform.split(' ').next().unwrap().parse().unwrap();
+    assert!((degrees - 36.87).abs() < 1.0, "{degrees}");
+}
+
+#[test]
+fn detect_polygon_primitives() {
+    // A square, a rotated rectangle, a triangle and a hexagon, black on white.
+    let in_regular_polygon = |x: f64, y: f64, center: (f64, f64), sides: f64, radius: f64| {
+        let (u, v) = (x - center.0, y - center.1);
+        let step = std::f64::consts::TAU / sides;
+        (0..sides as usize).all(|i| {
+            // Inside the half plane of every edge, the apothem from the center.
+            let angle = step * (i as f64 + 0.5) - std::f64::consts::FRAC_PI_2;
+            u * angle.cos() + v * angle.sin() < radius * (step / 2.0).cos()
+        })
+    };
+    let image = RgbaImage::from_fn(240, 60, |x, y| {
+        let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+        let (u, v) = (x - 80.0, y - 30.0);
+        let (u, v) = (u * 0.8 + v * 0.6, v * 0.8 - u * 0.6);
+        let is_filled = ((10.0..50.0).contains(&x) && (10.0..50.0).contains(&y))
+            || (u.abs() < 20.0 && v.abs() < 10.0)
+            || in_regular_polygon(x, y, (140.0, 33.0), 3.0, 24.0)
+            || in_regular_polygon(x, y, (200.0, 30.0), 6.0, 24.0);
+        match is_filled {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let options = Options {
+        color_mode: ColorMode::Black,
+        supersample: Supersample::Off,
+        line_fitting: Some(LineFitting::default()),
+        svg: SvgOptions {
+            detect_primitives: Some(2.0),
+            ..SvgOptions::default()
+        },
+        ..Options::default()
+    };
+    let svg = to_svg(&vectorize_image(&image, &options), &options);
+    let attribute = |element: &str, name: &str| -> String {
+        let value = element.split(&format!(" {name}=\"")).nth(1).unwrap();
+        value.split('"').next().unwrap().to_string()
+    };
+    let number = |element: &str, name: &str| attribute(element, name).parse::<f64>().unwrap();
+
+    assert!(!svg.contains("<path"), "{svg}");
+    let rects = svg.split("<rect").skip(1).collect::<Vec<_>>();
+    assert_eq!(rects.len(), 2);
+    assert!((number(rects[0], "x") - 10.0).abs() < 0.3);
+    assert!((number(rects[0], "width") - 40.0).abs() < 0.5);
+    assert!(!rects[0].split('>').next().unwrap().contains("transform"));
+    assert!((number(rects[1], "width") - 40.0).abs() < 0.5);
+    assert!((number(rects[1], "height") - 20.0).abs() < 0.5);
+    assert!(attribute(rects[1], "transform").starts_with("rotate(36."));
+
+    let mut vertices = svg
+        .split("<polygon")
+        .skip(1)
+        .map(|polygon| attribute(polygon, "points").split(' ').count())
+        .collect::<Vec<_>>();
+    vertices.sort();
+    assert_eq!(vertices, [3, 6]);
+}
+
+#[test]
+fn svg_symbols() {
+    let icon = |size: u32| {
+        RgbaImage::from_fn(24, 24, |x, y| match x.max(y) < size {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        })
+    };
+    let options = Options {
+        color_mode: ColorMode::Black,
+        ..Options::default()
+    };
+    let names = ["home", "home", "1 star", "a", "b-gradient"];
+    let symbols = names
+        .iter()
+        .zip([8, 12, 16, 20, 22])
+        .map(|(name, size)| (name.to_string(), vectorize_image(&icon(size), &options)))
+        .collect::<Vec<_>>();
+
+    let svg = to_svg_symbols(&symbols, &options);
+    for id in ["home", "home-2", "_1-star", "a", "b-gradient"] {
+        let symbol = format!(r#"<symbol id="{id}" viewBox="0 0 24 24">"#);
+        assert!(svg.contains(&symbol), "{svg}");
+    }
+    // Paths skip the ids of the symbols, and of the gradients they could define.
+    assert!(!svg.contains(r#"id="b""#), "{svg}");
+    for id in ["a", "c", "d", "e", "f", "g"] {
+        assert_eq!(svg.matches(&format!(r#"id="{id}""#)).count(), 1, "{svg}");
+    }
+    assert_eq!(svg.matches("<use").count(), 2 * names.len());
+
+    let options = Options {
+        svg: SvgOptions {
+            id_prefix: "icon-".to_string(),
+            ..SvgOptions::default()
+        },
+        ..options
+    };
+    let svg = to_svg_symbols(&symbols[..3], &options);
+    assert!(svg.contains(r#"<symbol id="icon-1-star""#), "{svg}");
+}
+
+#[test]
+fn fit_error_attributes() {
+    // A disc and a square, black on white.
+    let image = RgbaImage::from_fn(100, 50, |x, y| {
+        let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+        let is_filled = (x - 25.0).hypot(y - 25.0) < 18.0
+            || ((60.0..90.0).contains(&x) && (10.0..40.0).contains(&y));
+        match is_filled {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let options = Options {
+        color_mode: ColorMode::Black,
+        supersample: Supersample::Off,
+        ..Options::default()
+    };
+    let result = vectorize_image(&image, &options);
+    let layer = &result.layers[0];
+    let errors = layer.fit_errors();
+    let segments = layer.segments();
+    assert_eq!(errors.len(), segments.len());
+    for (errors, (_is_cyclic, segments)) in errors.iter().zip(&segments) {
+        assert_eq!(errors.len(), segments.len());
+    }
+    let max_error = errors.iter().flatten().copied().fold(0.0, f64::max);
+    assert!(max_error > 0.0 && max_error < options.error_threshold * 2.0);
+
+    let svg = |fit_error_attributes| {
+        let options = Options {
+            svg: SvgOptions {
+                fit_error_attributes,
+                max_path_commands: Some(1),
+                ..SvgOptions::default()
+            },
+            ..options.clone()
+        };
+        to_svg(&result, &options)
+    };
+    // One path per shape, the square being fitted exactly.
+    let svg_all = svg(Some(0.0));
+    assert_eq!(svg_all.matches("<path").count(), 2, "{svg_all}");
+    assert_eq!(svg_all.matches("data-fit-error=").count(), 1, "{svg_all}");
+    let value = svg_all.split(r#"data-fit-error=""#).nth(1).unwrap();
+    let value: f64 = value[..value.find('"').unwrap()].parse().unwrap();
+    assert!((value - max_error).abs() < 0.01, "{value} {max_error}");
+    assert!(!svg(Some(max_error)).contains("data-fit-error"));
+    assert!(!svg(None).contains("data-fit-error"));
+}
+
+#[test]
+fn svg_levels() {
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let options = Options::default();
+    let svg = create_svg_with_levels(&png, &options, &[4.0, 1.0]);
+    assert_eq!(svg.matches("data-error-threshold").count(), 2);
+    assert_eq!(svg.matches(r#"display="none""#).count(), 1);
+
+    // No levels, an empty document.
+    let empty = create_svg_with_levels(&png, &options, &[]);
+    assert_eq!(empty, to_svg_levels(&[], &options));
+    assert!(empty.contains(r#"viewBox="0 0 0 0""#));
+    assert!(!empty.contains("<g"));
+}
+
+#[test]
+fn animated_svg() {
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let options = Options::default();
+    let result = try_vectorize(&png, &options).unwrap();
+    let frames = [(result.clone(), 100.0), (result, 300.0)];
+    let svg = to_animated_svg(&frames, &options);
+    assert_eq!(svg.matches("<animate").count(), 2);
+    assert!(svg.contains(r#"dur="400ms""#));
+
+    // No frames, an empty document.
+    let empty = to_animated_svg(&[], &options);
+    assert!(empty.contains(r#"viewBox="0 0 0 0""#));
+    assert!(!empty.contains("<g"));
+}
+
+#[test]
+fn pixel_scale() {
+    let image = RgbaImage::from_fn(4, 3, |x, y| match (x, y) {
+        (1, 1) => Rgba([0, 0, 0, 255]),
+        _ => Rgba([255, 255, 255, 255]),
+    });
+    let trace = |pixel_scale| {
+        let options = Options {
+            color_mode: ColorMode::Black,
+            mode: Mode::PixelPerfect,
+            pixel_scale,
+            ..Options::default()
+        };
+        let result = vectorize_image(&image, &options);
+        let bbox = result.layers[0].bbox().unwrap();
+        ([result.width, result.height], [bbox.min.x, bbox.max.x])
+    };
+    assert_eq!(trace(3), ([12, 9], [3.0, 6.0]));
+    // Out of range factors are clamped.
+    assert_eq!(trace(0), trace(1));
+    assert_eq!(trace(u32::MAX), trace(PIXEL_SCALE_MAX));
+    assert_eq!(trace(u32::MAX).0, [4096, 3072]);
+}
+
+#[test]
+fn quadratic_curves() {
+    let png = fs::read("tests/fixtures/flat_logo.png").unwrap();
+    let options = Options {
+        curve_type: CurveType::Quadratic,
+        ..Options::default()
+    };
+    let svg = try_create_svg(&png, &options).unwrap();
+    let commands = svg
+        .split(" d=\"")
+        .skip(1)
+        .flat_map(|data| {
+            data.split('"')
+                .next()
+                .unwrap()
+                .parse::<OptimizedData>()
+                .unwrap()
+                .to_vec()
+        })
+        .collect::<Vec<_>>();
+    assert!(commands
+        .iter()
+        .any(|command| matches!(command, Command::Q(..))));
+    assert!(!commands
+        .iter()
+        .any(|command| matches!(command, Command::C(..) | Command::S(..))));
+}
+
+#[test]
+fn gradients() {
+    // Light blue shades, varying along x or with the distance from the center.
+    let shade = |t: f64| {
+        let value = (60.0 + 140.0 * t.min(1.0)) as u8;
+        Rgba([value, value, 255, 255])
+    };
+    let linear = RgbaImage::from_fn(64, 32, |x, _| shade(x as f64 / 63.0));
+    let radial = RgbaImage::from_fn(64, 64, |x, y| {
+        let (dx, dy) = (x as f64 - 31.5, y as f64 - 31.5);
+        shade(dx.hypot(dy) / 45.0)
+    });
+    let trace = |image: &RgbaImage, options: &Options| {
+        let result = vectorize_image(image, options);
+        let mut gradients = result
+            .layers
+            .iter()
+            .filter_map(|layer| layer.gradient.clone());
+        let gradient = gradients.next();
+        assert!(gradients.next().is_none());
+        (gradient, to_svg(&result, options))
+    };
+    let options = Options {
+        gradients: Some(GradientOptions::default()),
+        ..Options::default()
+    };
+
+    // The bands of the ramp are one layer, filled with a gradient from left to right.
+    let (gradient, svg) = trace(&linear, &options);
+    let Some(Gradient::Linear { start, end, stops }) = gradient else {
+        panic!("{gradient:?}");
+    };
+    assert!(start.x < 1.0 && end.x > 63.0, "{start:?} {end:?}");
+    assert_eq!(start.y, end.y);
+    assert!(stops
+        .windows(2)
+        .all(|s| s[0].0 < s[1].0 && s[0].1[0] < s[1].1[0]));
+    assert_eq!(svg.matches("<linearGradient").count(), 1);
+    assert_eq!(svg.matches(r#"fill="url(#"#).count(), 1);
+    assert!(!svg.contains("<radialGradient"));
+
+    let (gradient, svg) = trace(&radial, &options);
+    let Some(Gradient::Radial { center, radius, .. }) = gradient else {
+        panic!("{gradient:?}");
+    };
+    assert!(center.len_with(DVec2::new(32.0, 32.0)) < 1.0, "{center:?}");
+    assert!((radius - 45.0).abs() < 2.0, "{radius}");
+    assert_eq!(svg.matches("<radialGradient").count(), 1);
+
+    // Only linear gradients, which don't fit the radial shading.
+    let linear_only = Options {
+        gradients: Some(GradientOptions {
+            radial: false,
+            ..GradientOptions::default()
+        }),
+        ..Options::default()
+    };
+    assert_eq!(trace(&radial, &linear_only).0, None);
+
+    // Disabled by default, and in pixel perfect mode.
+    assert_eq!(trace(&linear, &Options::default()).0, None);
+    let pixel_perfect = Options {
+        mode: Mode::PixelPerfect,
+        ..options
+    };
+    let (gradient, svg) = trace(&linear, &pixel_perfect);
+    assert_eq!(gradient, None);
+    assert!(!svg.contains("Gradient"));
+}
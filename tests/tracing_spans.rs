@@ -0,0 +1,113 @@
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use svgen::{create_svg_with_options, structs::Options};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+/// `(name, value)` of the numeric fields recorded on a span.
+type Fields = Vec<(&'static str, f64)>;
+
+/// Records the name of every span along with the fields recorded on it.
+#[derive(Default)]
+struct SpanRecorder {
+    next_id: AtomicU64,
+    spans: Mutex<Vec<(&'static str, Fields)>>,
+}
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.push((field.name(), value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.push((field.name(), value as f64));
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+impl Subscriber for SpanRecorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        self.spans
+            .lock()
+            .unwrap()
+            .push((span.metadata().name(), Vec::new()));
+        span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        let fields = &mut spans[span.into_u64() as usize - 1].1;
+        values.record(&mut FieldVisitor(fields));
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+#[test]
+fn stage_spans() {
+    let image = RgbaImage::from_fn(40, 40, |x, y| {
+        match (x as f64 - 19.5).hypot(y as f64 - 19.5) < 12.0 {
+            true => Rgba([200, 30, 40, 255]),
+            false => Rgba([255, 255, 255, 255]),
+        }
+    });
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+
+    let recorder = std::sync::Arc::new(SpanRecorder::default());
+    tracing::subscriber::with_default(recorder.clone(), || {
+        create_svg_with_options(&png, &Options::default())
+    });
+    let spans = recorder.spans.lock().unwrap();
+
+    let names = spans.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    for stage in [
+        "decode",
+        "preprocess",
+        "quantize",
+        "masks",
+        "extract",
+        "fit",
+        "emit",
+    ] {
+        assert!(names.contains(&stage), "{stage} missing from {names:?}");
+    }
+    for (name, fields) in spans.iter() {
+        assert!(
+            fields.iter().any(|(field, _)| *field == "elapsed_ms"),
+            "{name} has no duration"
+        );
+    }
+    let fit_contours = spans
+        .iter()
+        .filter(|(name, _)| *name == "fit")
+        .flat_map(|(_, fields)| fields)
+        .find(|(field, _)| *field == "contours")
+        .map(|(_, contours)| *contours);
+    // The disc and the background around it.
+    assert!(fit_contours >= Some(2.0), "{fit_contours:?}");
+}
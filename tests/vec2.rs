@@ -0,0 +1,80 @@
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use svgen::vec2::{DVec2, IVec2};
+
+fn assert_near(a: DVec2, b: DVec2) {
+    assert!(a.len_with(b) < 1e-12, "{a:?} != {b:?}");
+}
+
+#[test]
+fn operators() {
+    let (a, b) = (DVec2::new(1.0, 2.0), DVec2::new(-3.0, 0.5));
+    assert_eq!(a + b, DVec2::new(-2.0, 2.5));
+    assert_eq!(a - b, DVec2::new(4.0, 1.5));
+    assert_eq!(a * 2.0, DVec2::new(2.0, 4.0));
+    assert_eq!(-a, DVec2::new(-1.0, -2.0));
+    let mut sum = a;
+    sum += b;
+    assert_eq!(sum, a + b);
+    assert_eq!([a[0], a[1]], [1.0, 2.0]);
+    // The methods are the operators.
+    assert_eq!(a.add(b), a + b);
+    assert_eq!(a.sub(b), a - b);
+    assert_eq!(a.mul(2.0), a * 2.0);
+    assert_eq!(a.negated(), -a);
+
+    let (a, b) = (IVec2::new(1, 2), IVec2::new(-3, 4));
+    assert_eq!(a + b, IVec2::new(-2, 6));
+    assert_eq!(a - b, IVec2::new(4, -2));
+    assert_eq!(a * 3, IVec2::new(3, 6));
+    assert_eq!(-a, IVec2::new(-1, -2));
+    let mut sum = a;
+    sum += b;
+    assert_eq!(sum, a + b);
+    assert_eq!([b[0], b[1]], [-3, 4]);
+}
+
+#[test]
+#[should_panic(expected = "index out of bounds")]
+fn index_out_of_bounds() {
+    let _ = DVec2::new(1.0, 2.0)[2];
+}
+
+#[test]
+fn rotations() {
+    let x = DVec2::new(1.0, 0.0);
+    let y = DVec2::new(0.0, 1.0);
+    assert_eq!(x.perp(), y);
+    assert_eq!(y.perp(), -x);
+    assert_eq!(DVec2::new(3.0, 4.0).perp().dot(DVec2::new(3.0, 4.0)), 0.0);
+
+    // Positive counter-clockwise.
+    assert_eq!(x.cross(y), 1.0);
+    assert_eq!(y.cross(x), -1.0);
+    assert_eq!(x.cross(x * 2.0), 0.0);
+    assert_eq!(x.angle_between(y), FRAC_PI_2);
+    assert_eq!(y.angle_between(x), -FRAC_PI_2);
+    assert_eq!(x.angle_between(-x), PI);
+    assert_eq!(x.angle_between(x * 3.0), 0.0);
+
+    assert_near(x.rotate(FRAC_PI_2), y);
+    assert_near(x.rotate(-FRAC_PI_2), -y);
+    let v = DVec2::new(3.0, -4.0);
+    assert_near(v.rotate(PI), -v);
+    assert!((v.rotate(1.0).len() - v.len()).abs() < 1e-12);
+    assert!((v.angle_between(v.rotate(0.75)) - 0.75).abs() < 1e-12);
+}
+
+#[test]
+fn conversions() {
+    let v = DVec2::new(1.5, -2.0);
+    assert_eq!(DVec2::from((1.5, -2.0)), v);
+    assert_eq!(DVec2::from([1.5, -2.0]), v);
+    assert_eq!(<[f64; 2]>::from(v), [1.5, -2.0]);
+
+    let v = IVec2::new(3, -4);
+    assert_eq!(IVec2::from((3, -4)), v);
+    assert_eq!(IVec2::from([3, -4]), v);
+    assert_eq!(<[i32; 2]>::from(v), [3, -4]);
+    assert_eq!(v.as_dvec2(), DVec2::new(3.0, -4.0));
+}